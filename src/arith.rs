@@ -0,0 +1,397 @@
+//! Evaluation of integer expressions of arithmetic expansion, like "$((2 + 3 * 4))".
+//!
+//! Operators and their precedence follow Bash, from lowest to highest: `||`, `&&`, `|`, `^`, `&`,
+//! `==` and `!=`, `<`, `<=`, `>`, and `>=`, `<<` and `>>`, `+` and `-`, `*`, `/`, and `%`, `**`,
+//! and finally the unary `-`, `+`, `!`, and `~`. Comparisons yield 1 if true and 0 otherwise, and
+//! results wrap around on overflow.
+
+use crate::env::Env;
+
+use std::error::Error;
+use std::fmt;
+
+/// Variables may hold expressions themselves, like `y` of "x + 1" in "$((y * 2))", which are
+/// evaluated up to this depth, such that `x=x` fails instead of looping forever.
+const MAX_DEPTH: usize = 32;
+
+/// Operators with two characters come first such that "<=" isn't read as "<" followed by "=".
+const OPERATORS: [&str; 21] = [
+    "**", "<<", ">>", "<=", ">=", "==", "!=", "&&", "||", "+", "-", "*", "/", "%", "<", ">", "&",
+    "|", "^", "!", "~",
+];
+
+/// Kind of error when evaluating an expression.
+#[derive(Debug, PartialEq, Clone)]
+pub enum ArithErrorKind {
+    DivisionByZero,
+    NegativeExponent,
+
+    /// Like in "1 +".
+    MissingOperand,
+
+    /// Like in "(1 + 2".
+    MissingParenthesis,
+
+    /// Rest of the expression from the unexpected token, like ")" of "1 + )".
+    UnexpectedToken(String),
+
+    /// Like "12ab" or a number too large for 64 bits.
+    InvalidNumber(String),
+
+    /// Variables refer to each other, like `x=x`.
+    RecursionTooDeep,
+}
+
+impl fmt::Display for ArithErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ArithErrorKind::DivisionByZero => write!(f, "division by 0"),
+            ArithErrorKind::NegativeExponent => write!(f, "exponent less than 0"),
+            ArithErrorKind::MissingOperand => write!(f, "operand expected"),
+            ArithErrorKind::MissingParenthesis => write!(f, "missing ')'"),
+            ArithErrorKind::UnexpectedToken(token) => {
+                write!(f, "syntax error near \"{}\"", token)
+            }
+            ArithErrorKind::InvalidNumber(number) => write!(f, "invalid number \"{}\"", number),
+            ArithErrorKind::RecursionTooDeep => write!(f, "expression recursion level exceeded"),
+        }
+    }
+}
+
+/// Error of evaluating expression `expr`, like "1 / 0: division by 0".
+#[derive(Debug, PartialEq)]
+pub struct ArithError {
+    pub kind: ArithErrorKind,
+    pub expr: String,
+}
+
+impl Error for ArithError {}
+
+impl fmt::Display for ArithError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.expr, self.kind)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    Num(i64),
+    Name(String),
+    Op(&'static str),
+    Open,
+    Close,
+}
+
+#[derive(Debug)]
+enum Expr {
+    Num(i64),
+    Var(String),
+    Unary(&'static str, Box<Expr>),
+    Binary(&'static str, Box<Expr>, Box<Expr>),
+}
+
+/// Evaluates integer expression `expr`, where names of variables resolve from `env`, like "x + 1",
+/// and undefined or empty variables are 0. An empty expression is 0 too.
+pub fn evaluate(expr: &str, env: &Env) -> Result<i64, ArithError> {
+    evaluate_at(expr, env, 0).map_err(|kind| ArithError {
+        kind,
+        expr: expr.trim().to_string(),
+    })
+}
+
+fn evaluate_at(expr: &str, env: &Env, depth: usize) -> Result<i64, ArithErrorKind> {
+    let mut parser = Parser {
+        tokens: tokenize(expr)?,
+        pos: 0,
+    };
+    if parser.tokens.is_empty() {
+        return Ok(0);
+    }
+    let tree = parser.expression(0)?;
+    if parser.pos < parser.tokens.len() {
+        return Err(parser.unexpected());
+    }
+    eval(&tree, env, depth)
+}
+
+fn tokenize(expr: &str) -> Result<Vec<Token>, ArithErrorKind> {
+    let word_end = |text: &str| {
+        text.find(|ch: char| !ch.is_ascii_alphanumeric() && ch != '_')
+            .unwrap_or(text.len())
+    };
+
+    let mut tokens = Vec::new();
+    let mut rest = expr.trim_start();
+    while let Some(ch) = rest.chars().next() {
+        let len = if ch.is_ascii_digit() {
+            let len = word_end(rest);
+            let number = &rest[..len];
+            let number = number
+                .parse()
+                .map_err(|_| ArithErrorKind::InvalidNumber(number.to_string()))?;
+            tokens.push(Token::Num(number));
+            len
+        } else if ch.is_ascii_alphabetic() || ch == '_' {
+            let len = word_end(rest);
+            tokens.push(Token::Name(rest[..len].to_string()));
+            len
+        } else if ch == '(' || ch == ')' {
+            tokens.push(if ch == '(' { Token::Open } else { Token::Close });
+            1
+        } else if let Some(op) = OPERATORS.iter().find(|op| rest.starts_with(*op)) {
+            tokens.push(Token::Op(op));
+            op.len()
+        } else {
+            return Err(ArithErrorKind::UnexpectedToken(rest.trim_end().to_string()));
+        };
+        rest = rest[len..].trim_start();
+    }
+    Ok(tokens)
+}
+
+/// Yields precedence of binary operator `op`, where higher binds tighter.
+fn precedence(op: &str) -> Option<u8> {
+    let prec = match op {
+        "||" => 1,
+        "&&" => 2,
+        "|" => 3,
+        "^" => 4,
+        "&" => 5,
+        "==" | "!=" => 6,
+        "<" | "<=" | ">" | ">=" => 7,
+        "<<" | ">>" => 8,
+        "+" | "-" => 9,
+        "*" | "/" | "%" => 10,
+        "**" => 11,
+        _ => return None,
+    };
+    Some(prec)
+}
+
+/// Precedence climbing parser of tokens into an expression tree.
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    /// Parses binary operations of at least precedence `min`. All operators are left-associative,
+    /// except for `**`, like "2 ** 3 ** 2" is "2 ** (3 ** 2)".
+    fn expression(&mut self, min: u8) -> Result<Expr, ArithErrorKind> {
+        let mut lhs = self.operand()?;
+        while let Some(Token::Op(op)) = self.tokens.get(self.pos) {
+            let op = *op;
+            let prec = match precedence(op) {
+                Some(prec) if prec >= min => prec,
+                _ => break,
+            };
+            self.pos += 1;
+            let rhs = self.expression(if op == "**" { prec } else { prec + 1 })?;
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    /// Parses a number, variable, parenthesized expression, or unary operation, which binds
+    /// tighter than any binary operation, like "-2 ** 2" is "(-2) ** 2".
+    fn operand(&mut self) -> Result<Expr, ArithErrorKind> {
+        let token = match self.tokens.get(self.pos) {
+            Some(token) => token,
+            None => return Err(ArithErrorKind::MissingOperand),
+        };
+        self.pos += 1;
+        match token {
+            Token::Num(number) => Ok(Expr::Num(*number)),
+            Token::Name(name) => Ok(Expr::Var(name.clone())),
+            Token::Op(op @ ("-" | "+" | "!" | "~")) => {
+                Ok(Expr::Unary(op, Box::new(self.operand()?)))
+            }
+            Token::Open => {
+                let expr = self.expression(0)?;
+                match self.tokens.get(self.pos) {
+                    Some(Token::Close) => {
+                        self.pos += 1;
+                        Ok(expr)
+                    }
+                    Some(_) => Err(self.unexpected()),
+                    None => Err(ArithErrorKind::MissingParenthesis),
+                }
+            }
+            Token::Op(_) | Token::Close => {
+                self.pos -= 1;
+                Err(self.unexpected())
+            }
+        }
+    }
+
+    /// Yields an error for the token at the current position and the ones following it.
+    fn unexpected(&self) -> ArithErrorKind {
+        let rest: Vec<String> = self.tokens[self.pos..]
+            .iter()
+            .map(|token| match token {
+                Token::Num(number) => number.to_string(),
+                Token::Name(name) => name.clone(),
+                Token::Op(op) => op.to_string(),
+                Token::Open => "(".to_string(),
+                Token::Close => ")".to_string(),
+            })
+            .collect();
+        ArithErrorKind::UnexpectedToken(rest.join(" "))
+    }
+}
+
+fn eval(expr: &Expr, env: &Env, depth: usize) -> Result<i64, ArithErrorKind> {
+    let (op, lhs, rhs) = match expr {
+        Expr::Num(number) => return Ok(*number),
+        Expr::Var(name) => {
+            return match env.get(name.as_str()).map(|value| value.trim()) {
+                None | Some("") => Ok(0),
+                Some(_) if depth >= MAX_DEPTH => Err(ArithErrorKind::RecursionTooDeep),
+                Some(value) => evaluate_at(value, env, depth + 1),
+            };
+        }
+        Expr::Unary(op, expr) => {
+            let value = eval(expr, env, depth)?;
+            return Ok(match *op {
+                "-" => value.wrapping_neg(),
+                "!" => (value == 0) as i64,
+                "~" => !value,
+                _ => value,
+            });
+        }
+        Expr::Binary(op, lhs, rhs) => (*op, eval(lhs, env, depth)?, rhs),
+    };
+
+    // The right-hand side of "&&" and "||" is only evaluated when needed, like in "0 && 1 / 0".
+    match op {
+        "&&" if lhs == 0 => return Ok(0),
+        "||" if lhs != 0 => return Ok(1),
+        _ => {}
+    }
+    let rhs = eval(rhs, env, depth)?;
+    Ok(match op {
+        "&&" | "||" => (rhs != 0) as i64,
+        "|" => lhs | rhs,
+        "^" => lhs ^ rhs,
+        "&" => lhs & rhs,
+        "==" => (lhs == rhs) as i64,
+        "!=" => (lhs != rhs) as i64,
+        "<" => (lhs < rhs) as i64,
+        "<=" => (lhs <= rhs) as i64,
+        ">" => (lhs > rhs) as i64,
+        ">=" => (lhs >= rhs) as i64,
+        "<<" => lhs.wrapping_shl(rhs as u32),
+        ">>" => lhs.wrapping_shr(rhs as u32),
+        "+" => lhs.wrapping_add(rhs),
+        "-" => lhs.wrapping_sub(rhs),
+        "*" => lhs.wrapping_mul(rhs),
+        "/" | "%" if rhs == 0 => return Err(ArithErrorKind::DivisionByZero),
+        "/" => lhs.wrapping_div(rhs),
+        "%" => lhs.wrapping_rem(rhs),
+        "**" if rhs < 0 => return Err(ArithErrorKind::NegativeExponent),
+        _ => lhs.wrapping_pow(u32::try_from(rhs).unwrap_or(u32::MAX)),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eval(expr: &str) -> i64 {
+        evaluate(expr, &Env::default()).unwrap()
+    }
+
+    fn eval_err(expr: &str) -> ArithErrorKind {
+        evaluate(expr, &Env::default()).unwrap_err().kind
+    }
+
+    #[test]
+    fn precedence() {
+        assert_eq!(eval("2 + 3 * 4"), 14);
+        assert_eq!(eval("(2 + 3) * 4"), 20);
+        assert_eq!(eval("10 - 2 - 3"), 5);
+        assert_eq!(eval("2 ** 3 ** 2"), 512);
+        assert_eq!(eval("-2 ** 2"), 4);
+        assert_eq!(eval("7 / 2 + 7 % 2"), 4);
+        assert_eq!(eval("-7 % 3"), -1);
+        assert_eq!(eval("1 + 2 == 3"), 1);
+        assert_eq!(eval("((1))"), 1);
+        assert_eq!(eval(""), 0);
+    }
+
+    #[test]
+    fn comparison_and_logic() {
+        assert_eq!(eval("1 < 2"), 1);
+        assert_eq!(eval("2 <= 1"), 0);
+        assert_eq!(eval("2 >= 2"), 1);
+        assert_eq!(eval("3 > 4"), 0);
+        assert_eq!(eval("3 == 3 && 2 != 2"), 0);
+        assert_eq!(eval("0 || 5"), 1);
+        assert_eq!(eval("!0"), 1);
+        assert_eq!(eval("!7"), 0);
+        assert_eq!(eval("0 && 1 / 0"), 0);
+        assert_eq!(eval("1 || 1 / 0"), 1);
+    }
+
+    #[test]
+    fn bitwise() {
+        assert_eq!(eval("6 & 3"), 2);
+        assert_eq!(eval("6 | 3"), 7);
+        assert_eq!(eval("6 ^ 3"), 5);
+        assert_eq!(eval("~0"), -1);
+        assert_eq!(eval("1 << 4"), 16);
+        assert_eq!(eval("-16 >> 2"), -4);
+    }
+
+    #[test]
+    fn wraps_around() {
+        assert_eq!(eval("9223372036854775807 + 1"), i64::MIN);
+    }
+
+    #[test]
+    fn variables() {
+        let mut env = Env::default();
+        env.insert("x".to_string(), "5".to_string());
+        env.insert("y".to_string(), " x * 2 ".to_string());
+        env.insert("empty".to_string(), "".to_string());
+        env.insert("z".to_string(), "z".to_string());
+        assert_eq!(evaluate("x + 1", &env), Ok(6));
+        assert_eq!(evaluate("y+1", &env), Ok(11));
+        assert_eq!(evaluate("undefined + empty + 1", &env), Ok(1));
+        assert_eq!(
+            evaluate("z", &env).unwrap_err().kind,
+            ArithErrorKind::RecursionTooDeep
+        );
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(eval_err("1 / 0"), ArithErrorKind::DivisionByZero);
+        assert_eq!(eval_err("5 % (2 - 2)"), ArithErrorKind::DivisionByZero);
+        assert_eq!(eval_err("2 ** -1"), ArithErrorKind::NegativeExponent);
+        assert_eq!(eval_err("1 +"), ArithErrorKind::MissingOperand);
+        assert_eq!(eval_err("(1 + 2"), ArithErrorKind::MissingParenthesis);
+        assert_eq!(
+            eval_err("1 2 + 3"),
+            ArithErrorKind::UnexpectedToken("2 + 3".to_string())
+        );
+        assert_eq!(
+            eval_err("1 + )"),
+            ArithErrorKind::UnexpectedToken(")".to_string())
+        );
+        assert_eq!(
+            eval_err("1 = 2"),
+            ArithErrorKind::UnexpectedToken("= 2".to_string())
+        );
+        assert_eq!(
+            eval_err("12ab"),
+            ArithErrorKind::InvalidNumber("12ab".to_string())
+        );
+    }
+
+    #[test]
+    fn error_display() {
+        let err = evaluate(" 1 / 0 ", &Env::default()).unwrap_err();
+        assert_eq!(err.to_string(), "1 / 0: division by 0");
+    }
+}
@@ -0,0 +1,64 @@
+use super::*;
+
+/// Array assign command assigns elements to an indexed array variable, like `arr=(a b c)`.
+pub struct ArrayAssignCommand {
+    pub name: String,
+    pub values: Vec<String>,
+}
+
+impl ArrayAssignCommand {
+    pub fn new(name: String, values: Vec<String>) -> ArrayAssignCommand {
+        ArrayAssignCommand { name, values }
+    }
+}
+
+impl Command for ArrayAssignCommand {
+    fn execute(&mut self, prompt: &mut Prompt) -> Result<bool, i32> {
+        let mut ctx = prompt.context.borrow_mut();
+        let success = match ctx.env.set_array(self.name.clone(), self.values.clone()) {
+            Ok(_) => true,
+            Err(err) => {
+                println!("{}", err);
+                false
+            }
+        };
+        ctx.env
+            .insert("?".to_string(), (!success as i32).to_string());
+        Ok(success)
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::context;
+
+    #[test]
+    fn assign() {
+        let mut prompt = Prompt::create(context::default());
+        let mut cmd = ArrayAssignCommand::new("arr".to_string(), vec!["a".to_string()]);
+        assert_eq!(cmd.execute(&mut prompt), Ok(true));
+        assert_eq!(
+            prompt.context.borrow().env.get_array("arr"),
+            Some(&vec!["a".to_string()])
+        );
+    }
+
+    #[test]
+    fn assign_readonly() {
+        let mut prompt = Prompt::create(context::default());
+        prompt
+            .context
+            .borrow_mut()
+            .env
+            .set_readonly("arr".to_string());
+        let mut cmd = ArrayAssignCommand::new("arr".to_string(), vec!["a".to_string()]);
+        assert_eq!(cmd.execute(&mut prompt), Ok(false));
+        assert_eq!(prompt.context.borrow().env.get_array("arr"), None);
+        assert_eq!(prompt.context.borrow().env["?"], "1");
+    }
+}
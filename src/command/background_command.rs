@@ -0,0 +1,177 @@
+use super::*;
+
+use super::general_command::GeneralCommand;
+use super::redirect_command::RedirectCommand;
+
+use std::process::Stdio;
+
+/// Background command starts a program as a job without waiting for it, like `sleep 10 &`, and
+/// prints its job number and process id, like "[1] 12345". The process id is kept in `$!`. Input
+/// is read from `/dev/null` unless redirected, such that the job doesn't compete with the prompt
+/// for the terminal. Only programs can run in the background, optionally with redirections.
+pub struct BackgroundCommand {
+    pub cmd: Box<dyn Command>,
+
+    /// Command line of the job, without the '&'.
+    pub line: String,
+}
+
+impl BackgroundCommand {
+    pub fn new(cmd: Box<dyn Command>, line: String) -> BackgroundCommand {
+        BackgroundCommand { cmd, line }
+    }
+}
+
+impl Command for BackgroundCommand {
+    fn execute(&mut self, prompt: &mut Prompt) -> Result<bool, i32> {
+        let mut ctx = prompt.context.borrow_mut();
+        let any = self.cmd.as_any();
+        let child = if let Some(cmd) = any.downcast_ref::<GeneralCommand>() {
+            cmd.spawn(&ctx, Stdio::null(), Stdio::inherit(), Stdio::inherit())
+        } else {
+            match any
+                .downcast_ref::<RedirectCommand>()
+                .and_then(|cmd| cmd.spawn(&ctx, Stdio::null()))
+            {
+                Some(Ok(child)) => Ok(child),
+                Some(Err(err)) => {
+                    println!("{}", err);
+                    ctx.env.insert("?".to_string(), "1".to_string());
+                    return Ok(false);
+                }
+                None => {
+                    println!("{}: only programs can run in the background", self.line);
+                    return Ok(false);
+                }
+            }
+        };
+
+        match child {
+            Ok(child) => {
+                let pid = child.id();
+                let id = ctx.jobs.add(child, self.line.clone());
+                ctx.env.insert("!".to_string(), pid.to_string());
+                println!("[{}] {}", id, pid);
+                Ok(true)
+            }
+            Err(err) => GeneralCommand::spawn_failed(err, &ctx),
+        }
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::context;
+    use crate::jobs::JobState;
+
+    use std::fs;
+    use std::thread;
+    use std::time::Duration;
+
+    use tempfile::TempDir;
+
+    fn run(prompt: &mut Prompt, input: &str) -> Option<i32> {
+        let cmd = prompt.parse_command(input);
+        crate::command::execute(cmd, prompt)
+    }
+
+    /// Waits for all jobs to finish and yields their notification lines.
+    fn wait_for_jobs(prompt: &mut Prompt) -> Vec<String> {
+        let mut lines = Vec::new();
+        while !prompt.context.borrow().jobs.is_empty() {
+            lines.append(&mut prompt.context.borrow_mut().jobs.reap());
+            thread::sleep(Duration::from_millis(10));
+        }
+        lines
+    }
+
+    #[test]
+    fn parse_trailing_ampersand() {
+        let mut prompt = Prompt::create(context::default());
+        let cmd = prompt.parse_command("sleep 10 &").unwrap();
+        let cmd = cmd.as_any().downcast_ref::<BackgroundCommand>().unwrap();
+        assert_eq!(cmd.line, "sleep 10");
+        let cmd = cmd.cmd.as_any().downcast_ref::<GeneralCommand>().unwrap();
+        assert_eq!(cmd.args, vec!["10"]);
+
+        for input in ["echo a '&'", r"echo a \&", "echo a && echo b"] {
+            let cmd = prompt.parse_command(input).unwrap();
+            assert!(!cmd.as_any().is::<BackgroundCommand>(), "{}", input);
+        }
+    }
+
+    #[test]
+    fn does_not_wait() {
+        let mut prompt = Prompt::create(context::default());
+        assert_eq!(run(&mut prompt, "sleep 10 &"), None);
+
+        let pid = {
+            let ctx = prompt.context.borrow();
+            assert_eq!(ctx.env["?"], "0");
+            let job = ctx.jobs.resolve("%1").unwrap();
+            assert_eq!(job.command, "sleep 10");
+            assert_eq!(job.state, JobState::Running);
+            assert_eq!(ctx.env["!"], job.pid.to_string());
+            job.pid
+        };
+
+        unsafe { libc::kill(pid as libc::pid_t, libc::SIGKILL) };
+        assert_eq!(wait_for_jobs(&mut prompt), vec!["[1]+ Done  sleep 10"]);
+    }
+
+    #[test]
+    fn exit_code_of_finished_job() {
+        let mut prompt = Prompt::create(context::default());
+        run(&mut prompt, "sh -c 'exit 3' &");
+        assert_eq!(prompt.context.borrow().env["?"], "0");
+        assert_eq!(
+            wait_for_jobs(&mut prompt),
+            vec!["[1]+ Exit 3  sh -c 'exit 3'"]
+        );
+    }
+
+    #[test]
+    fn with_redirection() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("out.txt");
+        let mut prompt = Prompt::create(context::default());
+        run(
+            &mut prompt,
+            &format!("sh -c 'echo hi' > {} &", path.display()),
+        );
+        wait_for_jobs(&mut prompt);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "hi\n");
+    }
+
+    #[test]
+    fn redirection_failure() {
+        let mut prompt = Prompt::create(context::default());
+        assert_eq!(run(&mut prompt, "cat < /carapace/no/such/file &"), None);
+        let ctx = prompt.context.borrow();
+        assert_eq!(ctx.env["?"], "1");
+        assert!(ctx.jobs.is_empty());
+    }
+
+    #[test]
+    fn builtins_not_supported() {
+        let mut prompt = Prompt::create(context::default());
+        assert_eq!(run(&mut prompt, "cd / &"), None);
+        let ctx = prompt.context.borrow();
+        assert_eq!(ctx.env["?"], "1");
+        assert!(ctx.jobs.is_empty());
+    }
+
+    #[test]
+    fn missing_program() {
+        let mut prompt = Prompt::create(context::default());
+        assert_eq!(run(&mut prompt, "carapace-no-such-program &"), None);
+        let ctx = prompt.context.borrow();
+        assert_eq!(ctx.env["?"], "1");
+        assert!(ctx.jobs.is_empty());
+    }
+}
@@ -1,12 +1,22 @@
 use super::*;
 
-use std::path::PathBuf;
+use crate::util;
+
+use std::path::{Path, PathBuf};
 
 use clap::{App, AppSettings, Arg};
 
 /// Cd command changes directory to defined path.
 pub struct CdCommand {
     pub path: String,
+
+    /// Resolve symbolic links, via `-P`.
+    pub physical: bool,
+
+    /// Swap the current directory with the head of the directory stack, via `pushd` without a
+    /// directory.
+    pub swap: bool,
+
     program: String,
     args: Vec<String>,
     app: App<'static, 'static>,
@@ -21,21 +31,90 @@ impl CdCommand {
             .about("Change directory and push to directory stack.")
             .setting(AppSettings::NoBinaryName)
             .setting(AppSettings::DisableVersion)
+            .arg(
+                Arg::with_name("logical")
+                    .short("L")
+                    .overrides_with("physical")
+                    .help("Keep symbolic links in $PWD (default)."),
+            )
+            .arg(
+                Arg::with_name("physical")
+                    .short("P")
+                    .overrides_with("logical")
+                    .help("Resolve symbolic links in $PWD."),
+            )
             .arg(Arg::with_name("directory").index(1).default_value("~"));
 
         let mut path = "~".to_string();
+        let mut physical = false;
+        let mut swap = false;
         let matches = app.get_matches_from_safe_borrow(&args);
         if let Ok(value) = matches {
             path = value.value_of("directory").unwrap().to_string();
+            physical = value.is_present("physical");
+            swap = program == "pushd" && value.occurrences_of("directory") == 0;
         }
 
         CdCommand {
+            path,
+            physical,
+            swap,
             args,
             program,
-            path,
             app,
         }
     }
+
+    /// Yields the directory to change to. With `cd_to_file_parent`, a path to an existing file
+    /// yields the directory containing it.
+    fn target(&self, cd_to_file_parent: bool) -> PathBuf {
+        let path = if self.path == "~" {
+            util::home_dir()
+        } else {
+            PathBuf::from(&self.path)
+        };
+
+        if cd_to_file_parent && path.is_file() {
+            return match path.parent() {
+                Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+                _ => PathBuf::from("."),
+            };
+        }
+        path
+    }
+
+    /// Changes to the head of the directory stack and replaces it with the previous directory, like
+    /// `pushd` without a directory in Bash. Yields false if the head can't be changed to, like when
+    /// it was removed, which keeps the stack as is.
+    fn swap_dirs(&self, prompt: &mut Prompt) -> bool {
+        let head = prompt.context.borrow().dir_stack.last().cloned();
+        let head = match head {
+            Some(head) => head,
+            None => {
+                println!("pushd: no other directory");
+                return false;
+            }
+        };
+
+        // Nothing to change when already there, which `set_cwd()` doesn't tell from failing.
+        if prompt.context.borrow().env.get("PWD") == Some(&head) {
+            prompt.context.borrow().print_short_dir_stack();
+            return true;
+        }
+
+        match prompt.set_cwd(Path::new(&head), self.physical) {
+            Some(oldpwd) => {
+                let mut ctx = prompt.context.borrow_mut();
+                if let Some(pwd) = ctx.env.get("PWD").cloned() {
+                    ctx.add_recent_dir(pwd);
+                }
+                ctx.replace_dir_stack_head(oldpwd);
+                ctx.print_short_dir_stack();
+                true
+            }
+            None => false,
+        }
+    }
 }
 
 impl Command for CdCommand {
@@ -46,14 +125,25 @@ impl Command for CdCommand {
             return Ok(false);
         }
 
-        let path = if self.path == "~" {
-            dirs_next::home_dir().unwrap_or_default()
-        } else {
-            PathBuf::from(&self.path)
-        };
+        if self.swap {
+            return Ok(self.swap_dirs(prompt));
+        }
 
-        if let Some(oldpwd) = prompt.set_cwd(&path) {
+        let cd_to_file_parent = prompt.context.borrow().config.cd_to_file_parent;
+        let path = self.target(cd_to_file_parent);
+        if cd_to_file_parent && Path::new(&self.path).is_file() {
+            println!(
+                "cd: {} is a file, changing to {}",
+                self.path,
+                path.display()
+            );
+        }
+
+        if let Some(oldpwd) = prompt.set_cwd(&path, self.physical) {
             let mut ctx = prompt.context.borrow_mut();
+            if let Some(pwd) = ctx.env.get("PWD").cloned() {
+                ctx.add_recent_dir(pwd);
+            }
 
             // Only add to stack if empty or not the same value as the head value.
             let head = ctx.dir_stack.last();
@@ -84,15 +174,96 @@ impl CommandAliases for CdCommand {
 mod tests {
     use super::*;
 
+    use crate::context;
+
+    use std::fs;
+
+    use tempfile::TempDir;
+
     #[test]
     fn no_args_is_tilde() {
         let cmd = CdCommand::new("cd".to_string(), vec![]);
         assert_eq!(cmd.path, "~");
     }
 
+    #[test]
+    fn pushd_no_args_swaps() {
+        assert!(CdCommand::new("pushd".to_string(), vec![]).swap);
+        assert!(CdCommand::new("pushd".to_string(), vec!["-P".to_string()]).swap);
+        assert!(!CdCommand::new("pushd".to_string(), vec!["~".to_string()]).swap);
+        assert!(!CdCommand::new("cd".to_string(), vec![]).swap);
+    }
+
+    #[test]
+    fn pushd_swap_empty_stack() {
+        let mut prompt = Prompt::create(context::default());
+        let mut cmd = CdCommand::new("pushd".to_string(), vec![]);
+        assert_eq!(cmd.execute(&mut prompt), Ok(false));
+        assert!(prompt.context.borrow().dir_stack.is_empty());
+    }
+
+    #[test]
+    fn pushd_swap_missing_dir() {
+        let dir = TempDir::new().unwrap();
+        let missing = dir.path().join("missing").display().to_string();
+
+        let mut prompt = Prompt::create(context::default());
+        prompt.context.borrow_mut().dir_stack.push(missing.clone());
+        let mut cmd = CdCommand::new("pushd".to_string(), vec![]);
+        assert_eq!(cmd.execute(&mut prompt), Ok(false));
+        assert_eq!(prompt.context.borrow().dir_stack, vec![missing]);
+    }
+
+    #[test]
+    fn logical_by_default() {
+        let cmd = CdCommand::new("cd".to_string(), vec![String::from("/tmp")]);
+        assert!(!cmd.physical);
+    }
+
+    #[test]
+    fn physical_flag() {
+        let cmd = CdCommand::new("cd".to_string(), vec!["-P".to_string(), "/tmp".to_string()]);
+        assert!(cmd.physical);
+        assert_eq!(cmd.path, "/tmp");
+
+        // Last one wins.
+        let args = vec!["-P".to_string(), "-L".to_string(), "/tmp".to_string()];
+        assert!(!CdCommand::new("cd".to_string(), args).physical);
+    }
+
     #[test]
     fn valid_arg() {
         let cmd = CdCommand::new("cd".to_string(), vec![String::from("/tmp")]);
         assert_eq!(cmd.path, "/tmp");
     }
+
+    #[test]
+    fn target_file_without_cd_to_file_parent() {
+        let dir = TempDir::new().unwrap();
+        let file = dir.path().join("file.txt");
+        fs::write(&file, "").unwrap();
+
+        let cmd = CdCommand::new("cd".to_string(), vec![file.display().to_string()]);
+        assert_eq!(cmd.target(false), file);
+    }
+
+    #[test]
+    fn target_file_with_cd_to_file_parent() {
+        let dir = TempDir::new().unwrap();
+        let file = dir.path().join("file.txt");
+        fs::write(&file, "").unwrap();
+
+        let cmd = CdCommand::new("cd".to_string(), vec![file.display().to_string()]);
+        assert_eq!(cmd.target(true), dir.path());
+
+        let cmd = CdCommand::new("cd".to_string(), vec!["Cargo.toml".to_string()]);
+        assert_eq!(cmd.target(true), PathBuf::from("."));
+    }
+
+    #[test]
+    fn target_dir_with_cd_to_file_parent() {
+        let dir = TempDir::new().unwrap();
+        let cmd = CdCommand::new("cd".to_string(), vec![dir.path().display().to_string()]);
+        assert_eq!(cmd.target(true), dir.path());
+    }
 }
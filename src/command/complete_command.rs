@@ -0,0 +1,244 @@
+use super::*;
+
+use crate::editor::CompletionSpec;
+
+use std::collections::HashMap;
+
+use clap::{App, AppSettings, Arg, ArgGroup};
+
+/// Complete command specifies how arguments of commands are completed, like only directories for
+/// `cd`.
+pub struct CompleteCommand {
+    args: Vec<String>,
+    app: App<'static, 'static>,
+}
+
+impl CompleteCommand {
+    pub fn new(args: Vec<String>) -> CompleteCommand {
+        CompleteCommand {
+            args,
+            app: App::new("complete")
+                .about("Specify how arguments of commands are completed.")
+                .setting(AppSettings::NoBinaryName)
+                .setting(AppSettings::DisableVersion)
+                .arg(
+                    Arg::with_name("directories")
+                        .short("d")
+                        .help("Only complete directory names."),
+                )
+                .arg(
+                    Arg::with_name("files")
+                        .short("f")
+                        .help("Complete file names, which is the default for commands."),
+                )
+                .arg(
+                    Arg::with_name("remove")
+                        .short("r")
+                        .help("Remove completion specifications."),
+                )
+                .arg(
+                    Arg::with_name("print")
+                        .short("p")
+                        .help("Print completion specifications (default)."),
+                )
+                .group(ArgGroup::with_name("action").args(&[
+                    "directories",
+                    "files",
+                    "remove",
+                    "print",
+                ]))
+                .arg(
+                    Arg::with_name("names")
+                        .multiple(true)
+                        .help("Command names. With no names, all specifications are used."),
+                ),
+        }
+    }
+
+    /// Yields `complete` line that recreates `spec` of `name`, like "complete -d cd".
+    fn format(name: &str, spec: CompletionSpec) -> String {
+        let flag = match spec {
+            CompletionSpec::Directories => "-d",
+            CompletionSpec::Files => "-f",
+        };
+        format!("complete {} {}", flag, name)
+    }
+
+    /// Yields `complete` lines of all `specs` in sorted order of names.
+    fn list(specs: &HashMap<String, CompletionSpec>) -> Vec<String> {
+        let mut names: Vec<&String> = specs.keys().collect();
+        names.sort();
+        names
+            .into_iter()
+            .map(|name| CompleteCommand::format(name, specs[name]))
+            .collect()
+    }
+}
+
+impl Command for CompleteCommand {
+    fn execute(&mut self, prompt: &mut Prompt) -> Result<bool, i32> {
+        let matches = self.app.get_matches_from_safe_borrow(&self.args);
+        if let Err(err) = matches {
+            println!("{}", err);
+            return Ok(false);
+        }
+
+        let matches = matches.unwrap();
+        let mut ctx = prompt.context.borrow_mut();
+
+        let names: Vec<&str> = matches
+            .values_of("names")
+            .map(|names| names.collect())
+            .unwrap_or_default();
+
+        let spec = if matches.is_present("directories") {
+            Some(CompletionSpec::Directories)
+        } else if matches.is_present("files") {
+            Some(CompletionSpec::Files)
+        } else {
+            None
+        };
+
+        if let Some(spec) = spec {
+            if names.is_empty() {
+                println!("complete: no command names given");
+                return Ok(false);
+            }
+            for name in names {
+                ctx.completion_specs.insert(name.to_string(), spec);
+            }
+            return Ok(true);
+        }
+
+        let remove = matches.is_present("remove");
+        if names.is_empty() {
+            if remove {
+                ctx.completion_specs.clear();
+            } else {
+                for line in CompleteCommand::list(&ctx.completion_specs) {
+                    println!("{}", line);
+                }
+            }
+            return Ok(true);
+        }
+
+        let mut success = true;
+        for name in names {
+            let spec = if remove {
+                ctx.completion_specs.remove(name)
+            } else {
+                ctx.completion_specs.get(name).copied()
+            };
+            match spec {
+                Some(_) if remove => {}
+                Some(spec) => println!("{}", CompleteCommand::format(name, spec)),
+                None => {
+                    println!("complete: {}: no completion specification", name);
+                    success = false;
+                }
+            }
+        }
+        Ok(success)
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+impl CommandAliases for CompleteCommand {
+    fn aliases() -> Vec<String> {
+        vec!["complete".to_string()]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::context;
+
+    fn complete(prompt: &mut Prompt, args: &[&str]) -> Result<bool, i32> {
+        let args = args.iter().map(|a| a.to_string()).collect();
+        CompleteCommand::new(args).execute(prompt)
+    }
+
+    #[test]
+    fn register_specs() {
+        let mut prompt = Prompt::create(context::default());
+        assert_eq!(complete(&mut prompt, &["-d", "a", "b"]), Ok(true));
+        assert_eq!(complete(&mut prompt, &["-f", "b"]), Ok(true));
+
+        let ctx = prompt.context.borrow();
+        assert_eq!(ctx.completion_specs["a"], CompletionSpec::Directories);
+        assert_eq!(ctx.completion_specs["b"], CompletionSpec::Files);
+    }
+
+    #[test]
+    fn register_without_names() {
+        let mut prompt = Prompt::create(context::default());
+        assert_eq!(complete(&mut prompt, &["-d"]), Ok(false));
+        assert!(prompt.context.borrow().completion_specs.is_empty());
+    }
+
+    #[test]
+    fn conflicting_actions() {
+        let mut prompt = Prompt::create(context::default());
+        assert_eq!(complete(&mut prompt, &["-d", "-f", "a"]), Ok(false));
+        assert!(prompt.context.borrow().completion_specs.is_empty());
+    }
+
+    #[test]
+    fn remove_specs() {
+        let mut prompt = Prompt::create(context::default());
+        assert_eq!(complete(&mut prompt, &["-d", "a", "b", "c"]), Ok(true));
+        assert_eq!(complete(&mut prompt, &["-r", "a"]), Ok(true));
+        assert_eq!(prompt.context.borrow().completion_specs.len(), 2);
+
+        assert_eq!(complete(&mut prompt, &["-r"]), Ok(true));
+        assert!(prompt.context.borrow().completion_specs.is_empty());
+    }
+
+    #[test]
+    fn add_list_and_remove() {
+        let mut prompt = Prompt::create(context::default());
+        let list =
+            |prompt: &Prompt| CompleteCommand::list(&prompt.context.borrow().completion_specs);
+        assert_eq!(complete(&mut prompt, &["-d", "pushd", "cd"]), Ok(true));
+        assert_eq!(list(&prompt), vec!["complete -d cd", "complete -d pushd"]);
+
+        assert_eq!(complete(&mut prompt, &["-f", "vim"]), Ok(true));
+        assert_eq!(
+            list(&prompt),
+            vec!["complete -d cd", "complete -d pushd", "complete -f vim"]
+        );
+
+        assert_eq!(complete(&mut prompt, &["-r", "cd"]), Ok(true));
+        assert_eq!(list(&prompt), vec!["complete -d pushd", "complete -f vim"]);
+
+        // Removing a name without a specification fails, but others are still removed.
+        assert_eq!(complete(&mut prompt, &["-r", "cd", "vim"]), Ok(false));
+        assert_eq!(list(&prompt), vec!["complete -d pushd"]);
+
+        assert_eq!(complete(&mut prompt, &["-r"]), Ok(true));
+        assert!(list(&prompt).is_empty());
+        assert_eq!(complete(&mut prompt, &[]), Ok(true));
+    }
+
+    #[test]
+    fn print_unknown_spec() {
+        let mut prompt = Prompt::create(context::default());
+        assert_eq!(complete(&mut prompt, &["-p", "a"]), Ok(false));
+    }
+
+    #[test]
+    fn list_sorted() {
+        let mut specs = HashMap::new();
+        specs.insert("pushd".to_string(), CompletionSpec::Directories);
+        specs.insert("cd".to_string(), CompletionSpec::Directories);
+        specs.insert("cat".to_string(), CompletionSpec::Files);
+        assert_eq!(
+            CompleteCommand::list(&specs),
+            vec!["complete -f cat", "complete -d cd", "complete -d pushd"]
+        );
+    }
+}
@@ -0,0 +1,62 @@
+use super::*;
+
+/// Confirm command asks for confirmation before running a command configured via
+/// "confirm_commands", and aborts unless the answer is yes.
+pub struct ConfirmCommand {
+    /// Expanded command line shown in the question.
+    pub line: String,
+
+    pub cmd: Box<dyn Command>,
+}
+
+impl ConfirmCommand {
+    pub fn new(line: String, cmd: Box<dyn Command>) -> ConfirmCommand {
+        ConfirmCommand { line, cmd }
+    }
+
+    /// Whether `answer` confirms, like "y" or "yes". Anything else, including no answer, doesn't.
+    fn is_yes(answer: &str) -> bool {
+        matches!(answer.trim().to_lowercase().as_ref(), "y" | "yes")
+    }
+}
+
+impl Command for ConfirmCommand {
+    fn execute(&mut self, prompt: &mut Prompt) -> Result<bool, i32> {
+        let question = format!("Run '{}'? [y/N] ", self.line);
+        let confirmed = match prompt.editor.readline(&question) {
+            Ok(answer) => ConfirmCommand::is_yes(&answer),
+            Err(_) => false,
+        };
+        if confirmed {
+            return self.cmd.execute(prompt);
+        }
+
+        println!("Aborted: {}", self.line);
+        prompt
+            .context
+            .borrow_mut()
+            .env
+            .insert("?".to_string(), "1".to_string());
+        Ok(false)
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn is_yes() {
+        assert!(ConfirmCommand::is_yes("y"));
+        assert!(ConfirmCommand::is_yes("Y"));
+        assert!(ConfirmCommand::is_yes(" yes\n"));
+        assert!(!ConfirmCommand::is_yes(""));
+        assert!(!ConfirmCommand::is_yes("n"));
+        assert!(!ConfirmCommand::is_yes("no"));
+        assert!(!ConfirmCommand::is_yes("yess"));
+    }
+}
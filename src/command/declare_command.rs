@@ -0,0 +1,263 @@
+use super::*;
+
+use crate::env::Env;
+use crate::util;
+
+use clap::{App, AppSettings, Arg};
+
+/// Declare command prints variables with their attributes, or sets variables and their
+/// attributes, like `declare -r X=1` for a readonly variable. All plain variables are exported to
+/// programs, so `-x` only assigns, and arrays are never exported.
+pub struct DeclareCommand {
+    args: Vec<String>,
+    app: App<'static, 'static>,
+}
+
+impl DeclareCommand {
+    pub fn new(args: Vec<String>) -> DeclareCommand {
+        DeclareCommand {
+            args,
+            app: App::new("declare")
+                .about("Print variables with their attributes, or set variables and attributes.")
+                .setting(AppSettings::NoBinaryName)
+                .setting(AppSettings::DisableVersion)
+                .arg(
+                    Arg::with_name("print")
+                        .short("p")
+                        .help("Print variables with their attributes, or all without variables."),
+                )
+                .arg(
+                    Arg::with_name("readonly")
+                        .short("r")
+                        .help("Mark variables as readonly, or print readonly variables."),
+                )
+                .arg(
+                    Arg::with_name("export")
+                        .short("x")
+                        .help("Export variables, or print exported variables."),
+                )
+                .arg(Arg::with_name("vars").multiple(true).help(
+                    "Variable with optional value input as: 'variable' or 'variable=value'.",
+                )),
+        }
+    }
+
+    /// Yields variable `key` like `declare -rx key="value"` such that it can be used as input
+    /// again, or `None` if not declared.
+    pub fn format_var(env: &Env, key: &str) -> Option<String> {
+        let readonly = if env.is_readonly(key) { "r" } else { "" };
+        if let Some(values) = env.get_array(key) {
+            let values: Vec<String> = values
+                .iter()
+                .enumerate()
+                .map(|(i, v)| format!("[{}]={}", i, util::double_quote(v)))
+                .collect();
+            return Some(format!(
+                "declare -a{} {}=({})",
+                readonly,
+                key,
+                values.join(" ")
+            ));
+        }
+        match env.get(key) {
+            Some(value) => Some(format!(
+                "declare -{}x {}={}",
+                readonly,
+                key,
+                util::double_quote(value)
+            )),
+            None if !readonly.is_empty() => Some(format!("declare -r {}", key)),
+            None => None,
+        }
+    }
+
+    /// Yields all declared variable keys in sorted order.
+    fn keys(env: &Env) -> Vec<String> {
+        let mut keys: Vec<String> = env.as_ref().keys().cloned().collect();
+        keys.extend(env.array_keys().into_iter().cloned());
+        keys.extend(env.readonly_keys().into_iter().cloned());
+        keys.sort();
+        keys.dedup();
+        keys
+    }
+}
+
+impl Command for DeclareCommand {
+    fn execute(&mut self, prompt: &mut Prompt) -> Result<bool, i32> {
+        let matches = match self.app.get_matches_from_safe_borrow(&self.args) {
+            Ok(matches) => matches,
+            Err(err) => {
+                println!("{}", err);
+                return Ok(false);
+            }
+        };
+        let readonly = matches.is_present("readonly");
+        let export = matches.is_present("export");
+        let vars: Vec<&str> = matches.values_of("vars").map_or(vec![], |v| v.collect());
+
+        let env = &mut prompt.context.borrow_mut().env;
+        if vars.is_empty() {
+            // Only the variables with the given attributes are printed, or all without any.
+            for k in DeclareCommand::keys(env) {
+                if (readonly && !env.is_readonly(&k)) || (export && !env.contains_key(&k)) {
+                    continue;
+                }
+                if let Some(line) = DeclareCommand::format_var(env, &k) {
+                    println!("{}", line);
+                }
+            }
+            return Ok(true);
+        }
+
+        let mut success = true;
+        if matches.is_present("print") {
+            for k in vars {
+                match DeclareCommand::format_var(env, k) {
+                    Some(line) => println!("{}", line),
+                    None => {
+                        println!("declare: {}: not found", k);
+                        success = false;
+                    }
+                }
+            }
+            return Ok(success);
+        }
+
+        for var in vars {
+            let k = match var.find('=') {
+                Some(pos) => {
+                    let k = var[..pos].to_string();
+                    if let Err(err) = env.set(k.clone(), var[pos + 1..].to_string()) {
+                        println!("declare: {}", err);
+                        success = false;
+                        continue;
+                    }
+                    k
+                }
+                None => var.to_string(),
+            };
+            if readonly {
+                env.set_readonly(k);
+            }
+        }
+        Ok(success)
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+impl CommandAliases for DeclareCommand {
+    fn aliases() -> Vec<String> {
+        vec!["declare".to_string(), "typeset".to_string()]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::context;
+
+    fn declare(prompt: &mut Prompt, args: &[&str]) -> bool {
+        let args = args.iter().map(|x| x.to_string()).collect();
+        DeclareCommand::new(args).execute(prompt).unwrap()
+    }
+
+    #[test]
+    fn format_var() {
+        let mut env = Env::default();
+        env.insert("FOO".to_string(), "a \"b\"".to_string());
+        env.set_array("arr".to_string(), vec!["x".to_string(), "y z".to_string()])
+            .unwrap();
+        assert_eq!(
+            DeclareCommand::format_var(&env, "FOO"),
+            Some(r#"declare -x FOO="a \"b\"""#.to_string())
+        );
+        assert_eq!(
+            DeclareCommand::format_var(&env, "arr"),
+            Some(r#"declare -a arr=([0]="x" [1]="y z")"#.to_string())
+        );
+        assert_eq!(DeclareCommand::format_var(&env, "BAR"), None);
+
+        env.set_readonly("FOO".to_string());
+        env.set_readonly("arr".to_string());
+        env.set_readonly("BAR".to_string());
+        assert_eq!(
+            DeclareCommand::format_var(&env, "FOO"),
+            Some(r#"declare -rx FOO="a \"b\"""#.to_string())
+        );
+        assert_eq!(
+            DeclareCommand::format_var(&env, "arr"),
+            Some(r#"declare -ar arr=([0]="x" [1]="y z")"#.to_string())
+        );
+        assert_eq!(
+            DeclareCommand::format_var(&env, "BAR"),
+            Some("declare -r BAR".to_string())
+        );
+    }
+
+    #[test]
+    fn keys_sorted_without_duplicates() {
+        let mut env = Env::default();
+        env.insert("B".to_string(), "1".to_string());
+        env.set_array("C".to_string(), vec![]).unwrap();
+        env.set_readonly("A".to_string());
+        env.set_readonly("B".to_string());
+        assert_eq!(DeclareCommand::keys(&env), vec!["A", "B", "C"]);
+    }
+
+    #[test]
+    fn print() {
+        let mut prompt = Prompt::create(context::default());
+        prompt
+            .context
+            .borrow_mut()
+            .env
+            .insert("FOO".to_string(), "bar".to_string());
+        assert!(declare(&mut prompt, &["-p"]));
+        assert!(declare(&mut prompt, &["-p", "FOO"]));
+        assert!(!declare(&mut prompt, &["-p", "FOO", "UNKNOWN_VAR"]));
+    }
+
+    #[test]
+    fn set_export() {
+        let mut prompt = Prompt::create(context::default());
+        assert!(declare(&mut prompt, &["-x", "A=1", "B="]));
+
+        let env = &prompt.context.borrow().env;
+        assert_eq!(env.get("A"), Some(&"1".to_string()));
+        assert_eq!(env.get("B"), Some(&"".to_string()));
+        assert!(!env.is_readonly("A"));
+    }
+
+    #[test]
+    fn set_readonly() {
+        let mut prompt = Prompt::create(context::default());
+        prompt
+            .context
+            .borrow_mut()
+            .env
+            .insert("BAR".to_string(), "1".to_string());
+        assert!(declare(&mut prompt, &["-r", "FOO=bar", "BAR"]));
+        assert!(prompt.context.borrow().env.is_readonly("FOO"));
+        assert!(prompt.context.borrow().env.is_readonly("BAR"));
+
+        assert!(!declare(&mut prompt, &["FOO=baz"]));
+        assert!(!declare(&mut prompt, &["-r", "BAR=2"]));
+        let mut cmd = UnsetCommand::new(vec!["FOO".to_string()]);
+        assert!(!cmd.execute(&mut prompt).unwrap());
+
+        let env = &prompt.context.borrow().env;
+        assert_eq!(env.get("FOO"), Some(&"bar".to_string()));
+        assert_eq!(env.get("BAR"), Some(&"1".to_string()));
+    }
+
+    #[test]
+    fn invalid_option() {
+        let mut prompt = Prompt::create(context::default());
+        assert!(!declare(&mut prompt, &["-i", "X=5"]));
+        assert!(!prompt.context.borrow().env.contains_key("X"));
+    }
+}
@@ -0,0 +1,151 @@
+use super::*;
+
+use crate::util;
+
+use std::io::Write;
+
+/// Echo command prints its arguments separated by spaces and followed by a newline, like in Bash.
+pub struct EchoCommand {
+    args: Vec<String>,
+}
+
+impl EchoCommand {
+    pub fn new(args: Vec<String>) -> EchoCommand {
+        EchoCommand { args }
+    }
+
+    /// Yields the output for `args`.
+    ///
+    /// Leading flags, also combined like "-ne", are processed in order: `-n` omits the newline,
+    /// `-e` interprets escape sequences, and `-E` doesn't, which is the default. The first argument
+    /// that isn't made of flags, like "--" or "-x", is printed together with the rest.
+    ///
+    /// Escape sequences are those of `util::unescape_one()`, except that octal values start with
+    /// `\0`, like `\0101`, and `\c` stops all output, including the newline.
+    pub fn output(args: &[String]) -> Vec<u8> {
+        let mut newline = true;
+        let mut escapes = false;
+        let mut words = args.iter().peekable();
+        while let Some(flags) = words
+            .peek()
+            .and_then(|word| word.strip_prefix('-'))
+            .filter(|flags| !flags.is_empty() && flags.chars().all(|c| "neE".contains(c)))
+        {
+            for flag in flags.chars() {
+                match flag {
+                    'n' => newline = false,
+                    'e' => escapes = true,
+                    _ => escapes = false,
+                }
+            }
+            words.next();
+        }
+
+        let mut output = Vec::new();
+        for (i, word) in words.enumerate() {
+            if i > 0 {
+                output.push(b' ');
+            }
+            if !escapes {
+                output.extend_from_slice(word.as_bytes());
+                continue;
+            }
+
+            let mut chars = word.chars().peekable();
+            while let Some(ch) = chars.next() {
+                if ch != '\\' {
+                    output.extend_from_slice(ch.encode_utf8(&mut [0; 4]).as_bytes());
+                    continue;
+                }
+                match chars.peek() {
+                    Some('c') => return output,
+                    Some('0') => {
+                        chars.next();
+                        match chars.peek() {
+                            Some('0'..='7') => output.append(&mut util::unescape_one(&mut chars)),
+                            _ => output.push(0),
+                        }
+                    }
+                    Some('1'..='7') => output.push(b'\\'),
+                    _ => output.append(&mut util::unescape_one(&mut chars)),
+                }
+            }
+        }
+        if newline {
+            output.push(b'\n');
+        }
+        output
+    }
+}
+
+impl Command for EchoCommand {
+    fn execute(&mut self, _prompt: &mut Prompt) -> Result<bool, i32> {
+        let mut stdout = io::stdout();
+        let _ = stdout.write_all(&EchoCommand::output(&self.args));
+        let _ = stdout.flush();
+        Ok(true)
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+impl CommandAliases for EchoCommand {
+    fn aliases() -> Vec<String> {
+        vec!["echo".to_string()]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn output(args: &[&str]) -> String {
+        let args: Vec<String> = args.iter().map(|a| a.to_string()).collect();
+        String::from_utf8(EchoCommand::output(&args)).unwrap()
+    }
+
+    #[test]
+    fn plain() {
+        assert_eq!(output(&[]), "\n");
+        assert_eq!(output(&["a", "b c"]), "a b c\n");
+    }
+
+    #[test]
+    fn escapes_kept_by_default() {
+        assert_eq!(output(&["a\\nb"]), "a\\nb\n");
+        assert_eq!(output(&["-E", "a\\tb"]), "a\\tb\n");
+        assert_eq!(output(&["-e", "-E", "a\\tb"]), "a\\tb\n");
+    }
+
+    #[test]
+    fn escapes_interpreted() {
+        assert_eq!(output(&["-e", "a\\tb", "\\\\"]), "a\tb \\\n");
+        assert_eq!(output(&["-E", "-e", "a\\nb"]), "a\nb\n");
+        assert_eq!(output(&["-e", "\\0101\\x42\\101\\0"]), "AB\\101\0\n");
+        assert_eq!(output(&["-e", "a\\cb", "c"]), "a");
+    }
+
+    #[test]
+    fn no_newline() {
+        assert_eq!(output(&["-n", "a"]), "a");
+        assert_eq!(output(&["-n"]), "");
+    }
+
+    #[test]
+    fn combined_flags() {
+        assert_eq!(output(&["-ne", "a\\n"]), "a\n");
+        assert_eq!(output(&["-eEn", "a\\n"]), "a\\n");
+        assert_eq!(output(&["-n", "-e", "a\\tb"]), "a\tb");
+    }
+
+    #[test]
+    fn flags_stop_at_first_other_word() {
+        assert_eq!(output(&["--", "-n"]), "-- -n\n");
+        assert_eq!(output(&["-nx", "a"]), "-nx a\n");
+        assert_eq!(output(&["-", "-n"]), "- -n\n");
+        assert_eq!(output(&["a", "-n"]), "a -n\n");
+        assert_eq!(output(&["-n", "a", "-e", "\\t"]), "a -e \\t");
+    }
+}
@@ -0,0 +1,183 @@
+use super::*;
+
+use std::collections::HashSet;
+
+use clap::{App, AppSettings, Arg};
+
+/// Enable command enables or disables builtins. A disabled builtin is resolved as a program in
+/// `$PATH` instead, like using the `echo` program instead of a builtin. `enable` itself can't be
+/// disabled.
+pub struct EnableCommand {
+    args: Vec<String>,
+    app: App<'static, 'static>,
+}
+
+impl EnableCommand {
+    pub fn new(args: Vec<String>) -> EnableCommand {
+        EnableCommand {
+            args,
+            app: App::new("enable")
+                .about("Enable and disable builtins.")
+                .setting(AppSettings::NoBinaryName)
+                .setting(AppSettings::DisableVersion)
+                .arg(
+                    Arg::with_name("disable")
+                        .short("n")
+                        .help("Disable builtins, or list disabled builtins if none are given."),
+                )
+                .arg(
+                    Arg::with_name("names")
+                        .multiple(true)
+                        .help("Builtin names. With no names, all enabled builtins are listed."),
+                ),
+        }
+    }
+
+    /// Yields `enable name` lines of enabled builtins, or `enable -n name` lines of disabled
+    /// builtins with `disabled`, in sorted order.
+    fn list(disabled_builtins: &HashSet<String>, disabled: bool) -> Vec<String> {
+        let mut names = builtins();
+        names.sort();
+        names
+            .into_iter()
+            .filter(|name| disabled_builtins.contains(name) == disabled)
+            .map(|name| {
+                if disabled {
+                    format!("enable -n {}", name)
+                } else {
+                    format!("enable {}", name)
+                }
+            })
+            .collect()
+    }
+}
+
+impl Command for EnableCommand {
+    fn execute(&mut self, prompt: &mut Prompt) -> Result<bool, i32> {
+        let matches = self.app.get_matches_from_safe_borrow(&self.args);
+        if let Err(err) = matches {
+            println!("{}", err);
+            return Ok(false);
+        }
+
+        let matches = matches.unwrap();
+        let disable = matches.is_present("disable");
+        let mut ctx = prompt.context.borrow_mut();
+
+        let names: Vec<&str> = match matches.values_of("names") {
+            Some(names) => names.collect(),
+            None => {
+                for line in EnableCommand::list(&ctx.disabled_builtins, disable) {
+                    println!("{}", line);
+                }
+                return Ok(true);
+            }
+        };
+
+        let builtins = builtins();
+        let mut success = true;
+        for name in names {
+            if !builtins.iter().any(|b| b == name) {
+                println!("enable: {}: not a shell builtin", name);
+                success = false;
+            } else if disable && name == "enable" {
+                // Otherwise, there would be no way to enable builtins again.
+                println!("enable: enable: cannot be disabled");
+                success = false;
+            } else if disable {
+                ctx.disabled_builtins.insert(name.to_string());
+            } else {
+                ctx.disabled_builtins.remove(name);
+            }
+        }
+        Ok(success)
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+impl CommandAliases for EnableCommand {
+    fn aliases() -> Vec<String> {
+        vec!["enable".to_string()]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::general_command::GeneralCommand;
+    use crate::context;
+
+    fn enable(prompt: &mut Prompt, args: &[&str]) -> Result<bool, i32> {
+        let args = args.iter().map(|a| a.to_string()).collect();
+        EnableCommand::new(args).execute(prompt)
+    }
+
+    #[test]
+    fn disable_and_enable() {
+        let mut prompt = Prompt::create(context::default());
+        assert_eq!(enable(&mut prompt, &["-n", "printf", "set"]), Ok(true));
+        {
+            let ctx = prompt.context.borrow();
+            assert!(ctx.disabled_builtins.contains("printf"));
+            assert!(ctx.disabled_builtins.contains("set"));
+        }
+
+        assert_eq!(enable(&mut prompt, &["printf"]), Ok(true));
+        let ctx = prompt.context.borrow();
+        assert!(!ctx.disabled_builtins.contains("printf"));
+        assert!(ctx.disabled_builtins.contains("set"));
+    }
+
+    #[test]
+    fn not_a_builtin() {
+        let mut prompt = Prompt::create(context::default());
+        assert_eq!(enable(&mut prompt, &["-n", "ls"]), Ok(false));
+        assert!(prompt.context.borrow().disabled_builtins.is_empty());
+    }
+
+    #[test]
+    fn enable_not_disabled() {
+        let mut prompt = Prompt::create(context::default());
+        assert_eq!(enable(&mut prompt, &["-n", "enable", "set"]), Ok(false));
+        let ctx = prompt.context.borrow();
+        assert!(!ctx.disabled_builtins.contains("enable"));
+        assert!(ctx.disabled_builtins.contains("set"));
+    }
+
+    #[test]
+    fn disabled_builtin_falls_through() {
+        let mut prompt = Prompt::create(context::default());
+        assert_eq!(enable(&mut prompt, &["-n", "printf"]), Ok(true));
+
+        let cmd = parse("printf".to_string(), vec![], &prompt.context.borrow());
+        assert!(cmd.as_any().downcast_ref::<GeneralCommand>().is_some());
+
+        assert_eq!(enable(&mut prompt, &["printf"]), Ok(true));
+        let cmd = parse("printf".to_string(), vec![], &prompt.context.borrow());
+        assert!(cmd.as_any().downcast_ref::<PrintfCommand>().is_some());
+    }
+
+    #[test]
+    fn list_enabled() {
+        let mut disabled = HashSet::new();
+        disabled.insert("set".to_string());
+        let lines = EnableCommand::list(&disabled, false);
+        assert_eq!(lines.len(), builtins().len() - 1);
+        assert!(lines.contains(&"enable cd".to_string()));
+        assert!(!lines.contains(&"enable set".to_string()));
+
+        let mut sorted = lines.clone();
+        sorted.sort();
+        assert_eq!(lines, sorted);
+    }
+
+    #[test]
+    fn list_disabled() {
+        let mut disabled = HashSet::new();
+        disabled.insert("set".to_string());
+        assert_eq!(EnableCommand::list(&disabled, true), vec!["enable -n set"]);
+    }
+}
@@ -3,9 +3,10 @@ use super::*;
 use clap::{App, AppSettings, Arg};
 
 /// Exit command provides an exit code on execution, if no argument is provided the code zero is
-/// used.
+/// used. Like other shells, the code is masked to the range 0-255.
 pub struct ExitCommand {
     pub code: i32,
+    invalid: Option<String>,
     args: Vec<String>,
     app: App<'static, 'static>,
 }
@@ -15,26 +16,51 @@ impl ExitCommand {
         let mut app = App::new("exit")
             .setting(AppSettings::NoBinaryName)
             .setting(AppSettings::DisableVersion)
+            .setting(AppSettings::AllowNegativeNumbers)
             .arg(
                 Arg::with_name("code")
                     .help("Exit code to return to parent program.")
                     .index(1)
-                    .default_value("0")
-                    .validator(|v: String| -> Result<(), String> {
-                        if v.parse::<i32>().is_ok() {
-                            return Ok(());
-                        }
-                        Err(String::from("Exit code must be an integer!"))
-                    }),
+                    .default_value("0"),
             );
 
         let mut code = 0;
+        let mut invalid = None;
         let matches = app.get_matches_from_safe_borrow(&args);
         if let Ok(value) = matches {
-            code = value.value_of("code").unwrap().parse::<i32>().unwrap();
+            let value = value.value_of("code").unwrap();
+            match ExitCommand::parse_code(value) {
+                Some(c) => code = c,
+                None => {
+                    code = 2;
+                    invalid = Some(value.to_string());
+                }
+            }
         }
 
-        ExitCommand { code, args, app }
+        ExitCommand {
+            code,
+            invalid,
+            args,
+            app,
+        }
+    }
+
+    /// Whether exiting is allowed regarding stopped jobs. Like in Bash, the first attempt warns and
+    /// is refused if any job is stopped, while trying again right after exits anyway.
+    pub fn stopped_jobs_allow_exit(ctx: &mut ContextData) -> bool {
+        if !ctx.jobs.has_stopped() || ctx.stopped_jobs_warned {
+            return true;
+        }
+        eprintln!("There are stopped jobs.");
+        ctx.stopped_jobs_warned = true;
+        false
+    }
+
+    /// Parses `value` as an integer and masks it to the range 0-255, such that `256` yields `0` and
+    /// `-1` yields `255`.
+    pub fn parse_code(value: &str) -> Option<i32> {
+        value.parse::<i64>().ok().map(|c| (c & 0xFF) as i32)
     }
 }
 
@@ -46,15 +72,24 @@ impl Command for ExitCommand {
             return Ok(false);
         }
 
+        if let Some(value) = &self.invalid {
+            eprintln!("exit: {}: numeric argument required", value);
+            return prompt.shutdown(self.code);
+        }
+
+        if !ExitCommand::stopped_jobs_allow_exit(&mut prompt.context.borrow_mut()) {
+            return Ok(false);
+        }
+
         // Set the exit code to the previous command if it was not passed explicitly to exit.
         if self.args.is_empty() {
             if let Some(c) = prompt.context.borrow().env.get("?") {
-                if let Ok(c) = c.parse::<i32>() {
+                if let Some(c) = ExitCommand::parse_code(c) {
                     self.code = c
                 }
             }
         }
-        Err(self.code)
+        prompt.shutdown(self.code)
     }
 
     fn as_any(&self) -> &dyn Any {
@@ -81,8 +116,71 @@ mod tests {
 
     #[test]
     fn invalid_arg() {
-        let cmd = ExitCommand::new(vec![String::from("abc")]);
-        assert_eq!(cmd.code, 0);
+        let mut prompt = Prompt::create(context::default());
+        let mut cmd = ExitCommand::new(vec![String::from("abc")]);
+        assert_eq!(cmd.code, 2);
+        assert_eq!(cmd.invalid, Some(String::from("abc")));
+        assert_eq!(cmd.execute(&mut prompt), Err(2));
+    }
+
+    #[test]
+    fn masked_codes() {
+        assert_eq!(ExitCommand::new(vec![String::from("256")]).code, 0);
+        assert_eq!(ExitCommand::new(vec![String::from("257")]).code, 1);
+        assert_eq!(ExitCommand::new(vec![String::from("-1")]).code, 255);
+        assert_eq!(ExitCommand::new(vec![String::from("-256")]).code, 0);
+        assert_eq!(ExitCommand::new(vec![String::from("255")]).code, 255);
+    }
+
+    #[test]
+    fn parse_code() {
+        assert_eq!(ExitCommand::parse_code("0"), Some(0));
+        assert_eq!(ExitCommand::parse_code("300"), Some(44));
+        assert_eq!(ExitCommand::parse_code("-2"), Some(254));
+        assert_eq!(ExitCommand::parse_code("foo"), None);
+        assert_eq!(ExitCommand::parse_code(""), None);
+    }
+
+    #[test]
+    fn refused_once_with_stopped_jobs() {
+        let mut prompt = Prompt::create(context::default());
+        let child = process::Command::new("sleep").arg("10").spawn().unwrap();
+        let pid = child.id() as libc::pid_t;
+        unsafe { libc::kill(pid, libc::SIGSTOP) };
+        prompt
+            .context
+            .borrow_mut()
+            .jobs
+            .add(child, "sleep 10".to_string());
+        while !prompt.context.borrow().jobs.has_stopped() {
+            prompt.context.borrow_mut().jobs.reap();
+        }
+
+        let exit = || Ok(Box::new(ExitCommand::new(vec!["3".to_string()])) as Box<dyn Command>);
+        assert_eq!(execute(exit(), &mut prompt), None);
+        assert!(prompt.context.borrow().stopped_jobs_warned);
+        assert_eq!(execute(exit(), &mut prompt), Some(3));
+
+        // Any command in between makes exit refuse again.
+        let set = Box::new(SetCommand::new(vec![]));
+        assert_eq!(execute(Ok(set), &mut prompt), None);
+        assert!(!prompt.context.borrow().stopped_jobs_warned);
+        assert_eq!(execute(exit(), &mut prompt), None);
+
+        unsafe { libc::kill(pid, libc::SIGKILL) };
+    }
+
+    #[test]
+    fn shuts_down() {
+        let mut prompt = Prompt::without_history(context::default());
+        let cmd = prompt.parse_command("exit 3");
+        assert_eq!(execute(cmd, &mut prompt), Some(3));
+        assert!(prompt.is_shut_down());
+
+        // Parsing input again means the shell went on, like after `$(exit)`.
+        let cmd = prompt.parse_command("true");
+        assert_eq!(execute(cmd, &mut prompt), None);
+        assert!(!prompt.is_shut_down());
     }
 
     #[test]
@@ -0,0 +1,116 @@
+use super::*;
+
+/// Expansion command prints how a line is expanded, one word per line, without running it, like
+/// `expansion 'ls $HOME/*.rs'`. The arguments are joined into the line, so quoting it keeps globs
+/// and quotes for the expansion shown.
+pub struct ExpansionCommand {
+    args: Vec<String>,
+}
+
+impl ExpansionCommand {
+    pub fn new(args: Vec<String>) -> ExpansionCommand {
+        ExpansionCommand { args }
+    }
+
+    /// Yields the program and arguments that `line` expands to, with aliases, variables, tilde, and
+    /// globs expanded and quotes removed.
+    pub fn words(line: &str, prompt: &mut Prompt) -> Result<Vec<String>, String> {
+        let expansion = prompt.expand(line).map_err(|err| err.to_string())?;
+        let mut words = vec![expansion.program];
+        words.extend(expansion.args);
+        Ok(words)
+    }
+}
+
+impl Command for ExpansionCommand {
+    fn execute(&mut self, prompt: &mut Prompt) -> Result<bool, i32> {
+        let line = self.args.join(" ");
+        if line.trim().is_empty() {
+            println!("expansion: usage: expansion LINE");
+            return Ok(false);
+        }
+
+        match ExpansionCommand::words(&line, prompt) {
+            Ok(words) => {
+                for word in words {
+                    println!("{}", word);
+                }
+                Ok(true)
+            }
+            Err(err) => {
+                println!("expansion: {}", err);
+                Ok(false)
+            }
+        }
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+impl CommandAliases for ExpansionCommand {
+    fn aliases() -> Vec<String> {
+        vec!["expansion".to_string()]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::context;
+
+    use std::fs;
+
+    use tempfile::TempDir;
+
+    #[test]
+    fn words() {
+        let dir = TempDir::new().unwrap();
+        for file in &["b.rs", "a.rs", "c.txt"] {
+            fs::write(dir.path().join(file), "").unwrap();
+        }
+
+        let mut prompt = Prompt::create(context::default());
+        prompt
+            .context
+            .borrow_mut()
+            .env
+            .insert("HOME".to_string(), "/home/user".to_string());
+        let line = format!("echo $HOME {}/*.rs", dir.path().display());
+        let expected = vec![
+            "echo".to_string(),
+            "/home/user".to_string(),
+            dir.path().join("a.rs").display().to_string(),
+            dir.path().join("b.rs").display().to_string(),
+        ];
+        assert_eq!(ExpansionCommand::words(&line, &mut prompt), Ok(expected));
+    }
+
+    #[test]
+    fn words_alias() {
+        let mut prompt = Prompt::create(context::default());
+        prompt
+            .context
+            .borrow_mut()
+            .config
+            .aliases
+            .insert("ll".to_string(), "ls -l".to_string());
+        assert_eq!(
+            ExpansionCommand::words("ll \"a b\"", &mut prompt),
+            Ok(vec!["ls".to_string(), "-l".to_string(), "a b".to_string()])
+        );
+    }
+
+    #[test]
+    fn does_not_run() {
+        let mut prompt = Prompt::create(context::default());
+        let mut cmd = ExpansionCommand::new(vec!["exit 3".to_string()]);
+        assert_eq!(cmd.execute(&mut prompt), Ok(true));
+
+        let mut cmd = ExpansionCommand::new(vec![]);
+        assert_eq!(cmd.execute(&mut prompt), Ok(false));
+        let mut cmd = ExpansionCommand::new(vec!["echo \"a".to_string()]);
+        assert_eq!(cmd.execute(&mut prompt), Ok(false));
+    }
+}
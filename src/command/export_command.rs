@@ -1,5 +1,7 @@
 use super::*;
 
+use crate::util;
+
 use clap::{App, AppSettings, Arg};
 
 /// Export command adds (variable, value) pairs to environment.
@@ -16,13 +18,17 @@ impl ExportCommand {
                 .about("List or export new environment variables with values.")
                 .setting(AppSettings::NoBinaryName)
                 .setting(AppSettings::DisableVersion)
-                .arg(
-                    Arg::with_name("vars").multiple(true).help(
-                        "Variable with optional value input as: 'variable' or 'variable=value'",
-                    ),
-                ),
+                .arg(Arg::with_name("vars").multiple(true).help(
+                    "Variable with optional value input as: 'variable' or 'variable=value'. \
+                     A variable without value prints its current value, if set.",
+                )),
         }
     }
+
+    /// Yields `export key="value"` with the value quoted such that it can be used as input again.
+    fn format_var(key: &str, value: &str) -> String {
+        format!("export {}={}", key, util::double_quote(value))
+    }
 }
 
 impl Command for ExportCommand {
@@ -33,18 +39,30 @@ impl Command for ExportCommand {
             return Ok(false);
         }
 
+        let mut success = true;
         if self.args.is_empty() {
             print!("{}", prompt.context.borrow().env);
         } else {
             for var in &self.args {
-                let (k, v) = match var.find('=') {
-                    Some(pos) => (var[..pos].to_string(), var[pos + 1..].to_string()),
-                    None => (var.clone(), String::from("")),
-                };
-                prompt.context.borrow_mut().env.insert(k, v);
+                match var.find('=') {
+                    Some(pos) => {
+                        let (k, v) = (var[..pos].to_string(), var[pos + 1..].to_string());
+                        if let Err(err) = prompt.context.borrow_mut().env.set(k, v) {
+                            println!("export: {}", err);
+                            success = false;
+                        }
+                    }
+
+                    // Print current value without modifying it, if set.
+                    None => {
+                        if let Some(v) = prompt.context.borrow().env.get(var) {
+                            println!("{}", ExportCommand::format_var(var, v));
+                        }
+                    }
+                }
             }
         }
-        Ok(true)
+        Ok(success)
     }
 
     fn as_any(&self) -> &dyn Any {
@@ -57,3 +75,68 @@ impl CommandAliases for ExportCommand {
         vec!["export".to_string()]
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::context;
+
+    #[test]
+    fn export_value() {
+        let mut prompt = Prompt::create(context::default());
+        let mut cmd = ExportCommand::new(vec!["A=1".to_string(), "B=".to_string()]);
+        assert!(cmd.execute(&mut prompt).unwrap());
+
+        let ctx = prompt.context.borrow();
+        assert_eq!(ctx.env.get("A"), Some(&"1".to_string()));
+        assert_eq!(ctx.env.get("B"), Some(&"".to_string()));
+    }
+
+    #[test]
+    fn export_name_keeps_value() {
+        let mut prompt = Prompt::create(context::default());
+        prompt
+            .context
+            .borrow_mut()
+            .env
+            .insert("PATH".to_string(), "/bin:/usr/bin".to_string());
+
+        let mut cmd = ExportCommand::new(vec!["PATH".to_string()]);
+        assert!(cmd.execute(&mut prompt).unwrap());
+        assert_eq!(
+            prompt.context.borrow().env.get("PATH"),
+            Some(&"/bin:/usr/bin".to_string())
+        );
+    }
+
+    #[test]
+    fn export_unknown_name_not_added() {
+        let mut prompt = Prompt::create(context::default());
+        let mut cmd = ExportCommand::new(vec!["FOO".to_string()]);
+        assert!(cmd.execute(&mut prompt).unwrap());
+        assert!(!prompt.context.borrow().env.contains_key("FOO"));
+    }
+
+    #[test]
+    fn export_readonly_fails() {
+        let mut prompt = Prompt::create(context::default());
+        {
+            let env = &mut prompt.context.borrow_mut().env;
+            env.insert("A".to_string(), "1".to_string());
+            env.set_readonly("A".to_string());
+        }
+
+        let mut cmd = ExportCommand::new(vec!["A=2".to_string()]);
+        assert!(!cmd.execute(&mut prompt).unwrap());
+        assert_eq!(prompt.context.borrow().env.get("A"), Some(&"1".to_string()));
+    }
+
+    #[test]
+    fn format_var() {
+        assert_eq!(
+            ExportCommand::format_var("PATH", "/bin:/usr/bin"),
+            r#"export PATH="/bin:/usr/bin""#
+        );
+    }
+}
@@ -1,6 +1,39 @@
 use super::*;
 
-use std::process::Stdio;
+use crate::context::ContextData;
+use crate::path_commands::DEFAULT_PATH;
+
+use std::io::Read;
+use std::mem;
+use std::os::unix::io::RawFd;
+use std::process::{Child, ExitStatus, Stdio};
+
+/// Terminal attributes saved before running a program in the foreground and restored after it
+/// exits, such that the prompt stays usable even if the program left the terminal in raw mode or
+/// with echo disabled, like when crashing. Only supported on Unix.
+pub struct TermState {
+    fd: RawFd,
+    attrs: libc::termios,
+}
+
+impl TermState {
+    /// Saves terminal attributes of `fd`, unless it isn't a terminal.
+    pub fn save(fd: RawFd) -> Option<TermState> {
+        if unsafe { libc::isatty(fd) } != 1 {
+            return None;
+        }
+        let mut attrs: libc::termios = unsafe { mem::zeroed() };
+        if unsafe { libc::tcgetattr(fd, &mut attrs) } == -1 {
+            return None;
+        }
+        Some(TermState { fd, attrs })
+    }
+
+    /// Restores the saved terminal attributes.
+    pub fn restore(&self) {
+        unsafe { libc::tcsetattr(self.fd, libc::TCSANOW, &self.attrs) };
+    }
+}
 
 /// General command that executes program with arguments and waits for it to finish.
 pub struct GeneralCommand {
@@ -12,50 +45,166 @@ impl GeneralCommand {
     pub fn new(program: String, args: Vec<String>) -> GeneralCommand {
         GeneralCommand { program, args }
     }
-}
 
-impl Command for GeneralCommand {
-    fn execute(&mut self, prompt: &mut Prompt) -> Result<bool, i32> {
-        let mut ctx = prompt.context.borrow_mut();
-
-        // Spawn child process and inherit stdout/stderr so it is displayed within carapace,
-        // including term colors.
-        let proc = process::Command::new(&self.program)
-            .args(&self.args)
-            .env_clear()
-            .envs(ctx.env.as_ref())
-            .stdout(Stdio::inherit())
-            .stderr(Stdio::inherit())
-            .spawn();
+    /// Creates command running `line` via `shell`, like `/bin/sh -c 'line'`, for syntax that isn't
+    /// supported by carapace itself.
+    pub fn with_shell(shell: &str, line: &str) -> GeneralCommand {
+        GeneralCommand::new(shell.to_string(), vec!["-c".to_string(), line.to_string()])
+    }
+
+    /// Executes program like `execute()` but with stdout captured instead of inherited, which is
+    /// used when the output is needed as a value, like with `$(...)`. The output is decoded as
+    /// lossy UTF-8 and trailing newlines are removed. Stderr is still inherited.
+    ///
+    /// At most `max_capture_bytes` of the config are kept, with a warning if there was more. The
+    /// output is closed at that point, such that a program writing endlessly is stopped, like by
+    /// `head`.
+    pub fn capture(&self, prompt: &mut Prompt) -> (Result<bool, i32>, String) {
+        let (proc, max) = {
+            let ctx = prompt.context.borrow();
+            let proc = self
+                .command(&ctx)
+                .stdout(Stdio::piped())
+                .stderr(Stdio::inherit())
+                .spawn();
+            (proc, ctx.config.max_capture_bytes)
+        };
 
         match proc {
             Ok(mut child) => {
-                // Wait for child process to exit.
+                let (output, truncated) = match child.stdout.take() {
+                    Some(stdout) => GeneralCommand::read_capped(stdout, max),
+                    None => (vec![], false),
+                };
+                if truncated {
+                    eprintln!("{}: output truncated to {} bytes", self.program, max);
+                }
                 if let Ok(status) = child.wait() {
-                    // Update $? with exit code.
-                    let code = status.code().unwrap_or(0);
-                    ctx.env.insert("?".to_string(), code.to_string());
-
-                    // Exit immediately if errexit option enabled.
-                    let success = status.success();
-                    if ctx.errexit && !success {
-                        return Err(code);
-                    } else {
-                        return Ok(success);
+                    let mut text = String::from_utf8_lossy(&output).into_owned();
+                    while text.ends_with('\n') {
+                        text.pop();
                     }
+                    let res = GeneralCommand::finish(status, &mut prompt.context.borrow_mut());
+                    return (res, text);
                 }
+                (Ok(false), String::new())
             }
-            Err(err) => {
-                println!("{}", err);
-                if ctx.errexit {
-                    return Err(1);
+            Err(err) => (
+                GeneralCommand::spawn_failed(err, &prompt.context.borrow()),
+                String::new(),
+            ),
+        }
+    }
+
+    /// Reads at most `max` bytes from `reader` and yields them, and whether there was more. The
+    /// reader is dropped afterwards without reading the rest.
+    pub fn read_capped<R: Read>(reader: R, max: usize) -> (Vec<u8>, bool) {
+        let mut output = Vec::new();
+        let mut reader = reader.take(max as u64 + 1);
+        if reader.read_to_end(&mut output).is_err() {
+            return (output, false);
+        }
+        let truncated = output.len() > max;
+        output.truncate(max);
+        (output, truncated)
+    }
+
+    /// Spawns process of program with `stdin`, `stdout`, and `stderr`, like for a command of a
+    /// pipeline or a background job, and yields it without waiting for it.
+    pub fn spawn(
+        &self,
+        ctx: &ContextData,
+        stdin: Stdio,
+        stdout: Stdio,
+        stderr: Stdio,
+    ) -> io::Result<Child> {
+        self.command(ctx)
+            .stdin(stdin)
+            .stdout(stdout)
+            .stderr(stderr)
+            .spawn()
+    }
+
+    /// Creates process of program and arguments with the environment of the shell. If `$PATH` is
+    /// unset, `DEFAULT_PATH` is used to find the program and is passed on.
+    fn command(&self, ctx: &ContextData) -> process::Command {
+        let mut cmd = process::Command::new(&self.program);
+        cmd.args(&self.args).env_clear().envs(ctx.env.as_ref());
+        if !ctx.env.contains_key("PATH") {
+            cmd.env("PATH", DEFAULT_PATH);
+        }
+        cmd
+    }
+
+    /// Updates $? with exit code of finished process and yields the command result.
+    pub fn finish(status: ExitStatus, ctx: &mut ContextData) -> Result<bool, i32> {
+        let code = status.code().unwrap_or(0);
+        ctx.env.insert("?".to_string(), code.to_string());
+
+        // Exit immediately if errexit option enabled, unless the status is checked.
+        let success = status.success();
+        if !success && ctx.exits_on_failure() {
+            Err(code)
+        } else {
+            Ok(success)
+        }
+    }
+
+    /// Reports that program could not be started and yields the command result.
+    pub fn spawn_failed(err: io::Error, ctx: &ContextData) -> Result<bool, i32> {
+        println!("{}", err);
+        if ctx.exits_on_failure() {
+            return Err(1);
+        }
+        Ok(false)
+    }
+}
+
+impl GeneralCommand {
+    /// Runs program with input from `stdin` and output to `stdout` and `stderr`, and waits for it to
+    /// finish.
+    pub fn run(
+        &self,
+        prompt: &mut Prompt,
+        stdin: Stdio,
+        stdout: Stdio,
+        stderr: Stdio,
+    ) -> Result<bool, i32> {
+        let term = TermState::save(libc::STDOUT_FILENO);
+
+        // Spawn child process and inherit stdout/stderr, unless redirected, so it is displayed
+        // within carapace, including term colors. The output is passed through untouched since it
+        // never goes through carapace itself.
+        let proc = self
+            .command(&prompt.context.borrow())
+            .stdin(stdin)
+            .stdout(stdout)
+            .stderr(stderr)
+            .spawn();
+
+        // The context is not borrowed while waiting for the child process to exit.
+        match proc {
+            Ok(mut child) => {
+                let status = child.wait();
+                if let Some(term) = term {
+                    term.restore();
+                }
+                if let Ok(status) = status {
+                    return GeneralCommand::finish(status, &mut prompt.context.borrow_mut());
                 }
             }
+            Err(err) => return GeneralCommand::spawn_failed(err, &prompt.context.borrow()),
         }
 
-        // Program could not be started.
+        // Program could not be waited for.
         Ok(false)
     }
+}
+
+impl Command for GeneralCommand {
+    fn execute(&mut self, prompt: &mut Prompt) -> Result<bool, i32> {
+        self.run(prompt, Stdio::inherit(), Stdio::inherit(), Stdio::inherit())
+    }
 
     fn as_any(&self) -> &dyn Any {
         self
@@ -66,6 +215,8 @@ impl Command for GeneralCommand {
 mod tests {
     use super::*;
 
+    use crate::context;
+
     #[test]
     fn new() {
         let prog = String::from("prog");
@@ -74,4 +225,159 @@ mod tests {
         assert_eq!(cmd.program, prog);
         assert_eq!(cmd.args, args);
     }
+
+    /// Opens a pseudo terminal and yields the file descriptors of its master and slave side.
+    fn open_pty() -> (RawFd, RawFd) {
+        unsafe {
+            let master = libc::posix_openpt(libc::O_RDWR | libc::O_NOCTTY);
+            assert!(master >= 0);
+            assert_eq!(libc::grantpt(master), 0);
+            assert_eq!(libc::unlockpt(master), 0);
+            let slave = libc::open(libc::ptsname(master), libc::O_RDWR | libc::O_NOCTTY);
+            assert!(slave >= 0);
+            (master, slave)
+        }
+    }
+
+    #[test]
+    fn term_state_not_saved_without_terminal() {
+        let mut fds = [0; 2];
+        assert_eq!(unsafe { libc::pipe(fds.as_mut_ptr()) }, 0);
+        assert!(TermState::save(fds[1]).is_none());
+        unsafe {
+            libc::close(fds[0]);
+            libc::close(fds[1]);
+        }
+    }
+
+    #[test]
+    fn term_state_restored() {
+        let (master, slave) = open_pty();
+        let term = TermState::save(slave).unwrap();
+        let lflag = term.attrs.c_lflag;
+        assert_ne!(lflag & libc::ECHO, 0);
+
+        // Put the terminal into raw mode, like an editor would.
+        let mut raw = term.attrs;
+        unsafe {
+            libc::cfmakeraw(&mut raw);
+            libc::tcsetattr(slave, libc::TCSANOW, &raw);
+        }
+        assert_eq!(
+            TermState::save(slave).unwrap().attrs.c_lflag & libc::ECHO,
+            0
+        );
+
+        term.restore();
+        assert_eq!(TermState::save(slave).unwrap().attrs.c_lflag, lflag);
+        unsafe {
+            libc::close(slave);
+            libc::close(master);
+        }
+    }
+
+    #[test]
+    fn with_shell() {
+        let cmd = GeneralCommand::with_shell("/bin/sh", "for i in 1 2; do echo $i; done");
+        assert_eq!(cmd.program, "/bin/sh");
+        assert_eq!(cmd.args, vec!["-c", "for i in 1 2; do echo $i; done"]);
+    }
+
+    #[test]
+    fn capture_strips_trailing_newlines() {
+        let mut prompt = Prompt::create(context::default());
+        let cmd = GeneralCommand::new("printf".to_string(), vec!["a\\nb\\n\\n\\n".to_string()]);
+        let (res, output) = cmd.capture(&mut prompt);
+        assert!(res.unwrap());
+        assert_eq!(output, "a\nb");
+        assert_eq!(prompt.context.borrow().env["?"], "0");
+    }
+
+    #[test]
+    fn capture_is_lossy_utf8() {
+        let mut prompt = Prompt::create(context::default());
+        let cmd = GeneralCommand::new("printf".to_string(), vec!["a\\377b".to_string()]);
+        let (res, output) = cmd.capture(&mut prompt);
+        assert!(res.unwrap());
+        assert_eq!(output, "a\u{FFFD}b");
+    }
+
+    #[test]
+    fn capture_failure_exit_code() {
+        let mut prompt = Prompt::create(context::default());
+        let cmd = GeneralCommand::new("false".to_string(), vec![]);
+        let (res, output) = cmd.capture(&mut prompt);
+        assert!(!res.unwrap());
+        assert_eq!(output, "");
+        assert_eq!(prompt.context.borrow().env["?"], "1");
+    }
+
+    #[test]
+    fn read_capped() {
+        let data: &[u8] = b"abcdef";
+        assert_eq!(
+            GeneralCommand::read_capped(data, 10),
+            (b"abcdef".to_vec(), false)
+        );
+        assert_eq!(
+            GeneralCommand::read_capped(data, 6),
+            (b"abcdef".to_vec(), false)
+        );
+        assert_eq!(
+            GeneralCommand::read_capped(data, 4),
+            (b"abcd".to_vec(), true)
+        );
+        assert_eq!(GeneralCommand::read_capped(data, 0), (vec![], true));
+    }
+
+    #[test]
+    fn capture_truncated() {
+        let mut prompt = Prompt::create(context::default());
+        prompt.context.borrow_mut().config.max_capture_bytes = 100;
+
+        // Endless output is stopped once the limit is reached.
+        let cmd = GeneralCommand::new("yes".to_string(), vec!["ab".to_string()]);
+        let (_, output) = cmd.capture(&mut prompt);
+        assert_eq!(output, "ab\n".repeat(34)[..100]);
+    }
+
+    #[test]
+    fn default_path_when_unset() {
+        let mut prompt = Prompt::create(context::default());
+        prompt.context.borrow_mut().env.remove("PATH");
+        let cmd = GeneralCommand::new("printenv".to_string(), vec!["PATH".to_string()]);
+        let (res, output) = cmd.capture(&mut prompt);
+        assert!(res.unwrap());
+        assert_eq!(output, DEFAULT_PATH);
+    }
+
+    #[test]
+    fn errexit_exits_on_failure() {
+        let mut prompt = Prompt::create(context::default());
+        prompt.context.borrow_mut().errexit = true;
+        let mut cmd = GeneralCommand::new("false".to_string(), vec![]);
+        assert_eq!(cmd.execute(&mut prompt), Err(1));
+    }
+
+    #[test]
+    fn errexit_suppressed_when_status_checked() {
+        let mut prompt = Prompt::create(context::default());
+        prompt.context.borrow_mut().errexit = true;
+        let mut cmd = GeneralCommand::new("false".to_string(), vec![]);
+        let res = prompt.with_status_checked(|prompt| cmd.execute(prompt));
+        assert_eq!(res, Ok(false));
+        assert_eq!(prompt.context.borrow().env["?"], "1");
+
+        // Applies again afterwards.
+        assert_eq!(cmd.execute(&mut prompt), Err(1));
+    }
+
+    #[test]
+    fn errexit_suppressed_for_missing_program_when_status_checked() {
+        let mut prompt = Prompt::create(context::default());
+        prompt.context.borrow_mut().errexit = true;
+        let mut cmd = GeneralCommand::new("carapace-no-such-program".to_string(), vec![]);
+        let res = prompt.with_status_checked(|prompt| cmd.execute(prompt));
+        assert_eq!(res, Ok(false));
+    }
 }
@@ -0,0 +1,437 @@
+use super::*;
+
+use crate::context::ContextData;
+
+/// Getopts command parses options of arguments one at a time, like `getopts "ab:c" opt`, by
+/// setting the option in the named variable, and any option argument in `$OPTARG`. `$OPTIND` holds
+/// the index of the next argument to process. When no arguments are given, the positional
+/// parameters `$1` to `$N` are used, where `N` is `$#`.
+///
+/// If the option string starts with ':', errors are not reported but signaled via the variables
+/// instead.
+pub struct GetoptsCommand {
+    args: Vec<String>,
+}
+
+/// Where a `getopts` call left off: the arguments, `$OPTIND`, and the character position within
+/// that argument. Parsing only continues there if the arguments are the same and `$OPTIND` hasn't
+/// been assigned in the meantime, even if to the same value, like `export OPTIND=1`.
+pub struct GetoptsPos {
+    args: Vec<String>,
+    optind: usize,
+    pos: usize,
+    assignments: usize,
+}
+
+/// Result of parsing the next option.
+#[derive(Debug, PartialEq)]
+enum Outcome {
+    /// Option, and option argument if any.
+    Option(char, Option<String>),
+
+    /// Option not in option string.
+    Invalid(char),
+
+    /// Option requires an argument but none was left.
+    MissingArgument(char),
+
+    /// No options left.
+    End,
+}
+
+impl GetoptsCommand {
+    pub fn new(args: Vec<String>) -> GetoptsCommand {
+        GetoptsCommand { args }
+    }
+
+    /// Parses the next option of `args` in `optstring` starting at 1-based index `optind`, and
+    /// character `pos` within that argument. Both are advanced to what should be parsed next.
+    fn next(optstring: &str, args: &[String], optind: &mut usize, pos: &mut usize) -> Outcome {
+        let arg: Vec<char> = match args.get(*optind - 1) {
+            Some(arg) => arg.chars().collect(),
+            None => return Outcome::End,
+        };
+        if *pos >= arg.len() {
+            *pos = 1;
+        }
+        if *pos == 1 {
+            if arg == ['-', '-'] {
+                *optind += 1;
+                return Outcome::End;
+            }
+            if arg.len() < 2 || arg[0] != '-' {
+                return Outcome::End;
+            }
+        }
+
+        let opt = arg[*pos];
+        *pos += 1;
+        let rest: String = arg[*pos..].iter().collect();
+        if *pos >= arg.len() {
+            *optind += 1;
+            *pos = 1;
+        }
+
+        let spec = optstring.find(opt).filter(|_| opt != ':');
+        let spec = match spec {
+            Some(spec) => spec,
+            None => return Outcome::Invalid(opt),
+        };
+        if !optstring[spec + opt.len_utf8()..].starts_with(':') {
+            return Outcome::Option(opt, None);
+        }
+
+        // Option argument is either the rest of the current argument or the next argument.
+        if !rest.is_empty() {
+            *optind += 1;
+            *pos = 1;
+            return Outcome::Option(opt, Some(rest));
+        }
+        match args.get(*optind - 1) {
+            Some(value) => {
+                *optind += 1;
+                Outcome::Option(opt, Some(value.clone()))
+            }
+            None => Outcome::MissingArgument(opt),
+        }
+    }
+
+    /// Yields positional parameters `$1` to `$N`, where `N` is `$#`.
+    fn positional_params(ctx: &ContextData) -> Vec<String> {
+        let count = ctx
+            .env
+            .get("#")
+            .and_then(|c| c.parse::<usize>().ok())
+            .unwrap_or(0);
+        (1..=count)
+            .map(|i| ctx.env.get(&i.to_string()).cloned().unwrap_or_default())
+            .collect()
+    }
+}
+
+impl Command for GetoptsCommand {
+    fn execute(&mut self, prompt: &mut Prompt) -> Result<bool, i32> {
+        let mut ctx = prompt.context.borrow_mut();
+        if self.args.len() < 2 {
+            eprintln!("getopts: usage: getopts optstring name [arg ...]");
+            ctx.env.insert("?".to_string(), "2".to_string());
+            return Ok(false);
+        }
+
+        let (optstring, silent) = match self.args[0].strip_prefix(':') {
+            Some(optstring) => (optstring, true),
+            None => (self.args[0].as_str(), false),
+        };
+        let name = self.args[1].clone();
+        let args = if self.args.len() > 2 {
+            self.args[2..].to_vec()
+        } else {
+            GetoptsCommand::positional_params(&ctx)
+        };
+
+        let mut optind = ctx
+            .env
+            .get("OPTIND")
+            .and_then(|i| i.parse::<usize>().ok())
+            .unwrap_or(1)
+            .max(1);
+
+        // Continue within grouped options, like "-ab", unless the arguments or $OPTIND were changed
+        // in the meantime.
+        let assignments = ctx.env.assignments("OPTIND");
+        let mut pos = match &ctx.getopts_pos {
+            Some(last)
+                if last.optind == optind
+                    && last.assignments == assignments
+                    && last.args == args =>
+            {
+                last.pos
+            }
+            _ => 1,
+        };
+
+        let outcome = GetoptsCommand::next(optstring, &args, &mut optind, &mut pos);
+        ctx.env.insert("OPTIND".to_string(), optind.to_string());
+
+        let (value, optarg, success) = match outcome {
+            Outcome::Option(opt, optarg) => (opt.to_string(), optarg, true),
+            Outcome::Invalid(opt) => {
+                if silent {
+                    ("?".to_string(), Some(opt.to_string()), true)
+                } else {
+                    eprintln!("getopts: illegal option -- {}", opt);
+                    ("?".to_string(), None, true)
+                }
+            }
+            Outcome::MissingArgument(opt) => {
+                if silent {
+                    (":".to_string(), Some(opt.to_string()), true)
+                } else {
+                    eprintln!("getopts: option requires an argument -- {}", opt);
+                    ("?".to_string(), None, true)
+                }
+            }
+            Outcome::End => ("?".to_string(), None, false),
+        };
+
+        ctx.getopts_pos = Some(GetoptsPos {
+            args,
+            optind,
+            pos,
+            assignments,
+        });
+
+        match optarg {
+            Some(optarg) => ctx.env.insert("OPTARG".to_string(), optarg),
+            None => ctx.env.remove("OPTARG"),
+        }
+
+        let success = match ctx.env.set(name, value) {
+            Ok(_) => success,
+            Err(err) => {
+                eprintln!("getopts: {}", err);
+                false
+            }
+        };
+        ctx.env
+            .insert("?".to_string(), (!success as i32).to_string());
+        Ok(success)
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+impl CommandAliases for GetoptsCommand {
+    fn aliases() -> Vec<String> {
+        vec!["getopts".to_string()]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::context;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|a| a.to_string()).collect()
+    }
+
+    /// Runs getopts with `optstring` over `params` and yields success, option, $OPTARG, and
+    /// $OPTIND.
+    fn getopts(
+        prompt: &mut Prompt,
+        optstring: &str,
+        params: &[&str],
+    ) -> (bool, String, Option<String>, String) {
+        let mut all = args(&[optstring, "opt"]);
+        all.append(&mut args(params));
+        let success = GetoptsCommand::new(all).execute(prompt).unwrap();
+        let ctx = prompt.context.borrow();
+        (
+            success,
+            ctx.env["opt"].clone(),
+            ctx.env.get("OPTARG").cloned(),
+            ctx.env["OPTIND"].clone(),
+        )
+    }
+
+    fn opt(
+        success: bool,
+        opt: &str,
+        optarg: Option<&str>,
+        optind: &str,
+    ) -> (bool, String, Option<String>, String) {
+        (
+            success,
+            opt.to_string(),
+            optarg.map(|a| a.to_string()),
+            optind.to_string(),
+        )
+    }
+
+    #[test]
+    fn sequence() {
+        let mut prompt = Prompt::create(context::default());
+        let params = ["-a", "-b", "val", "-c", "file"];
+        assert_eq!(
+            getopts(&mut prompt, "ab:c", &params),
+            opt(true, "a", None, "2")
+        );
+        assert_eq!(
+            getopts(&mut prompt, "ab:c", &params),
+            opt(true, "b", Some("val"), "4")
+        );
+        assert_eq!(
+            getopts(&mut prompt, "ab:c", &params),
+            opt(true, "c", None, "5")
+        );
+        assert_eq!(
+            getopts(&mut prompt, "ab:c", &params),
+            opt(false, "?", None, "5")
+        );
+        assert_eq!(prompt.context.borrow().env["?"], "1");
+    }
+
+    #[test]
+    fn grouped_options() {
+        let mut prompt = Prompt::create(context::default());
+        let params = ["-ac", "-bval"];
+        assert_eq!(
+            getopts(&mut prompt, "ab:c", &params),
+            opt(true, "a", None, "1")
+        );
+        assert_eq!(
+            getopts(&mut prompt, "ab:c", &params),
+            opt(true, "c", None, "2")
+        );
+        assert_eq!(
+            getopts(&mut prompt, "ab:c", &params),
+            opt(true, "b", Some("val"), "3")
+        );
+        assert_eq!(
+            getopts(&mut prompt, "ab:c", &params),
+            opt(false, "?", None, "3")
+        );
+    }
+
+    #[test]
+    fn double_dash_ends_options() {
+        let mut prompt = Prompt::create(context::default());
+        let params = ["-a", "--", "-b"];
+        assert_eq!(
+            getopts(&mut prompt, "ab", &params),
+            opt(true, "a", None, "2")
+        );
+        assert_eq!(
+            getopts(&mut prompt, "ab", &params),
+            opt(false, "?", None, "3")
+        );
+    }
+
+    #[test]
+    fn non_option_ends_options() {
+        let mut prompt = Prompt::create(context::default());
+        assert_eq!(
+            getopts(&mut prompt, "a", &["file", "-a"]),
+            opt(false, "?", None, "1")
+        );
+        assert_eq!(
+            getopts(&mut prompt, "a", &["-"]),
+            opt(false, "?", None, "1")
+        );
+    }
+
+    #[test]
+    fn invalid_option() {
+        let mut prompt = Prompt::create(context::default());
+        assert_eq!(
+            getopts(&mut prompt, "a", &["-x"]),
+            opt(true, "?", None, "2")
+        );
+    }
+
+    #[test]
+    fn invalid_option_silent() {
+        let mut prompt = Prompt::create(context::default());
+        assert_eq!(
+            getopts(&mut prompt, ":a", &["-x"]),
+            opt(true, "?", Some("x"), "2")
+        );
+    }
+
+    #[test]
+    fn missing_argument() {
+        let mut prompt = Prompt::create(context::default());
+        assert_eq!(
+            getopts(&mut prompt, "b:", &["-b"]),
+            opt(true, "?", None, "2")
+        );
+    }
+
+    #[test]
+    fn missing_argument_silent() {
+        let mut prompt = Prompt::create(context::default());
+        assert_eq!(
+            getopts(&mut prompt, ":b:", &["-b"]),
+            opt(true, ":", Some("b"), "2")
+        );
+    }
+
+    #[test]
+    fn reset_optind() {
+        let mut prompt = Prompt::create(context::default());
+        let params = ["-a", "-b"];
+        assert_eq!(
+            getopts(&mut prompt, "ab", &params),
+            opt(true, "a", None, "2")
+        );
+        prompt
+            .context
+            .borrow_mut()
+            .env
+            .insert("OPTIND".to_string(), "1".to_string());
+        assert_eq!(
+            getopts(&mut prompt, "ab", &params),
+            opt(true, "a", None, "2")
+        );
+    }
+
+    #[test]
+    fn reset_within_grouped_options() {
+        let mut prompt = Prompt::create(context::default());
+        assert_eq!(
+            getopts(&mut prompt, "ab", &["-ab"]),
+            opt(true, "a", None, "1")
+        );
+
+        // Other arguments at the same $OPTIND start over instead of indexing past "-x".
+        assert_eq!(
+            getopts(&mut prompt, "x", &["-x"]),
+            opt(true, "x", None, "2")
+        );
+
+        // Assigning $OPTIND starts over, even if to the same value.
+        let reset = |prompt: &mut Prompt| {
+            let cmd = prompt.parse_command("export OPTIND=1");
+            crate::command::execute(cmd, prompt);
+        };
+        reset(&mut prompt);
+        assert_eq!(
+            getopts(&mut prompt, "ab", &["-ab"]),
+            opt(true, "a", None, "1")
+        );
+        reset(&mut prompt);
+        assert_eq!(
+            getopts(&mut prompt, "ab", &["-ab"]),
+            opt(true, "a", None, "1")
+        );
+        assert_eq!(
+            getopts(&mut prompt, "ab", &["-ab"]),
+            opt(true, "b", None, "2")
+        );
+    }
+
+    #[test]
+    fn positional_params() {
+        let mut prompt = Prompt::create(context::default());
+        {
+            let env = &mut prompt.context.borrow_mut().env;
+            env.insert("#".to_string(), "2".to_string());
+            env.insert("1".to_string(), "-a".to_string());
+            env.insert("2".to_string(), "-b".to_string());
+        }
+        assert_eq!(getopts(&mut prompt, "ab", &[]), opt(true, "a", None, "2"));
+        assert_eq!(getopts(&mut prompt, "ab", &[]), opt(true, "b", None, "3"));
+        assert_eq!(getopts(&mut prompt, "ab", &[]), opt(false, "?", None, "3"));
+    }
+
+    #[test]
+    fn usage() {
+        let mut prompt = Prompt::create(context::default());
+        let res = GetoptsCommand::new(args(&["ab"])).execute(&mut prompt);
+        assert_eq!(res, Ok(false));
+        assert_eq!(prompt.context.borrow().env["?"], "2");
+    }
+}
@@ -1,8 +1,13 @@
 use super::*;
 
+use crate::path_commands::PathCommands;
+
 use clap::{App, AppSettings, Arg};
 
-/// Hash command checks if command is known.
+/// Hash command checks if command is known, and lists or forgets the full paths of commands.
+/// Builtins are known but have no path, so they are reported as such. The paths are those found
+/// when rehashing, which are used for completion and to check if commands are known, whereas
+/// programs are always looked up in `$PATH` when run.
 pub struct HashCommand {
     args: Vec<String>,
     app: App<'static, 'static>,
@@ -10,21 +15,79 @@ pub struct HashCommand {
 
 impl HashCommand {
     pub fn new(args: Vec<String>) -> HashCommand {
-        let app =
-            App::new("hash")
-                .about("Check command existence or rehash.")
-                .setting(AppSettings::NoBinaryName)
-                .setting(AppSettings::DisableVersion)
-                .arg(Arg::with_name("rehash").short("r").long("rehash").help(
-                    "Detects commands from $PATH from scratch. Is equivalent to running \
+        let app = App::new("hash")
+            .about("Check command existence or rehash.")
+            .setting(AppSettings::NoBinaryName)
+            .setting(AppSettings::DisableVersion)
+            .arg(Arg::with_name("rehash").short("r").long("rehash").help(
+                "Detects commands from $PATH from scratch. Is equivalent to running \
                      the 'rehash' command.",
-                ))
-                .arg(Arg::with_name("command").index(1).help(
-                    "Checks if command is known. Exit code is 0 for success and 1 otherwise.",
-                ));
+            ))
+            .arg(
+                Arg::with_name("list")
+                    .short("l")
+                    .long("list")
+                    .help("Lists known commands and their full paths."),
+            )
+            .arg(
+                Arg::with_name("delete")
+                    .short("d")
+                    .long("delete")
+                    .takes_value(true)
+                    .value_name("name")
+                    .help(
+                        "Forgets the full path of command until the next rehash, which only \
+                             affects completion and checking if it is known. Exit code is 0 for \
+                             success and 1 if the command wasn't known or is a builtin.",
+                    ),
+            )
+            .arg(Arg::with_name("type").short("t").requires("command").help(
+                "Prints the full path of each command, prefixed by the name and a tab if \
+                     several are given. Exit code is 0 for success and 1 if any command wasn't \
+                     known or is a builtin.",
+            ))
+            .arg(Arg::with_name("command").multiple(true).help(
+                "Checks if commands are known, and reports builtins. Exit code is 0 for \
+                     success and 1 otherwise.",
+            ));
 
         HashCommand { args, app }
     }
+
+    /// Whether `name` is a builtin that isn't disabled, and thereby run instead of any program of
+    /// the same name.
+    fn is_builtin(ctx: &ContextData, name: &str) -> bool {
+        !ctx.disabled_builtins.contains(name) && builtins().iter().any(|builtin| builtin == name)
+    }
+
+    /// Prints that `name` is a builtin if it is one, and yields whether it is.
+    fn report_builtin(ctx: &ContextData, name: &str) -> bool {
+        let builtin = HashCommand::is_builtin(ctx, name);
+        if builtin {
+            println!("hash: {}: is a shell builtin", name);
+        }
+        builtin
+    }
+
+    /// Yields full path of command `name`, like "/bin/ls", or "name\tpath" `with_name`, or None if
+    /// not known.
+    fn path_line(commands: &PathCommands, name: &str, with_name: bool) -> Option<String> {
+        let path = commands.get(name)?;
+        if with_name {
+            Some(format!("{}\t{}", name, path.display()))
+        } else {
+            Some(path.display().to_string())
+        }
+    }
+
+    /// Yields lines of "name\tpath" for all known commands, sorted by name.
+    fn list(commands: &PathCommands) -> String {
+        commands
+            .as_ref()
+            .iter()
+            .map(|(name, path)| format!("{}\t{}\n", name, path.display()))
+            .collect()
+    }
 }
 
 impl Command for HashCommand {
@@ -37,15 +100,64 @@ impl Command for HashCommand {
         let m = matches.unwrap();
 
         let mut ctx = prompt.context.borrow_mut();
-        let commands = &mut ctx.commands;
 
         // -r
         if m.is_present("rehash") {
-            commands.rehash();
+            ctx.commands.rehash();
+        }
+        // -l
+        else if m.is_present("list") {
+            print!("{}", HashCommand::list(&ctx.commands));
+        }
+        // -d <name>
+        else if let Some(cmd) = m.value_of("delete") {
+            let success = if HashCommand::report_builtin(&ctx, cmd) {
+                false
+            } else if ctx.commands.remove(cmd) {
+                true
+            } else {
+                println!("hash: {}: not found", cmd);
+                false
+            };
+
+            // Reflect the success in $?.
+            ctx.env
+                .insert("?".to_string(), if success { 0 } else { 1 }.to_string());
+
+            return Ok(success);
+        }
+        // -t <command>...
+        else if m.is_present("type") {
+            let cmds: Vec<&str> = m.values_of("command").unwrap().collect();
+            let mut success = true;
+            for cmd in &cmds {
+                if HashCommand::report_builtin(&ctx, cmd) {
+                    success = false;
+                    continue;
+                }
+                match HashCommand::path_line(&ctx.commands, cmd, cmds.len() > 1) {
+                    Some(line) => println!("{}", line),
+                    None => {
+                        println!("hash: {}: not found", cmd);
+                        success = false;
+                    }
+                }
+            }
+
+            // Reflect the success in $?.
+            ctx.env
+                .insert("?".to_string(), if success { 0 } else { 1 }.to_string());
+
+            return Ok(success);
         }
-        // command
-        else if let Some(cmd) = m.value_of("command") {
-            let success = commands.contains(cmd);
+        // command...
+        else if let Some(cmds) = m.values_of("command") {
+            let mut success = true;
+            for cmd in cmds {
+                if !HashCommand::report_builtin(&ctx, cmd) && !ctx.commands.contains(cmd) {
+                    success = false;
+                }
+            }
 
             // Reflect the success in $?.
             ctx.env
@@ -74,6 +186,8 @@ mod tests {
 
     use crate::context;
 
+    use std::path::PathBuf;
+
     #[test]
     fn new() {
         let args = vec![String::from("arg")];
@@ -111,7 +225,9 @@ mod tests {
     #[test]
     fn command_known() {
         let ctx = context::default();
-        ctx.borrow_mut().commands.insert("command".to_string());
+        ctx.borrow_mut()
+            .commands
+            .insert("command".to_string(), PathBuf::from("/bin/command"));
 
         let mut prompt = Prompt::create(ctx);
         let mut cmd = HashCommand::new(vec!["command".to_string()]);
@@ -122,4 +238,109 @@ mod tests {
         assert!(env.contains_key("?"));
         assert_eq!("0", env["?"]);
     }
+
+    #[test]
+    fn builtins_reported() {
+        let ctx = context::default();
+        ctx.borrow_mut()
+            .commands
+            .insert("cd".to_string(), PathBuf::from("/usr/bin/cd"));
+        let mut prompt = Prompt::create(ctx);
+        let run = |prompt: &mut Prompt, args: &[&str]| {
+            let args = args.iter().map(|x| x.to_string()).collect();
+            HashCommand::new(args).execute(prompt).unwrap()
+        };
+
+        // Builtins are known, but have no path to print or forget.
+        assert!(run(&mut prompt, &["cd", "hash"]));
+        assert!(!run(&mut prompt, &["-t", "cd"]));
+        assert!(!run(&mut prompt, &["-d", "cd"]));
+        assert!(prompt.context.borrow().commands.contains("cd"));
+
+        // A disabled builtin is resolved as a program.
+        prompt
+            .context
+            .borrow_mut()
+            .disabled_builtins
+            .insert("cd".to_string());
+        assert!(run(&mut prompt, &["-t", "cd"]));
+        assert!(run(&mut prompt, &["-d", "cd"]));
+        assert!(!run(&mut prompt, &["cd"]));
+    }
+
+    #[test]
+    fn path_line() {
+        let mut commands = PathCommands::default();
+        commands.insert("ls".to_string(), PathBuf::from("/bin/ls"));
+        assert_eq!(
+            HashCommand::path_line(&commands, "ls", false),
+            Some("/bin/ls".to_string())
+        );
+        assert_eq!(
+            HashCommand::path_line(&commands, "ls", true),
+            Some("ls\t/bin/ls".to_string())
+        );
+        assert_eq!(HashCommand::path_line(&commands, "foo", false), None);
+    }
+
+    #[test]
+    fn type_known_and_unknown() {
+        let ctx = context::default();
+        ctx.borrow_mut()
+            .commands
+            .insert("command".to_string(), PathBuf::from("/bin/command"));
+
+        let mut prompt = Prompt::create(ctx);
+        let mut cmd = HashCommand::new(vec!["-t".to_string(), "command".to_string()]);
+        assert!(cmd.execute(&mut prompt).unwrap());
+        assert_eq!("0", prompt.context.borrow().env["?"]);
+
+        let args = ["-t", "command", "unknown"];
+        let mut cmd = HashCommand::new(args.iter().map(|x| x.to_string()).collect());
+        assert!(!cmd.execute(&mut prompt).unwrap());
+        assert_eq!("1", prompt.context.borrow().env["?"]);
+
+        // At least one command is required.
+        let mut cmd = HashCommand::new(vec!["-t".to_string()]);
+        assert!(!cmd.execute(&mut prompt).unwrap());
+    }
+
+    #[test]
+    fn list() {
+        let mut commands = PathCommands::default();
+        assert_eq!(HashCommand::list(&commands), "");
+
+        commands.insert("foo".to_string(), PathBuf::from("/usr/bin/foo"));
+        commands.insert("bar".to_string(), PathBuf::from("/bin/bar"));
+        assert_eq!(
+            HashCommand::list(&commands),
+            "bar\t/bin/bar\nfoo\t/usr/bin/foo\n"
+        );
+    }
+
+    #[test]
+    fn delete_known() {
+        let ctx = context::default();
+        ctx.borrow_mut()
+            .commands
+            .insert("command".to_string(), PathBuf::from("/bin/command"));
+
+        let mut prompt = Prompt::create(ctx);
+        let mut cmd = HashCommand::new(vec!["-d".to_string(), "command".to_string()]);
+        let res = cmd.execute(&mut prompt);
+        assert!(res.unwrap());
+
+        let ctx = prompt.context.borrow();
+        assert!(!ctx.commands.contains("command"));
+        assert_eq!("0", ctx.env["?"]);
+    }
+
+    #[test]
+    fn delete_unknown() {
+        let mut prompt = Prompt::create(context::default());
+        let mut cmd = HashCommand::new(vec!["-d".to_string(), "command".to_string()]);
+        let res = cmd.execute(&mut prompt);
+        assert!(!res.unwrap());
+        assert_eq!("1", prompt.context.borrow().env["?"]);
+    }
 }
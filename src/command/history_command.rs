@@ -46,10 +46,8 @@ impl Command for HistoryCommand {
         } else if matches.is_present("write") {
             prompt.save_history();
         } else {
-            let mut num = 1;
-            for line in prompt.editor.history().iter() {
+            for (num, line) in (1..).zip(prompt.editor.history().iter()) {
                 println!("{:4}: {}", num, line);
-                num += 1;
             }
         }
         Ok(true)
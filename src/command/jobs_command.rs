@@ -0,0 +1,229 @@
+use super::*;
+
+use crate::jobs::{Job, JobState};
+
+use clap::{App, AppSettings, Arg};
+
+/// How jobs are listed by `jobs`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum JobsFormat {
+    /// Like "[1]+ Running  sleep 10".
+    Normal,
+
+    /// Including the pid, like "[1]+ 1234 Running  sleep 10".
+    Long,
+
+    /// Only the pid, like "1234", such that it can be used as input, like `kill $(jobs -p)`.
+    Pids,
+}
+
+/// Jobs command lists background jobs, optionally only the running or stopped ones via `-r` and
+/// `-s`, or the given job specs, like `jobs %1`.
+pub struct JobsCommand {
+    args: Vec<String>,
+    app: App<'static, 'static>,
+}
+
+impl JobsCommand {
+    pub fn new(args: Vec<String>) -> JobsCommand {
+        JobsCommand {
+            args,
+            app: App::new("jobs")
+                .about("List background jobs.")
+                .setting(AppSettings::NoBinaryName)
+                .setting(AppSettings::DisableVersion)
+                .arg(
+                    Arg::with_name("long")
+                        .short("l")
+                        .help("Include the process id of jobs.")
+                        .overrides_with("pids"),
+                )
+                .arg(
+                    Arg::with_name("pids")
+                        .short("p")
+                        .help("Only print the process id of jobs, one per line.")
+                        .overrides_with("long"),
+                )
+                .arg(
+                    Arg::with_name("running")
+                        .short("r")
+                        .help("Only list running jobs."),
+                )
+                .arg(
+                    Arg::with_name("stopped")
+                        .short("s")
+                        .help("Only list stopped jobs."),
+                )
+                .arg(
+                    Arg::with_name("specs")
+                        .multiple(true)
+                        .help("Jobs to list, like '%1', or all if none are given."),
+                ),
+        }
+    }
+
+    /// Yields line of `job` in `format`, where `marker` is like for `Job::notification()`.
+    pub fn format(job: &Job, marker: char, format: JobsFormat) -> String {
+        match format {
+            JobsFormat::Normal => job.notification(marker),
+            JobsFormat::Long => format!(
+                "[{}]{} {} {}  {}",
+                job.id,
+                marker,
+                job.pid,
+                job.status(),
+                job.command
+            ),
+            JobsFormat::Pids => job.pid.to_string(),
+        }
+    }
+
+    /// Whether `job` is listed when only `running` and/or `stopped` jobs are requested. All jobs
+    /// are listed if neither are.
+    pub fn selected(job: &Job, running: bool, stopped: bool) -> bool {
+        if !running && !stopped {
+            return true;
+        }
+        (running && job.state == JobState::Running) || (stopped && job.state == JobState::Stopped)
+    }
+}
+
+impl Command for JobsCommand {
+    fn execute(&mut self, prompt: &mut Prompt) -> Result<bool, i32> {
+        let matches = match self.app.get_matches_from_safe_borrow(&self.args) {
+            Ok(matches) => matches,
+            Err(err) => {
+                println!("{}", err);
+                return Ok(false);
+            }
+        };
+        let format = if matches.is_present("pids") {
+            JobsFormat::Pids
+        } else if matches.is_present("long") {
+            JobsFormat::Long
+        } else {
+            JobsFormat::Normal
+        };
+        let (running, stopped) = (matches.is_present("running"), matches.is_present("stopped"));
+
+        let ctx = prompt.context.borrow();
+        let mut success = true;
+        let jobs: Vec<&Job> = match matches.values_of("specs") {
+            Some(specs) => specs
+                .filter_map(|spec| {
+                    let job = ctx.jobs.resolve(spec);
+                    if job.is_none() {
+                        println!("jobs: {}: no such job", spec);
+                        success = false;
+                    }
+                    job
+                })
+                .collect(),
+            None => ctx.jobs.iter().collect(),
+        };
+        for job in jobs {
+            if JobsCommand::selected(job, running, stopped) {
+                let marker = ctx.jobs.marker(job.id);
+                println!("{}", JobsCommand::format(job, marker, format));
+            }
+        }
+        Ok(success)
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+impl CommandAliases for JobsCommand {
+    fn aliases() -> Vec<String> {
+        vec!["jobs".to_string()]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::context;
+    use crate::jobs::Jobs;
+
+    /// Yields jobs of a running and a stopped `sleep` process.
+    fn jobs() -> Jobs {
+        let mut jobs = Jobs::default();
+        for _ in 0..2 {
+            let child = process::Command::new("sleep").arg("10").spawn().unwrap();
+            jobs.add(child, "sleep 10".to_string());
+        }
+
+        let stopped = jobs.iter().nth(1).unwrap().pid as libc::pid_t;
+        unsafe { libc::kill(stopped, libc::SIGSTOP) };
+        while !jobs.has_stopped() {
+            jobs.reap();
+        }
+        jobs
+    }
+
+    fn kill_all(jobs: &Jobs) {
+        for job in jobs.iter() {
+            unsafe { libc::kill(job.pid as libc::pid_t, libc::SIGKILL) };
+        }
+    }
+
+    #[test]
+    fn format() {
+        let jobs = jobs();
+        let job = jobs.iter().next().unwrap();
+        assert_eq!(
+            JobsCommand::format(job, '-', JobsFormat::Normal),
+            "[1]- Running  sleep 10"
+        );
+        assert_eq!(
+            JobsCommand::format(job, '-', JobsFormat::Long),
+            format!("[1]- {} Running  sleep 10", job.pid)
+        );
+        assert_eq!(
+            JobsCommand::format(job, '-', JobsFormat::Pids),
+            job.pid.to_string()
+        );
+        kill_all(&jobs);
+    }
+
+    #[test]
+    fn selected() {
+        let jobs = jobs();
+        let ids = |running, stopped| -> Vec<usize> {
+            jobs.iter()
+                .filter(|j| JobsCommand::selected(j, running, stopped))
+                .map(|j| j.id)
+                .collect()
+        };
+        assert_eq!(ids(false, false), vec![1, 2]);
+        assert_eq!(ids(true, false), vec![1]);
+        assert_eq!(ids(false, true), vec![2]);
+        assert_eq!(ids(true, true), vec![1, 2]);
+        kill_all(&jobs);
+    }
+
+    #[test]
+    fn list() {
+        let mut prompt = Prompt::create(context::default());
+        prompt.context.borrow_mut().jobs = jobs();
+        for args in [
+            &[][..],
+            &["-l"],
+            &["-p", "-s"],
+            &["-r", "%1"],
+            &["%%", "%-"],
+        ] {
+            let args = args.iter().map(|x| x.to_string()).collect();
+            assert_eq!(JobsCommand::new(args).execute(&mut prompt), Ok(true));
+        }
+
+        let args = vec!["%1".to_string(), "%3".to_string()];
+        assert_eq!(JobsCommand::new(args).execute(&mut prompt), Ok(false));
+        let args = vec!["-x".to_string()];
+        assert_eq!(JobsCommand::new(args).execute(&mut prompt), Ok(false));
+        kill_all(&prompt.context.borrow().jobs);
+    }
+}
@@ -0,0 +1,340 @@
+use super::*;
+
+use std::io;
+
+/// Signals by name and number.
+const SIGNALS: &[(&str, i32)] = &[
+    ("HUP", libc::SIGHUP),
+    ("INT", libc::SIGINT),
+    ("QUIT", libc::SIGQUIT),
+    ("ILL", libc::SIGILL),
+    ("TRAP", libc::SIGTRAP),
+    ("ABRT", libc::SIGABRT),
+    ("BUS", libc::SIGBUS),
+    ("FPE", libc::SIGFPE),
+    ("KILL", libc::SIGKILL),
+    ("USR1", libc::SIGUSR1),
+    ("SEGV", libc::SIGSEGV),
+    ("USR2", libc::SIGUSR2),
+    ("PIPE", libc::SIGPIPE),
+    ("ALRM", libc::SIGALRM),
+    ("TERM", libc::SIGTERM),
+    ("CHLD", libc::SIGCHLD),
+    ("CONT", libc::SIGCONT),
+    ("STOP", libc::SIGSTOP),
+    ("TSTP", libc::SIGTSTP),
+    ("TTIN", libc::SIGTTIN),
+    ("TTOU", libc::SIGTTOU),
+    ("URG", libc::SIGURG),
+    ("XCPU", libc::SIGXCPU),
+    ("XFSZ", libc::SIGXFSZ),
+    ("VTALRM", libc::SIGVTALRM),
+    ("PROF", libc::SIGPROF),
+    ("WINCH", libc::SIGWINCH),
+    ("IO", libc::SIGIO),
+    ("SYS", libc::SIGSYS),
+];
+
+/// Kill command sends a signal, `TERM` by default, to processes or jobs, like `kill -9 1234` or
+/// `kill -TERM %1`. Signal 0 only checks that targets exist, like `kill -0 1234`. `kill -l` lists
+/// signal names. Only supported on Unix.
+pub struct KillCommand {
+    args: Vec<String>,
+}
+
+impl KillCommand {
+    pub fn new(args: Vec<String>) -> KillCommand {
+        KillCommand { args }
+    }
+
+    /// Yields number of signal by name, with or without "SIG" prefix and in any case, or by
+    /// number.
+    pub fn signal_number(signal: &str) -> Option<i32> {
+        if let Ok(num) = signal.parse::<i32>() {
+            return SIGNALS.iter().find(|(_, n)| *n == num).map(|(_, n)| *n);
+        }
+        let name = signal.to_uppercase();
+        let name = name.strip_prefix("SIG").unwrap_or(&name);
+        SIGNALS.iter().find(|(s, _)| *s == name).map(|(_, n)| *n)
+    }
+
+    /// Yields number of signal to send by `spec`, like `signal_number()`, where 0 only checks
+    /// whether the process exists and can be signaled, like in `kill -0 1234`.
+    fn send_signal_number(spec: &str) -> Option<i32> {
+        if spec == "0" {
+            return Some(0);
+        }
+        KillCommand::signal_number(spec)
+    }
+
+    /// Yields name of signal by number, without "SIG" prefix.
+    pub fn signal_name(num: i32) -> Option<&'static str> {
+        SIGNALS.iter().find(|(_, n)| *n == num).map(|(s, _)| *s)
+    }
+
+    /// Lists signal names, or the names of the signals given by number.
+    fn list(signals: &[String]) -> bool {
+        if signals.is_empty() {
+            let names: Vec<&str> = SIGNALS.iter().map(|(s, _)| *s).collect();
+            println!("{}", names.join(" "));
+            return true;
+        }
+
+        let mut success = true;
+        for signal in signals {
+            // Exit codes of processes killed by signals are 128 + signal number.
+            let name = signal
+                .parse::<i32>()
+                .ok()
+                .map(|n| if n > 128 { n - 128 } else { n })
+                .and_then(KillCommand::signal_name);
+            match name {
+                Some(name) => println!("{}", name),
+                None => {
+                    println!("kill: {}: invalid signal specification", signal);
+                    success = false;
+                }
+            }
+        }
+        success
+    }
+
+    /// Resolves process ID of target, which is either a process ID or a job spec like "%1".
+    fn resolve_pid(target: &str, prompt: &Prompt) -> Result<libc::pid_t, String> {
+        if target.starts_with('%') {
+            return match prompt.context.borrow().jobs.resolve(target) {
+                Some(job) => Ok(job.pid as libc::pid_t),
+                None => Err(format!("kill: {}: no such job", target)),
+            };
+        }
+        match target.parse::<libc::pid_t>() {
+            Ok(pid) => Ok(pid),
+            Err(_) => Err(format!(
+                "kill: {}: arguments must be process or job IDs",
+                target
+            )),
+        }
+    }
+
+    /// Sends signal to targets and yields whether all succeeded.
+    fn kill(&self, prompt: &Prompt) -> bool {
+        let mut args = self.args.as_slice();
+        let mut signal = libc::SIGTERM;
+        if let Some(first) = args.first() {
+            if first == "-s" || first == "-n" {
+                let spec = match args.get(1) {
+                    Some(spec) => spec,
+                    None => {
+                        println!("kill: {}: option requires an argument", first);
+                        return false;
+                    }
+                };
+                signal = match KillCommand::send_signal_number(spec) {
+                    Some(signal) => signal,
+                    None => {
+                        println!("kill: {}: invalid signal specification", spec);
+                        return false;
+                    }
+                };
+                args = &args[2..];
+            } else if first == "--" {
+                args = &args[1..];
+            } else if let Some(spec) = first.strip_prefix('-') {
+                signal = match KillCommand::send_signal_number(spec) {
+                    Some(signal) => signal,
+                    None => {
+                        println!("kill: {}: invalid signal specification", spec);
+                        return false;
+                    }
+                };
+                args = &args[1..];
+            }
+        }
+
+        if args.is_empty() {
+            println!("kill: usage: kill [-s sigspec | -n signum | -sigspec] pid | jobspec ...");
+            return false;
+        }
+
+        let mut success = true;
+        for target in args {
+            let pid = match KillCommand::resolve_pid(target, prompt) {
+                Ok(pid) => pid,
+                Err(err) => {
+                    println!("{}", err);
+                    success = false;
+                    continue;
+                }
+            };
+            if unsafe { libc::kill(pid, signal) } == -1 {
+                println!("kill: ({}) - {}", pid, io::Error::last_os_error());
+                success = false;
+            }
+        }
+        success
+    }
+}
+
+impl Command for KillCommand {
+    fn execute(&mut self, prompt: &mut Prompt) -> Result<bool, i32> {
+        let success = match self.args.first().map(|a| a.as_str()) {
+            Some("-l") | Some("-L") => KillCommand::list(&self.args[1..]),
+            _ => self.kill(prompt),
+        };
+        prompt
+            .context
+            .borrow_mut()
+            .env
+            .insert("?".to_string(), (!success as i32).to_string());
+        Ok(success)
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+impl CommandAliases for KillCommand {
+    fn aliases() -> Vec<String> {
+        vec!["kill".to_string()]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::context;
+
+    use std::os::unix::process::ExitStatusExt;
+    use std::process;
+    use std::thread;
+    use std::time::Duration;
+
+    fn kill(prompt: &mut Prompt, args: &[&str]) -> Result<bool, i32> {
+        let args = args.iter().map(|a| a.to_string()).collect();
+        KillCommand::new(args).execute(prompt)
+    }
+
+    #[test]
+    fn signal_number_by_name() {
+        assert_eq!(KillCommand::signal_number("KILL"), Some(libc::SIGKILL));
+        assert_eq!(KillCommand::signal_number("SIGKILL"), Some(libc::SIGKILL));
+        assert_eq!(KillCommand::signal_number("term"), Some(libc::SIGTERM));
+        assert_eq!(KillCommand::signal_number("SigHup"), Some(libc::SIGHUP));
+        assert_eq!(KillCommand::signal_number("NOPE"), None);
+    }
+
+    #[test]
+    fn signal_number_by_number() {
+        assert_eq!(KillCommand::signal_number("9"), Some(libc::SIGKILL));
+        assert_eq!(KillCommand::signal_number("15"), Some(libc::SIGTERM));
+        assert_eq!(KillCommand::signal_number("1000"), None);
+        assert_eq!(KillCommand::signal_number("-1"), None);
+    }
+
+    #[test]
+    fn send_signal_number() {
+        assert_eq!(KillCommand::send_signal_number("0"), Some(0));
+        assert_eq!(KillCommand::send_signal_number("HUP"), Some(libc::SIGHUP));
+        assert_eq!(KillCommand::signal_number("0"), None);
+    }
+
+    #[test]
+    fn signal_name() {
+        assert_eq!(KillCommand::signal_name(libc::SIGINT), Some("INT"));
+        assert_eq!(KillCommand::signal_name(1000), None);
+    }
+
+    #[test]
+    fn resolve_pid() {
+        let prompt = Prompt::create(context::default());
+        assert_eq!(KillCommand::resolve_pid("1234", &prompt), Ok(1234));
+        assert!(KillCommand::resolve_pid("abc", &prompt).is_err());
+        assert_eq!(
+            KillCommand::resolve_pid("%1", &prompt),
+            Err("kill: %1: no such job".to_string())
+        );
+    }
+
+    #[test]
+    fn resolve_job_spec() {
+        let prompt = Prompt::create(context::default());
+        let child = process::Command::new("true").spawn().unwrap();
+        let pid = child.id() as libc::pid_t;
+        prompt
+            .context
+            .borrow_mut()
+            .jobs
+            .add(child, "true".to_string());
+        assert_eq!(KillCommand::resolve_pid("%1", &prompt), Ok(pid));
+        assert_eq!(KillCommand::resolve_pid("%%", &prompt), Ok(pid));
+    }
+
+    #[test]
+    fn invalid_signal() {
+        let mut prompt = Prompt::create(context::default());
+        assert_eq!(kill(&mut prompt, &["-FOO", "1234"]), Ok(false));
+        assert_eq!(prompt.context.borrow().env["?"], "1");
+    }
+
+    #[test]
+    fn missing_target() {
+        let mut prompt = Prompt::create(context::default());
+        assert_eq!(kill(&mut prompt, &["-9"]), Ok(false));
+        assert_eq!(kill(&mut prompt, &["%1"]), Ok(false));
+    }
+
+    #[test]
+    fn list() {
+        let mut prompt = Prompt::create(context::default());
+        assert_eq!(kill(&mut prompt, &["-l"]), Ok(true));
+        assert_eq!(kill(&mut prompt, &["-l", "9", "143"]), Ok(true));
+        assert_eq!(kill(&mut prompt, &["-l", "1000"]), Ok(false));
+        assert_eq!(kill(&mut prompt, &["-l", "0"]), Ok(false));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn kill_process() {
+        let mut prompt = Prompt::create(context::default());
+        let mut child = process::Command::new("sleep").arg("10").spawn().unwrap();
+        let pid = child.id().to_string();
+        assert_eq!(kill(&mut prompt, &["-s", "KILL", &pid]), Ok(true));
+        assert_eq!(prompt.context.borrow().env["?"], "0");
+        assert_eq!(child.wait().unwrap().signal(), Some(libc::SIGKILL));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn signal_zero_checks_process() {
+        let mut prompt = Prompt::create(context::default());
+        let mut child = process::Command::new("sleep").arg("10").spawn().unwrap();
+        let pid = child.id().to_string();
+        assert_eq!(kill(&mut prompt, &["-0", &pid]), Ok(true));
+        assert_eq!(kill(&mut prompt, &["-s", "0", &pid]), Ok(true));
+
+        child.kill().unwrap();
+        child.wait().unwrap();
+        assert_eq!(kill(&mut prompt, &["-0", &pid]), Ok(false));
+        assert_eq!(prompt.context.borrow().env["?"], "1");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn kill_job() {
+        let mut prompt = Prompt::create(context::default());
+        let child = process::Command::new("sleep").arg("10").spawn().unwrap();
+        prompt
+            .context
+            .borrow_mut()
+            .jobs
+            .add(child, "sleep 10".to_string());
+        assert_eq!(kill(&mut prompt, &["-TERM", "%1"]), Ok(true));
+
+        // Reap the job so no zombie process is left behind.
+        while !prompt.context.borrow().jobs.is_empty() {
+            prompt.context.borrow_mut().jobs.reap();
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+}
@@ -0,0 +1,77 @@
+use super::*;
+
+use std::env;
+
+use crate::util;
+
+/// Logout command exits a login shell like `exit` does, with the same optional exit code. Shells
+/// that aren't login shells refuse, pointing to `exit` instead.
+pub struct LogoutCommand {
+    exit: ExitCommand,
+}
+
+impl LogoutCommand {
+    pub fn new(args: Vec<String>) -> LogoutCommand {
+        LogoutCommand {
+            exit: ExitCommand::new(args),
+        }
+    }
+
+    /// Exits like `exit` if `login` is true, otherwise it prints an error and fails.
+    fn logout(&mut self, prompt: &mut Prompt, login: bool) -> Result<bool, i32> {
+        if login {
+            return self.exit.execute(prompt);
+        }
+        eprintln!("logout: not login shell: use 'exit'");
+        prompt
+            .context
+            .borrow_mut()
+            .env
+            .insert("?".to_string(), "1".to_string());
+        Ok(false)
+    }
+}
+
+impl Command for LogoutCommand {
+    fn execute(&mut self, prompt: &mut Prompt) -> Result<bool, i32> {
+        let name = env::args().next().unwrap_or_default();
+        self.logout(prompt, util::is_login_shell(&name))
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+impl CommandAliases for LogoutCommand {
+    fn aliases() -> Vec<String> {
+        vec!["logout".to_string()]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::context;
+
+    #[test]
+    fn not_login_shell() {
+        let mut prompt = Prompt::create(context::default());
+        let mut cmd = LogoutCommand::new(vec!["3".to_string()]);
+        assert_eq!(cmd.logout(&mut prompt, false), Ok(false));
+        assert_eq!(prompt.context.borrow().env["?"], "1");
+
+        // The test binary isn't started as a login shell.
+        assert_eq!(cmd.execute(&mut prompt), Ok(false));
+    }
+
+    #[test]
+    fn login_shell_exits() {
+        let mut prompt = Prompt::create(context::default());
+        let mut cmd = LogoutCommand::new(vec!["3".to_string()]);
+        assert_eq!(cmd.logout(&mut prompt, true), Err(3));
+
+        let mut cmd = LogoutCommand::new(vec!["foo".to_string()]);
+        assert_eq!(cmd.logout(&mut prompt, true), Err(2));
+    }
+}
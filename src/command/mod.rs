@@ -1,20 +1,65 @@
 use std::any::Any;
+use std::fs::File;
+use std::io;
 use std::process;
+use std::thread;
 
+use super::context::ContextData;
 use super::prompt::{EofError, Prompt, PromptResult};
 
+pub mod complete_command;
+use self::complete_command::CompleteCommand;
+
+pub mod declare_command;
+use self::declare_command::DeclareCommand;
+
+pub mod echo_command;
+use self::echo_command::EchoCommand;
+
+pub mod enable_command;
+use self::enable_command::EnableCommand;
+
+pub mod expansion_command;
+use self::expansion_command::ExpansionCommand;
+
 pub mod exit_command;
 use self::exit_command::ExitCommand;
 
+pub mod printf_command;
+use self::printf_command::PrintfCommand;
+
 pub mod quit_command;
 use self::quit_command::QuitCommand;
 
 pub mod cd_command;
 use self::cd_command::CdCommand;
 
+pub mod getopts_command;
+use self::getopts_command::GetoptsCommand;
+
 pub mod general_command;
 use self::general_command::GeneralCommand;
 
+pub mod array_assign_command;
+
+pub mod sequence_command;
+
+pub mod redirect_command;
+use self::redirect_command::Redirect;
+
+pub mod background_command;
+
+pub mod pipeline_command;
+use self::pipeline_command::Pipe;
+
+pub mod confirm_command;
+
+pub mod jobs_command;
+use self::jobs_command::JobsCommand;
+
+pub mod kill_command;
+use self::kill_command::KillCommand;
+
 pub mod history_command;
 use self::history_command::HistoryCommand;
 
@@ -27,18 +72,42 @@ use self::export_command::ExportCommand;
 pub mod set_command;
 use self::set_command::SetCommand;
 
+pub mod sh_eval_command;
+use self::sh_eval_command::ShEvalCommand;
+
+pub mod readonly_command;
+use self::readonly_command::ReadonlyCommand;
+
+pub mod source_command;
+use self::source_command::SourceCommand;
+
 pub mod rehash_command;
 use self::rehash_command::RehashCommand;
 
 pub mod hash_command;
 use self::hash_command::HashCommand;
 
+pub mod please_command;
+use self::please_command::PleaseCommand;
+
 pub mod popd_command;
 use self::popd_command::PopdCommand;
 
 pub mod dirs_command;
 use self::dirs_command::DirsCommand;
 
+pub mod logout_command;
+use self::logout_command::LogoutCommand;
+
+pub mod repeat_command;
+use self::repeat_command::RepeatCommand;
+
+pub mod suspend_command;
+use self::suspend_command::SuspendCommand;
+
+pub mod time_command;
+use self::time_command::TimeCommand;
+
 /// Base trait of all commands.
 pub trait Command {
     /// Execute command and return `Ok(true)` if command was run successfully, `Ok(false)` if not,
@@ -50,6 +119,10 @@ pub trait Command {
     fn as_any(&self) -> &dyn Any;
 }
 
+/// Creates command instance from arguments. Used to register custom commands via
+/// `ContextData::register_command()`.
+pub type CommandFactory = Box<dyn Fn(Vec<String>) -> Box<dyn Command>>;
+
 /// Commands define their name and aliases with the CommandAliases trait.
 pub trait CommandAliases {
     fn aliases() -> Vec<String>;
@@ -59,15 +132,32 @@ pub trait CommandAliases {
 pub fn builtins() -> Vec<String> {
     vec![
         CdCommand::aliases(),
+        CompleteCommand::aliases(),
+        DeclareCommand::aliases(),
         DirsCommand::aliases(),
+        EchoCommand::aliases(),
+        EnableCommand::aliases(),
         ExitCommand::aliases(),
+        ExpansionCommand::aliases(),
         ExportCommand::aliases(),
+        GetoptsCommand::aliases(),
         HashCommand::aliases(),
         HistoryCommand::aliases(),
+        JobsCommand::aliases(),
+        KillCommand::aliases(),
+        LogoutCommand::aliases(),
+        PleaseCommand::aliases(),
         PopdCommand::aliases(),
+        PrintfCommand::aliases(),
         QuitCommand::aliases(),
+        ReadonlyCommand::aliases(),
         RehashCommand::aliases(),
+        RepeatCommand::aliases(),
         SetCommand::aliases(),
+        ShEvalCommand::aliases(),
+        SourceCommand::aliases(),
+        SuspendCommand::aliases(),
+        TimeCommand::aliases(),
         UnsetCommand::aliases(),
     ]
     .into_iter()
@@ -75,31 +165,73 @@ pub fn builtins() -> Vec<String> {
     .collect()
 }
 
-/// Create command instance from `program` and `args`.
-pub fn parse(program: String, args: Vec<String>) -> Box<dyn Command> {
+/// Create command instance from `program` and `args`. Custom commands registered in context take
+/// precedence over builtins, and disabled builtins are resolved as programs.
+pub fn parse(program: String, args: Vec<String>, ctx: &ContextData) -> Box<dyn Command> {
+    if let Some(factory) = ctx.custom_commands.get(&program) {
+        return factory(args);
+    }
+    if ctx.disabled_builtins.contains(&program) {
+        return Box::new(GeneralCommand::new(program, args));
+    }
+
     match program.as_ref() {
         "cd" | "pushd" => Box::new(CdCommand::new(program, args)),
+        "complete" => Box::new(CompleteCommand::new(args)),
+        "declare" | "typeset" => Box::new(DeclareCommand::new(args)),
         "dirs" => Box::new(DirsCommand::new(args)),
+        "echo" => Box::new(EchoCommand::new(args)),
+        "enable" => Box::new(EnableCommand::new(args)),
         "exit" => Box::new(ExitCommand::new(args)),
+        "expansion" => Box::new(ExpansionCommand::new(args)),
         "export" => Box::new(ExportCommand::new(args)),
+        "getopts" => Box::new(GetoptsCommand::new(args)),
         "hash" => Box::new(HashCommand::new(args)),
         "history" | "hist" | "h" => Box::new(HistoryCommand::new(args)),
+        "jobs" => Box::new(JobsCommand::new(args)),
+        "kill" => Box::new(KillCommand::new(args)),
+        "logout" => Box::new(LogoutCommand::new(args)),
+        "please" => Box::new(PleaseCommand {}),
         "popd" => Box::new(PopdCommand {}),
+        "printf" => Box::new(PrintfCommand::new(args)),
         "quit" => Box::new(QuitCommand {}),
+        "readonly" => Box::new(ReadonlyCommand::new(args)),
         "rehash" => Box::new(RehashCommand {}),
+        "repeat" => Box::new(RepeatCommand::new(args)),
         "set" => Box::new(SetCommand::new(args)),
+        "sh-eval" => Box::new(ShEvalCommand::new(args)),
+        "." | "source" => Box::new(SourceCommand::new(args)),
+        "suspend" => Box::new(SuspendCommand {}),
+        "time" => Box::new(TimeCommand::new(args)),
         "unset" => Box::new(UnsetCommand::new(args)),
         _ => Box::new(GeneralCommand::new(program, args)),
     }
 }
 
-/// Execute command and yield optional exit code value.
+/// Execute command and yield optional exit code value. `$?` is updated with the status of the
+/// command, also for builtins that don't set it, and a failing command exits with `errexit`.
 pub fn execute(cmd: PromptResult, prompt: &mut Prompt) -> Option<i32> {
     match cmd {
-        Ok(mut cmd) => match cmd.execute(prompt) {
-            Ok(_) => None,
-            Err(code) => Some(code),
-        },
+        Ok(mut cmd) => {
+            // A refused exit is only remembered until the next command.
+            let warned = prompt.context.borrow().stopped_jobs_warned;
+            let res = cmd.execute(prompt);
+            let mut ctx = prompt.context.borrow_mut();
+            if warned {
+                ctx.stopped_jobs_warned = false;
+            }
+
+            let success = match res {
+                Ok(success) => success,
+                Err(code) => return Some(code),
+            };
+            let code = status(success, &ctx);
+            ctx.env.insert("?".to_string(), code.to_string());
+            if !success && ctx.exits_on_failure() {
+                return Some(code);
+            }
+            None
+        }
         Err(err) => {
             if err.is::<EofError>() {
                 if prompt.context.borrow().ignoreeof {
@@ -115,16 +247,126 @@ pub fn execute(cmd: PromptResult, prompt: &mut Prompt) -> Option<i32> {
     }
 }
 
+/// Executes command and yields its output with trailing newlines removed, like for `$(...)`. `$?`
+/// is updated, but the shell never exits, not even via `exit` or `errexit`, like for a subshell.
+/// Programs are run via `GeneralCommand::capture()`, and other commands, like builtins and
+/// pipelines, with the output of carapace redirected to a pipe. At most `max_capture_bytes` of the
+/// config are kept.
+pub fn capture(cmd: PromptResult, prompt: &mut Prompt) -> String {
+    let mut cmd = match cmd {
+        Ok(cmd) => cmd,
+        Err(err) => {
+            execute(Err(err), prompt);
+            return String::new();
+        }
+    };
+    if let Some(cmd) = cmd.as_any().downcast_ref::<GeneralCommand>() {
+        return cmd.capture(prompt).1;
+    }
+
+    let (read, write) = match Pipe::new() {
+        Ok(Pipe {
+            read: Some(read),
+            write: Some(write),
+        }) => (read, write),
+        Ok(_) => return String::new(),
+        Err(err) => {
+            eprintln!("Could not capture output: {}", err);
+            return String::new();
+        }
+    };
+
+    // The output is read while the command runs, such that it doesn't block when the pipe is full.
+    // All of it is read, even beyond the limit, so builtins don't fail writing.
+    let max = prompt.context.borrow().config.max_capture_bytes;
+    let reader = thread::spawn(move || {
+        let mut read = File::from(read);
+        let (output, truncated) = GeneralCommand::read_capped(&mut read, max);
+        let _ = io::copy(&mut read, &mut io::sink());
+        (output, truncated)
+    });
+    match Redirect::new(libc::STDOUT_FILENO, &write) {
+        Ok(_redirect) => {
+            drop(write);
+            let res = cmd.execute(prompt);
+            let mut ctx = prompt.context.borrow_mut();
+            let code = match res {
+                Ok(success) => status(success, &ctx),
+                Err(code) => code,
+            };
+            ctx.env.insert("?".to_string(), code.to_string());
+        }
+        Err(err) => {
+            drop(write);
+            eprintln!("Could not capture output: {}", err);
+        }
+    }
+
+    let (output, truncated) = reader.join().unwrap_or_default();
+    if truncated {
+        eprintln!("Output truncated to {} bytes", max);
+    }
+    let mut text = String::from_utf8_lossy(&output).into_owned();
+    while text.ends_with('\n') {
+        text.pop();
+    }
+    text
+}
+
+/// Yields the exit status of a command that finished with `success`. Failing commands keep a
+/// non-zero `$?` they set, and otherwise yield `1`.
+fn status(success: bool, ctx: &ContextData) -> i32 {
+    if success {
+        return 0;
+    }
+    match ctx.env.get("?").and_then(|code| code.parse().ok()) {
+        Some(code) if code != 0 => code,
+        _ => 1,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::context;
 
     #[test]
     fn check_builtins() {
         // The order is important!
         let cmds: Vec<String> = vec![
-            "cd", "pushd", "dirs", "exit", "export", "hash", "h", "hist", "history", "popd",
-            "quit", "rehash", "set", "unset",
+            "cd",
+            "pushd",
+            "complete",
+            "declare",
+            "typeset",
+            "dirs",
+            "echo",
+            "enable",
+            "exit",
+            "expansion",
+            "export",
+            "getopts",
+            "hash",
+            "h",
+            "hist",
+            "history",
+            "jobs",
+            "kill",
+            "logout",
+            "please",
+            "popd",
+            "printf",
+            "quit",
+            "readonly",
+            "rehash",
+            "repeat",
+            "set",
+            "sh-eval",
+            ".",
+            "source",
+            "suspend",
+            "time",
+            "unset",
         ]
         .into_iter()
         .map(|x| x.to_string())
@@ -134,22 +376,29 @@ mod tests {
 
     #[test]
     fn parse_quit() {
-        let cmd = parse(String::from("quit"), vec![]);
+        let cmd = parse(String::from("quit"), vec![], &ContextData::default());
         let cmd = cmd.as_any().downcast_ref::<QuitCommand>();
         assert!(cmd.is_some());
     }
 
     #[test]
     fn parse_exit() {
-        let cmd = parse(String::from("exit"), vec![]);
+        let cmd = parse(String::from("exit"), vec![], &ContextData::default());
         let cmd = cmd.as_any().downcast_ref::<ExitCommand>();
         assert!(cmd.is_some());
         assert_eq!(cmd.unwrap().code, 0);
     }
 
+    #[test]
+    fn parse_expansion() {
+        let cmd = parse(String::from("expansion"), vec![], &ContextData::default());
+        let cmd = cmd.as_any().downcast_ref::<ExpansionCommand>();
+        assert!(cmd.is_some());
+    }
+
     #[test]
     fn parse_cd() {
-        let cmd = parse(String::from("cd"), vec![]);
+        let cmd = parse(String::from("cd"), vec![], &ContextData::default());
         let cmd = cmd.as_any().downcast_ref::<CdCommand>();
         assert!(cmd.is_some());
         assert_eq!(cmd.unwrap().path, "~");
@@ -157,21 +406,21 @@ mod tests {
 
     #[test]
     fn parse_history() {
-        let cmd = parse(String::from("history"), vec![]);
+        let cmd = parse(String::from("history"), vec![], &ContextData::default());
         let cmd = cmd.as_any().downcast_ref::<HistoryCommand>();
         assert!(cmd.is_some());
     }
 
     #[test]
     fn parse_history_hist() {
-        let cmd = parse(String::from("hist"), vec![]);
+        let cmd = parse(String::from("hist"), vec![], &ContextData::default());
         let cmd = cmd.as_any().downcast_ref::<HistoryCommand>();
         assert!(cmd.is_some());
     }
 
     #[test]
     fn parse_history_h() {
-        let cmd = parse(String::from("h"), vec![]);
+        let cmd = parse(String::from("h"), vec![], &ContextData::default());
         let cmd = cmd.as_any().downcast_ref::<HistoryCommand>();
         assert!(cmd.is_some());
     }
@@ -180,7 +429,7 @@ mod tests {
     fn parse_general() {
         let prog = String::from("ls");
         let args = vec![String::from("-lh"), String::from("~/git")];
-        let cmd = parse(prog.clone(), args.clone());
+        let cmd = parse(prog.clone(), args.clone(), &ContextData::default());
 
         let cmd = cmd.as_any().downcast_ref::<GeneralCommand>();
         assert!(cmd.is_some());
@@ -190,37 +439,193 @@ mod tests {
         assert_eq!(cmd.args, args);
     }
 
+    #[test]
+    fn parse_custom() {
+        let mut ctx = ContextData::default();
+        ctx.register_command("set", |_args| Box::new(QuitCommand {}));
+
+        // Custom commands take precedence over builtins.
+        let cmd = parse(String::from("set"), vec![], &ctx);
+        let cmd = cmd.as_any().downcast_ref::<QuitCommand>();
+        assert!(cmd.is_some());
+    }
+
     #[test]
     fn parse_set() {
-        let cmd = parse(String::from("set"), vec![]);
+        let cmd = parse(String::from("set"), vec![], &ContextData::default());
         let cmd = cmd.as_any().downcast_ref::<SetCommand>();
         assert!(cmd.is_some());
     }
 
+    #[test]
+    fn parse_sh_eval() {
+        let cmd = parse(String::from("sh-eval"), vec![], &ContextData::default());
+        let cmd = cmd.as_any().downcast_ref::<ShEvalCommand>();
+        assert!(cmd.is_some());
+    }
+
     #[test]
     fn parse_unset() {
-        let cmd = parse(String::from("unset"), vec![]);
+        let cmd = parse(String::from("unset"), vec![], &ContextData::default());
         let cmd = cmd.as_any().downcast_ref::<UnsetCommand>();
         assert!(cmd.is_some());
     }
 
     #[test]
     fn parse_export() {
-        let cmd = parse(String::from("export"), vec![]);
+        let cmd = parse(String::from("export"), vec![], &ContextData::default());
         let cmd = cmd.as_any().downcast_ref::<ExportCommand>();
         assert!(cmd.is_some());
     }
 
+    #[test]
+    fn parse_getopts() {
+        let cmd = parse(String::from("getopts"), vec![], &ContextData::default());
+        let cmd = cmd.as_any().downcast_ref::<GetoptsCommand>();
+        assert!(cmd.is_some());
+    }
+
+    #[test]
+    fn parse_complete() {
+        let cmd = parse(String::from("complete"), vec![], &ContextData::default());
+        let cmd = cmd.as_any().downcast_ref::<CompleteCommand>();
+        assert!(cmd.is_some());
+    }
+
+    #[test]
+    fn parse_declare() {
+        for name in &["declare", "typeset"] {
+            let cmd = parse(name.to_string(), vec![], &ContextData::default());
+            let cmd = cmd.as_any().downcast_ref::<DeclareCommand>();
+            assert!(cmd.is_some());
+        }
+    }
+
+    #[test]
+    fn parse_enable() {
+        let cmd = parse(String::from("enable"), vec![], &ContextData::default());
+        let cmd = cmd.as_any().downcast_ref::<EnableCommand>();
+        assert!(cmd.is_some());
+    }
+
+    #[test]
+    fn parse_jobs() {
+        let cmd = parse(String::from("jobs"), vec![], &ContextData::default());
+        let cmd = cmd.as_any().downcast_ref::<JobsCommand>();
+        assert!(cmd.is_some());
+    }
+
+    #[test]
+    fn parse_kill() {
+        let cmd = parse(String::from("kill"), vec![], &ContextData::default());
+        let cmd = cmd.as_any().downcast_ref::<KillCommand>();
+        assert!(cmd.is_some());
+    }
+
+    #[test]
+    fn parse_logout() {
+        let cmd = parse(String::from("logout"), vec![], &ContextData::default());
+        let cmd = cmd.as_any().downcast_ref::<LogoutCommand>();
+        assert!(cmd.is_some());
+    }
+
+    #[test]
+    fn parse_please() {
+        let cmd = parse(String::from("please"), vec![], &ContextData::default());
+        let cmd = cmd.as_any().downcast_ref::<PleaseCommand>();
+        assert!(cmd.is_some());
+    }
+
+    #[test]
+    fn parse_echo() {
+        let cmd = parse(String::from("echo"), vec![], &ContextData::default());
+        let cmd = cmd.as_any().downcast_ref::<EchoCommand>();
+        assert!(cmd.is_some());
+    }
+
+    #[test]
+    fn parse_printf() {
+        let cmd = parse(String::from("printf"), vec![], &ContextData::default());
+        let cmd = cmd.as_any().downcast_ref::<PrintfCommand>();
+        assert!(cmd.is_some());
+    }
+
+    #[test]
+    fn parse_source() {
+        for program in &[".", "source"] {
+            let cmd = parse(program.to_string(), vec![], &ContextData::default());
+            let cmd = cmd.as_any().downcast_ref::<SourceCommand>();
+            assert!(cmd.is_some());
+        }
+    }
+
+    #[test]
+    fn parse_time() {
+        let cmd = parse(String::from("time"), vec![], &ContextData::default());
+        let cmd = cmd.as_any().downcast_ref::<TimeCommand>();
+        assert!(cmd.is_some());
+    }
+
+    #[test]
+    fn parse_repeat() {
+        let cmd = parse(String::from("repeat"), vec![], &ContextData::default());
+        let cmd = cmd.as_any().downcast_ref::<RepeatCommand>();
+        assert!(cmd.is_some());
+    }
+
+    #[test]
+    fn parse_suspend() {
+        let cmd = parse(String::from("suspend"), vec![], &ContextData::default());
+        let cmd = cmd.as_any().downcast_ref::<SuspendCommand>();
+        assert!(cmd.is_some());
+    }
+
+    #[test]
+    fn parse_readonly() {
+        let cmd = parse(String::from("readonly"), vec![], &ContextData::default());
+        let cmd = cmd.as_any().downcast_ref::<ReadonlyCommand>();
+        assert!(cmd.is_some());
+    }
+
+    #[test]
+    fn capture_builtins_and_pipelines() {
+        let mut prompt = Prompt::create(context::default());
+        let mut capture = |input: &str| {
+            let cmd = prompt.parse_command(input);
+            super::capture(cmd, &mut prompt)
+        };
+        assert_eq!(capture("printf 'a\\nb\\n\\n'"), "a\nb");
+        assert_eq!(capture("printf 'x\\ny\\n' | tr y z"), "x\nz");
+        assert_eq!(capture("printf a; printf b"), "ab");
+        assert_eq!(capture("exit 3"), "");
+    }
+
+    #[test]
+    fn capture_sets_status() {
+        let mut prompt = Prompt::create(context::default());
+        let cmd = prompt.parse_command("complete -p none");
+        super::capture(cmd, &mut prompt);
+        assert_eq!(prompt.context.borrow().env["?"], "1");
+    }
+
+    #[test]
+    fn capture_truncated() {
+        let mut prompt = Prompt::create(context::default());
+        prompt.context.borrow_mut().config.max_capture_bytes = 4;
+        let cmd = prompt.parse_command("printf abcdefgh");
+        assert_eq!(super::capture(cmd, &mut prompt), "abcd");
+    }
+
     #[test]
     fn parse_rehash() {
-        let cmd = parse(String::from("rehash"), vec![]);
+        let cmd = parse(String::from("rehash"), vec![], &ContextData::default());
         let cmd = cmd.as_any().downcast_ref::<RehashCommand>();
         assert!(cmd.is_some());
     }
 
     #[test]
     fn parse_hash() {
-        let cmd = parse(String::from("hash"), vec![]);
+        let cmd = parse(String::from("hash"), vec![], &ContextData::default());
         let cmd = cmd.as_any().downcast_ref::<HashCommand>();
         assert!(cmd.is_some());
     }
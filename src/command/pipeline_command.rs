@@ -0,0 +1,235 @@
+use super::*;
+
+use super::general_command::{GeneralCommand, TermState};
+use super::redirect_command::Redirect;
+
+use std::os::unix::io::{FromRawFd, OwnedFd};
+use std::process::{Child, Stdio};
+
+/// Pipe connecting the output of a command of a pipeline to the input of the next. The ends are
+/// closed when dropped, unless taken by the command using them.
+pub struct Pipe {
+    pub read: Option<OwnedFd>,
+    pub write: Option<OwnedFd>,
+}
+
+impl Pipe {
+    /// Creates pipe whose ends aren't inherited by other programs than the one they are given to.
+    pub fn new() -> io::Result<Pipe> {
+        let mut fds = [0; 2];
+        if unsafe { libc::pipe(fds.as_mut_ptr()) } == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        let pipe = unsafe {
+            Pipe {
+                read: Some(OwnedFd::from_raw_fd(fds[0])),
+                write: Some(OwnedFd::from_raw_fd(fds[1])),
+            }
+        };
+        for fd in &fds {
+            if unsafe { libc::fcntl(*fd, libc::F_SETFD, libc::FD_CLOEXEC) } == -1 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+        Ok(pipe)
+    }
+}
+
+/// Pipeline command runs commands with the output of each connected to the input of the next, like
+/// `ls | grep foo`. The last command writes to the terminal, and its exit code is the one of the
+/// pipeline in `$?`.
+///
+/// Programs are started first and run concurrently. Builtins then run one after another within
+/// carapace with their input and output redirected, so output of a builtin read by another builtin
+/// must fit into the pipe.
+pub struct PipelineCommand {
+    pub commands: Vec<Box<dyn Command>>,
+}
+
+impl PipelineCommand {
+    pub fn new(commands: Vec<Box<dyn Command>>) -> PipelineCommand {
+        PipelineCommand { commands }
+    }
+
+    /// Runs builtin `cmd` with input from `stdin` and output to `stdout`, if given.
+    fn run_builtin(
+        cmd: &mut Box<dyn Command>,
+        stdin: Option<OwnedFd>,
+        stdout: Option<OwnedFd>,
+        prompt: &mut Prompt,
+    ) -> Result<bool, i32> {
+        let redirects = (
+            stdin
+                .as_ref()
+                .map(|fd| Redirect::new(libc::STDIN_FILENO, fd)),
+            stdout
+                .as_ref()
+                .map(|fd| Redirect::new(libc::STDOUT_FILENO, fd)),
+        );
+        match redirects {
+            (Some(Err(err)), _) | (_, Some(Err(err))) => {
+                println!("Could not redirect builtin: {}", err);
+                Ok(false)
+            }
+
+            // The redirects are restored before the pipe ends are closed.
+            _ => cmd.execute(prompt),
+        }
+    }
+}
+
+impl Command for PipelineCommand {
+    fn execute(&mut self, prompt: &mut Prompt) -> Result<bool, i32> {
+        let count = self.commands.len();
+        let mut pipes = Vec::new();
+        for _ in 1..count {
+            match Pipe::new() {
+                Ok(pipe) => pipes.push(pipe),
+                Err(err) => {
+                    println!("Could not create pipe: {}", err);
+                    return Ok(false);
+                }
+            }
+        }
+
+        let term = TermState::save(libc::STDOUT_FILENO);
+        let last = count - 1;
+        let mut result = Ok(false);
+
+        // Programs are started before builtins run, such that a builtin writing to a program
+        // doesn't block when the pipe is full.
+        let mut children: Vec<(usize, Child)> = Vec::new();
+        for (i, cmd) in self.commands.iter().enumerate() {
+            let cmd = match cmd.as_any().downcast_ref::<GeneralCommand>() {
+                Some(cmd) => cmd,
+                None => continue,
+            };
+            let stdin = match i {
+                0 => Stdio::inherit(),
+                _ => pipes[i - 1]
+                    .read
+                    .take()
+                    .map_or_else(Stdio::null, Stdio::from),
+            };
+            let stdout = match i {
+                _ if i == last => Stdio::inherit(),
+                _ => pipes[i].write.take().map_or_else(Stdio::null, Stdio::from),
+            };
+            let ctx = prompt.context.borrow();
+            match cmd.spawn(&ctx, stdin, stdout, Stdio::inherit()) {
+                Ok(child) => children.push((i, child)),
+                Err(err) => {
+                    let res = GeneralCommand::spawn_failed(err, &ctx);
+                    if i == last {
+                        result = res;
+                    }
+                }
+            }
+        }
+
+        for (i, cmd) in self.commands.iter_mut().enumerate() {
+            if cmd.as_any().is::<GeneralCommand>() {
+                continue;
+            }
+            let stdin = if i > 0 {
+                pipes[i - 1].read.take()
+            } else {
+                None
+            };
+            let stdout = if i < last {
+                pipes[i].write.take()
+            } else {
+                None
+            };
+            let res = PipelineCommand::run_builtin(cmd, stdin, stdout, prompt);
+            if i == last {
+                result = res;
+            }
+        }
+
+        // Close the remaining pipe ends so the programs see the end of their input, or that their
+        // output isn't read anymore.
+        drop(pipes);
+
+        // The exit codes of all but the last command are ignored.
+        for (i, mut child) in children {
+            let status = child.wait();
+            if i == last {
+                result = match status {
+                    Ok(status) => GeneralCommand::finish(status, &mut prompt.context.borrow_mut()),
+                    Err(_) => Ok(false),
+                };
+            }
+        }
+        if let Some(term) = term {
+            term.restore();
+        }
+        result
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::context;
+
+    use std::fs;
+
+    use tempfile::TempDir;
+
+    fn run(prompt: &mut Prompt, input: &str) -> Result<bool, i32> {
+        let mut cmd = prompt.parse_command(input).unwrap();
+        assert!(cmd.as_any().downcast_ref::<PipelineCommand>().is_some());
+        cmd.execute(prompt)
+    }
+
+    #[test]
+    fn output_passed_on() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("out");
+        let mut prompt = Prompt::create(context::default());
+        let input = format!(
+            "printf 'b\\na\\nb\\n' | sort | uniq -c | sh -c 'tr -d \" \" > {}'",
+            path.display()
+        );
+        assert_eq!(run(&mut prompt, &input), Ok(true));
+        assert_eq!(fs::read_to_string(&path).unwrap(), "1a\n2b\n");
+    }
+
+    #[test]
+    fn status_of_last_command() {
+        let mut prompt = Prompt::create(context::default());
+        assert_eq!(run(&mut prompt, "true | sh -c 'exit 3'"), Ok(false));
+        assert_eq!(prompt.context.borrow().env["?"], "3");
+
+        assert_eq!(run(&mut prompt, "false | true"), Ok(true));
+        assert_eq!(prompt.context.borrow().env["?"], "0");
+
+        assert_eq!(
+            run(&mut prompt, "true | nonexistent_program_xyz"),
+            Ok(false)
+        );
+    }
+
+    #[test]
+    fn later_command_stops_reading() {
+        // The first program gets SIGPIPE once the output isn't read anymore instead of blocking.
+        let mut prompt = Prompt::create(context::default());
+        assert_eq!(
+            run(&mut prompt, "yes | head -n 1 | sh -c 'cat > /dev/null'"),
+            Ok(true)
+        );
+    }
+
+    #[test]
+    fn errexit_applies_to_last_command() {
+        let mut prompt = Prompt::create(context::default());
+        prompt.context.borrow_mut().errexit = true;
+        assert_eq!(run(&mut prompt, "false | true"), Ok(true));
+        assert_eq!(run(&mut prompt, "true | false"), Err(1));
+    }
+}
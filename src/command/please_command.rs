@@ -0,0 +1,154 @@
+use super::*;
+
+/// Please command runs the previous command again prefixed with the `elevate_prefix` of the
+/// config, like `sudo`, for when it was forgotten. The line that runs is printed first, like with
+/// `sudo !!` in other shells.
+pub struct PleaseCommand;
+
+impl PleaseCommand {
+    /// Yields the last line of `history`, which is ordered oldest first, prefixed with `prefix`.
+    /// Lines invoking `please` itself are skipped, like the one currently running. A line already
+    /// starting with `prefix`, like the elevated line of a previous `please`, is kept as is.
+    pub fn elevated_line<'a, I>(history: I, prefix: &str) -> Option<String>
+    where
+        I: DoubleEndedIterator<Item = &'a String>,
+    {
+        let line = history.rev().map(|l| l.trim()).find(|l| {
+            let first = l.split_whitespace().next();
+            first.is_some() && first != Some("please")
+        })?;
+        let elevated = line
+            .strip_prefix(prefix)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with(char::is_whitespace));
+        if prefix.is_empty() || elevated {
+            Some(line.to_string())
+        } else {
+            Some(format!("{} {}", prefix, line))
+        }
+    }
+}
+
+impl Command for PleaseCommand {
+    fn execute(&mut self, prompt: &mut Prompt) -> Result<bool, i32> {
+        let prefix = prompt.context.borrow().config.elevate_prefix.clone();
+        let line = match PleaseCommand::elevated_line(prompt.editor.history().iter(), &prefix) {
+            Some(line) => line,
+            None => {
+                eprintln!("please: no previous command");
+                prompt
+                    .context
+                    .borrow_mut()
+                    .env
+                    .insert("?".to_string(), "1".to_string());
+                return Ok(false);
+            }
+        };
+
+        println!("{}", line);
+        match prompt.parse_command(&line) {
+            Ok(mut cmd) => cmd.execute(prompt),
+            Err(err) => {
+                println!("{}", err);
+                Ok(false)
+            }
+        }
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+impl CommandAliases for PleaseCommand {
+    fn aliases() -> Vec<String> {
+        vec!["please".to_string()]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::context;
+
+    fn history(lines: &[&str]) -> Vec<String> {
+        lines.iter().map(|x| x.to_string()).collect()
+    }
+
+    #[test]
+    fn elevated_line() {
+        let lines = history(&["ls", "apt install foo", "please", "  please  "]);
+        assert_eq!(
+            PleaseCommand::elevated_line(lines.iter(), "sudo"),
+            Some("sudo apt install foo".to_string())
+        );
+        assert_eq!(
+            PleaseCommand::elevated_line(lines.iter(), "doas -u root"),
+            Some("doas -u root apt install foo".to_string())
+        );
+        assert_eq!(
+            PleaseCommand::elevated_line(lines.iter(), ""),
+            Some("apt install foo".to_string())
+        );
+        assert_eq!(
+            PleaseCommand::elevated_line(history(&["please", ""]).iter(), "sudo"),
+            None
+        );
+
+        // Already elevated lines aren't prefixed again, like after running `please` twice.
+        let lines = history(&["sudo apt install foo", "please"]);
+        assert_eq!(
+            PleaseCommand::elevated_line(lines.iter(), "sudo"),
+            Some("sudo apt install foo".to_string())
+        );
+        let lines = history(&["sudoedit /etc/hosts", "doas -u root ls"]);
+        assert_eq!(
+            PleaseCommand::elevated_line(lines[..1].iter(), "sudo"),
+            Some("sudo sudoedit /etc/hosts".to_string())
+        );
+        assert_eq!(
+            PleaseCommand::elevated_line(lines.iter(), "doas -u root"),
+            Some("doas -u root ls".to_string())
+        );
+        assert_eq!(
+            PleaseCommand::elevated_line(history(&[]).iter(), "sudo"),
+            None
+        );
+    }
+
+    #[test]
+    fn runs_previous_command_with_prefix() {
+        let mut prompt = Prompt::create(context::default());
+        prompt.context.borrow_mut().config.elevate_prefix = "env FOO=bar".to_string();
+
+        // The previous command fails unless the prefix sets the variable.
+        prompt
+            .editor
+            .add_history_entry("sh -c 'test \"$FOO\" = bar'");
+        let mut cmd = prompt.parse_command("please").unwrap();
+        assert!(cmd.as_any().downcast_ref::<PleaseCommand>().is_some());
+        assert_eq!(cmd.execute(&mut prompt), Ok(true));
+
+        let history = prompt.editor.history();
+        assert_eq!(
+            history.get(history.len() - 1).unwrap(),
+            "env FOO=bar sh -c 'test \"$FOO\" = bar'"
+        );
+
+        // Running it again doesn't prefix the elevated line once more.
+        let mut cmd = prompt.parse_command("please").unwrap();
+        assert_eq!(cmd.execute(&mut prompt), Ok(true));
+        let history = prompt.editor.history();
+        assert_eq!(
+            history.get(history.len() - 1).unwrap(),
+            "env FOO=bar sh -c 'test \"$FOO\" = bar'"
+        );
+    }
+
+    #[test]
+    fn no_previous_command() {
+        let mut prompt = Prompt::create(context::default());
+        let mut cmd = prompt.parse_command("please").unwrap();
+        assert_eq!(cmd.execute(&mut prompt), Ok(false));
+        assert_eq!(prompt.context.borrow().env["?"], "1");
+    }
+}
@@ -9,7 +9,7 @@ impl Command for PopdCommand {
     fn execute(&mut self, prompt: &mut Prompt) -> Result<bool, i32> {
         let path = prompt.context.borrow_mut().dir_stack.pop();
         if let Some(path) = &path {
-            prompt.set_cwd(Path::new(&path));
+            prompt.set_cwd(Path::new(&path), false);
             prompt.context.borrow().print_short_dir_stack();
         } else {
             println!("Directory stack is empty");
@@ -0,0 +1,256 @@
+use super::*;
+
+use crate::util;
+
+use std::io::Write;
+
+/// Printf command prints arguments formatted according to a format string.
+pub struct PrintfCommand {
+    args: Vec<String>,
+}
+
+impl PrintfCommand {
+    pub fn new(args: Vec<String>) -> PrintfCommand {
+        PrintfCommand { args }
+    }
+
+    /// Formats `args` according to `format` and yields the output bytes and whether all arguments
+    /// were valid for their conversions.
+    ///
+    /// Supported conversions are `%s`, `%d`, `%x`, and `%%`, with optional '-' and '0' flags and
+    /// width, like `%-5s` and `%05d`. Escape sequences, like `\n`, are interpreted in the format
+    /// string. The format is reused while there are arguments left, and missing arguments are
+    /// treated as empty strings or zero.
+    pub fn format(format: &str, args: &[String]) -> (Vec<u8>, bool) {
+        let mut output = Vec::new();
+        let mut success = true;
+        let mut args = args.iter();
+        loop {
+            let consumed = PrintfCommand::format_once(format, &mut args, &mut output, &mut success);
+            if consumed == 0 || args.len() == 0 {
+                break;
+            }
+        }
+        (output, success)
+    }
+
+    /// Formats `format` once while taking arguments from `args`. Returns the number of arguments
+    /// consumed.
+    fn format_once(
+        format: &str,
+        args: &mut std::slice::Iter<String>,
+        output: &mut Vec<u8>,
+        success: &mut bool,
+    ) -> usize {
+        let mut consumed = 0;
+        let mut chars = format.chars().peekable();
+        while let Some(ch) = chars.next() {
+            match ch {
+                '\\' => output.append(&mut util::unescape_one(&mut chars)),
+                '%' => {
+                    if chars.peek() == Some(&'%') {
+                        chars.next();
+                        output.push(b'%');
+                        continue;
+                    }
+
+                    // Flags and width.
+                    let mut left = false;
+                    let mut zero = false;
+                    while let Some(&flag) = chars.peek() {
+                        match flag {
+                            '-' => left = true,
+                            '0' => zero = true,
+                            _ => break,
+                        }
+                        chars.next();
+                    }
+                    let mut width = 0;
+                    while let Some(digit) = chars.peek().and_then(|c| c.to_digit(10)) {
+                        width = width * 10 + digit as usize;
+                        chars.next();
+                    }
+
+                    let conv = match chars.next() {
+                        Some(conv) => conv,
+                        None => {
+                            eprintln!("printf: %: missing format character");
+                            *success = false;
+                            break;
+                        }
+                    };
+                    let arg = args.next();
+                    if arg.is_some() {
+                        consumed += 1;
+                    }
+                    let arg = arg.map_or("", |a| a.as_str());
+
+                    let value = match conv {
+                        's' => arg.to_string(),
+                        'd' | 'x' => {
+                            let num = PrintfCommand::parse_number(arg, success);
+                            if conv == 'd' {
+                                num.to_string()
+                            } else {
+                                format!("{:x}", num)
+                            }
+                        }
+                        _ => {
+                            eprintln!("printf: %{}: invalid format character", conv);
+                            *success = false;
+                            format!("%{}", conv)
+                        }
+                    };
+
+                    let pad = width.saturating_sub(value.chars().count());
+                    let value = if left {
+                        format!("{}{}", value, " ".repeat(pad))
+                    } else if zero && conv != 's' {
+                        // Keep sign in front of zero padding.
+                        match value.strip_prefix('-') {
+                            Some(rest) => format!("-{}{}", "0".repeat(pad), rest),
+                            None => format!("{}{}", "0".repeat(pad), value),
+                        }
+                    } else {
+                        format!("{}{}", " ".repeat(pad), value)
+                    };
+                    output.extend_from_slice(value.as_bytes());
+                }
+                _ => output.extend_from_slice(ch.encode_utf8(&mut [0; 4]).as_bytes()),
+            }
+        }
+        consumed
+    }
+
+    /// Parses number argument, or warns and yields zero if invalid. An empty argument is zero.
+    fn parse_number(arg: &str, success: &mut bool) -> i64 {
+        if arg.is_empty() {
+            return 0;
+        }
+        match arg.trim_start_matches('+').parse::<i64>() {
+            Ok(num) => num,
+            Err(_) => {
+                eprintln!("printf: {}: invalid number", arg);
+                *success = false;
+                0
+            }
+        }
+    }
+}
+
+impl Command for PrintfCommand {
+    fn execute(&mut self, prompt: &mut Prompt) -> Result<bool, i32> {
+        if self.args.is_empty() {
+            println!("printf: usage: printf format [arguments]");
+            return Ok(false);
+        }
+
+        let (output, success) = PrintfCommand::format(&self.args[0], &self.args[1..]);
+        let mut stdout = io::stdout();
+        let _ = stdout.write_all(&output);
+        let _ = stdout.flush();
+
+        prompt
+            .context
+            .borrow_mut()
+            .env
+            .insert("?".to_string(), if success { 0 } else { 1 }.to_string());
+        Ok(success)
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+impl CommandAliases for PrintfCommand {
+    fn aliases() -> Vec<String> {
+        vec!["printf".to_string()]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn format(format: &str, args: &[&str]) -> (String, bool) {
+        let args: Vec<String> = args.iter().map(|a| a.to_string()).collect();
+        let (output, success) = PrintfCommand::format(format, &args);
+        (String::from_utf8(output).unwrap(), success)
+    }
+
+    #[test]
+    fn plain() {
+        assert_eq!(format("hello", &[]), ("hello".to_string(), true));
+    }
+
+    #[test]
+    fn conversions() {
+        assert_eq!(
+            format("%s=%d (%x)\\n", &["name", "42", "255"]),
+            ("name=42 (ff)\n".to_string(), true)
+        );
+    }
+
+    #[test]
+    fn percent_literal() {
+        assert_eq!(format("100%%", &[]), ("100%".to_string(), true));
+        assert_eq!(format("%d%%", &["5"]), ("5%".to_string(), true));
+    }
+
+    #[test]
+    fn escapes() {
+        assert_eq!(
+            format("a\\tb\\\\c\\101\\x42", &[]),
+            ("a\tb\\cAB".to_string(), true)
+        );
+    }
+
+    #[test]
+    fn escapes_yield_bytes() {
+        let (output, success) = PrintfCommand::format("\\377\\x00é", &[]);
+        assert_eq!(output, b"\xff\x00\xc3\xa9");
+        assert!(success);
+    }
+
+    #[test]
+    fn format_cycles_for_remaining_args() {
+        assert_eq!(
+            format("%s=%d\\n", &["a", "1", "b", "2", "c"]),
+            ("a=1\nb=2\nc=0\n".to_string(), true)
+        );
+    }
+
+    #[test]
+    fn missing_args() {
+        assert_eq!(format("[%s|%d]", &[]), ("[|0]".to_string(), true));
+    }
+
+    #[test]
+    fn no_conversions_with_args() {
+        assert_eq!(format("x", &["a", "b"]), ("x".to_string(), true));
+    }
+
+    #[test]
+    fn invalid_number() {
+        assert_eq!(format("%d %x", &["abc", "1"]), ("0 1".to_string(), false));
+    }
+
+    #[test]
+    fn negative_number() {
+        assert_eq!(format("%d", &["-5"]), ("-5".to_string(), true));
+    }
+
+    #[test]
+    fn width() {
+        assert_eq!(format("[%5s]", &["ab"]), ("[   ab]".to_string(), true));
+        assert_eq!(format("[%-5s]", &["ab"]), ("[ab   ]".to_string(), true));
+        assert_eq!(format("[%05d]", &["-42"]), ("[-0042]".to_string(), true));
+        assert_eq!(format("[%4x]", &["255"]), ("[  ff]".to_string(), true));
+    }
+
+    #[test]
+    fn invalid_conversion() {
+        assert_eq!(format("%q", &["a"]), ("%q".to_string(), false));
+    }
+}
@@ -1,11 +1,11 @@
 use super::*;
 
-/// Quit command provides an exit code of zeroo on execution.
+/// Quit command shuts the shell down with an exit code of zero on execution.
 pub struct QuitCommand;
 
 impl Command for QuitCommand {
-    fn execute(&mut self, _prompt: &mut Prompt) -> Result<bool, i32> {
-        Err(0)
+    fn execute(&mut self, prompt: &mut Prompt) -> Result<bool, i32> {
+        prompt.shutdown(0)
     }
 
     fn as_any(&self) -> &dyn Any {
@@ -18,3 +18,17 @@ impl CommandAliases for QuitCommand {
         vec!["quit".to_string()]
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::context;
+
+    #[test]
+    fn shuts_down() {
+        let mut prompt = Prompt::without_history(context::default());
+        let cmd = prompt.parse_command("quit");
+        assert_eq!(execute(cmd, &mut prompt), Some(0));
+        assert!(prompt.is_shut_down());
+    }
+}
@@ -0,0 +1,154 @@
+use super::*;
+
+use crate::util;
+
+use clap::{App, AppSettings, Arg};
+
+/// Readonly command marks variables as readonly so they cannot be changed or unset.
+pub struct ReadonlyCommand {
+    args: Vec<String>,
+    app: App<'static, 'static>,
+}
+
+impl ReadonlyCommand {
+    pub fn new(args: Vec<String>) -> ReadonlyCommand {
+        ReadonlyCommand {
+            args,
+            app: App::new("readonly")
+                .about("List readonly variables or mark variables as readonly.")
+                .setting(AppSettings::NoBinaryName)
+                .setting(AppSettings::DisableVersion)
+                .arg(Arg::with_name("vars").multiple(true).help(
+                    "Variable with optional value input as: 'variable' or 'variable=value'. \
+                     With no variables, all readonly variables are listed.",
+                )),
+        }
+    }
+
+    /// Yields `readonly key="value"`, or `readonly key` if it has no value.
+    fn format_var(key: &str, value: Option<&String>) -> String {
+        match value {
+            Some(value) => format!("readonly {}={}", key, util::double_quote(value)),
+            None => format!("readonly {}", key),
+        }
+    }
+}
+
+impl Command for ReadonlyCommand {
+    fn execute(&mut self, prompt: &mut Prompt) -> Result<bool, i32> {
+        let matches = self.app.get_matches_from_safe_borrow(&self.args);
+        if let Err(err) = matches {
+            println!("{}", err);
+            return Ok(false);
+        }
+
+        let env = &mut prompt.context.borrow_mut().env;
+        if self.args.is_empty() {
+            for k in env.readonly_keys() {
+                println!("{}", ReadonlyCommand::format_var(k, env.get(k)));
+            }
+            return Ok(true);
+        }
+
+        let mut success = true;
+        for var in &self.args {
+            let k = match var.find('=') {
+                Some(pos) => {
+                    let k = var[..pos].to_string();
+                    if let Err(err) = env.set(k.clone(), var[pos + 1..].to_string()) {
+                        println!("readonly: {}", err);
+                        success = false;
+                        continue;
+                    }
+                    k
+                }
+                None => var.clone(),
+            };
+            env.set_readonly(k);
+        }
+        Ok(success)
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+impl CommandAliases for ReadonlyCommand {
+    fn aliases() -> Vec<String> {
+        vec!["readonly".to_string()]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::context;
+
+    #[test]
+    fn mark_with_value() {
+        let mut prompt = Prompt::create(context::default());
+        let mut cmd = ReadonlyCommand::new(vec!["FOO=bar".to_string()]);
+        assert!(cmd.execute(&mut prompt).unwrap());
+
+        let env = &prompt.context.borrow().env;
+        assert_eq!(env.get("FOO"), Some(&"bar".to_string()));
+        assert!(env.is_readonly("FOO"));
+    }
+
+    #[test]
+    fn mark_existing() {
+        let mut prompt = Prompt::create(context::default());
+        prompt
+            .context
+            .borrow_mut()
+            .env
+            .insert("FOO".to_string(), "bar".to_string());
+
+        let mut cmd = ReadonlyCommand::new(vec!["FOO".to_string()]);
+        assert!(cmd.execute(&mut prompt).unwrap());
+
+        let env = &prompt.context.borrow().env;
+        assert_eq!(env.get("FOO"), Some(&"bar".to_string()));
+        assert!(env.is_readonly("FOO"));
+    }
+
+    #[test]
+    fn overwrite_fails() {
+        let mut prompt = Prompt::create(context::default());
+        let mut cmd = ReadonlyCommand::new(vec!["FOO=bar".to_string()]);
+        assert!(cmd.execute(&mut prompt).unwrap());
+
+        let mut cmd = ReadonlyCommand::new(vec!["FOO=baz".to_string()]);
+        assert!(!cmd.execute(&mut prompt).unwrap());
+
+        let mut cmd = ExportCommand::new(vec!["FOO=baz".to_string()]);
+        assert!(!cmd.execute(&mut prompt).unwrap());
+
+        assert_eq!(
+            prompt.context.borrow().env.get("FOO"),
+            Some(&"bar".to_string())
+        );
+    }
+
+    #[test]
+    fn unset_fails() {
+        let mut prompt = Prompt::create(context::default());
+        let mut cmd = ReadonlyCommand::new(vec!["FOO=bar".to_string()]);
+        assert!(cmd.execute(&mut prompt).unwrap());
+
+        let mut cmd = UnsetCommand::new(vec!["FOO".to_string()]);
+        assert!(!cmd.execute(&mut prompt).unwrap());
+        assert!(prompt.context.borrow().env.contains_key("FOO"));
+    }
+
+    #[test]
+    fn format_var() {
+        assert_eq!(
+            ReadonlyCommand::format_var("FOO", Some(&"bar".to_string())),
+            r#"readonly FOO="bar""#
+        );
+        assert_eq!(ReadonlyCommand::format_var("FOO", None), "readonly FOO");
+    }
+}
@@ -0,0 +1,344 @@
+use super::*;
+
+use super::general_command::GeneralCommand;
+
+use crate::context::ContextData;
+use crate::tokenizer::Redirection;
+
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::os::unix::io::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::process::{Child, Stdio};
+
+/// Duplicates `fd` into a new descriptor that isn't inherited by programs.
+fn dup(fd: RawFd) -> io::Result<OwnedFd> {
+    let new_fd = unsafe { libc::fcntl(fd, libc::F_DUPFD_CLOEXEC, 0) };
+    if new_fd == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(unsafe { OwnedFd::from_raw_fd(new_fd) })
+}
+
+/// Standard input or output of carapace redirected while a builtin runs, like to a pipe of a
+/// pipeline or to a file. It is restored when dropped.
+pub struct Redirect {
+    fd: RawFd,
+    saved: OwnedFd,
+}
+
+impl Redirect {
+    pub fn new(fd: RawFd, to: &OwnedFd) -> io::Result<Redirect> {
+        let _ = io::stdout().flush();
+        let saved = dup(fd)?;
+        if unsafe { libc::dup2(to.as_raw_fd(), fd) } == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Redirect { fd, saved })
+    }
+}
+
+impl Drop for Redirect {
+    fn drop(&mut self) {
+        let _ = io::stdout().flush();
+        unsafe { libc::dup2(self.saved.as_raw_fd(), self.fd) };
+    }
+}
+
+/// Redirect command runs a command with its input read from a file, like `sort < data.txt`, or its
+/// output written to a file, like `echo hi > out.txt`, or appended to it, like `ls >> log.txt`.
+/// Errors are written to a file via `2>` and `2>>`, or where the output goes via `2>&1`. The command
+/// doesn't run if a file can't be opened.
+pub struct RedirectCommand {
+    pub cmd: Box<dyn Command>,
+    pub redirects: Vec<Redirection>,
+}
+
+/// Files opened for the redirections of a command. Streams that aren't redirected are `None`.
+#[derive(Default)]
+struct Files {
+    stdin: Option<File>,
+    stdout: Option<File>,
+    stderr: Option<File>,
+}
+
+impl RedirectCommand {
+    pub fn new(cmd: Box<dyn Command>, redirects: Vec<Redirection>) -> RedirectCommand {
+        RedirectCommand { cmd, redirects }
+    }
+
+    /// Opens the files to read from and write to, in the order of the redirections. Files written
+    /// to are created if missing. With "2>&1", stderr gets a handle of the file of stdout at that
+    /// point, which shares the file offset so output of the two streams is kept in the order it is
+    /// written, or of the output of carapace if stdout isn't redirected yet.
+    fn open(&self) -> Result<Files, String> {
+        let error = |path: &str, err: io::Error| format!("Could not open {}: {}", path, err);
+        let create = |path: &str, append: bool| {
+            let mut options = OpenOptions::new();
+            if append {
+                options.append(true);
+            } else {
+                options.write(true).truncate(true);
+            }
+            options
+                .create(true)
+                .open(path)
+                .map_err(|err| error(path, err))
+        };
+
+        let mut files = Files::default();
+        for redirect in &self.redirects {
+            match redirect {
+                Redirection::Stdin(path) => {
+                    files.stdin = Some(File::open(path).map_err(|err| error(path, err))?)
+                }
+                Redirection::Stdout { path, append } => files.stdout = Some(create(path, *append)?),
+                Redirection::Stderr { path, append } => files.stderr = Some(create(path, *append)?),
+                Redirection::StderrToStdout => {
+                    let file = match &files.stdout {
+                        Some(file) => file.try_clone(),
+                        None => dup(libc::STDOUT_FILENO).map(File::from),
+                    };
+                    files.stderr =
+                        Some(file.map_err(|err| format!("Could not redirect stderr: {}", err))?);
+                }
+            }
+        }
+        Ok(files)
+    }
+}
+
+impl RedirectCommand {
+    /// Spawns the program with its input and output redirected, like for a background job, and
+    /// yields it without waiting for it. Input that isn't redirected is read from `stdin`. Yields
+    /// `None` if the command isn't a program.
+    pub fn spawn(&self, ctx: &ContextData, stdin: Stdio) -> Option<Result<Child, String>> {
+        let cmd = self.cmd.as_any().downcast_ref::<GeneralCommand>()?;
+        let files = match self.open() {
+            Ok(files) => files,
+            Err(err) => return Some(Err(err)),
+        };
+        let stdio = |file: Option<File>| file.map_or_else(Stdio::inherit, Stdio::from);
+        let child = cmd.spawn(
+            ctx,
+            files.stdin.map_or(stdin, Stdio::from),
+            stdio(files.stdout),
+            stdio(files.stderr),
+        );
+        Some(child.map_err(|err| err.to_string()))
+    }
+}
+
+impl Command for RedirectCommand {
+    fn execute(&mut self, prompt: &mut Prompt) -> Result<bool, i32> {
+        let files = match self.open() {
+            Ok(files) => files,
+            Err(err) => {
+                println!("{}", err);
+                prompt
+                    .context
+                    .borrow_mut()
+                    .env
+                    .insert("?".to_string(), "1".to_string());
+                return Ok(false);
+            }
+        };
+
+        // Programs get the files as their input and output, while builtins run with the input and
+        // output of carapace redirected to them.
+        if let Some(cmd) = self.cmd.as_any().downcast_ref::<GeneralCommand>() {
+            let stdio = |file: Option<File>| file.map_or_else(Stdio::inherit, Stdio::from);
+            return cmd.run(
+                prompt,
+                stdio(files.stdin),
+                stdio(files.stdout),
+                stdio(files.stderr),
+            );
+        }
+        let mut redirects = Vec::new();
+        for (fd, file) in [
+            (libc::STDIN_FILENO, files.stdin),
+            (libc::STDOUT_FILENO, files.stdout),
+            (libc::STDERR_FILENO, files.stderr),
+        ] {
+            if let Some(file) = file {
+                match Redirect::new(fd, &OwnedFd::from(file)) {
+                    Ok(redirect) => redirects.push(redirect),
+                    Err(err) => {
+                        println!("Could not redirect builtin: {}", err);
+                        return Ok(false);
+                    }
+                }
+            }
+        }
+        self.cmd.execute(prompt)
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::context;
+
+    use std::fs;
+
+    use tempfile::TempDir;
+
+    fn run(prompt: &mut Prompt, input: &str) -> Option<i32> {
+        let cmd = prompt.parse_command(input);
+        crate::command::execute(cmd, prompt)
+    }
+
+    #[test]
+    fn truncate_and_append() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("out.txt");
+        let mut prompt = Prompt::create(context::default());
+
+        run(&mut prompt, &format!("echo hello > {}", path.display()));
+        run(&mut prompt, &format!("echo world >> {}", path.display()));
+        assert_eq!(fs::read_to_string(&path).unwrap(), "hello\nworld\n");
+
+        run(&mut prompt, &format!("echo again > {}", path.display()));
+        assert_eq!(fs::read_to_string(&path).unwrap(), "again\n");
+        assert_eq!(prompt.context.borrow().env["?"], "0");
+    }
+
+    #[test]
+    fn target_expanded() {
+        let dir = TempDir::new().unwrap();
+        let mut prompt = Prompt::create(context::default());
+        prompt
+            .context
+            .borrow_mut()
+            .env
+            .insert("DIR".to_string(), dir.path().display().to_string());
+
+        run(&mut prompt, "echo hi '>' x > $DIR/out.txt");
+        assert_eq!(
+            fs::read_to_string(dir.path().join("out.txt")).unwrap(),
+            "hi > x\n"
+        );
+    }
+
+    #[test]
+    fn stdout_and_stderr() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("all.txt");
+        let mut prompt = Prompt::create(context::default());
+
+        let script = "sh -c 'echo out; echo err >&2; echo out2'";
+        run(&mut prompt, &format!("{} &> {}", script, path.display()));
+        assert_eq!(fs::read_to_string(&path).unwrap(), "out\nerr\nout2\n");
+
+        run(&mut prompt, &format!("{} &>> {}", script, path.display()));
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "out\nerr\nout2\nout\nerr\nout2\n"
+        );
+
+        // Only stdout is written with ">".
+        run(&mut prompt, &format!("{} > {}", script, path.display()));
+        assert_eq!(fs::read_to_string(&path).unwrap(), "out\nout2\n");
+    }
+
+    #[test]
+    fn stderr_to_file() {
+        let dir = TempDir::new().unwrap();
+        let out = dir.path().join("out.txt");
+        let errs = dir.path().join("errs.txt");
+        let mut prompt = Prompt::create(context::default());
+
+        let script = "sh -c 'echo out; echo err >&2'";
+        let input = format!("{} > {} 2> {}", script, out.display(), errs.display());
+        run(&mut prompt, &input);
+        run(
+            &mut prompt,
+            &format!("{} > /dev/null 2>> {}", script, errs.display()),
+        );
+        assert_eq!(fs::read_to_string(&out).unwrap(), "out\n");
+        assert_eq!(fs::read_to_string(&errs).unwrap(), "err\nerr\n");
+    }
+
+    #[test]
+    fn stderr_to_stdout_order() {
+        let dir = TempDir::new().unwrap();
+        let out = dir.path().join("out.txt");
+        let piped = dir.path().join("piped.txt");
+        let mut prompt = Prompt::create(context::default());
+        let script = "sh -c 'echo out; echo err >&2; echo out2'";
+
+        // Stderr goes where stdout goes at that point, which is the pipe here.
+        let run_piped = |prompt: &mut Prompt, redirects: &str| {
+            let input = format!(
+                "{} {} | tr a-z A-Z > {}",
+                script,
+                redirects.replace("OUT", &out.display().to_string()),
+                piped.display()
+            );
+            run(prompt, &input);
+            (
+                fs::read_to_string(&out).unwrap(),
+                fs::read_to_string(&piped).unwrap(),
+            )
+        };
+        assert_eq!(
+            run_piped(&mut prompt, "> OUT 2>&1"),
+            ("out\nerr\nout2\n".to_string(), "".to_string())
+        );
+        assert_eq!(
+            run_piped(&mut prompt, "2>&1 > OUT"),
+            ("out\nout2\n".to_string(), "ERR\n".to_string())
+        );
+        assert_eq!(
+            run_piped(&mut prompt, "2> OUT 2>&1"),
+            ("".to_string(), "OUT\nERR\nOUT2\n".to_string())
+        );
+    }
+
+    #[test]
+    fn stdin_from_file() {
+        let dir = TempDir::new().unwrap();
+        let data = dir.path().join("data.txt");
+        let path = dir.path().join("out.txt");
+        fs::write(&data, "b\nfoo 1\na\nfoo 2\n").unwrap();
+        let mut prompt = Prompt::create(context::default());
+
+        run(
+            &mut prompt,
+            &format!("sort < {} > {}", data.display(), path.display()),
+        );
+        assert_eq!(fs::read_to_string(&path).unwrap(), "a\nb\nfoo 1\nfoo 2\n");
+
+        // Before the program and within a pipeline.
+        let input = format!(
+            "< {} grep foo | wc -l | tr -d ' ' > {}",
+            data.display(),
+            path.display()
+        );
+        run(&mut prompt, &input);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "2\n");
+    }
+
+    #[test]
+    fn open_failure() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("missing").join("out.txt");
+        let marker = dir.path().join("marker");
+        let mut prompt = Prompt::create(context::default());
+
+        // The program isn't run.
+        let input = format!("touch {} > {}", marker.display(), path.display());
+        assert_eq!(run(&mut prompt, &input), None);
+        assert_eq!(prompt.context.borrow().env["?"], "1");
+        assert!(!marker.exists());
+
+        let input = format!("< {} touch {}", path.display(), marker.display());
+        assert_eq!(run(&mut prompt, &input), None);
+        assert_eq!(prompt.context.borrow().env["?"], "1");
+        assert!(!marker.exists());
+    }
+}
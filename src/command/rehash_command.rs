@@ -1,10 +1,25 @@
 use super::*;
 
+/// Rehash command rebuilds the set of executable programs in `$PATH`. With verbose output, the
+/// number of commands and directories is reported.
 pub struct RehashCommand;
 
+impl RehashCommand {
+    pub fn summary(commands: usize, dirs: usize) -> String {
+        format!(
+            "rehash: indexed {} commands from {} directories",
+            commands, dirs
+        )
+    }
+}
+
 impl Command for RehashCommand {
     fn execute(&mut self, prompt: &mut Prompt) -> Result<bool, i32> {
-        prompt.context.borrow_mut().commands.rehash();
+        let mut ctx = prompt.context.borrow_mut();
+        let dirs = ctx.commands.rehash();
+        if ctx.verbose > 0 {
+            println!("{}", RehashCommand::summary(ctx.commands.len(), dirs));
+        }
         Ok(true)
     }
 
@@ -18,3 +33,30 @@ impl CommandAliases for RehashCommand {
         vec!["rehash".to_string()]
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::context;
+    use crate::path_commands::PathCommands;
+
+    #[test]
+    fn summary() {
+        assert_eq!(
+            RehashCommand::summary(42, 3),
+            "rehash: indexed 42 commands from 3 directories"
+        );
+    }
+
+    #[test]
+    fn execute_indexes_commands() {
+        let mut prompt = Prompt::create(context::default());
+        assert!(prompt.context.borrow().commands.is_empty());
+        assert_eq!(RehashCommand.execute(&mut prompt), Ok(true));
+
+        // Same set as a fresh rehash from $PATH.
+        let mut expected = PathCommands::default();
+        expected.rehash();
+        assert_eq!(prompt.context.borrow().commands.len(), expected.len());
+    }
+}
@@ -0,0 +1,224 @@
+use super::*;
+
+use std::thread;
+use std::time::Duration;
+
+/// Repeat command runs a command a number of times, like `repeat 3 make test`, optionally waiting
+/// between runs via `-d SECONDS`. With `errexit`, it stops at the first failing run. `$?` is the
+/// exit status of the last run.
+pub struct RepeatCommand {
+    args: Vec<String>,
+}
+
+impl RepeatCommand {
+    pub fn new(args: Vec<String>) -> RepeatCommand {
+        RepeatCommand { args }
+    }
+
+    /// Parses `value` as the number of runs, which must be a positive integer.
+    pub fn parse_count(value: &str) -> Option<usize> {
+        value.parse::<usize>().ok().filter(|&n| n > 0)
+    }
+
+    /// Parses `value` as the delay in seconds between runs, like "0.5".
+    pub fn parse_delay(value: &str) -> Option<Duration> {
+        value
+            .parse::<f64>()
+            .ok()
+            .filter(|d| d.is_finite() && *d >= 0.0)
+            .map(Duration::from_secs_f64)
+    }
+
+    /// Parses arguments into the number of runs, the delay between them, and the command with its
+    /// arguments.
+    fn parse_args(&self) -> Result<(usize, Duration, &[String]), String> {
+        let mut args = self.args.as_slice();
+        let mut delay = Duration::ZERO;
+        if let Some(first) = args.first() {
+            if first == "-d" {
+                let value = args.get(1).ok_or("-d: option requires an argument")?;
+                delay =
+                    RepeatCommand::parse_delay(value).ok_or(format!("{}: invalid delay", value))?;
+                args = &args[2..];
+            }
+        }
+
+        let value = args
+            .first()
+            .ok_or("usage: repeat [-d SECONDS] COUNT COMMAND [ARGS...]")?;
+        let count = RepeatCommand::parse_count(value)
+            .ok_or(format!("{}: count must be a positive integer", value))?;
+        if args.len() < 2 {
+            return Err("usage: repeat [-d SECONDS] COUNT COMMAND [ARGS...]".to_string());
+        }
+        Ok((count, delay, &args[1..]))
+    }
+}
+
+impl Command for RepeatCommand {
+    fn execute(&mut self, prompt: &mut Prompt) -> Result<bool, i32> {
+        let (count, delay, command) = match self.parse_args() {
+            Ok(res) => res,
+            Err(err) => {
+                eprintln!("repeat: {}", err);
+                prompt
+                    .context
+                    .borrow_mut()
+                    .env
+                    .insert("?".to_string(), "2".to_string());
+                return Ok(false);
+            }
+        };
+
+        let (program, args) = command.split_first().unwrap();
+        let mut success = true;
+        for i in 0..count {
+            if i > 0 && !delay.is_zero() {
+                thread::sleep(delay);
+            }
+
+            let mut cmd = parse(program.clone(), args.to_vec(), &prompt.context.borrow());
+            success = cmd.execute(prompt)?;
+            if !success && prompt.context.borrow().exits_on_failure() {
+                break;
+            }
+        }
+        Ok(success)
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+impl CommandAliases for RepeatCommand {
+    fn aliases() -> Vec<String> {
+        vec!["repeat".to_string()]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::context;
+
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    /// Counts its runs and fails from run `fail_from` onwards.
+    struct CountCommand {
+        runs: Rc<Cell<usize>>,
+        fail_from: usize,
+    }
+
+    impl Command for CountCommand {
+        fn execute(&mut self, _prompt: &mut Prompt) -> Result<bool, i32> {
+            self.runs.set(self.runs.get() + 1);
+            Ok(self.runs.get() < self.fail_from)
+        }
+
+        fn as_any(&self) -> &dyn Any {
+            self
+        }
+    }
+
+    /// Yields prompt with "count" command counting its runs in the returned cell.
+    fn counting_prompt(fail_from: usize) -> (Prompt, Rc<Cell<usize>>) {
+        let prompt = Prompt::create(context::default());
+        let runs = Rc::new(Cell::new(0));
+        let counter = runs.clone();
+        prompt
+            .context
+            .borrow_mut()
+            .register_command("count", move |_args| {
+                Box::new(CountCommand {
+                    runs: counter.clone(),
+                    fail_from,
+                })
+            });
+        (prompt, runs)
+    }
+
+    fn repeat(args: &[&str]) -> RepeatCommand {
+        RepeatCommand::new(args.iter().map(|x| x.to_string()).collect())
+    }
+
+    #[test]
+    fn parse_count() {
+        assert_eq!(RepeatCommand::parse_count("1"), Some(1));
+        assert_eq!(RepeatCommand::parse_count("42"), Some(42));
+        assert_eq!(RepeatCommand::parse_count("0"), None);
+        assert_eq!(RepeatCommand::parse_count("-1"), None);
+        assert_eq!(RepeatCommand::parse_count("1.5"), None);
+        assert_eq!(RepeatCommand::parse_count("foo"), None);
+        assert_eq!(RepeatCommand::parse_count(""), None);
+    }
+
+    #[test]
+    fn parse_delay() {
+        assert_eq!(
+            RepeatCommand::parse_delay("2"),
+            Some(Duration::from_secs(2))
+        );
+        assert_eq!(
+            RepeatCommand::parse_delay("0.5"),
+            Some(Duration::from_millis(500))
+        );
+        assert_eq!(RepeatCommand::parse_delay("-1"), None);
+        assert_eq!(RepeatCommand::parse_delay("inf"), None);
+        assert_eq!(RepeatCommand::parse_delay("foo"), None);
+    }
+
+    #[test]
+    fn runs_count_times() {
+        let (mut prompt, runs) = counting_prompt(usize::MAX);
+        assert_eq!(repeat(&["3", "count"]).execute(&mut prompt), Ok(true));
+        assert_eq!(runs.get(), 3);
+
+        assert_eq!(
+            repeat(&["-d", "0.01", "2", "count"]).execute(&mut prompt),
+            Ok(true)
+        );
+        assert_eq!(runs.get(), 5);
+    }
+
+    #[test]
+    fn invalid_args() {
+        let (mut prompt, runs) = counting_prompt(usize::MAX);
+        for args in [
+            &["0", "count"][..],
+            &["x", "count"],
+            &["3"],
+            &[],
+            &["-d"],
+            &["-d", "x", "3", "count"],
+        ] {
+            assert_eq!(repeat(args).execute(&mut prompt), Ok(false), "{:?}", args);
+            assert_eq!(prompt.context.borrow().env["?"], "2");
+        }
+        assert_eq!(runs.get(), 0);
+    }
+
+    #[test]
+    fn last_run_yields_status() {
+        let mut prompt = Prompt::create(context::default());
+        assert_eq!(repeat(&["2", "false"]).execute(&mut prompt), Ok(false));
+        assert_eq!(prompt.context.borrow().env["?"], "1");
+        assert_eq!(repeat(&["2", "true"]).execute(&mut prompt), Ok(true));
+        assert_eq!(prompt.context.borrow().env["?"], "0");
+    }
+
+    #[test]
+    fn errexit_stops_at_failure() {
+        let (mut prompt, runs) = counting_prompt(2);
+        assert_eq!(repeat(&["5", "count"]).execute(&mut prompt), Ok(false));
+        assert_eq!(runs.get(), 5);
+
+        runs.set(0);
+        prompt.context.borrow_mut().errexit = true;
+        assert_eq!(repeat(&["5", "count"]).execute(&mut prompt), Ok(false));
+        assert_eq!(runs.get(), 2);
+
+        assert_eq!(repeat(&["5", "false"]).execute(&mut prompt), Err(1));
+    }
+}
@@ -0,0 +1,170 @@
+use super::*;
+
+use crate::tokenizer::Separator;
+
+/// Sequence command runs statements one after another, like `cd /tmp; ls` or `make && make
+/// install`. Each statement is parsed right before it runs so that it sees the effects of the
+/// previous ones, like `cd /tmp; echo $PWD`.
+pub struct SequenceCommand {
+    pub statements: Vec<(Separator, String)>,
+}
+
+impl SequenceCommand {
+    pub fn new(statements: Vec<(Separator, String)>) -> SequenceCommand {
+        SequenceCommand { statements }
+    }
+}
+
+impl Command for SequenceCommand {
+    fn execute(&mut self, prompt: &mut Prompt) -> Result<bool, i32> {
+        let mut success = true;
+        for (i, (separator, statement)) in self.statements.iter().enumerate() {
+            let skip = match separator {
+                Separator::Sequence => false,
+                Separator::And => !success,
+                Separator::Or => success,
+            };
+            if skip {
+                continue;
+            }
+
+            // The exit status is checked if followed by "&&" or "||", so `errexit` doesn't apply.
+            let checked = matches!(
+                self.statements.get(i + 1),
+                Some((Separator::And, _)) | Some((Separator::Or, _))
+            );
+            let run = |prompt: &mut Prompt| match prompt.parse_statement(statement) {
+                Ok(mut cmd) => cmd.execute(prompt),
+                Err(err) => {
+                    println!("{}", err);
+                    Ok(false)
+                }
+            };
+            success = if checked {
+                prompt.with_status_checked(run)?
+            } else {
+                run(prompt)?
+            };
+
+            // Not all builtins set `$?`, so it is updated after each statement like after a single
+            // command, such that the next statement sees it.
+            let mut ctx = prompt.context.borrow_mut();
+            let code = status(success, &ctx);
+            ctx.env.insert("?".to_string(), code.to_string());
+        }
+        Ok(success)
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::context;
+
+    fn run(prompt: &mut Prompt, input: &str) -> Result<bool, i32> {
+        let mut cmd = prompt.parse_command(input).unwrap();
+        assert!(cmd.as_any().downcast_ref::<SequenceCommand>().is_some());
+        cmd.execute(prompt)
+    }
+
+    fn var(prompt: &Prompt, name: &str) -> Option<String> {
+        prompt.context.borrow().env.get(name).cloned()
+    }
+
+    #[test]
+    fn runs_all_statements() {
+        let mut prompt = Prompt::create(context::default());
+        assert_eq!(run(&mut prompt, "export A=1; export B=$A"), Ok(true));
+        assert_eq!(var(&prompt, "A"), Some("1".to_string()));
+        assert_eq!(var(&prompt, "B"), Some("1".to_string()));
+    }
+
+    #[test]
+    fn and_or() {
+        let mut prompt = Prompt::create(context::default());
+        assert_eq!(run(&mut prompt, "false && export A=1"), Ok(false));
+        assert_eq!(var(&prompt, "A"), None);
+
+        assert_eq!(run(&mut prompt, "false || export A=2"), Ok(true));
+        assert_eq!(var(&prompt, "A"), Some("2".to_string()));
+
+        assert_eq!(
+            run(&mut prompt, "true && export B=3 || export B=4"),
+            Ok(true)
+        );
+        assert_eq!(var(&prompt, "B"), Some("3".to_string()));
+
+        assert_eq!(
+            run(&mut prompt, "false && export C=5 || export C=6"),
+            Ok(true)
+        );
+        assert_eq!(var(&prompt, "C"), Some("6".to_string()));
+    }
+
+    #[test]
+    fn and_or_with_pipelines() {
+        // The status of a pipeline is the one of its last command.
+        let mut prompt = Prompt::create(context::default());
+        assert_eq!(
+            run(
+                &mut prompt,
+                "false | true && export A=1; true | false || export B=1; true | false && export C=1"
+            ),
+            Ok(false)
+        );
+        assert_eq!(var(&prompt, "A"), Some("1".to_string()));
+        assert_eq!(var(&prompt, "B"), Some("1".to_string()));
+        assert_eq!(var(&prompt, "C"), None);
+    }
+
+    #[test]
+    fn errexit_only_applies_to_unchecked_statements() {
+        let mut prompt = Prompt::create(context::default());
+        prompt.context.borrow_mut().errexit = true;
+        assert_eq!(run(&mut prompt, "false || export A=1"), Ok(true));
+        assert_eq!(var(&prompt, "A"), Some("1".to_string()));
+
+        assert_eq!(run(&mut prompt, "false; export B=1"), Err(1));
+        assert_eq!(var(&prompt, "B"), None);
+    }
+
+    #[test]
+    fn status_updated_after_each_statement() {
+        let mut prompt = Prompt::create(context::default());
+        assert_eq!(
+            run(
+                &mut prompt,
+                "complete -p none; export A=$?; true; export B=$?"
+            ),
+            Ok(true)
+        );
+        assert_eq!(var(&prompt, "A"), Some("1".to_string()));
+        assert_eq!(var(&prompt, "B"), Some("0".to_string()));
+
+        assert_eq!(run(&mut prompt, "sh -c 'exit 3'; export C=$?"), Ok(true));
+        assert_eq!(var(&prompt, "C"), Some("3".to_string()));
+    }
+
+    #[test]
+    fn quoted_and_escaped_semicolons_kept() {
+        let mut prompt = Prompt::create(context::default());
+        assert_eq!(
+            run(&mut prompt, r#"export A="x;y" B=x\;y; export C=1"#),
+            Ok(true)
+        );
+        assert_eq!(var(&prompt, "A"), Some("x;y".to_string()));
+        assert_eq!(var(&prompt, "B"), Some("x;y".to_string()));
+        assert_eq!(var(&prompt, "C"), Some("1".to_string()));
+    }
+
+    #[test]
+    fn exit_stops_sequence() {
+        let mut prompt = Prompt::create(context::default());
+        assert_eq!(run(&mut prompt, "exit 3; export A=1"), Err(3));
+        assert_eq!(var(&prompt, "A"), None);
+    }
+}
@@ -1,10 +1,25 @@
 use super::*;
 
+use crate::config;
+use crate::context::ContextData;
+
 use clap::{App, AppSettings, Arg};
 
 use rustyline::config::Configurer;
 use rustyline::EditMode;
 
+/// Names of options that can be given to `set -o`, like "xtrace".
+pub const OPTION_NAMES: &[&str] = &[
+    "xtrace",
+    "errexit",
+    "noglob",
+    "verbose",
+    "emacs",
+    "vi",
+    "ignoreeof",
+    "expand_aliases",
+];
+
 /// Set command manipulates shell options.
 pub struct SetCommand {
     args: Vec<String>,
@@ -23,6 +38,11 @@ impl SetCommand {
   Options currently set can be displayed via environment variable $-.
   Note that it only applies to options with a shorthand form, like 'x' for xtrace.
 
+  The current edit mode is in $EDIT_MODE, either "emacs" or "vi". With "persist_edit_mode" in
+  the config, changing it via 'set -o' is written back to the config file on exit.
+
+  The xtrace output is prefixed by $PS4, or "+carapace> " if not set.
+
 EXAMPLES:
 
   Set xtrace option:
@@ -37,7 +57,10 @@ EXAMPLES:
   Unset errexit mode:
     set +e
     set +o errexit
-    set +option errexit"#,
+    set +option errexit
+
+  List all options with their values and where they were set:
+    set -o"#,
                 )
                 .setting(AppSettings::NoBinaryName)
                 .setting(AppSettings::DisableVersion)
@@ -51,6 +74,11 @@ EXAMPLES:
                         .short("e")
                         .help("Exit shell if a command yields non-zero exit code."),
                 )
+                .arg(
+                    Arg::with_name("noglob")
+                        .short("f")
+                        .help("Disable expansion of file globs, like '*'."),
+                )
                 .arg(Arg::with_name("verbose").short("v").multiple(true).help(
                     "Sets verbosity level. Can be used multiple times, like '-v -v -v' or '-vvv' \
                      for a verbosity level of 3. With >=1 the shell prints input lines as they \
@@ -61,16 +89,19 @@ EXAMPLES:
                         .short("o")
                         .long("option")
                         .takes_value(true)
+                        .min_values(0)
                         .value_name("name")
                         .help(
-                            r#"Sets option given option name:
-  xtrace     equivalent to -x
-  errexit    equivalent to -e
-  verbose    equivalent to -v (verbose level 1)
-
-  emacs      edit mode
-  vi         edit mode
-  ignoreeof  Don't exit shell when reading EOF"#,
+                            r#"Sets option given option name, or lists all options without one:
+  xtrace          equivalent to -x
+  errexit         equivalent to -e
+  noglob          equivalent to -f
+  verbose         equivalent to -v (verbose level 1)
+
+  emacs           edit mode
+  vi              edit mode
+  ignoreeof       Don't exit shell when reading EOF
+  expand_aliases  Substitute aliases, which is off by default when not interactive"#,
                         ),
                 )
                 .arg(Arg::with_name("unset").value_name("+NAME").help(
@@ -85,10 +116,34 @@ EXAMPLES:
         }
     }
 
+    /// Yields lines of all options with their current values and origins, sorted by name, like
+    /// "xtrace             on     (set)".
+    fn options(ctx: &ContextData, edit_mode: EditMode) -> Vec<String> {
+        let on_off = |enabled: bool| if enabled { "on" } else { "off" }.to_string();
+        let config = &ctx.config;
+        let options = vec![
+            ("auto_cd", on_off(config.auto_cd)),
+            ("cd_to_file_parent", on_off(config.cd_to_file_parent)),
+            ("edit_mode", config::edit_mode_name(edit_mode).to_string()),
+            ("errexit", on_off(ctx.errexit)),
+            ("expand_aliases", on_off(!ctx.noalias)),
+            ("ignoreeof", on_off(ctx.ignoreeof)),
+            ("interpret_escapes", on_off(config.interpret_escapes)),
+            ("noglob", on_off(ctx.noglob)),
+            ("show_banner", on_off(config.show_banner)),
+            ("verbose", ctx.verbose.to_string()),
+            ("xtrace", on_off(ctx.xtrace)),
+        ];
+        options
+            .into_iter()
+            .map(|(name, value)| format!("{:<18} {:<6} ({})", name, value, ctx.option_origin(name)))
+            .collect()
+    }
+
     /// Set or unset options by adding or removing from `$-` in environment.
     fn set(&mut self, opt: &str, enable: bool, prompt: &mut Prompt) -> bool {
         match opt {
-            "x" | "e" | "v" => {
+            "x" | "e" | "f" | "v" => {
                 let mut ctx = prompt.context.borrow_mut();
 
                 // Add or remove the option from $-.
@@ -101,10 +156,16 @@ EXAMPLES:
 
                 if opt == "x" {
                     ctx.xtrace = enable;
+                    ctx.set_option_origin("xtrace");
                 } else if opt == "e" {
                     ctx.errexit = enable;
+                    ctx.set_option_origin("errexit");
+                } else if opt == "f" {
+                    ctx.noglob = enable;
+                    ctx.set_option_origin("noglob");
                 } else if opt == "v" {
                     ctx.verbose = if enable { 1 } else { 0 };
+                    ctx.set_option_origin("verbose");
                 }
             }
             _ => {
@@ -134,6 +195,10 @@ impl Command for SetCommand {
         else if m.is_present("errexit") {
             return Ok(self.set("e", true, prompt));
         }
+        // -f
+        else if m.is_present("noglob") {
+            return Ok(self.set("f", true, prompt));
+        }
         // -v..
         else if m.is_present("verbose") {
             let mut ctx = prompt.context.borrow_mut();
@@ -141,6 +206,15 @@ impl Command for SetCommand {
 
             let level = m.occurrences_of("verbose");
             ctx.verbose = level;
+            ctx.set_option_origin("verbose");
+            return Ok(true);
+        }
+        // -o without name
+        else if m.is_present("option") && m.value_of("option").is_none() {
+            let edit_mode = prompt.editor.config_mut().edit_mode();
+            for line in SetCommand::options(&prompt.context.borrow(), edit_mode) {
+                println!("{}", line);
+            }
             return Ok(true);
         }
         // -o <name>
@@ -148,17 +222,26 @@ impl Command for SetCommand {
             let opt = match opt {
                 "xtrace" => "x",
                 "errexit" => "e",
+                "noglob" => "f",
                 "verbose" => "v",
                 "emacs" => {
-                    prompt.editor.set_edit_mode(EditMode::Emacs);
+                    prompt.set_edit_mode(EditMode::Emacs);
                     return Ok(true);
                 }
                 "vi" => {
-                    prompt.editor.set_edit_mode(EditMode::Vi);
+                    prompt.set_edit_mode(EditMode::Vi);
                     return Ok(true);
                 }
                 "ignoreeof" => {
-                    prompt.context.borrow_mut().ignoreeof = true;
+                    let mut ctx = prompt.context.borrow_mut();
+                    ctx.ignoreeof = true;
+                    ctx.set_option_origin("ignoreeof");
+                    return Ok(true);
+                }
+                "expand_aliases" => {
+                    let mut ctx = prompt.context.borrow_mut();
+                    ctx.noalias = false;
+                    ctx.set_option_origin("expand_aliases");
                     return Ok(true);
                 }
                 _ => {
@@ -175,6 +258,7 @@ impl Command for SetCommand {
                     let opt = match opt_name {
                         "xtrace" => "x",
                         "errexit" => "e",
+                        "noglob" => "f",
                         "verbose" => "v",
                         "emacs" | "vi" => {
                             println!(
@@ -185,7 +269,15 @@ impl Command for SetCommand {
                         }
 
                         "ignoreeof" => {
-                            prompt.context.borrow_mut().ignoreeof = false;
+                            let mut ctx = prompt.context.borrow_mut();
+                            ctx.ignoreeof = false;
+                            ctx.set_option_origin("ignoreeof");
+                            return Ok(true);
+                        }
+                        "expand_aliases" => {
+                            let mut ctx = prompt.context.borrow_mut();
+                            ctx.noalias = true;
+                            ctx.set_option_origin("expand_aliases");
                             return Ok(true);
                         }
                         _ => {
@@ -230,7 +322,8 @@ impl CommandAliases for SetCommand {
 mod tests {
     use super::*;
 
-    use crate::context;
+    use crate::config::Config;
+    use crate::context::{self, OptionOrigin};
 
     #[test]
     fn new() {
@@ -463,6 +556,51 @@ mod tests {
         }
     }
 
+    #[test]
+    fn set_f() {
+        let mut prompt = Prompt::create(context::default());
+        assert!(!prompt.context.borrow().env.contains_key("-"));
+
+        let mut cmd = SetCommand::new(vec!["-f".to_string()]);
+        assert!(cmd.execute(&mut prompt).is_ok());
+
+        let ctx = prompt.context.borrow();
+        assert_eq!(ctx.env["-"], "f");
+        assert!(ctx.noglob);
+    }
+
+    #[test]
+    fn set_noglob() {
+        let mut prompt = Prompt::create(context::default());
+        let mut cmd = SetCommand::new(vec!["-o".to_string(), "noglob".to_string()]);
+        assert!(cmd.execute(&mut prompt).is_ok());
+
+        let ctx = prompt.context.borrow();
+        assert_eq!(ctx.env["-"], "f");
+        assert!(ctx.noglob);
+    }
+
+    #[test]
+    fn unset_f() {
+        let mut prompt = Prompt::create(context::default());
+        let mut cmd = SetCommand::new(vec!["-f".to_string()]);
+        assert!(cmd.execute(&mut prompt).is_ok());
+
+        let mut cmd = SetCommand::new(vec!["+f".to_string()]);
+        assert!(cmd.execute(&mut prompt).is_ok());
+        {
+            let ctx = prompt.context.borrow();
+            assert_eq!(ctx.env["-"], "");
+            assert!(!ctx.noglob);
+        }
+
+        let mut cmd = SetCommand::new(vec!["-f".to_string()]);
+        assert!(cmd.execute(&mut prompt).is_ok());
+        let mut cmd = SetCommand::new(vec!["+o".to_string(), "noglob".to_string()]);
+        assert!(cmd.execute(&mut prompt).is_ok());
+        assert!(!prompt.context.borrow().noglob);
+    }
+
     #[test]
     fn set_emacs() {
         let mut prompt = Prompt::create(context::default());
@@ -474,6 +612,10 @@ mod tests {
         assert!(cmd.execute(&mut prompt).is_ok());
 
         assert_eq!(EditMode::Emacs, prompt.editor.config_mut().edit_mode());
+
+        let ctx = prompt.context.borrow();
+        assert_eq!(ctx.config.edit_mode, EditMode::Emacs);
+        assert_eq!(ctx.env["EDIT_MODE"], "emacs");
     }
 
     #[test]
@@ -496,6 +638,15 @@ mod tests {
         assert!(cmd.execute(&mut prompt).is_ok());
 
         assert_eq!(EditMode::Vi, prompt.editor.config_mut().edit_mode());
+
+        // Remembered for the session and observable via $EDIT_MODE and `set -o`.
+        let ctx = prompt.context.borrow();
+        assert_eq!(ctx.config.edit_mode, EditMode::Vi);
+        assert_eq!(ctx.env["EDIT_MODE"], "vi");
+        assert_eq!(ctx.option_origin("edit_mode"), OptionOrigin::Runtime);
+        assert!(SetCommand::options(&ctx, EditMode::Vi)
+            .iter()
+            .any(|line| line.starts_with("edit_mode") && line.contains("vi")));
     }
 
     #[test]
@@ -586,6 +737,71 @@ mod tests {
         assert_eq!(ctx.env["-"], "");
     }
 
+    #[test]
+    fn option_names_are_known() {
+        let mut prompt = Prompt::create(context::default());
+        for name in OPTION_NAMES {
+            let mut cmd = SetCommand::new(vec!["-o".to_string(), name.to_string()]);
+            assert_eq!(cmd.execute(&mut prompt), Ok(true), "{}", name);
+        }
+    }
+
+    #[test]
+    fn list_options() {
+        let mut prompt = Prompt::create(context::default());
+        let mut cmd = SetCommand::new(vec!["-o".to_string()]);
+        assert_eq!(cmd.execute(&mut prompt), Ok(true));
+
+        let ctx = prompt.context.borrow();
+        let lines = SetCommand::options(&ctx, EditMode::Emacs);
+        assert_eq!(lines.len(), 11);
+        assert_eq!(lines[0], "auto_cd            on     (default)");
+        assert_eq!(lines[2], "edit_mode          emacs  (default)");
+        assert_eq!(lines[4], "expand_aliases     on     (default)");
+        assert_eq!(lines[10], "xtrace             off    (default)");
+    }
+
+    #[test]
+    fn list_options_origins() {
+        let mut config = Config {
+            auto_cd: false,
+            ..Config::default()
+        };
+        config.loaded.insert("auto_cd".to_string());
+        let mut prompt = Prompt::create(context::with_config(0, config));
+
+        let mut cmd = SetCommand::new(vec!["-x".to_string()]);
+        assert!(cmd.execute(&mut prompt).is_ok());
+        let mut cmd = SetCommand::new(vec!["-o".to_string(), "vi".to_string()]);
+        assert!(cmd.execute(&mut prompt).is_ok());
+        let mut cmd = SetCommand::new(vec!["+o".to_string(), "ignoreeof".to_string()]);
+        assert!(cmd.execute(&mut prompt).is_ok());
+
+        let ctx = prompt.context.borrow();
+        let lines = SetCommand::options(&ctx, EditMode::Vi);
+        assert_eq!(lines[0], "auto_cd            off    (config file)");
+        assert_eq!(lines[2], "edit_mode          vi     (set)");
+        assert_eq!(lines[5], "ignoreeof          off    (set)");
+        assert_eq!(lines[7], "noglob             off    (default)");
+        assert_eq!(lines[10], "xtrace             on     (set)");
+    }
+
+    #[test]
+    fn set_and_unset_expand_aliases() {
+        let mut prompt = Prompt::create(context::default());
+        prompt.context.borrow_mut().noalias = true;
+
+        let mut cmd = SetCommand::new(vec!["-o".to_string(), "expand_aliases".to_string()]);
+        assert!(cmd.execute(&mut prompt).is_ok());
+        assert!(!prompt.context.borrow().noalias);
+
+        let mut cmd = SetCommand::new(vec!["+o".to_string(), "expand_aliases".to_string()]);
+        assert!(cmd.execute(&mut prompt).is_ok());
+        let ctx = prompt.context.borrow();
+        assert!(ctx.noalias);
+        assert_eq!(ctx.option_origin("expand_aliases"), OptionOrigin::Runtime);
+    }
+
     #[test]
     fn set_ignoreeof() {
         let mut prompt = Prompt::create(context::default());
@@ -0,0 +1,62 @@
+use super::*;
+
+use super::general_command::GeneralCommand;
+
+/// Sh-eval command runs a line via the `fallback_shell` of the config, `/bin/sh` by default, as an
+/// escape hatch for syntax that carapace doesn't support, like `sh-eval 'for f in *; do ...; done'`.
+/// The shell gets the environment of carapace but changes to it, like of variables, are lost.
+pub struct ShEvalCommand {
+    args: Vec<String>,
+}
+
+impl ShEvalCommand {
+    pub fn new(args: Vec<String>) -> ShEvalCommand {
+        ShEvalCommand { args }
+    }
+}
+
+impl Command for ShEvalCommand {
+    fn execute(&mut self, prompt: &mut Prompt) -> Result<bool, i32> {
+        let line = self.args.join(" ");
+        let shell = prompt.context.borrow().config.fallback_shell.clone();
+        GeneralCommand::with_shell(&shell, &line).execute(prompt)
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+impl CommandAliases for ShEvalCommand {
+    fn aliases() -> Vec<String> {
+        vec!["sh-eval".to_string()]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::context;
+
+    fn sh_eval(prompt: &mut Prompt, args: &[&str]) -> Result<bool, i32> {
+        let args = args.iter().map(|x| x.to_string()).collect();
+        ShEvalCommand::new(args).execute(prompt)
+    }
+
+    #[test]
+    fn runs_via_fallback_shell() {
+        let mut prompt = Prompt::create(context::default());
+        assert_eq!(
+            sh_eval(
+                &mut prompt,
+                &["for i in 1 2; do test $i -lt 3 || exit 4; done"]
+            ),
+            Ok(true)
+        );
+        assert_eq!(sh_eval(&mut prompt, &["exit", "3"]), Ok(false));
+        assert_eq!(prompt.context.borrow().env["?"], "3");
+
+        prompt.context.borrow_mut().config.fallback_shell = "/nonexistent/shell".to_string();
+        assert_eq!(sh_eval(&mut prompt, &["true"]), Ok(false));
+    }
+}
@@ -0,0 +1,242 @@
+use super::*;
+
+use crate::util;
+
+use std::fs;
+use std::path::PathBuf;
+
+use clap::{App, AppSettings, Arg};
+
+/// Source command reads and executes commands from a file in the current shell, one per line.
+/// Blank lines and comment lines, starting with '#', are skipped. Failing lines don't stop it, unless
+/// given `-e` or the shell exits on failures via `set -e`.
+pub struct SourceCommand {
+    args: Vec<String>,
+    app: App<'static, 'static>,
+}
+
+impl SourceCommand {
+    pub fn new(args: Vec<String>) -> SourceCommand {
+        SourceCommand {
+            args,
+            app: App::new("source")
+                .about("Execute commands from file in the current shell.")
+                .setting(AppSettings::NoBinaryName)
+                .setting(AppSettings::DisableVersion)
+                .arg(
+                    Arg::with_name("errexit")
+                        .short("e")
+                        .long("errexit")
+                        .help("Stop at the first failing line, regardless of 'set -e'."),
+                )
+                .arg(
+                    Arg::with_name("file")
+                        .required(true)
+                        .help("File to read commands from."),
+                ),
+        }
+    }
+
+    /// Executes `lines` of `file` and yields the result of the last command, or an exit code if any
+    /// command yields one. With `errexit`, it stops at the first failing line and reports it.
+    fn run(file: &str, lines: &str, errexit: bool, prompt: &mut Prompt) -> Result<bool, i32> {
+        let mut success = true;
+        for (num, line) in lines.lines().enumerate() {
+            if util::is_blank_or_comment(line) {
+                continue;
+            }
+
+            success = match prompt.parse_without_history(line) {
+                Ok(mut cmd) => cmd.execute(prompt)?,
+                Err(err) => {
+                    println!("{}", err);
+                    false
+                }
+            };
+            if !success && errexit {
+                eprintln!("source: {}: line {}: {}", file, num + 1, line.trim());
+                break;
+            }
+        }
+        Ok(success)
+    }
+}
+
+impl Command for SourceCommand {
+    fn execute(&mut self, prompt: &mut Prompt) -> Result<bool, i32> {
+        let matches = self.app.get_matches_from_safe_borrow(&self.args);
+        if let Err(err) = matches {
+            println!("{}", err);
+            return Ok(false);
+        }
+
+        let matches = matches.unwrap();
+        let file = matches.value_of("file").unwrap().to_string();
+        let errexit = matches.is_present("errexit");
+        let fail = |prompt: &mut Prompt, msg: String| {
+            eprintln!("source: {}", msg);
+            prompt
+                .context
+                .borrow_mut()
+                .env
+                .insert("?".to_string(), "1".to_string());
+            Ok(false)
+        };
+
+        let (path, lines) = match (PathBuf::from(&file).canonicalize(), fs::read(&file)) {
+            (Ok(path), Ok(data)) => (path, String::from_utf8_lossy(&data).into_owned()),
+            (_, Err(err)) | (Err(err), _) => return fail(prompt, format!("{}: {}", file, err)),
+        };
+
+        // Refuse to source a file that is already being sourced since it would never end.
+        if prompt.context.borrow().sourcing.contains(&path) {
+            return fail(prompt, format!("cycle detected: {}", path.display()));
+        }
+
+        prompt.context.borrow_mut().sourcing.push(path);
+        let res = SourceCommand::run(&file, &lines, errexit, prompt);
+        prompt.context.borrow_mut().sourcing.pop();
+        res
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+impl CommandAliases for SourceCommand {
+    fn aliases() -> Vec<String> {
+        vec![".".to_string(), "source".to_string()]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::context;
+
+    use tempfile::TempDir;
+
+    #[test]
+    fn source_file() {
+        let dir = TempDir::new().unwrap();
+        let file = dir.path().join("init");
+        fs::write(&file, "# Comment.\n\nexport A=1\nexport B=2\n").unwrap();
+
+        let mut prompt = Prompt::create(context::default());
+        let mut cmd = SourceCommand::new(vec![file.display().to_string()]);
+        assert_eq!(cmd.execute(&mut prompt), Ok(true));
+
+        let ctx = prompt.context.borrow();
+        assert_eq!(ctx.env["A"], "1");
+        assert_eq!(ctx.env["B"], "2");
+        assert!(ctx.sourcing.is_empty());
+    }
+
+    #[test]
+    fn source_keeps_history() {
+        let dir = TempDir::new().unwrap();
+        let file = dir.path().join("init");
+        fs::write(&file, "export A=1\nexport B=2\n").unwrap();
+
+        let mut prompt = Prompt::create(context::default());
+        let mut cmd = SourceCommand::new(vec![file.display().to_string()]);
+        assert_eq!(cmd.execute(&mut prompt), Ok(true));
+        assert_eq!(prompt.editor.history().len(), 0);
+    }
+
+    #[test]
+    fn source_missing_file() {
+        let dir = TempDir::new().unwrap();
+        let file = dir.path().join("missing");
+
+        let mut prompt = Prompt::create(context::default());
+        let mut cmd = SourceCommand::new(vec![file.display().to_string()]);
+        assert_eq!(cmd.execute(&mut prompt), Ok(false));
+        assert_eq!(prompt.context.borrow().env["?"], "1");
+    }
+
+    #[test]
+    fn source_exit_code() {
+        let dir = TempDir::new().unwrap();
+        let file = dir.path().join("init");
+        fs::write(&file, "exit 3\nexport A=1\n").unwrap();
+
+        let mut prompt = Prompt::create(context::default());
+        let mut cmd = SourceCommand::new(vec![file.display().to_string()]);
+        assert_eq!(cmd.execute(&mut prompt), Err(3));
+        assert!(!prompt.context.borrow().env.contains_key("A"));
+        assert!(prompt.context.borrow().sourcing.is_empty());
+    }
+
+    #[test]
+    fn source_continues_after_failure() {
+        let dir = TempDir::new().unwrap();
+        let file = dir.path().join("init");
+        fs::write(&file, "export A=1\nsh -c 'exit 2'\nexport B=2\n").unwrap();
+
+        let mut prompt = Prompt::create(context::default());
+        let mut cmd = SourceCommand::new(vec![file.display().to_string()]);
+        assert_eq!(cmd.execute(&mut prompt), Ok(true));
+
+        let ctx = prompt.context.borrow();
+        assert_eq!(ctx.env["A"], "1");
+        assert_eq!(ctx.env["B"], "2");
+    }
+
+    #[test]
+    fn source_errexit_stops_at_failure() {
+        let dir = TempDir::new().unwrap();
+        let file = dir.path().join("init");
+        fs::write(&file, "export A=1\nsh -c 'exit 2'\nexport B=2\n").unwrap();
+
+        for flag in ["-e", "--errexit"] {
+            let mut prompt = Prompt::create(context::default());
+            let mut cmd = SourceCommand::new(vec![flag.to_string(), file.display().to_string()]);
+            assert_eq!(cmd.execute(&mut prompt), Ok(false));
+
+            // The global errexit isn't enabled, so the shell doesn't exit.
+            let ctx = prompt.context.borrow();
+            assert_eq!(ctx.env["A"], "1");
+            assert_eq!(ctx.env["?"], "2");
+            assert!(!ctx.env.contains_key("B"));
+            assert!(ctx.sourcing.is_empty());
+        }
+    }
+
+    #[test]
+    fn source_self_stops() {
+        let dir = TempDir::new().unwrap();
+        let file = dir.path().join("init");
+        fs::write(
+            &file,
+            format!("export A=1\nsource {}\nexport B=2\n", file.display()),
+        )
+        .unwrap();
+
+        let mut prompt = Prompt::create(context::default());
+        let mut cmd = SourceCommand::new(vec![file.display().to_string()]);
+        assert_eq!(cmd.execute(&mut prompt), Ok(true));
+
+        // The rest of the file is still executed once.
+        let ctx = prompt.context.borrow();
+        assert_eq!(ctx.env["A"], "1");
+        assert_eq!(ctx.env["B"], "2");
+        assert!(ctx.sourcing.is_empty());
+    }
+
+    #[test]
+    fn source_chain_stops() {
+        let dir = TempDir::new().unwrap();
+        let file_a = dir.path().join("a");
+        let file_b = dir.path().join("b");
+        fs::write(&file_a, format!("source {}\n", file_b.display())).unwrap();
+        fs::write(&file_b, format!(". {}\n", file_a.display())).unwrap();
+
+        let mut prompt = Prompt::create(context::default());
+        let mut cmd = SourceCommand::new(vec![file_a.display().to_string()]);
+        assert_eq!(cmd.execute(&mut prompt), Ok(false));
+        assert_eq!(prompt.context.borrow().env["?"], "1");
+        assert!(prompt.context.borrow().sourcing.is_empty());
+    }
+}
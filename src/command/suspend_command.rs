@@ -0,0 +1,64 @@
+use super::*;
+
+use std::env;
+
+use crate::util;
+
+/// Suspend command stops the shell by sending `STOP` to its own process group, returning control
+/// to the parent shell until it is continued, like with `fg`. Login shells have no parent to return
+/// to and refuse to suspend. Only supported on Unix.
+pub struct SuspendCommand;
+
+impl SuspendCommand {
+    /// Whether the shell is a login shell, started with a name prefixed with '-', like "-carapace",
+    /// or directly by init such that there is no parent with job control.
+    pub fn is_login_shell(name: &str, parent_pid: libc::pid_t) -> bool {
+        util::is_login_shell(name) || parent_pid == 1
+    }
+}
+
+impl Command for SuspendCommand {
+    fn execute(&mut self, prompt: &mut Prompt) -> Result<bool, i32> {
+        let name = env::args().next().unwrap_or_default();
+        let success = if SuspendCommand::is_login_shell(&name, unsafe { libc::getppid() }) {
+            println!("suspend: cannot suspend a login shell");
+            false
+        } else if unsafe { libc::kill(0, libc::SIGSTOP) } == -1 {
+            println!("suspend: {}", io::Error::last_os_error());
+            false
+        } else {
+            true
+        };
+        prompt
+            .context
+            .borrow_mut()
+            .env
+            .insert("?".to_string(), (!success as i32).to_string());
+        Ok(success)
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+impl CommandAliases for SuspendCommand {
+    fn aliases() -> Vec<String> {
+        vec!["suspend".to_string()]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn is_login_shell() {
+        assert!(SuspendCommand::is_login_shell("-carapace", 42));
+        assert!(SuspendCommand::is_login_shell("-", 42));
+        assert!(SuspendCommand::is_login_shell("carapace", 1));
+        assert!(!SuspendCommand::is_login_shell("carapace", 42));
+        assert!(!SuspendCommand::is_login_shell("/usr/bin/carapace", 42));
+        assert!(!SuspendCommand::is_login_shell("", 42));
+    }
+}
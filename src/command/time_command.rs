@@ -0,0 +1,171 @@
+use super::*;
+
+use std::mem;
+use std::time::{Duration, Instant};
+
+/// Time command runs a command and reports the elapsed real time and the user and system CPU time
+/// to stderr, like `time make`. With `-p`, the POSIX format is used. User and system time are taken
+/// from the resource usage of finished child processes, so they are approximate and don't include
+/// builtins. Only supported on Unix.
+pub struct TimeCommand {
+    args: Vec<String>,
+}
+
+impl TimeCommand {
+    pub fn new(args: Vec<String>) -> TimeCommand {
+        TimeCommand { args }
+    }
+
+    /// Yields user and system CPU time of finished child processes.
+    fn children_usage() -> (Duration, Duration) {
+        let mut usage: libc::rusage = unsafe { mem::zeroed() };
+        if unsafe { libc::getrusage(libc::RUSAGE_CHILDREN, &mut usage) } == -1 {
+            return (Duration::ZERO, Duration::ZERO);
+        }
+        let duration = |time: libc::timeval| {
+            Duration::from_secs(time.tv_sec as u64) + Duration::from_micros(time.tv_usec as u64)
+        };
+        (duration(usage.ru_utime), duration(usage.ru_stime))
+    }
+
+    /// Formats `duration` like "1m3.120s".
+    fn minutes_seconds(duration: Duration) -> String {
+        let millis = duration.as_millis();
+        format!(
+            "{}m{}.{:03}s",
+            millis / 60_000,
+            millis % 60_000 / 1000,
+            millis % 1000
+        )
+    }
+
+    /// Formats report like bash does by default, with tab-separated "real", "user", and "sys"
+    /// lines, like "real\t0m3.120s".
+    pub fn format(real: Duration, user: Duration, sys: Duration) -> String {
+        format!(
+            "\nreal\t{}\nuser\t{}\nsys\t{}",
+            TimeCommand::minutes_seconds(real),
+            TimeCommand::minutes_seconds(user),
+            TimeCommand::minutes_seconds(sys)
+        )
+    }
+
+    /// Formats report in the POSIX format of `-p`, with plain seconds, like "real 3.12".
+    pub fn format_posix(real: Duration, user: Duration, sys: Duration) -> String {
+        format!(
+            "real {:.2}\nuser {:.2}\nsys {:.2}",
+            real.as_secs_f64(),
+            user.as_secs_f64(),
+            sys.as_secs_f64()
+        )
+    }
+}
+
+impl Command for TimeCommand {
+    fn execute(&mut self, prompt: &mut Prompt) -> Result<bool, i32> {
+        let mut args = self.args.as_slice();
+        let mut posix = false;
+        while let Some(first) = args.first() {
+            match first.as_str() {
+                "-p" => posix = true,
+                "--" => {
+                    args = &args[1..];
+                    break;
+                }
+                _ => break,
+            }
+            args = &args[1..];
+        }
+
+        let (user_before, sys_before) = TimeCommand::children_usage();
+        let start = Instant::now();
+        let res = match args.split_first() {
+            Some((program, args)) => {
+                let mut cmd = parse(program.clone(), args.to_vec(), &prompt.context.borrow());
+                cmd.execute(prompt)
+            }
+            None => Ok(true),
+        };
+        let real = start.elapsed();
+        let (user_after, sys_after) = TimeCommand::children_usage();
+
+        let user = user_after.saturating_sub(user_before);
+        let sys = sys_after.saturating_sub(sys_before);
+        if posix {
+            eprintln!("{}", TimeCommand::format_posix(real, user, sys));
+        } else {
+            eprintln!("{}", TimeCommand::format(real, user, sys));
+        }
+        res
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+impl CommandAliases for TimeCommand {
+    fn aliases() -> Vec<String> {
+        vec!["time".to_string()]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::context;
+
+    #[test]
+    fn format() {
+        assert_eq!(
+            TimeCommand::format(
+                Duration::from_millis(3120),
+                Duration::from_micros(1500),
+                Duration::ZERO
+            ),
+            "\nreal\t0m3.120s\nuser\t0m0.001s\nsys\t0m0.000s"
+        );
+        assert_eq!(
+            TimeCommand::format(
+                Duration::from_millis(125_007),
+                Duration::from_secs(60),
+                Duration::from_millis(59_999)
+            ),
+            "\nreal\t2m5.007s\nuser\t1m0.000s\nsys\t0m59.999s"
+        );
+    }
+
+    #[test]
+    fn format_posix() {
+        assert_eq!(
+            TimeCommand::format_posix(
+                Duration::from_millis(3120),
+                Duration::from_millis(6),
+                Duration::ZERO
+            ),
+            "real 3.12\nuser 0.01\nsys 0.00"
+        );
+        assert_eq!(
+            TimeCommand::format_posix(
+                Duration::from_secs(125),
+                Duration::from_millis(1234),
+                Duration::from_millis(10)
+            ),
+            "real 125.00\nuser 1.23\nsys 0.01"
+        );
+    }
+
+    #[test]
+    fn yields_result_of_command() {
+        let mut prompt = Prompt::create(context::default());
+        let mut cmd = TimeCommand::new(vec!["-p".to_string(), "false".to_string()]);
+        assert_eq!(cmd.execute(&mut prompt), Ok(false));
+        assert_eq!(prompt.context.borrow().env["?"], "1");
+
+        let mut cmd = TimeCommand::new(vec!["exit".to_string(), "3".to_string()]);
+        assert_eq!(cmd.execute(&mut prompt), Err(3));
+
+        let mut cmd = TimeCommand::new(vec![]);
+        assert_eq!(cmd.execute(&mut prompt), Ok(true));
+    }
+}
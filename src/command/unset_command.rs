@@ -19,6 +19,14 @@ impl UnsetCommand {
                 .arg(Arg::with_name("vars").multiple(true).help("Variable name.")),
         }
     }
+
+    /// Yields warning about consequences of unsetting `var`, if any.
+    fn warning(var: &str) -> Option<&'static str> {
+        match var {
+            "PATH" => Some("warning: unsetting PATH will break command lookup"),
+            _ => None,
+        }
+    }
 }
 
 impl Command for UnsetCommand {
@@ -29,10 +37,18 @@ impl Command for UnsetCommand {
             return Ok(false);
         }
 
+        let mut success = true;
         for var in &self.args {
-            prompt.context.borrow_mut().env.remove(var);
+            let mut ctx = prompt.context.borrow_mut();
+            let present = ctx.env.contains_key(var);
+            if let Err(err) = ctx.env.unset(var) {
+                println!("unset: {}", err);
+                success = false;
+            } else if let Some(warning) = UnsetCommand::warning(var).filter(|_| present) {
+                eprintln!("unset: {}", warning);
+            }
         }
-        Ok(true)
+        Ok(success)
     }
 
     fn as_any(&self) -> &dyn Any {
@@ -45,3 +61,47 @@ impl CommandAliases for UnsetCommand {
         vec!["unset".to_string()]
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::context;
+
+    #[test]
+    fn unset() {
+        let mut prompt = Prompt::create(context::default());
+        prompt
+            .context
+            .borrow_mut()
+            .env
+            .insert("A".to_string(), "1".to_string());
+
+        let mut cmd = UnsetCommand::new(vec!["A".to_string()]);
+        assert!(cmd.execute(&mut prompt).unwrap());
+        assert!(!prompt.context.borrow().env.contains_key("A"));
+    }
+
+    #[test]
+    fn warning() {
+        assert_eq!(
+            UnsetCommand::warning("PATH"),
+            Some("warning: unsetting PATH will break command lookup")
+        );
+        assert_eq!(UnsetCommand::warning("A"), None);
+    }
+
+    #[test]
+    fn unset_readonly_fails() {
+        let mut prompt = Prompt::create(context::default());
+        {
+            let env = &mut prompt.context.borrow_mut().env;
+            env.insert("A".to_string(), "1".to_string());
+            env.set_readonly("A".to_string());
+        }
+
+        let mut cmd = UnsetCommand::new(vec!["A".to_string()]);
+        assert!(!cmd.execute(&mut prompt).unwrap());
+        assert!(prompt.context.borrow().env.contains_key("A"));
+    }
+}
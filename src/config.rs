@@ -2,18 +2,71 @@ use crate::util;
 
 use rustyline::{CompletionType, EditMode};
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fs;
 use std::path::{Path, PathBuf};
 
+/// Yields name of edit `mode` as in the config file, like "vi".
+pub fn edit_mode_name(mode: EditMode) -> &'static str {
+    match mode {
+        EditMode::Vi => "vi",
+        _ /*EditMode::Emacs*/ => "emacs",
+    }
+}
+
 #[derive(Debug, PartialEq)]
 pub struct Config {
     pub max_history_size: usize,
     pub edit_mode: EditMode,
     pub completion_type: CompletionType,
     pub auto_cd: bool,
-    pub aliases: HashMap<String, String>, // alias -> actual command.
-    pub env: HashMap<String, String>,     // env var -> value.
+
+    /// Change to the parent directory when `cd` is given a file.
+    pub cd_to_file_parent: bool,
+
+    /// Print a banner when starting the interactive shell.
+    pub show_banner: bool,
+
+    /// Alias -> actual command. Values are kept verbatim, so a '#' only starts a comment in the
+    /// command line an alias is substituted into.
+    pub aliases: HashMap<String, String>,
+    pub env: HashMap<String, String>, // env var -> value.
+
+    /// Interpret C-style escapes, like `\n` and `\t`, in alias and env values.
+    pub interpret_escapes: bool,
+
+    /// Commands that must be confirmed before running, matched as prefixes of the expanded
+    /// command line, like "rm -rf".
+    pub confirm_commands: Vec<String>,
+
+    /// Characters where deleting a word backwards via Ctrl-W stops, like "/ \t" to also stop at
+    /// path separators. Empty keeps the default of stopping at whitespace.
+    pub word_delimiters: String,
+
+    /// Command prepended by `please` when running the previous command again, like "sudo".
+    pub elevate_prefix: String,
+
+    /// Shell that runs lines via `sh-eval`, as an escape hatch for syntax that isn't supported.
+    pub fallback_shell: String,
+
+    /// Command run after each successful change of the current directory, like via `cd`, `pushd`,
+    /// or `popd`. Empty runs nothing.
+    pub on_cd: String,
+
+    /// Maximum number of bytes of output kept when captured, like via `$(...)`. More is cut off.
+    pub max_capture_bytes: usize,
+
+    /// Write the edit mode back to the config file on exit when changed via `set -o vi` or
+    /// `set -o emacs`.
+    pub persist_edit_mode: bool,
+
+    /// Substitute aliases for the program of commands. Unset, like by default, aliases are only
+    /// expanded when the shell is interactive, like in Bash, but not when running commands via
+    /// `-c`, `-s`, or `-f`.
+    pub expand_aliases: Option<bool>,
+
+    /// Names of entries read from the config file, to tell which options weren't defaults.
+    pub loaded: HashSet<String>,
 }
 
 impl Config {
@@ -23,15 +76,17 @@ impl Config {
         c
     }
 
-    pub fn load(&mut self, path: Option<&str>) {
-        let path = if let Some(path_) = path {
+    /// Yields config file `path`, or "~/.carapace/config.json" if not given.
+    pub fn path(path: Option<&str>) -> PathBuf {
+        if let Some(path_) = path {
             PathBuf::from(path_)
         } else {
-            dirs_next::home_dir()
-                .unwrap()
-                .join(".carapace")
-                .join("config.json")
-        };
+            util::carapace_dir().join("config.json")
+        }
+    }
+
+    pub fn load(&mut self, path: Option<&str>) {
+        let path = Config::path(path);
 
         // If config does not exist then save defaults to disk.
         if !path.exists() {
@@ -48,39 +103,68 @@ impl Config {
         }
     }
 
+    /// Writes config to `path`, creating any missing parent directories, like for a `--config`
+    /// path that doesn't exist yet.
     pub fn save(&self, path: &Path) {
         let output = self.encode();
-        if let Err(err) = fs::write(&path, output) {
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            if let Err(err) = fs::create_dir_all(parent) {
+                println!(
+                    "Could not create config folder: {}\n{}",
+                    parent.display(),
+                    err
+                );
+                return;
+            }
+        }
+        if let Err(err) = fs::write(path, output) {
             println!("Could not write config to: {}\n{}", path.display(), err);
         }
     }
 
+    /// Whether expanded command `line` must be confirmed before running, which is when it starts
+    /// with any of `confirm_commands`.
+    pub fn needs_confirmation(&self, line: &str) -> bool {
+        self.confirm_commands
+            .iter()
+            .any(|prefix| !prefix.is_empty() && line.starts_with(prefix.as_str()))
+    }
+
     /// Encodes config values into a JSON string.
-    fn encode(&self) -> String {
+    pub fn encode(&self) -> String {
         let output = json::object![
             "max_history_size" => self.max_history_size,
-            "edit_mode" => match self.edit_mode {
-                EditMode::Vi => "vi",
-                _ /*EditMode::Emacs*/ => "emacs",
-            },
+            "edit_mode" => edit_mode_name(self.edit_mode),
             "completion_type" => match self.completion_type {
                 CompletionType::Circular => "circular",
                 _ /*CompletionType::List*/ => "list",
             },
             "auto_cd" => self.auto_cd,
+            "cd_to_file_parent" => self.cd_to_file_parent,
+            "show_banner" => self.show_banner,
             "aliases" => util::hash_map_to_json(&self.aliases),
             "env" => util::hash_map_to_json(&self.env),
+            "interpret_escapes" => self.interpret_escapes,
+            "confirm_commands" => self.confirm_commands.clone(),
+            "word_delimiters" => self.word_delimiters.clone(),
+            "elevate_prefix" => self.elevate_prefix.clone(),
+            "fallback_shell" => self.fallback_shell.clone(),
+            "on_cd" => self.on_cd.clone(),
+            "max_capture_bytes" => self.max_capture_bytes,
+            "persist_edit_mode" => self.persist_edit_mode,
+            "expand_aliases" => self.expand_aliases,
         ];
 
         json::stringify_pretty(output, 2)
     }
 
     /// Decodes JSON `data` into config values.
-    fn decode(&mut self, data: &str) -> bool {
+    pub fn decode(&mut self, data: &str) -> bool {
         match json::parse(data) {
             Ok(input) => {
                 for (key, value) in input.entries() {
-                    match key.to_lowercase().as_ref() {
+                    let name = key.to_lowercase();
+                    match name.as_ref() {
                         "max_history_size" => {
                             self.max_history_size =
                                 value.as_usize().unwrap_or(self.max_history_size)
@@ -100,13 +184,62 @@ impl Config {
                         "auto_cd" => {
                             self.auto_cd = value.as_bool().unwrap_or(true);
                         }
+                        "cd_to_file_parent" => {
+                            self.cd_to_file_parent = value.as_bool().unwrap_or(false);
+                        }
+                        "show_banner" => {
+                            self.show_banner = value.as_bool().unwrap_or(false);
+                        }
                         "aliases" => {
                             self.aliases = util::json_obj_to_hash_map(value);
                         }
                         "env" => {
                             self.env = util::json_obj_to_hash_map(value);
                         }
-                        _ => println!("Unknown config entry: {}={}", key, value),
+                        "interpret_escapes" => {
+                            self.interpret_escapes = value.as_bool().unwrap_or(false);
+                        }
+                        "confirm_commands" => {
+                            self.confirm_commands = value
+                                .members()
+                                .filter_map(|v| v.as_str())
+                                .map(|v| v.to_string())
+                                .collect();
+                        }
+                        "word_delimiters" => {
+                            self.word_delimiters = value.as_str().unwrap_or("").to_string();
+                        }
+                        "elevate_prefix" => {
+                            self.elevate_prefix = value.as_str().unwrap_or("sudo").to_string();
+                        }
+                        "fallback_shell" => {
+                            self.fallback_shell = value.as_str().unwrap_or("/bin/sh").to_string();
+                        }
+                        "on_cd" => {
+                            self.on_cd = value.as_str().unwrap_or("").to_string();
+                        }
+                        "max_capture_bytes" => {
+                            self.max_capture_bytes =
+                                value.as_usize().unwrap_or(self.max_capture_bytes)
+                        }
+                        "persist_edit_mode" => {
+                            self.persist_edit_mode = value.as_bool().unwrap_or(false);
+                        }
+                        "expand_aliases" => {
+                            self.expand_aliases = value.as_bool();
+                        }
+                        _ => {
+                            println!("Unknown config entry: {}={}", key, value);
+                            continue;
+                        }
+                    }
+                    self.loaded.insert(name);
+                }
+
+                // Done after all entries are read since the flag can appear after the maps.
+                if self.interpret_escapes {
+                    for value in self.aliases.values_mut().chain(self.env.values_mut()) {
+                        *value = util::unescape(value);
                     }
                 }
                 return true;
@@ -124,8 +257,20 @@ impl Default for Config {
             edit_mode: EditMode::Emacs,
             completion_type: CompletionType::List,
             auto_cd: true,
+            cd_to_file_parent: false,
+            show_banner: false,
             aliases: HashMap::new(),
             env: HashMap::new(),
+            interpret_escapes: false,
+            confirm_commands: Vec::new(),
+            word_delimiters: String::new(),
+            elevate_prefix: "sudo".to_string(),
+            fallback_shell: "/bin/sh".to_string(),
+            on_cd: String::new(),
+            max_capture_bytes: 16 * 1024 * 1024,
+            persist_edit_mode: false,
+            expand_aliases: None,
+            loaded: HashSet::new(),
         }
     }
 }
@@ -145,8 +290,19 @@ mod tests {
   "edit_mode": "emacs",
   "completion_type": "list",
   "auto_cd": true,
+  "cd_to_file_parent": false,
+  "show_banner": false,
   "aliases": {},
-  "env": {}
+  "env": {},
+  "interpret_escapes": false,
+  "confirm_commands": [],
+  "word_delimiters": "",
+  "elevate_prefix": "sudo",
+  "fallback_shell": "/bin/sh",
+  "on_cd": "",
+  "max_capture_bytes": 16777216,
+  "persist_edit_mode": false,
+  "expand_aliases": null
 }"#
         );
     }
@@ -158,8 +314,20 @@ mod tests {
             edit_mode: EditMode::Vi,
             completion_type: CompletionType::Circular,
             auto_cd: false,
+            cd_to_file_parent: true,
+            show_banner: true,
             aliases: HashMap::new(),
             env: HashMap::new(),
+            interpret_escapes: true,
+            confirm_commands: vec!["rm".to_string()],
+            word_delimiters: "/".to_string(),
+            elevate_prefix: "doas".to_string(),
+            fallback_shell: "/bin/bash".to_string(),
+            on_cd: "ls".to_string(),
+            max_capture_bytes: 1,
+            persist_edit_mode: true,
+            expand_aliases: Some(false),
+            loaded: HashSet::new(),
         };
         assert!(config.decode(
             r#"{
@@ -167,19 +335,31 @@ mod tests {
   "edit_mode": "emacs",
   "completion_type": "list",
   "auto_cd": true,
+  "cd_to_file_parent": false,
+  "show_banner": false,
   "aliases": {
     "l": "ls",
     "ll": "ls -l"
   },
   "env": {
     "PATH": "$PATH:/something/bin"
-  }
+  },
+  "confirm_commands": ["rm -rf", "git push -f"],
+  "word_delimiters": "/ \t",
+  "elevate_prefix": "sudo -E",
+  "fallback_shell": "/bin/dash",
+  "on_cd": "source .env",
+  "max_capture_bytes": 4096,
+  "persist_edit_mode": false,
+  "expand_aliases": null
 }"#
         ));
         assert_eq!(config.max_history_size, 123);
         assert_eq!(config.edit_mode, EditMode::Emacs);
         assert_eq!(config.completion_type, CompletionType::List);
         assert!(config.auto_cd);
+        assert!(!config.cd_to_file_parent);
+        assert!(!config.show_banner);
         assert_eq!(config.aliases.len(), 2);
         assert!(config.aliases.contains_key("l"));
         assert_eq!(config.aliases.get("l"), Some(&String::from("ls")));
@@ -191,6 +371,16 @@ mod tests {
             config.env.get("PATH"),
             Some(&String::from("$PATH:/something/bin"))
         );
+        assert_eq!(config.confirm_commands, vec!["rm -rf", "git push -f"]);
+        assert_eq!(config.word_delimiters, "/ \t");
+        assert_eq!(config.elevate_prefix, "sudo -E");
+        assert_eq!(config.fallback_shell, "/bin/dash");
+        assert_eq!(config.on_cd, "source .env");
+        assert_eq!(config.max_capture_bytes, 4096);
+        assert!(!config.persist_edit_mode);
+        assert_eq!(config.expand_aliases, None);
+        assert!(config.loaded.contains("auto_cd"));
+        assert!(!config.loaded.contains("interpret_escapes"));
     }
 
     #[test]
@@ -202,13 +392,97 @@ mod tests {
             edit_mode: EditMode::Vi,
             completion_type: CompletionType::Circular,
             auto_cd: false,
+            cd_to_file_parent: true,
+            show_banner: true,
             aliases: HashMap::new(),
             env: HashMap::new(),
+            interpret_escapes: true,
+            confirm_commands: vec!["rm".to_string()],
+            word_delimiters: "/".to_string(),
+            elevate_prefix: "doas".to_string(),
+            fallback_shell: "/bin/bash".to_string(),
+            on_cd: "ls".to_string(),
+            max_capture_bytes: 1,
+            persist_edit_mode: true,
+            expand_aliases: Some(false),
+            loaded: HashSet::new(),
         };
         assert!(config2.decode(output.as_ref()));
+
+        // All entries are encoded, so all are loaded.
+        assert_eq!(config2.loaded.len(), 17);
+        config2.loaded.clear();
         assert_eq!(config, config2);
     }
 
+    #[test]
+    fn decode_expand_aliases() {
+        let mut config = Config::default();
+        assert!(config.decode(r#"{"expand_aliases": true}"#));
+        assert_eq!(config.expand_aliases, Some(true));
+        assert!(config.decode(r#"{"expand_aliases": false}"#));
+        assert_eq!(config.expand_aliases, Some(false));
+        assert!(config.decode(r#"{"expand_aliases": null}"#));
+        assert_eq!(config.expand_aliases, None);
+    }
+
+    #[test]
+    fn decode_interpret_escapes() {
+        let mut config = Config::default();
+        assert!(config.decode(
+            r#"{
+  "aliases": {
+    "greet": "echo hi\\tthere"
+  },
+  "env": {
+    "PROMPT_NL": "line1\\nline2",
+    "BACKSLASH": "a\\\\b"
+  },
+  "interpret_escapes": true
+}"#
+        ));
+        assert!(config.interpret_escapes);
+        assert_eq!(
+            config.aliases.get("greet"),
+            Some(&String::from("echo hi\tthere"))
+        );
+        assert_eq!(
+            config.env.get("PROMPT_NL"),
+            Some(&String::from("line1\nline2"))
+        );
+        assert_eq!(config.env.get("BACKSLASH"), Some(&String::from("a\\b")));
+    }
+
+    #[test]
+    fn decode_escapes_not_interpreted_by_default() {
+        let mut config = Config::default();
+        assert!(config.decode(
+            r#"{
+  "env": {
+    "PROMPT_NL": "line1\\nline2"
+  }
+}"#
+        ));
+        assert!(!config.interpret_escapes);
+        assert_eq!(
+            config.env.get("PROMPT_NL"),
+            Some(&String::from("line1\\nline2"))
+        );
+    }
+
+    #[test]
+    fn needs_confirmation() {
+        let mut config = Config::default();
+        assert!(!config.needs_confirmation("rm -rf /"));
+
+        config.confirm_commands = vec!["rm -rf".to_string(), "".to_string()];
+        assert!(config.needs_confirmation("rm -rf /"));
+        assert!(config.needs_confirmation("rm -rf"));
+        assert!(!config.needs_confirmation("rm -r -f /"));
+        assert!(!config.needs_confirmation("ls -l"));
+        assert!(!config.needs_confirmation("echo rm -rf"));
+    }
+
     #[test]
     fn decode_invalid_data() {
         let mut config = Config::default();
@@ -1,16 +1,43 @@
 use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
 
+use crate::command::getopts_command::GetoptsPos;
+use crate::command::{self, Command, CommandFactory};
 use crate::config::Config;
+use crate::editor::CompletionSpec;
 use crate::env::Env;
+use crate::jobs::Jobs;
 use crate::path_commands::PathCommands;
+use crate::timings::Timings;
+use crate::util;
 
+/// Shared context of the shell. Cloning is cheap since only the reference is counted, like when
+/// the editor helper keeps a clone for completion. Borrows must be short-lived: completion can be
+/// triggered while a command executes, so it only uses `try_borrow()`.
 pub type Context = Rc<RefCell<ContextData>>;
 
 pub fn new(verbose: u64, config_path: Option<&str>) -> Context {
     Rc::new(RefCell::new(ContextData::new(verbose, config_path)))
 }
 
+/// Creates context like `new()` and records the durations of loading the config and scanning
+/// `$PATH` in `timings`.
+pub fn new_timed(verbose: u64, config_path: Option<&str>, timings: &mut Timings) -> Context {
+    Rc::new(RefCell::new(ContextData::new_timed(
+        verbose,
+        config_path,
+        timings,
+    )))
+}
+
+/// Creates context with `config` as is, like defaults that aren't loaded from disk.
+pub fn with_config(verbose: u64, config: Config) -> Context {
+    Rc::new(RefCell::new(ContextData::with_config(verbose, config)))
+}
+
 pub fn default() -> Context {
     Rc::new(RefCell::new(ContextData::default()))
 }
@@ -20,71 +47,262 @@ pub struct ContextData {
     pub verbose: u64,
     pub config: Config,
 
+    /// Path of the config file if loaded from disk, where changes to be persisted are written.
+    pub config_path: Option<PathBuf>,
+
     /// Environment passed to newly spawned processes.
     pub env: Env,
 
     /// Commands detected in $PATH.
     pub commands: PathCommands,
 
-    /// Extra trace option (set via `set -x`) outputs command trace to stdout.
+    /// Extra trace option (set via `set -x`) outputs command trace to stderr.
     pub xtrace: bool,
 
     /// Whether or not to exit shell immediately if a command exit with non-zero status
     /// (set via `set -e`).
     pub errexit: bool,
 
+    /// Whether or not to disable expansion of file globs (set via `set -f`).
+    pub noglob: bool,
+
+    /// Whether or not to disable substituting aliases (set via `set +o expand_aliases`). Defaults
+    /// to the inverse of the `expand_aliases` config entry, or off if unset.
+    pub noalias: bool,
+
+    /// Depth of positions where the exit status of commands is checked, like the left side of
+    /// `&&`/`||` or the condition of `if`, where `errexit` doesn't apply.
+    pub status_checked: usize,
+
     /// Whether or not to not exit shell when reading EOF.
     pub ignoreeof: bool,
 
     /// Stack of directories manipulated via `pushd` and `popd`.
     pub dir_stack: Vec<String>,
+
+    /// Directories recently changed to via `cd` and `pushd`, most recent last, without duplicates
+    /// and at most `MAX_RECENT_DIRS`.
+    pub recent_dirs: Vec<String>,
+
+    /// Where the last `getopts` call left off, to continue within grouped options like "-ab".
+    pub getopts_pos: Option<GetoptsPos>,
+
+    /// Canonical paths of files currently being sourced, innermost last.
+    pub sourcing: Vec<PathBuf>,
+
+    /// Jobs running in the background.
+    pub jobs: Jobs,
+
+    /// Whether the last command was an `exit` or `logout` refused because of stopped jobs, such
+    /// that exiting right after is allowed.
+    pub stopped_jobs_warned: bool,
+
+    /// Builtins disabled via `enable -n`, which are resolved as programs instead.
+    pub disabled_builtins: HashSet<String>,
+
+    /// Commands registered from outside the crate, by name.
+    pub custom_commands: HashMap<String, CommandFactory>,
+
+    /// How arguments of commands are completed, by command name, via `complete`.
+    pub completion_specs: HashMap<String, CompletionSpec>,
+
+    /// Origins of options not set by default or from the config file, by option name, like
+    /// "xtrace" after `set -x`.
+    pub option_origins: HashMap<String, OptionOrigin>,
+
+    /// How many times commands were run in this session, by program name, to rank completions.
+    pub command_counts: HashMap<String, usize>,
+}
+
+/// Where the current value of an option was set.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum OptionOrigin {
+    /// Default value.
+    Default,
+
+    /// Entry of the config file.
+    ConfigFile,
+
+    /// Command line argument, like `-v`.
+    CommandLine,
+
+    /// Set at runtime, like via `set -x`.
+    Runtime,
+}
+
+impl fmt::Display for OptionOrigin {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let text = match self {
+            OptionOrigin::Default => "default",
+            OptionOrigin::ConfigFile => "config file",
+            OptionOrigin::CommandLine => "command line",
+            OptionOrigin::Runtime => "set",
+        };
+        write!(f, "{}", text)
+    }
+}
+
+/// Maximum number of directories kept in `ContextData::recent_dirs`.
+pub const MAX_RECENT_DIRS: usize = 20;
+
+/// Only directories are completed for `cd` and `pushd` by default.
+fn default_completion_specs() -> HashMap<String, CompletionSpec> {
+    let mut specs = HashMap::new();
+    specs.insert("cd".to_string(), CompletionSpec::Directories);
+    specs.insert("pushd".to_string(), CompletionSpec::Directories);
+    specs
 }
 
 impl ContextData {
     pub fn new(verbose: u64, config_path: Option<&str>) -> ContextData {
+        ContextData::new_timed(verbose, config_path, &mut Timings::default())
+    }
+
+    /// Creates context like `new()` and records the durations of loading the config and scanning
+    /// `$PATH` for commands in `timings`.
+    pub fn new_timed(
+        verbose: u64,
+        config_path: Option<&str>,
+        timings: &mut Timings,
+    ) -> ContextData {
+        let config = timings.measure("config load", || Config::new(config_path));
+        let mut ctx = timings.measure("PATH rehash", || ContextData::with_config(verbose, config));
+        ctx.config_path = Some(Config::path(config_path));
+        ctx
+    }
+
+    pub fn with_config(verbose: u64, config: Config) -> ContextData {
+        let mut option_origins = HashMap::new();
+        if verbose > 0 {
+            option_origins.insert("verbose".to_string(), OptionOrigin::CommandLine);
+        }
+        let noalias = !config.expand_aliases.unwrap_or(true);
         ContextData {
             verbose,
-            config: Config::new(config_path),
+            config,
+            config_path: None,
             env: Env::new(),
             commands: PathCommands::new(),
             xtrace: false,
             errexit: false,
+            noglob: false,
+            noalias,
+            status_checked: 0,
             ignoreeof: false,
             dir_stack: Vec::new(),
+            recent_dirs: Vec::new(),
+            getopts_pos: None,
+            sourcing: Vec::new(),
+            jobs: Jobs::default(),
+            stopped_jobs_warned: false,
+            disabled_builtins: HashSet::new(),
+            custom_commands: HashMap::new(),
+            completion_specs: default_completion_specs(),
+            option_origins,
+            command_counts: HashMap::new(),
+        }
+    }
+
+    /// Yields where option `name` was set, like "xtrace" or "auto_cd".
+    pub fn option_origin(&self, name: &str) -> OptionOrigin {
+        if let Some(origin) = self.option_origins.get(name) {
+            *origin
+        } else if self.config.loaded.contains(name) {
+            OptionOrigin::ConfigFile
+        } else {
+            OptionOrigin::Default
         }
     }
 
+    /// Marks option `name` as set at runtime.
+    pub fn set_option_origin(&mut self, name: &str) {
+        self.option_origins
+            .insert(name.to_string(), OptionOrigin::Runtime);
+    }
+
+    /// Whether a failing command must exit the shell, which is when `errexit` is enabled and the
+    /// exit status isn't checked.
+    pub fn exits_on_failure(&self) -> bool {
+        self.errexit && self.status_checked == 0
+    }
+
+    /// Whether `name` is run as a command, which is a builtin that isn't disabled, a registered
+    /// custom command, or a program in `$PATH`.
+    pub fn is_command(&self, name: &str) -> bool {
+        self.custom_commands.contains_key(name)
+            || self.commands.contains(name)
+            || (!self.disabled_builtins.contains(name)
+                && command::builtins().iter().any(|builtin| builtin == name))
+    }
+
+    /// Registers custom command `name` created via `factory` from the arguments. It takes
+    /// precedence over any builtin or program of the same name.
+    pub fn register_command<F>(&mut self, name: &str, factory: F)
+    where
+        F: Fn(Vec<String>) -> Box<dyn Command> + 'static,
+    {
+        self.custom_commands
+            .insert(name.to_string(), Box::new(factory));
+    }
+
     /// Prints directory stack to stdout.
     ///
     /// `short` means all on one line, otherwise a list.
     pub fn print_dir_stack(&self, short: bool) {
-        if self.dir_stack.is_empty() {
-            return;
+        print!("{}", self.format_dir_stack(short, &util::home_dir()));
+    }
+
+    /// Formats directory stack for display with the `home` directory abbreviated as "~". Head dir
+    /// is first.
+    ///
+    /// `short` means all on one line, otherwise a list.
+    pub fn format_dir_stack(&self, short: bool, home: &Path) -> String {
+        let dirs: Vec<String> = self
+            .dir_stack
+            .iter()
+            .rev()
+            .map(|dir| util::abbreviate_home(Path::new(dir), home))
+            .collect();
+        if dirs.is_empty() {
+            return String::new();
         }
 
-        // Print in reverse order so that the head dir is first.
-        let len = self.dir_stack.len();
-        for (num, dir) in self.dir_stack.iter().rev().enumerate() {
-            if short {
-                if num == 0 {
-                    if len == 1 {
-                        println!("{}", dir);
-                    } else {
-                        print!("[{}] ", dir);
-                    }
-                } else if num < len - 1 {
-                    print!("{} ", dir);
-                } else {
-                    println!("{}", dir);
+        if short {
+            return match dirs.split_first() {
+                Some((head, rest)) if !rest.is_empty() => {
+                    format!("[{}] {}\n", head, rest.join(" "))
                 }
+                _ => format!("{}\n", dirs[0]),
+            };
+        }
 
-            // Long formats.
-            } else if num == 0 {
-                println!("{}>\t{}", num, dir);
+        // Long format.
+        let mut output = String::new();
+        for (num, dir) in dirs.iter().enumerate() {
+            if num == 0 {
+                output.push_str(&format!("{}>\t{}\n", num, dir));
             } else {
-                println!("{} \t{}", num, dir);
+                output.push_str(&format!("{} \t{}\n", num, dir));
             }
         }
+        output
+    }
+
+    /// Replaces the head of the directory stack with `dir` and yields the previous head, if any.
+    pub fn replace_dir_stack_head(&mut self, dir: String) -> Option<String> {
+        let head = self.dir_stack.pop();
+        self.dir_stack.push(dir);
+        head
+    }
+
+    /// Adds `dir` as the most recent directory, moving it there if already present, and drops the
+    /// oldest one beyond `MAX_RECENT_DIRS`.
+    pub fn add_recent_dir(&mut self, dir: String) {
+        self.recent_dirs.retain(|recent| *recent != dir);
+        self.recent_dirs.push(dir);
+        if self.recent_dirs.len() > MAX_RECENT_DIRS {
+            self.recent_dirs.remove(0);
+        }
     }
 
     pub fn print_short_dir_stack(&self) {
@@ -92,3 +310,104 @@ impl ContextData {
         self.print_dir_stack(short);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stack(dirs: &[&str]) -> ContextData {
+        ContextData {
+            dir_stack: dirs.iter().map(|d| d.to_string()).collect(),
+            ..ContextData::default()
+        }
+    }
+
+    #[test]
+    fn option_origin() {
+        let mut config = Config::default();
+        config.loaded.insert("auto_cd".to_string());
+        let mut ctx = ContextData {
+            config,
+            ..ContextData::default()
+        };
+        assert_eq!(ctx.option_origin("xtrace"), OptionOrigin::Default);
+        assert_eq!(ctx.option_origin("auto_cd"), OptionOrigin::ConfigFile);
+
+        ctx.set_option_origin("xtrace");
+        assert_eq!(ctx.option_origin("xtrace"), OptionOrigin::Runtime);
+    }
+
+    #[test]
+    fn option_origin_verbose_from_command_line() {
+        let ctx = ContextData::with_config(2, Config::default());
+        assert_eq!(ctx.option_origin("verbose"), OptionOrigin::CommandLine);
+
+        let ctx = ContextData::with_config(0, Config::default());
+        assert_eq!(ctx.option_origin("verbose"), OptionOrigin::Default);
+    }
+
+    #[test]
+    fn replace_dir_stack_head() {
+        let mut ctx = stack(&["/usr", "/tmp"]);
+        assert_eq!(
+            ctx.replace_dir_stack_head("/home".to_string()),
+            Some("/tmp".to_string())
+        );
+        assert_eq!(ctx.dir_stack, vec!["/usr", "/home"]);
+
+        // Swapping back toggles between the two directories.
+        assert_eq!(
+            ctx.replace_dir_stack_head("/tmp".to_string()),
+            Some("/home".to_string())
+        );
+        assert_eq!(ctx.dir_stack, vec!["/usr", "/tmp"]);
+
+        let mut ctx = stack(&[]);
+        assert_eq!(ctx.replace_dir_stack_head("/tmp".to_string()), None);
+        assert_eq!(ctx.dir_stack, vec!["/tmp"]);
+    }
+
+    #[test]
+    fn add_recent_dir() {
+        let mut ctx = ContextData::default();
+        ctx.add_recent_dir("/usr".to_string());
+        ctx.add_recent_dir("/tmp".to_string());
+        ctx.add_recent_dir("/usr".to_string());
+        assert_eq!(ctx.recent_dirs, vec!["/tmp", "/usr"]);
+
+        for i in 0..MAX_RECENT_DIRS {
+            ctx.add_recent_dir(format!("/dir{}", i));
+        }
+        assert_eq!(ctx.recent_dirs.len(), MAX_RECENT_DIRS);
+        assert_eq!(ctx.recent_dirs[0], "/dir0");
+        assert_eq!(
+            ctx.recent_dirs.last().unwrap(),
+            &format!("/dir{}", MAX_RECENT_DIRS - 1)
+        );
+    }
+
+    #[test]
+    fn format_dir_stack_empty() {
+        let ctx = stack(&[]);
+        assert_eq!(ctx.format_dir_stack(true, Path::new("/home/user")), "");
+        assert_eq!(ctx.format_dir_stack(false, Path::new("/home/user")), "");
+    }
+
+    #[test]
+    fn format_dir_stack_short() {
+        let home = Path::new("/home/user");
+        assert_eq!(stack(&["/tmp"]).format_dir_stack(true, home), "/tmp\n");
+
+        let ctx = stack(&["/tmp", "/home/user/src", "/usr"]);
+        assert_eq!(ctx.format_dir_stack(true, home), "[/usr] ~/src /tmp\n");
+    }
+
+    #[test]
+    fn format_dir_stack_long() {
+        let ctx = stack(&["/home/user", "/usr"]);
+        assert_eq!(
+            ctx.format_dir_stack(false, Path::new("/home/user")),
+            "0>\t/usr\n1 \t~\n"
+        );
+    }
+}
@@ -1,13 +1,23 @@
-use rustyline::completion::{Completer, FilenameCompleter, Pair};
+use rustyline::completion::{self, Completer, FilenameCompleter, Pair};
 use rustyline::error::ReadlineError;
 use rustyline::highlight::Highlighter;
 use rustyline::hint::Hinter;
 use rustyline::validate::Validator;
-use rustyline::{Config, Editor, Helper};
+use rustyline::{
+    Cmd, ConditionalEventHandler, Config, Editor, Event, EventContext, EventHandler, Helper,
+    KeyEvent, Movement, RepeatCount,
+};
 
-use crate::command;
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf, MAIN_SEPARATOR};
+
+use is_executable::IsExecutable;
+
+use crate::command::{self, set_command};
 use crate::context::Context;
 use crate::env::Env;
+use crate::tokenizer::{self, SplitErrorKind};
 use crate::util;
 
 /// Creates `Editor` instance with proper config and completion.
@@ -26,9 +36,76 @@ pub fn create(context: &Context) -> Editor<EditorHelper> {
     let h = EditorHelper::new(context.clone());
     editor.set_helper(Some(h));
 
+    if !config.word_delimiters.is_empty() {
+        editor.bind_sequence(
+            KeyEvent::ctrl('W'),
+            EventHandler::Conditional(Box::new(WordDeleteHandler {
+                delimiters: config.word_delimiters.clone(),
+            })),
+        );
+    }
+
     editor
 }
 
+/// Deletes the word before the cursor via Ctrl-W, stopping at any of the `delimiters` instead of
+/// only at whitespace.
+struct WordDeleteHandler {
+    delimiters: String,
+}
+
+impl WordDeleteHandler {
+    /// Yields the number of characters to delete before `pos` in `line`: any delimiters right
+    /// before it followed by the characters back to the previous delimiter, like "bar/" of
+    /// "foo/bar/".
+    fn delete_count(&self, line: &str, pos: usize) -> usize {
+        let is_delimiter = |ch: &char| self.delimiters.contains(*ch);
+        let mut chars = line[..pos].chars().rev().peekable();
+        let mut count = 0;
+        while chars.next_if(is_delimiter).is_some() {
+            count += 1;
+        }
+        while chars.next_if(|ch| !is_delimiter(ch)).is_some() {
+            count += 1;
+        }
+        count
+    }
+}
+
+impl ConditionalEventHandler for WordDeleteHandler {
+    fn handle(&self, _: &Event, n: RepeatCount, _: bool, ctx: &EventContext) -> Option<Cmd> {
+        let line = ctx.line();
+        let mut pos = ctx.pos();
+        let mut count = 0;
+        for _ in 0..n.max(1) {
+            let words = self.delete_count(line, pos);
+            if words == 0 {
+                break;
+            }
+            count += words;
+            pos = line[..pos]
+                .char_indices()
+                .rev()
+                .nth(words - 1)
+                .map_or(0, |(i, _)| i);
+        }
+        if count == 0 {
+            return None;
+        }
+        Some(Cmd::Kill(Movement::BackwardChar(count)))
+    }
+}
+
+/// Kind of candidates completed for the arguments of a command, specified via `complete`.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum CompletionSpec {
+    /// Only directory names, like for `cd`.
+    Directories,
+
+    /// File names, which is the default.
+    Files,
+}
+
 pub struct EditorHelper {
     pub context: Context,
     pub file_comp: Box<FilenameCompleter>,
@@ -42,22 +119,39 @@ impl EditorHelper {
         }
     }
 
+    /// Completes commands, which are builtins, aliases, custom commands, and programs in `$PATH`.
+    /// An exact match comes first, then commands starting with the input, and within each, the
+    /// commands run most often in this session.
     fn command_completer(&self, line: &str, pos: usize) -> Vec<Pair> {
         // Start with builtin commands.
         let mut cmds = command::builtins();
+        let mut counts = HashMap::new();
+
+        // The context is mutably borrowed if completion is triggered while a command is executing,
+        // like when a command reads input via the editor. Then only builtins are completed.
+        if let Ok(ctx) = self.context.try_borrow() {
+            // Add aliases, if any.
+            for alias in ctx.config.aliases.keys() {
+                if !cmds.contains(alias) {
+                    cmds.push(alias.clone());
+                }
+            }
 
-        // Add aliases, if any.
-        for alias in self.context.borrow().config.aliases.keys() {
-            if !cmds.contains(alias) {
-                cmds.push(alias.clone());
+            // Add custom commands, if any.
+            for cmd in ctx.custom_commands.keys() {
+                if !cmds.contains(cmd) {
+                    cmds.push(cmd.clone());
+                }
             }
-        }
 
-        // Add detected commands from PATH, if any.
-        for cmd in self.context.borrow().commands.as_ref() {
-            if !cmds.contains(cmd) {
-                cmds.push(cmd.clone());
+            // Add detected commands from PATH, if any.
+            for cmd in ctx.commands.as_ref().keys() {
+                if !cmds.contains(cmd) {
+                    cmds.push(cmd.clone());
+                }
             }
+
+            counts = ctx.command_counts.clone();
         }
 
         let mut candidates = Vec::new();
@@ -87,6 +181,12 @@ impl EditorHelper {
             }
         }
 
+        // The sort is stable, so commands run equally often keep their order.
+        let slice = &line[..pos];
+        candidates.sort_by_key(|pair| {
+            let count = counts.get(&pair.display).copied().unwrap_or(0);
+            (pair.display != slice, Reverse(count))
+        });
         candidates
     }
 
@@ -96,7 +196,13 @@ impl EditorHelper {
 
             Some(word) => {
                 let mut candidates = Vec::new();
-                for k in self.context.borrow().env.as_ref().keys() {
+
+                // No candidates if the context is mutably borrowed by an executing command.
+                let ctx = match self.context.try_borrow() {
+                    Ok(ctx) => ctx,
+                    Err(_) => return Some(candidates),
+                };
+                for k in ctx.env.as_ref().keys() {
                     let lookfor = format!("${}", k);
                     let lookfor2 = format!("${{{}", k);
 
@@ -131,7 +237,11 @@ impl EditorHelper {
             return None;
         }
 
-        let joined = exp.join(" ");
+        let joined = exp
+            .iter()
+            .map(|path| util::escape_word(path))
+            .collect::<Vec<String>>()
+            .join(" ");
         if joined.is_empty() || word == joined {
             return None;
         }
@@ -144,6 +254,114 @@ impl EditorHelper {
             word.len(),
         ))
     }
+
+    /// Yields the directories of the stack and then the recently visited ones, each most recent
+    /// first and without duplicates, if `line` is `cd` or `pushd` with no argument yet at `pos`.
+    fn recent_dirs_completer(&self, line: &str, pos: usize) -> Option<Vec<Pair>> {
+        let before = &line[..pos];
+        if !matches!(before.trim(), "cd" | "pushd") || !before.ends_with(char::is_whitespace) {
+            return None;
+        }
+
+        let ctx = self.context.try_borrow().ok()?;
+        let mut candidates: Vec<Pair> = Vec::new();
+        for dir in ctx
+            .dir_stack
+            .iter()
+            .rev()
+            .chain(ctx.recent_dirs.iter().rev())
+        {
+            if !candidates.iter().any(|pair| pair.display == *dir) {
+                candidates.push(Pair {
+                    display: dir.clone(),
+                    replacement: util::escape_word(dir),
+                });
+            }
+        }
+        Some(candidates)
+    }
+
+    /// Yields the start position and option names matching the word at `pos`, if it is the option
+    /// name of `set -o` or `set +o`, like "xtrace".
+    fn option_name_completer(line: &str, pos: usize) -> Option<(usize, Vec<Pair>)> {
+        let before = &line[..pos];
+        let prefix = before
+            .rsplit(char::is_whitespace)
+            .next()
+            .unwrap_or_default();
+        let start = pos - prefix.len();
+        let words: Vec<&str> = before[..start].split_whitespace().collect();
+        if !matches!(
+            words.as_slice(),
+            ["set", "-o" | "+o" | "--option" | "+option"]
+        ) {
+            return None;
+        }
+
+        let candidates = set_command::OPTION_NAMES
+            .iter()
+            .filter(|name| name.starts_with(prefix))
+            .map(|name| Pair {
+                display: name.to_string(),
+                replacement: name.to_string(),
+            })
+            .collect();
+        Some((start, candidates))
+    }
+
+    /// Yields the completion spec of the command of `line`, if any.
+    fn completion_spec(&self, line: &str) -> Option<CompletionSpec> {
+        let program = line.split_whitespace().next()?;
+        let ctx = self.context.try_borrow().ok()?;
+        ctx.completion_specs.get(program).copied()
+    }
+
+    /// Yields the path of filename completion `replacement`, which is escaped and can start with
+    /// "~".
+    fn candidate_path(replacement: &str) -> PathBuf {
+        let path = completion::unescape(replacement, Some('\\'));
+        match path.strip_prefix('~') {
+            Some(rest) => util::home_dir().join(rest.trim_start_matches(MAIN_SEPARATOR)),
+            None => Path::new(path.as_ref()).to_path_buf(),
+        }
+    }
+
+    fn filename_completer(
+        &self,
+        line: &str,
+        pos: usize,
+        ctx: &rustyline::Context,
+    ) -> Result<(usize, Vec<Pair>), ReadlineError> {
+        let (start, mut candidates) = self.file_comp.complete(line, pos, ctx)?;
+
+        // The completer appends a separator to directory names.
+        if self.completion_spec(line) == Some(CompletionSpec::Directories) {
+            candidates.retain(|pair| pair.replacement.ends_with(MAIN_SEPARATOR));
+        }
+
+        // Only offer executables, and directories that can lead to them, as programs.
+        if util::in_first_word(pos, line) {
+            candidates.retain(|pair| {
+                let path = EditorHelper::candidate_path(&pair.replacement);
+                path.is_dir() || path.is_executable()
+            });
+        }
+
+        // Within an unterminated quote, the quote already keeps the replacement as one word.
+        if let Err(err) = tokenizer::split(&line[..pos]) {
+            if err.kind != SplitErrorKind::DanglingBackslash {
+                return Ok((start, candidates));
+            }
+        }
+
+        // Escape whitespace and special characters of replacements so the line parses correctly,
+        // like "my\ file.txt". Some are escaped already by the completer, so unescape first.
+        for pair in &mut candidates {
+            let replacement = completion::unescape(&pair.replacement, Some('\\'));
+            pair.replacement = util::escape_word(&replacement);
+        }
+        Ok((start, candidates))
+    }
 }
 
 impl Completer for EditorHelper {
@@ -166,6 +384,11 @@ impl Completer for EditorHelper {
             }
         }
 
+        // Do option name completion of `set -o`.
+        if let Some((start, candidates)) = EditorHelper::option_name_completer(line, pos) {
+            return Ok((start, candidates));
+        }
+
         // Do environment variable completion.
         match self.env_var_completer(line, pos) {
             Some(candidates) => {
@@ -181,7 +404,14 @@ impl Completer for EditorHelper {
         }
 
         // Otherwise, default to file completion.
-        self.file_comp.complete(line, pos, ctx)
+        let (start, mut candidates) = self.filename_completer(line, pos, ctx)?;
+
+        // Offer the directory stack and recent directories before the directories on disk.
+        if let Some(mut dirs) = self.recent_dirs_completer(line, pos) {
+            dirs.append(&mut candidates);
+            return Ok((start, dirs));
+        }
+        Ok((start, candidates))
     }
 }
 
@@ -207,6 +437,13 @@ mod tests {
     use crate::context;
     use crate::env::Env;
 
+    use rustyline::history::History;
+
+    use std::fs;
+    use std::path::PathBuf;
+
+    use tempfile::TempDir;
+
     macro_rules! create_test_editor {
         ($e:ident) => {
             let ctx = context::default();
@@ -232,7 +469,40 @@ mod tests {
     fn command_complete_no_input_all_candidates() {
         create_test_editor!(editor);
         let pairs = editor.helper().unwrap().command_completer("", 0);
-        assert_eq!(pairs.len(), 14);
+        assert_eq!(pairs.len(), 33);
+    }
+
+    #[test]
+    fn command_complete_ranked_by_count() {
+        let ctx = context::default();
+        ctx.borrow_mut()
+            .command_counts
+            .insert("suspend".to_string(), 3);
+        ctx.borrow_mut().command_counts.insert("set".to_string(), 1);
+        create_test_editor_with_context!(editor; ctx);
+
+        let pairs = editor.helper().unwrap().command_completer("s", 1);
+        let names: Vec<&str> = pairs.iter().map(|p| p.display.as_str()).collect();
+        assert_eq!(names, vec!["suspend", "set", "sh-eval", "source"]);
+        assert_eq!(&pairs[0].replacement, "uspend");
+    }
+
+    #[test]
+    fn command_complete_exact_match_first() {
+        let ctx = context::default();
+        {
+            let mut ctx = ctx.borrow_mut();
+            ctx.commands
+                .insert("set".to_string(), PathBuf::from("/bin/set"));
+            ctx.commands
+                .insert("setup".to_string(), PathBuf::from("/bin/setup"));
+            ctx.command_counts.insert("setup".to_string(), 5);
+        }
+        create_test_editor_with_context!(editor; ctx);
+
+        let pairs = editor.helper().unwrap().command_completer("set", 3);
+        let names: Vec<&str> = pairs.iter().map(|p| p.display.as_str()).collect();
+        assert_eq!(names, vec!["set", "setup"]);
     }
 
     #[test]
@@ -258,10 +528,12 @@ mod tests {
         create_test_editor!(editor);
         let pairs = editor.helper().unwrap().command_completer("h", 1);
         assert_eq!(pairs.len(), 4);
-        assert_eq!(&pairs[0].display, "hash");
-        assert_eq!(&pairs[0].replacement, "ash");
-        assert_eq!(&pairs[1].display, "h");
-        assert_eq!(&pairs[1].replacement, "");
+
+        // The exact match comes first.
+        assert_eq!(&pairs[0].display, "h");
+        assert_eq!(&pairs[0].replacement, "");
+        assert_eq!(&pairs[1].display, "hash");
+        assert_eq!(&pairs[1].replacement, "ash");
         assert_eq!(&pairs[2].display, "hist");
         assert_eq!(&pairs[2].replacement, "ist");
         assert_eq!(&pairs[3].display, "history");
@@ -300,19 +572,25 @@ mod tests {
     #[test]
     fn command_complete_export_cmd() {
         create_test_editor!(editor);
-        let pairs = editor.helper().unwrap().command_completer("exp", 3);
+        let pairs = editor.helper().unwrap().command_completer("expo", 4);
         assert_eq!(pairs.len(), 1);
         assert_eq!(&pairs[0].display, "export");
-        assert_eq!(&pairs[0].replacement, "ort");
+        assert_eq!(&pairs[0].replacement, "rt");
     }
 
     #[test]
     fn command_complete_export_cmd_set() {
         create_test_editor!(editor);
         let pairs = editor.helper().unwrap().command_completer("s", 1);
-        assert_eq!(pairs.len(), 1);
+        assert_eq!(pairs.len(), 4);
         assert_eq!(&pairs[0].display, "set");
         assert_eq!(&pairs[0].replacement, "et");
+        assert_eq!(&pairs[1].display, "sh-eval");
+        assert_eq!(&pairs[1].replacement, "h-eval");
+        assert_eq!(&pairs[2].display, "source");
+        assert_eq!(&pairs[2].replacement, "ource");
+        assert_eq!(&pairs[3].display, "suspend");
+        assert_eq!(&pairs[3].replacement, "uspend");
     }
 
     #[test]
@@ -333,8 +611,8 @@ mod tests {
             let commands = &mut ctx.borrow_mut().commands;
 
             // Add in reverse order but expect in sorted order.
-            commands.insert("whoami".to_string());
-            commands.insert("who".to_string());
+            commands.insert("whoami".to_string(), PathBuf::from("/usr/bin/whoami"));
+            commands.insert("who".to_string(), PathBuf::from("/usr/bin/who"));
         }
 
         create_test_editor_with_context!(editor; ctx);
@@ -352,6 +630,34 @@ mod tests {
         assert_eq!(&pairs[0].replacement, "mi");
     }
 
+    #[test]
+    fn complete_while_context_mutably_borrowed() {
+        let ctx = context::default();
+        {
+            let mut ctx = ctx.borrow_mut();
+            ctx.config
+                .aliases
+                .insert("exa".to_string(), "ls".to_string());
+            ctx.env.insert("HELLO".to_string(), "WORLD".to_string());
+        }
+        create_test_editor_with_context!(editor; ctx);
+
+        // Simulate completion while a command holds a mutable borrow.
+        let _borrow = ctx.borrow_mut();
+
+        let history = History::new();
+        let rl_ctx = rustyline::Context::new(&history);
+        let helper = editor.helper().unwrap();
+
+        // Only builtins are completed since the alias cannot be read.
+        let (_, pairs) = helper.complete("ex", 2, &rl_ctx).unwrap();
+        let displays: Vec<&str> = pairs.iter().map(|p| p.display.as_str()).collect();
+        assert_eq!(displays, vec!["exit", "expansion", "export"]);
+
+        let (_, pairs) = helper.complete("echo $HE", 8, &rl_ctx).unwrap();
+        assert!(pairs.is_empty());
+    }
+
     #[test]
     fn env_var_completer_normal_var() {
         let mut env = Env::default();
@@ -422,4 +728,259 @@ mod tests {
         assert_eq!(&pairs[0].display, "${HELLO}");
         assert_eq!(&pairs[0].replacement, "LLO}");
     }
+
+    #[test]
+    fn filename_completer_escapes_spaces() {
+        let dir = TempDir::new().unwrap();
+        fs::write(dir.path().join("my file.txt"), "").unwrap();
+        create_test_editor!(editor);
+
+        let history = History::new();
+        let rl_ctx = rustyline::Context::new(&history);
+        let prefix = format!("cat {}/my", dir.path().display());
+        let (start, pairs) = editor
+            .helper()
+            .unwrap()
+            .complete(&prefix, prefix.len(), &rl_ctx)
+            .unwrap();
+        assert_eq!(start, 4);
+        assert_eq!(pairs.len(), 1);
+
+        let replacement = &pairs[0].replacement;
+        assert_eq!(
+            replacement,
+            &format!("{}/my\\ file.txt", dir.path().display())
+        );
+
+        // The completed line parses back to the file name as a single argument.
+        let line = format!("cat {}", replacement);
+        let args = tokenizer::split(&line).unwrap();
+        assert_eq!(
+            args[1],
+            dir.path().join("my file.txt").display().to_string()
+        );
+    }
+
+    #[test]
+    fn filename_completer_within_quotes() {
+        let dir = TempDir::new().unwrap();
+        fs::write(dir.path().join("my file.txt"), "").unwrap();
+        create_test_editor!(editor);
+
+        let history = History::new();
+        let rl_ctx = rustyline::Context::new(&history);
+        let prefix = format!("cat \"{}/my", dir.path().display());
+        let (_, pairs) = editor
+            .helper()
+            .unwrap()
+            .complete(&prefix, prefix.len(), &rl_ctx)
+            .unwrap();
+        assert_eq!(pairs.len(), 1);
+        assert!(pairs[0].replacement.contains("my file.txt"));
+    }
+
+    #[test]
+    fn filename_completer_directories_spec() {
+        let dir = TempDir::new().unwrap();
+        fs::create_dir(dir.path().join("sub")).unwrap();
+        fs::write(dir.path().join("sfile"), "").unwrap();
+
+        let ctx = context::default();
+        ctx.borrow_mut()
+            .completion_specs
+            .insert("mycmd".to_string(), CompletionSpec::Directories);
+        create_test_editor_with_context!(editor; ctx);
+
+        let history = History::new();
+        let rl_ctx = rustyline::Context::new(&history);
+        let helper = editor.helper().unwrap();
+
+        let prefix = format!("mycmd {}/s", dir.path().display());
+        let (_, pairs) = helper.complete(&prefix, prefix.len(), &rl_ctx).unwrap();
+        let displays: Vec<&str> = pairs.iter().map(|p| p.display.as_str()).collect();
+        assert_eq!(displays, vec!["sub"]);
+
+        // Other commands complete files as well.
+        let prefix = format!("cat {}/s", dir.path().display());
+        let (_, pairs) = helper.complete(&prefix, prefix.len(), &rl_ctx).unwrap();
+        let displays: Vec<&str> = pairs.iter().map(|p| p.display.as_str()).collect();
+        assert_eq!(displays, vec!["sfile", "sub"]);
+
+        // Overriding with file completion offers files again.
+        ctx.borrow_mut()
+            .completion_specs
+            .insert("mycmd".to_string(), CompletionSpec::Files);
+        let prefix = format!("mycmd {}/s", dir.path().display());
+        let (_, pairs) = helper.complete(&prefix, prefix.len(), &rl_ctx).unwrap();
+        assert_eq!(pairs.len(), 2);
+    }
+
+    #[test]
+    fn filename_completer_only_executables_as_program() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = TempDir::new().unwrap();
+        let run = dir.path().join("run");
+        fs::write(&run, "").unwrap();
+        fs::set_permissions(&run, fs::Permissions::from_mode(0o755)).unwrap();
+        fs::write(dir.path().join("readme"), "").unwrap();
+        fs::create_dir(dir.path().join("rdir")).unwrap();
+        create_test_editor!(editor);
+
+        let history = History::new();
+        let rl_ctx = rustyline::Context::new(&history);
+        let helper = editor.helper().unwrap();
+
+        let prefix = format!("{}/r", dir.path().display());
+        let (_, pairs) = helper.complete(&prefix, prefix.len(), &rl_ctx).unwrap();
+        let displays: Vec<&str> = pairs.iter().map(|p| p.display.as_str()).collect();
+        assert_eq!(displays, vec!["rdir", "run"]);
+
+        // All files are offered as arguments.
+        let prefix = format!("cat {}/r", dir.path().display());
+        let (_, pairs) = helper.complete(&prefix, prefix.len(), &rl_ctx).unwrap();
+        let displays: Vec<&str> = pairs.iter().map(|p| p.display.as_str()).collect();
+        assert_eq!(displays, vec!["rdir", "readme", "run"]);
+    }
+
+    #[test]
+    fn candidate_path_unescapes() {
+        assert_eq!(
+            EditorHelper::candidate_path(r"/tmp/my\ file"),
+            PathBuf::from("/tmp/my file")
+        );
+        assert_eq!(
+            EditorHelper::candidate_path("~/x"),
+            util::home_dir().join("x")
+        );
+    }
+
+    #[test]
+    fn recent_dirs_completer() {
+        let ctx = context::default();
+        {
+            let mut ctx = ctx.borrow_mut();
+            ctx.dir_stack = vec![
+                "/usr".to_string(),
+                "/tmp/my dir".to_string(),
+                "/usr".to_string(),
+            ];
+            ctx.recent_dirs = vec!["/opt".to_string(), "/usr".to_string(), "/var".to_string()];
+        }
+        create_test_editor_with_context!(editor; ctx);
+        let helper = editor.helper().unwrap();
+
+        let pairs = helper.recent_dirs_completer("cd ", 3).unwrap();
+        let displays: Vec<&str> = pairs.iter().map(|p| p.display.as_str()).collect();
+        assert_eq!(displays, vec!["/usr", "/tmp/my dir", "/var", "/opt"]);
+        assert_eq!(pairs[1].replacement, r"/tmp/my\ dir");
+
+        assert!(helper.recent_dirs_completer("pushd  ", 7).is_some());
+        assert!(helper.recent_dirs_completer("cd", 2).is_none());
+        assert!(helper.recent_dirs_completer("cd /", 4).is_none());
+        assert!(helper.recent_dirs_completer("ls ", 3).is_none());
+    }
+
+    #[test]
+    fn complete_cd_offers_recent_dirs_first() {
+        let dir = TempDir::new().unwrap();
+        let stacked = dir.path().join("stacked").display().to_string();
+        let recent = dir.path().join("recent").display().to_string();
+        fs::create_dir(&stacked).unwrap();
+        fs::create_dir(&recent).unwrap();
+
+        let ctx = context::default();
+        {
+            let mut ctx = ctx.borrow_mut();
+            ctx.dir_stack = vec![stacked.clone()];
+            ctx.recent_dirs = vec![recent.clone()];
+            ctx.completion_specs
+                .insert("cd".to_string(), CompletionSpec::Directories);
+        }
+        create_test_editor_with_context!(editor; ctx);
+
+        let history = History::new();
+        let rl_ctx = rustyline::Context::new(&history);
+        let (start, pairs) = editor
+            .helper()
+            .unwrap()
+            .complete("cd ", 3, &rl_ctx)
+            .unwrap();
+        assert_eq!(start, 3);
+        assert_eq!(pairs[0].display, stacked);
+        assert_eq!(pairs[1].display, recent);
+
+        // Only directories of the cwd follow, whichever it is.
+        assert!(pairs[2..]
+            .iter()
+            .all(|p| EditorHelper::candidate_path(&p.replacement).is_dir()));
+    }
+
+    #[test]
+    fn option_name_completer() {
+        let (start, pairs) = EditorHelper::option_name_completer("set -o ", 7).unwrap();
+        assert_eq!(start, 7);
+        assert_eq!(pairs.len(), set_command::OPTION_NAMES.len());
+
+        let (start, pairs) = EditorHelper::option_name_completer("set +o e", 8).unwrap();
+        assert_eq!(start, 7);
+        let names: Vec<&str> = pairs.iter().map(|p| p.replacement.as_str()).collect();
+        assert_eq!(names, vec!["errexit", "emacs", "expand_aliases"]);
+
+        let (_, pairs) = EditorHelper::option_name_completer("set --option xt", 15).unwrap();
+        assert_eq!(pairs[0].replacement, "xtrace");
+
+        let (_, pairs) = EditorHelper::option_name_completer("set -o q", 8).unwrap();
+        assert!(pairs.is_empty());
+
+        assert!(EditorHelper::option_name_completer("set -o", 6).is_none());
+        assert!(EditorHelper::option_name_completer("set -x ", 7).is_none());
+        assert!(EditorHelper::option_name_completer("set -o xtrace ", 14).is_none());
+        assert!(EditorHelper::option_name_completer("ls -o ", 6).is_none());
+    }
+
+    #[test]
+    fn complete_set_option_names() {
+        create_test_editor!(editor);
+        let history = History::new();
+        let rl_ctx = rustyline::Context::new(&history);
+        let (start, pairs) = editor
+            .helper()
+            .unwrap()
+            .complete("set -o ig", 9, &rl_ctx)
+            .unwrap();
+        assert_eq!(start, 7);
+        assert_eq!(pairs.len(), 1);
+        assert_eq!(pairs[0].replacement, "ignoreeof");
+    }
+
+    #[test]
+    fn word_delete_count() {
+        let handler = WordDeleteHandler {
+            delimiters: "/ \t".to_string(),
+        };
+        assert_eq!(handler.delete_count("cd /usr/local/bin", 17), 3);
+        assert_eq!(handler.delete_count("cd /usr/local/", 14), 6);
+        assert_eq!(handler.delete_count("cd /usr/local/bin", 13), 5);
+        assert_eq!(handler.delete_count("ls  ", 4), 4);
+        assert_eq!(handler.delete_count("cd /æø/åx", "cd /æø/åx".len()), 2);
+        assert_eq!(handler.delete_count("", 0), 0);
+    }
+
+    #[test]
+    fn word_delimiters_bind_ctrl_w() {
+        let mut editor = create(&context::default());
+        assert!(editor
+            .bind_sequence(KeyEvent::ctrl('W'), Cmd::Noop)
+            .is_none());
+
+        let config = crate::config::Config {
+            word_delimiters: "/ ".to_string(),
+            ..crate::config::Config::default()
+        };
+        let mut editor = create(&context::with_config(0, config));
+        assert!(editor
+            .bind_sequence(KeyEvent::ctrl('W'), Cmd::Noop)
+            .is_some());
+    }
 }
@@ -1,7 +1,11 @@
+use crate::util;
+
+use glob::Pattern;
 use regex::{Captures, Regex};
 use std::borrow::Borrow;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::env;
+use std::error::Error;
 use std::fmt::{Display, Formatter, Result};
 use std::hash::Hash;
 use std::ops::Index;
@@ -11,6 +15,11 @@ lazy_static! {
     static ref PARTIAL_BRACKET_ENV_VAR_REGEX: Regex =
         Regex::new(r"(\$\{([\w\?\-#!\$_@\*]*)\}?)").unwrap();
     static ref BRACKET_ENV_VAR_REGEX: Regex = Regex::new(r"(\$\{([\w\?\-#!\$_@\*]+)\})").unwrap();
+    static ref ARRAY_VAR_REGEX: Regex = Regex::new(r"\$\{(#?)(\w+)\[([^\]]*)\]\}").unwrap();
+    static ref SUBSTRING_VAR_REGEX: Regex =
+        Regex::new(r"\$\{(\w+):(\d+| +-\d+|\(-?\d+\))(?::(-?\d+))?\}").unwrap();
+    static ref CASE_VAR_REGEX: Regex = Regex::new(r"\$\{(\w+)(\^\^|,,|\^|,)\}").unwrap();
+    static ref AFFIX_VAR_REGEX: Regex = Regex::new(r"\$\{(\w+)(##|#|%%|%)([^}]*)\}").unwrap();
 }
 
 type Key = String;
@@ -18,22 +27,111 @@ type Value = String;
 type Map = HashMap<Key, Value>;
 
 /// Env encapsulates environment variables and their manipulation.
+///
+/// Indexed arrays, like `arr=(a b c)`, are kept separately since they are never passed to spawned
+/// processes. Associative arrays are not supported.
 #[derive(Default)]
 pub struct Env {
     env: Map,
+
+    /// Indexed array variables.
+    arrays: HashMap<Key, Vec<Value>>,
+
+    /// Keys of variables that cannot be changed or removed via `set()` and `unset()`.
+    readonly: HashSet<Key>,
+
+    /// Number of times each key was assigned or removed via `set()`, `set_array()`, and `unset()`.
+    assignments: HashMap<Key, usize>,
 }
 
 impl Env {
     pub fn new() -> Env {
         Env {
             env: env::vars().collect(),
+            arrays: HashMap::new(),
+            readonly: HashSet::new(),
+            assignments: HashMap::new(),
         }
     }
 
+    /// Inserts value at key regardless of the key being readonly. Use `set()` for variables that
+    /// are assigned by the user.
     pub fn insert(&mut self, key: Key, value: Value) {
         self.env.insert(key, value);
     }
 
+    /// Inserts value at key unless the key is readonly.
+    pub fn set(&mut self, key: Key, value: Value) -> std::result::Result<(), ReadonlyError> {
+        if self.is_readonly(&key) {
+            return Err(ReadonlyError(key));
+        }
+        *self.assignments.entry(key.clone()).or_insert(0) += 1;
+        self.env.insert(key, value);
+        Ok(())
+    }
+
+    /// Removes key unless it is readonly.
+    pub fn unset(&mut self, key: &str) -> std::result::Result<(), ReadonlyError> {
+        if self.is_readonly(key) {
+            return Err(ReadonlyError(key.to_string()));
+        }
+        *self.assignments.entry(key.to_string()).or_insert(0) += 1;
+        self.env.remove(key);
+        self.arrays.remove(key);
+        Ok(())
+    }
+
+    /// Assigns array `values` to key unless the key is readonly. Any plain variable of the same
+    /// key is replaced.
+    pub fn set_array(
+        &mut self,
+        key: Key,
+        values: Vec<Value>,
+    ) -> std::result::Result<(), ReadonlyError> {
+        if self.is_readonly(&key) {
+            return Err(ReadonlyError(key));
+        }
+        *self.assignments.entry(key.clone()).or_insert(0) += 1;
+        self.env.remove(&key);
+        self.arrays.insert(key, values);
+        Ok(())
+    }
+
+    /// Number of times key was assigned or removed by the user, via `set()`, `set_array()`, and
+    /// `unset()`, such that a reassignment of the same value can be told apart.
+    pub fn assignments(&self, key: &str) -> usize {
+        self.assignments.get(key).copied().unwrap_or(0)
+    }
+
+    pub fn get_array(&self, key: &str) -> Option<&Vec<Value>> {
+        self.arrays.get(key)
+    }
+
+    /// Returns the keys of array variables in sorted order.
+    pub fn array_keys(&self) -> Vec<&Key> {
+        let mut keys: Vec<&Key> = self.arrays.keys().collect();
+        keys.sort();
+        keys
+    }
+
+    /// Marks key as readonly such that `set()` and `unset()` refuse to change it.
+    pub fn set_readonly(&mut self, key: Key) {
+        self.readonly.insert(key);
+    }
+
+    pub fn is_readonly(&self, key: &str) -> bool {
+        self.readonly.contains(key)
+    }
+
+    /// Returns the readonly keys in sorted order.
+    pub fn readonly_keys(&self) -> Vec<&Key> {
+        let mut keys: Vec<&Key> = self.readonly.iter().collect();
+        keys.sort();
+        keys
+    }
+
+    /// Removes key regardless of the key being readonly. Use `unset()` for variables that are
+    /// removed by the user.
     pub fn remove<S>(&mut self, key: &S)
     where
         S: ?Sized + Hash + Eq,
@@ -92,35 +190,282 @@ impl Env {
         }
     }
 
-    /// Replaces all environment variables in \p data and returns resulting string.
+    /// Replaces all environment variables in \p data and returns resulting string. Variables in
+    /// single quotes or with an escaped '$' are kept as is, like in "'$HOME'" or "\$HOME".
     pub fn replace_vars<S>(&self, data: &S) -> Value
     where
         S: ?Sized + Hash + Eq + ToString,
         Key: Borrow<S>,
     {
-        let mut res = data.to_string();
-        for (k, v) in &self.env {
-            // Bracketed version always replaces.
-            res = res.replace(&format!("${{{}}}", k), v);
-
-            // Non-bracketed version can only replace when complete subset of string. For instance,
-            // "$USER" must not replace in "$USERNAME" but "$USERNAME" can since it's the complete
-            // string.
-            let lookfor = format!("${}", k);
-            res = ENV_VAR_REGEX
-                .replace_all(&res, |caps: &Captures| {
-                    let m = caps.get(0).unwrap().as_str();
-                    if m == lookfor {
-                        v.to_string()
-                    } else {
-                        m.to_string()
+        let data = data.to_string();
+        let mut res = String::with_capacity(data.len());
+        let mut last = 0;
+        let mut double = false;
+        let mut chars = data.char_indices();
+        while let Some((pos, ch)) = chars.next() {
+            let end = match ch {
+                '"' => {
+                    double = !double;
+                    continue;
+                }
+                '\\' => match chars.next() {
+                    Some((_, '$')) => pos + 2,
+                    _ => continue,
+                },
+                '\'' if !double => match chars.find(|(_, ch)| *ch == '\'') {
+                    Some((end, _)) => end + 1,
+                    None => data.len(),
+                },
+                _ => continue,
+            };
+            res.push_str(&self.replace_unquoted_vars(&data[last..pos]));
+            res.push_str(&data[pos..end]);
+            last = end;
+        }
+        res.push_str(&self.replace_unquoted_vars(&data[last..]));
+        res
+    }
+
+    /// Replaces all environment variables in `data`, which has neither single quotes nor escaped '$'.
+    fn replace_unquoted_vars(&self, data: &str) -> Value {
+        let mut res = self.replace_array_vars(data);
+        res = self.replace_substring_vars(&res);
+        res = self.replace_case_vars(&res);
+        res = self.replace_affix_vars(&res);
+
+        // Each variable is looked up as it is found in a single pass, such that long input with
+        // many variables stays fast. Bracketed version always replaces.
+        res = Env::replace_matches(&BRACKET_ENV_VAR_REGEX, &res, |m| {
+            self.var(&m[2..m.len() - 1])
+        });
+
+        // Non-bracketed version can only replace when complete subset of string. For instance,
+        // "$USER" must not replace in "$USERNAME" but "$USERNAME" can since it's the complete
+        // string.
+        Env::replace_matches(&ENV_VAR_REGEX, &res, |m| self.var(&m[1..]))
+    }
+
+    /// Replaces each match of `regex` in `data` with the value yielded for it, or keeps the match if
+    /// `None`. Matches are found with `find_iter()` since resolving captures of every match, like
+    /// `replace_all()` with a closure does, is quadratic with regex 1.6 for lines with thousands of
+    /// variables, as `parse_command_long_line_linear` in prompt.rs checks. The other `replace_*`
+    /// helpers keep using captures since their forms, like `${VAR:1}`, rarely occur many times.
+    fn replace_matches<F>(regex: &Regex, data: &str, value: F) -> Value
+    where
+        F: Fn(&str) -> Option<Value>,
+    {
+        let mut res = String::with_capacity(data.len());
+        let mut last = 0;
+        for m in regex.find_iter(data) {
+            res.push_str(&data[last..m.start()]);
+            match value(m.as_str()) {
+                Some(value) => res.push_str(&value),
+                None => res.push_str(m.as_str()),
+            }
+            last = m.end();
+        }
+        res.push_str(&data[last..]);
+        res
+    }
+
+    /// Yields value of variable `key`, or the first element if it's an array, since arrays without
+    /// index refer to their first element.
+    fn var(&self, key: &str) -> Option<Value> {
+        match self.env.get(key) {
+            Some(value) => Some(value.clone()),
+            None => self
+                .arrays
+                .get(key)
+                .map(|values| values.first().cloned().unwrap_or_default()),
+        }
+    }
+
+    /// Replaces array accesses in `data`: `${arr[N]}` yields element N, `${arr[@]}` and `${arr[*]}`
+    /// all elements, and `${#arr[@]}` the number of elements. Plain variables act as arrays of one
+    /// element.
+    ///
+    /// Inside double quotes, the values are escaped, and `"${arr[@]}"` keeps each element as a
+    /// separate argument whereas `"${arr[*]}"` joins them into one.
+    pub fn replace_array_vars(&self, data: &str) -> Value {
+        let mut res = String::new();
+        let mut last = 0;
+        let mut quoted = QuoteState::default();
+        for caps in ARRAY_VAR_REGEX.captures_iter(data) {
+            let m = caps.get(0).unwrap();
+            quoted.scan(&data[last..m.start()]);
+            res.push_str(&data[last..m.start()]);
+            last = m.end();
+
+            let key = &caps[2];
+            let index = &caps[3];
+            let values = match self.arrays.get(key) {
+                Some(values) => values.clone(),
+                None => self.env.get(key).cloned().into_iter().collect(),
+            };
+            let all = index == "@" || index == "*";
+
+            // Length of array or of element.
+            if !caps[1].is_empty() {
+                if all {
+                    res.push_str(&values.len().to_string());
+                } else if let Ok(i) = index.parse::<usize>() {
+                    let len = values.get(i).map_or(0, |v| v.chars().count());
+                    res.push_str(&len.to_string());
+                } else {
+                    res.push_str(m.as_str());
+                }
+                continue;
+            }
+
+            let value = if all {
+                if quoted.double && index == "@" {
+                    // Quote each element separately, but leave out the surrounding quotes
+                    // already in the input.
+                    let elements: Vec<String> =
+                        values.iter().map(|v| util::double_quote(v)).collect();
+                    let joined = elements.join(" ");
+                    if !joined.is_empty() {
+                        res.push_str(&joined[1..joined.len() - 1]);
                     }
-                })
-                .into_owned();
+                    continue;
+                }
+                values.join(" ")
+            } else if let Ok(i) = index.parse::<usize>() {
+                values.get(i).cloned().unwrap_or_default()
+            } else {
+                res.push_str(m.as_str());
+                continue;
+            };
+
+            if quoted.double {
+                let value = util::double_quote(&value);
+                res.push_str(&value[1..value.len() - 1]);
+            } else {
+                res.push_str(&value);
+            }
         }
+        res.push_str(&data[last..]);
         res
     }
 
+    /// Yields value of variable `key`, or the first element if it's an array, or an empty string if
+    /// not set.
+    fn scalar(&self, key: &str) -> Value {
+        self.var(key).unwrap_or_default()
+    }
+
+    /// Replaces substring expansions in `data`: `${VAR:offset}` yields the characters of `VAR` from
+    /// offset and `${VAR:offset:length}` at most length characters from offset. A negative offset,
+    /// written like `${VAR: -2}` or `${VAR:(-2)}` to not be confused with `${VAR:-default}`, counts
+    /// from the end, and a negative length excludes that many characters from the end. Out of range
+    /// offsets yield an empty string.
+    pub fn replace_substring_vars(&self, data: &str) -> Value {
+        SUBSTRING_VAR_REGEX
+            .replace_all(data, |caps: &Captures| {
+                let key = &caps[1];
+                let value = self.scalar(key);
+                let offset = caps[2].trim_matches(|c| c == ' ' || c == '(' || c == ')');
+
+                // Numbers too large for 64 bits are out of range, except for a length beyond the
+                // end.
+                let offset = match offset.parse::<i64>() {
+                    Ok(offset) => offset,
+                    Err(_) => return String::new(),
+                };
+                let length = match caps.get(3).map(|m| m.as_str()) {
+                    Some(length) => match length.parse::<i64>() {
+                        Ok(length) => Some(length),
+                        Err(_) if length.starts_with('-') => return String::new(),
+                        Err(_) => None,
+                    },
+                    None => None,
+                };
+                Env::substring(&value, offset, length)
+            })
+            .into_owned()
+    }
+
+    /// Yields characters of `value` from `offset` with at most `length` characters, where negative
+    /// values count from the end.
+    fn substring(value: &str, offset: i64, length: Option<i64>) -> Value {
+        let count = value.chars().count() as i64;
+        let start = if offset < 0 { count + offset } else { offset };
+        if start < 0 || start > count {
+            return String::new();
+        }
+        let end = match length {
+            Some(length) if length < 0 => count + length,
+            Some(length) => start.saturating_add(length).min(count),
+            None => count,
+        };
+        if end <= start {
+            return String::new();
+        }
+        value
+            .chars()
+            .skip(start as usize)
+            .take((end - start) as usize)
+            .collect()
+    }
+
+    /// Replaces case modifications in `data`: `${VAR^^}` yields `VAR` in uppercase, `${VAR,,}` in
+    /// lowercase, and `${VAR^}` and `${VAR,}` only change the first character.
+    pub fn replace_case_vars(&self, data: &str) -> Value {
+        CASE_VAR_REGEX
+            .replace_all(data, |caps: &Captures| {
+                let key = &caps[1];
+                let value = self.scalar(key);
+                let mut chars = value.chars();
+                match &caps[2] {
+                    "^^" => value.to_uppercase(),
+                    ",," => value.to_lowercase(),
+                    "^" => chars
+                        .next()
+                        .map(|first| first.to_uppercase().chain(chars).collect())
+                        .unwrap_or_default(),
+                    _ /*","*/ => chars
+                        .next()
+                        .map(|first| first.to_lowercase().chain(chars).collect())
+                        .unwrap_or_default(),
+                }
+            })
+            .into_owned()
+    }
+
+    /// Replaces prefix and suffix removals in `data`: `${VAR#pattern}` yields `VAR` without the
+    /// shortest prefix matching the glob pattern, like `*` and `?`, and `${VAR##pattern}` without
+    /// the longest. `${VAR%pattern}` and `${VAR%%pattern}` remove the shortest and longest suffix.
+    /// The value is unchanged if the pattern doesn't match. Variables in the pattern are replaced,
+    /// like in `${FILE%.$EXT}`.
+    pub fn replace_affix_vars(&self, data: &str) -> Value {
+        AFFIX_VAR_REGEX
+            .replace_all(data, |caps: &Captures| {
+                let value = self.scalar(&caps[1]);
+                let pattern = match Pattern::new(&self.replace_vars(&caps[3])) {
+                    Ok(pattern) => pattern,
+                    Err(_) => return value,
+                };
+
+                let mut bounds: Vec<usize> = value.char_indices().map(|(i, _)| i).collect();
+                bounds.push(value.len());
+                let op = &caps[2];
+                if op == "##" || op == "%" {
+                    bounds.reverse();
+                }
+                for i in bounds {
+                    if op.starts_with('#') {
+                        if pattern.matches(&value[..i]) {
+                            return value[i..].to_string();
+                        }
+                    } else if pattern.matches(&value[i..]) {
+                        return value[..i].to_string();
+                    }
+                }
+                value
+            })
+            .into_owned()
+    }
+
     /// Returns environment variable at position in text.
     pub fn var_at_pos(pos: usize, text: &str) -> Option<Value> {
         assert!(pos <= text.len());
@@ -164,6 +509,41 @@ impl Env {
     }
 }
 
+/// Error of trying to change or remove a readonly variable.
+#[derive(Debug, PartialEq)]
+pub struct ReadonlyError(pub Key);
+
+impl Error for ReadonlyError {}
+
+/// Tracks whether scanned text ends inside single or double quotes.
+#[derive(Default)]
+struct QuoteState {
+    single: bool,
+    double: bool,
+}
+
+impl QuoteState {
+    fn scan(&mut self, text: &str) {
+        let mut chars = text.chars();
+        while let Some(ch) = chars.next() {
+            match ch {
+                '\\' if !self.single => {
+                    chars.next();
+                }
+                '\'' if !self.double => self.single = !self.single,
+                '"' if !self.single => self.double = !self.double,
+                _ => {}
+            }
+        }
+    }
+}
+
+impl Display for ReadonlyError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "{}: readonly variable", self.0)
+    }
+}
+
 impl Display for Env {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result {
         let mut keys: Vec<&Key> = self.env.keys().peekable().collect();
@@ -235,6 +615,76 @@ mod tests {
         assert!(!env.contains_key("a"));
     }
 
+    #[test]
+    fn set() {
+        let mut env = Env::default();
+        assert_eq!(env.set("a".to_string(), "b".to_string()), Ok(()));
+        assert_eq!(env["a"], "b");
+    }
+
+    #[test]
+    fn assignments() {
+        let mut env = Env::default();
+        env.insert("a".to_string(), "b".to_string());
+        assert_eq!(env.assignments("a"), 0);
+        env.set("a".to_string(), "b".to_string()).unwrap();
+        env.set_array("a".to_string(), vec![]).unwrap();
+        env.unset("a").unwrap();
+        assert_eq!(env.assignments("a"), 3);
+    }
+
+    #[test]
+    fn set_readonly_fails() {
+        let mut env = Env::default();
+        env.insert("a".to_string(), "b".to_string());
+        env.set_readonly("a".to_string());
+        assert_eq!(
+            env.set("a".to_string(), "c".to_string()),
+            Err(ReadonlyError("a".to_string()))
+        );
+        assert_eq!(env["a"], "b");
+
+        // Insert bypasses readonly.
+        env.insert("a".to_string(), "c".to_string());
+        assert_eq!(env["a"], "c");
+    }
+
+    #[test]
+    fn unset() {
+        let mut env = Env::default();
+        env.insert("a".to_string(), "b".to_string());
+        assert_eq!(env.unset("a"), Ok(()));
+        assert!(!env.contains_key("a"));
+    }
+
+    #[test]
+    fn unset_readonly_fails() {
+        let mut env = Env::default();
+        env.insert("a".to_string(), "b".to_string());
+        env.set_readonly("a".to_string());
+        assert_eq!(env.unset("a"), Err(ReadonlyError("a".to_string())));
+        assert!(env.contains_key("a"));
+    }
+
+    #[test]
+    fn readonly_keys() {
+        let mut env = Env::default();
+        assert!(env.readonly_keys().is_empty());
+        env.set_readonly("b".to_string());
+        env.set_readonly("a".to_string());
+        assert!(env.is_readonly("a"));
+        assert!(!env.is_readonly("c"));
+        assert_eq!(env.readonly_keys(), vec!["a", "b"]);
+    }
+
+    #[test]
+    fn readonly_error_display() {
+        assert_eq!(
+            ReadonlyError("FOO".to_string()).to_string(),
+            "FOO: readonly variable"
+        );
+    }
+
     #[test]
     fn get() {
         let mut env = Env::default();
@@ -314,6 +764,27 @@ mod tests {
         assert_eq!(output, "1, 2, 1, $THREE".to_string());
     }
 
+    #[test]
+    fn replace_vars_escaped() {
+        let mut env = Env::default();
+        env.insert("ONE".to_string(), "1".to_string());
+        assert_eq!(env.replace_vars(r"\$ONE $ONE"), r"\$ONE 1");
+        assert_eq!(env.replace_vars(r"\\$ONE \${ONE}x"), r"\\1 \${ONE}x");
+        assert_eq!(env.replace_vars(r#""\$ONE" \"$ONE\""#), r#""\$ONE" \"1\""#);
+    }
+
+    #[test]
+    fn replace_vars_single_quoted() {
+        let mut env = Env::default();
+        env.insert("ONE".to_string(), "1".to_string());
+        assert_eq!(env.replace_vars("'$ONE' $ONE"), "'$ONE' 1");
+        assert_eq!(env.replace_vars("x'${ONE}'$ONE'"), "x'${ONE}'1'");
+        assert_eq!(
+            env.replace_vars(r#""it's $ONE" '"$ONE"'"#),
+            r#""it's 1" '"$ONE"'"#
+        );
+    }
+
     #[test]
     fn replace_vars_dont_when_subset() {
         let input = String::from("$USERNAME");
@@ -353,6 +824,242 @@ mod tests {
         assert_eq!(output, "foobar".to_string());
     }
 
+    fn array_env() -> Env {
+        let mut env = Env::default();
+        env.set_array(
+            "arr".to_string(),
+            vec!["a".to_string(), "b c".to_string(), "d".to_string()],
+        )
+        .unwrap();
+        env
+    }
+
+    #[test]
+    fn set_array() {
+        let mut env = Env::default();
+        env.insert("arr".to_string(), "x".to_string());
+        assert!(env
+            .set_array("arr".to_string(), vec!["a".to_string()])
+            .is_ok());
+        assert_eq!(env.get_array("arr"), Some(&vec!["a".to_string()]));
+        assert!(!env.contains_key("arr"));
+    }
+
+    #[test]
+    fn set_array_readonly_fails() {
+        let mut env = Env::default();
+        env.set_readonly("arr".to_string());
+        assert_eq!(
+            env.set_array("arr".to_string(), vec![]),
+            Err(ReadonlyError("arr".to_string()))
+        );
+        assert_eq!(env.get_array("arr"), None);
+    }
+
+    #[test]
+    fn unset_array() {
+        let mut env = array_env();
+        assert!(env.unset("arr").is_ok());
+        assert_eq!(env.get_array("arr"), None);
+    }
+
+    #[test]
+    fn replace_vars_array_index() {
+        let env = array_env();
+        assert_eq!(env.replace_vars("${arr[0]}"), "a");
+        assert_eq!(env.replace_vars("x${arr[1]}y"), "xb cy");
+        assert_eq!(env.replace_vars("${arr[2]}"), "d");
+        assert_eq!(env.replace_vars("${arr[3]}"), "");
+        assert_eq!(env.replace_vars("${nope[0]}"), "");
+    }
+
+    #[test]
+    fn replace_vars_array_first_element() {
+        let env = array_env();
+        assert_eq!(env.replace_vars("$arr"), "a");
+        assert_eq!(env.replace_vars("${arr}"), "a");
+    }
+
+    #[test]
+    fn replace_vars_array_all() {
+        let env = array_env();
+        assert_eq!(env.replace_vars("${arr[@]}"), "a b c d");
+        assert_eq!(env.replace_vars("${arr[*]}"), "a b c d");
+    }
+
+    #[test]
+    fn replace_vars_array_all_quoted() {
+        let env = array_env();
+        assert_eq!(env.replace_vars(r#""${arr[@]}""#), r#""a" "b c" "d""#);
+        assert_eq!(env.replace_vars(r#""${arr[*]}""#), r#""a b c d""#);
+        assert_eq!(env.replace_vars(r#""x${arr[@]}y""#), r#""xa" "b c" "dy""#);
+    }
+
+    #[test]
+    fn replace_vars_array_quoted_escapes() {
+        let mut env = Env::default();
+        env.set_array("arr".to_string(), vec![r#"say "hi""#.to_string()])
+            .unwrap();
+        assert_eq!(env.replace_vars(r#""${arr[0]}""#), r#""say \"hi\"""#);
+        assert_eq!(env.replace_vars(r#"'"'${arr[0]}"#), r#"'"'say "hi""#);
+    }
+
+    #[test]
+    fn replace_vars_array_empty_quoted() {
+        let mut env = Env::default();
+        env.set_array("arr".to_string(), vec![]).unwrap();
+        assert_eq!(env.replace_vars(r#""${arr[@]}""#), r#""""#);
+        assert_eq!(env.replace_vars("${#arr[@]}"), "0");
+    }
+
+    #[test]
+    fn replace_vars_array_length() {
+        let env = array_env();
+        assert_eq!(env.replace_vars("${#arr[@]}"), "3");
+        assert_eq!(env.replace_vars("${#arr[*]}"), "3");
+        assert_eq!(env.replace_vars("${#arr[1]}"), "3");
+    }
+
+    #[test]
+    fn replace_vars_plain_var_as_array() {
+        let mut env = Env::default();
+        env.insert("A".to_string(), "val".to_string());
+        assert_eq!(env.replace_vars("${A[0]}"), "val");
+        assert_eq!(env.replace_vars("${A[1]}"), "");
+        assert_eq!(env.replace_vars("${#A[@]}"), "1");
+    }
+
+    #[test]
+    fn replace_vars_array_invalid_index_kept() {
+        let env = array_env();
+        assert_eq!(env.replace_vars("${arr[x]}"), "${arr[x]}");
+    }
+
+    #[test]
+    fn replace_vars_substring() {
+        let mut env = Env::default();
+        env.insert("A".to_string(), "abcdefg".to_string());
+        assert_eq!(env.replace_vars("${A:2:3}"), "cde");
+        assert_eq!(env.replace_vars("${A:2}"), "cdefg");
+        assert_eq!(env.replace_vars("x${A:0:1}y"), "xay");
+        assert_eq!(env.replace_vars("${A:0}"), "abcdefg");
+        assert_eq!(env.replace_vars("${A:5:10}"), "fg");
+        assert_eq!(env.replace_vars("${A:2:0}"), "");
+        assert_eq!(env.replace_vars("${A:7}"), "");
+        assert_eq!(env.replace_vars("${A:8}"), "");
+        assert_eq!(env.replace_vars("${A:100:2}"), "");
+        assert_eq!(env.replace_vars("${NOPE:1}"), "");
+    }
+
+    #[test]
+    fn replace_vars_substring_negative() {
+        let mut env = Env::default();
+        env.insert("A".to_string(), "abcdefg".to_string());
+        assert_eq!(env.replace_vars("${A: -2}"), "fg");
+        assert_eq!(env.replace_vars("${A:(-3):2}"), "ef");
+        assert_eq!(env.replace_vars("${A:1:-1}"), "bcdef");
+        assert_eq!(env.replace_vars("${A: -3:-1}"), "ef");
+        assert_eq!(env.replace_vars("${A:5:-3}"), "");
+        assert_eq!(env.replace_vars("${A: -10}"), "");
+    }
+
+    #[test]
+    fn replace_vars_substring_huge() {
+        let mut env = Env::default();
+        env.insert("A".to_string(), "abcdefg".to_string());
+        assert_eq!(env.replace_vars("${A:1:9223372036854775807}"), "bcdefg");
+        assert_eq!(env.replace_vars("${A:1:99999999999999999999}"), "bcdefg");
+        assert_eq!(env.replace_vars("${A:1:-99999999999999999999}"), "");
+        assert_eq!(env.replace_vars("${A:99999999999999999999}"), "");
+        assert_eq!(env.replace_vars("${A: -99999999999999999999}"), "");
+    }
+
+    #[test]
+    fn replace_vars_substring_multibyte() {
+        let mut env = Env::default();
+        env.insert("A".to_string(), "æøå日本語".to_string());
+        assert_eq!(env.replace_vars("${A:1:2}"), "øå");
+        assert_eq!(env.replace_vars("${A:3}"), "日本語");
+        assert_eq!(env.replace_vars("${A: -1}"), "語");
+        assert_eq!(env.replace_vars("${A:6}"), "");
+    }
+
+    #[test]
+    fn replace_vars_substring_array_first_element() {
+        let env = array_env();
+        assert_eq!(env.replace_vars("${arr:0:1}"), "a");
+    }
+
+    #[test]
+    fn replace_vars_case_all() {
+        let mut env = Env::default();
+        env.insert("A".to_string(), "hello World".to_string());
+        env.insert("UP".to_string(), "LOUD".to_string());
+        env.insert("LOW".to_string(), "quiet".to_string());
+        assert_eq!(env.replace_vars("${A^^}"), "HELLO WORLD");
+        assert_eq!(env.replace_vars("${A,,}"), "hello world");
+        assert_eq!(env.replace_vars("${UP^^}"), "LOUD");
+        assert_eq!(env.replace_vars("${LOW,,}"), "quiet");
+        assert_eq!(env.replace_vars("x${LOW^^}y"), "xQUIETy");
+    }
+
+    #[test]
+    fn replace_vars_case_first() {
+        let mut env = Env::default();
+        env.insert("A".to_string(), "hello World".to_string());
+        env.insert("B".to_string(), "Hello".to_string());
+        assert_eq!(env.replace_vars("${A^}"), "Hello World");
+        assert_eq!(env.replace_vars("${A,}"), "hello World");
+        assert_eq!(env.replace_vars("${B^}"), "Hello");
+        assert_eq!(env.replace_vars("${B,}"), "hello");
+    }
+
+    #[test]
+    fn replace_vars_case_multibyte_and_empty() {
+        let mut env = Env::default();
+        env.insert("A".to_string(), "æøå".to_string());
+        env.insert("E".to_string(), String::new());
+        assert_eq!(env.replace_vars("${A^^}"), "ÆØÅ");
+        assert_eq!(env.replace_vars("${A^}"), "Æøå");
+        assert_eq!(env.replace_vars("${E^}"), "");
+        assert_eq!(env.replace_vars("${NOPE,,}"), "");
+    }
+
+    #[test]
+    fn replace_vars_remove_suffix() {
+        let mut env = Env::default();
+        env.insert("FILE".to_string(), "notes.txt.txt".to_string());
+        env.insert("EXT".to_string(), "txt".to_string());
+        assert_eq!(env.replace_vars("${FILE%.txt}"), "notes.txt");
+        assert_eq!(env.replace_vars("${FILE%.*}"), "notes.txt");
+        assert_eq!(env.replace_vars("${FILE%%.*}"), "notes");
+        assert_eq!(env.replace_vars("${FILE%.$EXT}"), "notes.txt");
+        assert_eq!(env.replace_vars("${FILE%t?t}"), "notes.txt.");
+        assert_eq!(env.replace_vars("${FILE%.md}"), "notes.txt.txt");
+    }
+
+    #[test]
+    fn replace_vars_remove_prefix() {
+        let mut env = Env::default();
+        env.insert("P".to_string(), "/usr/local/bin".to_string());
+        assert_eq!(env.replace_vars("${P##*/}"), "bin");
+        assert_eq!(env.replace_vars("${P#*/}"), "usr/local/bin");
+        assert_eq!(env.replace_vars("${P#/usr}"), "/local/bin");
+        assert_eq!(env.replace_vars("${P#?}"), "usr/local/bin");
+        assert_eq!(env.replace_vars("${P#local}"), "/usr/local/bin");
+        assert_eq!(env.replace_vars("${P#}"), "/usr/local/bin");
+        assert_eq!(env.replace_vars("${NOPE#x}"), "");
+    }
+
+    #[test]
+    fn replace_vars_remove_affix_multibyte() {
+        let mut env = Env::default();
+        env.insert("A".to_string(), "æøå.日本".to_string());
+        assert_eq!(env.replace_vars("${A%.*}"), "æøå");
+        assert_eq!(env.replace_vars("${A#??}"), "å.日本");
+        assert_eq!(env.replace_vars("${A%?}"), "æøå.日");
+    }
+
     #[test]
     fn partial_env_var_at_pos_start() {
         assert_eq!(
@@ -0,0 +1,287 @@
+use std::process::Child;
+
+/// State of a job.
+#[derive(Debug, PartialEq)]
+pub enum JobState {
+    Running,
+
+    /// Stopped by a signal, like `SIGSTOP` or `SIGTSTP`.
+    Stopped,
+
+    /// Finished with exit code.
+    Done(i32),
+}
+
+/// Job is a process running in the background.
+pub struct Job {
+    /// Job number used to reference the job, like "%1".
+    pub id: usize,
+    pub pid: u32,
+
+    /// Command line that started the job.
+    pub command: String,
+
+    pub state: JobState,
+    child: Child,
+}
+
+impl Job {
+    /// Yields notification line of job like "[1]+ Done  sleep 5", where `marker` is '+' for the
+    /// current job, '-' for the previous one, and ' ' otherwise.
+    pub fn notification(&self, marker: char) -> String {
+        format!(
+            "[{}]{} {}  {}",
+            self.id,
+            marker,
+            self.status(),
+            self.command
+        )
+    }
+
+    /// Yields state of job like "Running", "Stopped", "Done", or "Exit 2".
+    pub fn status(&self) -> String {
+        match self.state {
+            JobState::Running => "Running".to_string(),
+            JobState::Stopped => "Stopped".to_string(),
+            JobState::Done(0) => "Done".to_string(),
+            JobState::Done(code) => format!("Exit {}", code),
+        }
+    }
+}
+
+/// Table of background jobs ordered by job number.
+#[derive(Default)]
+pub struct Jobs {
+    jobs: Vec<Job>,
+}
+
+impl Jobs {
+    /// Adds running child process as job and returns the job number, which is one higher than the
+    /// highest job number in use.
+    pub fn add(&mut self, child: Child, command: String) -> usize {
+        let id = self.jobs.last().map_or(1, |j| j.id + 1);
+        self.jobs.push(Job {
+            id,
+            pid: child.id(),
+            command,
+            state: JobState::Running,
+            child,
+        });
+        id
+    }
+
+    pub fn len(&self) -> usize {
+        self.jobs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.jobs.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Job> {
+        self.jobs.iter()
+    }
+
+    /// Returns marker of job: '+' for the current (most recent) job, '-' for the previous one, and
+    /// ' ' otherwise.
+    pub fn marker(&self, id: usize) -> char {
+        let len = self.jobs.len();
+        match self.jobs.iter().position(|j| j.id == id) {
+            Some(pos) if pos + 1 == len => '+',
+            Some(pos) if pos + 2 == len => '-',
+            _ => ' ',
+        }
+    }
+
+    /// Resolves job spec, like "%1" for job 1, "%%" or "%+" for the current job, and "%-" for the
+    /// previous job.
+    pub fn resolve(&self, spec: &str) -> Option<&Job> {
+        let spec = spec.strip_prefix('%')?;
+        let len = self.jobs.len();
+        match spec {
+            "%" | "+" | "" => self.jobs.last(),
+            "-" if len >= 2 => self.jobs.get(len - 2),
+            "-" => None,
+            _ => {
+                let id = spec.parse::<usize>().ok()?;
+                self.jobs.iter().find(|j| j.id == id)
+            }
+        }
+    }
+
+    /// Whether any job is stopped.
+    pub fn has_stopped(&self) -> bool {
+        self.jobs.iter().any(|j| j.state == JobState::Stopped)
+    }
+
+    /// Polls all jobs without blocking, updates whether they are stopped, and removes the ones that
+    /// finished. Returns notification lines of the finished jobs.
+    pub fn reap(&mut self) -> Vec<String> {
+        for job in &mut self.jobs {
+            // Waited for directly, instead of via the child, to also learn about stops.
+            let mut status = 0;
+            let flags = libc::WNOHANG | libc::WUNTRACED | libc::WCONTINUED;
+            if unsafe { libc::waitpid(job.child.id() as libc::pid_t, &mut status, flags) } <= 0 {
+                continue;
+            }
+            job.state = if libc::WIFSTOPPED(status) {
+                JobState::Stopped
+            } else if libc::WIFCONTINUED(status) {
+                JobState::Running
+            } else if libc::WIFEXITED(status) {
+                JobState::Done(libc::WEXITSTATUS(status))
+            } else {
+                // Killed by signal.
+                JobState::Done(0)
+            };
+        }
+
+        let notifications = self
+            .jobs
+            .iter()
+            .filter(|j| matches!(j.state, JobState::Done(_)))
+            .map(|j| j.notification(self.marker(j.id)))
+            .collect();
+        self.jobs.retain(|j| !matches!(j.state, JobState::Done(_)));
+        notifications
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::process::Command;
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    fn spawn(program: &str, args: &[&str]) -> Child {
+        Command::new(program).args(args).spawn().unwrap()
+    }
+
+    /// Reaps jobs until `count` notifications are yielded or a timeout is reached.
+    fn reap_until(jobs: &mut Jobs, count: usize) -> Vec<String> {
+        let start = Instant::now();
+        let mut notifications = Vec::new();
+        while notifications.len() < count && start.elapsed() < Duration::from_secs(5) {
+            notifications.append(&mut jobs.reap());
+            thread::sleep(Duration::from_millis(10));
+        }
+        notifications
+    }
+
+    #[test]
+    fn add() {
+        let mut jobs = Jobs::default();
+        assert!(jobs.is_empty());
+        assert_eq!(jobs.add(spawn("true", &[]), "true".to_string()), 1);
+        assert_eq!(jobs.add(spawn("true", &[]), "true".to_string()), 2);
+        assert_eq!(jobs.len(), 2);
+        reap_until(&mut jobs, 2);
+    }
+
+    #[test]
+    fn marker() {
+        let mut jobs = Jobs::default();
+        for _ in 0..3 {
+            jobs.add(spawn("true", &[]), "true".to_string());
+        }
+        assert_eq!(jobs.marker(1), ' ');
+        assert_eq!(jobs.marker(2), '-');
+        assert_eq!(jobs.marker(3), '+');
+        assert_eq!(jobs.marker(4), ' ');
+        reap_until(&mut jobs, 3);
+    }
+
+    #[test]
+    fn resolve() {
+        let mut jobs = Jobs::default();
+        assert!(jobs.resolve("%%").is_none());
+        for _ in 0..3 {
+            jobs.add(spawn("true", &[]), "true".to_string());
+        }
+        let id = |spec: &str| jobs.resolve(spec).map(|j| j.id);
+        assert_eq!(id("%1"), Some(1));
+        assert_eq!(id("%3"), Some(3));
+        assert_eq!(id("%%"), Some(3));
+        assert_eq!(id("%+"), Some(3));
+        assert_eq!(id("%"), Some(3));
+        assert_eq!(id("%-"), Some(2));
+        assert_eq!(id("%4"), None);
+        assert_eq!(id("%x"), None);
+        assert_eq!(id("1"), None);
+        reap_until(&mut jobs, 3);
+    }
+
+    #[test]
+    fn notification() {
+        let job = Job {
+            id: 1,
+            pid: 0,
+            command: "sleep 5".to_string(),
+            state: JobState::Done(0),
+            child: spawn("true", &[]),
+        };
+        assert_eq!(job.notification('+'), "[1]+ Done  sleep 5");
+
+        let job = Job {
+            state: JobState::Done(2),
+            ..job
+        };
+        assert_eq!(job.notification('-'), "[1]- Exit 2  sleep 5");
+    }
+
+    #[test]
+    fn reap_finished() {
+        let mut jobs = Jobs::default();
+        jobs.add(spawn("sleep", &["10"]), "sleep 10".to_string());
+        jobs.add(spawn("sh", &["-c", "exit 3"]), "sh -c exit 3".to_string());
+
+        let notifications = reap_until(&mut jobs, 1);
+        assert_eq!(notifications, vec!["[2]+ Exit 3  sh -c exit 3"]);
+
+        // The running job is kept.
+        assert_eq!(jobs.len(), 1);
+        assert_eq!(jobs.iter().next().unwrap().command, "sleep 10");
+        assert!(jobs.reap().is_empty());
+
+        // Next job number follows the highest in use.
+        assert_eq!(jobs.add(spawn("true", &[]), "true".to_string()), 2);
+
+        for job in &mut jobs.jobs {
+            job.child.kill().unwrap();
+        }
+        reap_until(&mut jobs, 2);
+    }
+
+    /// Reaps jobs until the first job has `state` or a timeout is reached.
+    fn reap_until_state(jobs: &mut Jobs, state: JobState) {
+        let start = Instant::now();
+        while jobs.jobs[0].state != state && start.elapsed() < Duration::from_secs(5) {
+            jobs.reap();
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    #[test]
+    fn reap_stopped() {
+        let mut jobs = Jobs::default();
+        jobs.add(spawn("sleep", &["10"]), "sleep 10".to_string());
+        assert!(!jobs.has_stopped());
+
+        let pid = jobs.jobs[0].pid as libc::pid_t;
+        unsafe { libc::kill(pid, libc::SIGSTOP) };
+        reap_until_state(&mut jobs, JobState::Stopped);
+        assert!(jobs.has_stopped());
+        assert_eq!(jobs.len(), 1);
+        assert_eq!(jobs.jobs[0].notification('+'), "[1]+ Stopped  sleep 10");
+
+        unsafe { libc::kill(pid, libc::SIGCONT) };
+        reap_until_state(&mut jobs, JobState::Running);
+        assert!(!jobs.has_stopped());
+
+        jobs.jobs[0].child.kill().unwrap();
+        reap_until(&mut jobs, 1);
+        assert!(jobs.is_empty());
+    }
+}
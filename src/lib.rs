@@ -22,70 +22,148 @@
 //! ## Options
 //!
 //! - `max_history_size` takes a positive number as the maximum of entries to keep in history (at
-//! "~/.carapace/history").
-//! - `edit_mode` gives either `"emacs"` or `"vi"` bindings.
+//!   "~/.carapace/history").
+//! - `edit_mode` gives either `"emacs"` or `"vi"` bindings. The current one is in `$EDIT_MODE`.
+//! - `persist_edit_mode` writes the edit mode back to the config file on exit when changed via
+//!   `set -o vi` or `set -o emacs`.
 //! - `completion_type` can either give a `"list"` of all possibilities, like Bash, or provide a
-//! `"circular"` completion of each candidate, like VI.
-//! - `auto_cd` enables implicit `cd` command usage by inputting existing folder paths.
+//!   `"circular"` completion of each candidate, like VI.
+//! - `auto_cd` enables implicit `cd` command usage by inputting existing folder paths, unless a
+//!   command has the same name.
+//! - `cd_to_file_parent` makes `cd` change to the directory containing a file when given one.
+//! - `show_banner` prints a banner with the version when starting the interactive shell.
 //! - `aliases` is a "map" of (alias, command replacement) pairs, like `"ll": "ls -l"`.
+//! - `expand_aliases` enables or disables substituting aliases. If unset, or `null`, aliases are
+//!   only expanded in the interactive shell, like in Bash, but not via `-c`, `-s`, or `-f`.
+//! - `env` is a "map" of (env var, value) pairs set when starting the shell, like
+//!   `"PATH": "$PATH:/opt/bin"`.
+//! - `confirm_commands` is a list of commands, like `["rm -rf"]`, that must be confirmed before
+//!   running. They are matched as prefixes of the expanded command line.
+//! - `word_delimiters` are the characters where deleting a word backwards via Ctrl-W stops, like
+//!   `"/ \t"` to also stop at path separators. By default, it stops at whitespace.
+//!
+//! Variables, like `$HOME`, are replaced in values of `env` when starting the shell, and in values
+//! of `aliases` each time the alias is used, with the environment at that point.
 
 #[macro_use]
 extern crate lazy_static;
 
+pub mod arith;
 pub mod command;
 pub mod config;
 pub mod context;
 pub mod editor;
 pub mod env;
+pub mod jobs;
 pub mod path_commands;
 pub mod prompt;
+pub mod timings;
+pub mod tokenizer;
 pub mod util;
 
+use crate::config::Config;
 use crate::prompt::Prompt;
+use crate::timings::Timings;
 
 use clap::ArgMatches;
 
-use std::fs;
-use std::io::{self, BufRead};
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader};
+
+/// Version of carapace.
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Version of carapace with build info: git commit hash and build date, like
+/// "0.2.0 (abc1234 2022-06-01)".
+pub const LONG_VERSION: &str = concat!(
+    env!("CARGO_PKG_VERSION"),
+    " (",
+    env!("CARAPACE_GIT_HASH"),
+    " ",
+    env!("CARAPACE_BUILD_DATE"),
+    ")"
+);
 
 /// Starts the read-eval-print-loop of the Carapace shell, with supplied, parsed CLI arguments, if
 /// any. Returns the exit code.
 pub fn repl(arg_matches: &ArgMatches) -> i32 {
-    // Create init folder if not present.
-    let path = dirs_next::home_dir().unwrap().join(".carapace");
-    if let Err(err) = fs::create_dir_all(&path) {
-        println!("Could not create init folder: {}\n{}", path.display(), err);
-        return 1;
+    let verbose = arg_matches.occurrences_of("verbose");
+    let mut timings = Timings::default();
+
+    // With --norc, nothing is read from or written to the init folder.
+    let norc = arg_matches.is_present("norc");
+    let context = if norc {
+        timings.measure("PATH rehash", || {
+            context::with_config(verbose, Config::default())
+        })
+    } else {
+        // Create init folder if not present.
+        let path = util::carapace_dir();
+        if let Err(err) = fs::create_dir_all(&path) {
+            println!("Could not create init folder: {}\n{}", path.display(), err);
+            return 1;
+        }
+
+        context::new_timed(verbose, arg_matches.value_of("config"), &mut timings)
+    };
+
+    // Print the effective config and exit, before history is loaded.
+    if arg_matches.is_present("dump_config") {
+        println!("{}", context.borrow().config.encode());
+        return 0;
     }
 
-    let context = context::new(
-        arg_matches.occurrences_of("verbose"),
-        arg_matches.value_of("config"),
-    );
-    let mut prompt = Prompt::new(context);
+    // Like in Bash, aliases aren't expanded when not interactive unless enabled in the config.
+    let interactive = ["command", "stdin", "command_file"]
+        .iter()
+        .all(|arg| !arg_matches.is_present(arg));
+    if !interactive {
+        let mut ctx = context.borrow_mut();
+        ctx.noalias = !ctx.config.expand_aliases.unwrap_or(false);
+    }
+
+    let mut prompt = if norc {
+        timings.measure("env setup", || Prompt::without_history(context))
+    } else {
+        Prompt::new_timed(context, &mut timings)
+    };
+
+    // Startup timings are written to stderr to not mix with the output of commands.
+    if Timings::enabled(arg_matches.is_present("time_startup")) {
+        eprintln!("{}", timings.report());
+    }
 
-    // If -c <command> is specified then run command and exit.
+    // If -c <command> is specified then run command and exit with its status.
     if let Some(command) = arg_matches.value_of("command") {
         let cmd = prompt.parse_command(command);
         if let Some(code) = command::execute(cmd, &mut prompt) {
             return code;
         }
-        return 0;
+        return last_status(&prompt);
     }
     // Read commands from STDIN, one per line, and exit.
     else if arg_matches.is_present("stdin") {
         let stdin = io::stdin();
-        for line in stdin.lock().lines() {
-            if line.is_err() {
-                return 1;
+        if arg_matches.is_present("report") {
+            let report = run_lines_with_report(stdin.lock(), &mut prompt);
+            println!("{}", report.summary());
+            return report.code;
+        }
+        return run_lines(stdin.lock(), &mut prompt);
+    }
+    // Read commands from file, one per line, and exit.
+    else if let Some(path) = arg_matches.value_of("command_file") {
+        return match File::open(path) {
+            Ok(file) => run_lines(BufReader::new(file), &mut prompt),
+            Err(err) => {
+                eprintln!("Could not read commands from: {}\n{}", path, err);
+                127
             }
+        };
+    }
 
-            let cmd = prompt.parse_command(&line.unwrap());
-            if let Some(code) = command::execute(cmd, &mut prompt) {
-                return code;
-            }
-        }
-        return 0;
+    if let Some(banner) = banner(&prompt.context.borrow().config) {
+        println!("{}", banner);
     }
 
     loop {
@@ -94,3 +172,253 @@ pub fn repl(arg_matches: &ArgMatches) -> i32 {
         }
     }
 }
+
+/// Yields the banner shown when starting the interactive shell, if enabled in `config`.
+fn banner(config: &Config) -> Option<String> {
+    if !config.show_banner {
+        return None;
+    }
+    Some(format!(
+        "Carapace {} - shell written in Rust\nType 'exit' or press Ctrl-D to quit.",
+        VERSION
+    ))
+}
+
+/// Reads commands from `reader`, one per line, and executes them until the input is exhausted or a
+/// command yields an exit code, like via `exit` or `errexit`. Blank lines and comment lines,
+/// starting with '#', are skipped. Returns the exit code, which is the status of the last command
+/// if the input is exhausted.
+pub fn run_lines<R: BufRead>(reader: R, prompt: &mut Prompt) -> i32 {
+    for line in reader.lines() {
+        if line.is_err() {
+            return 1;
+        }
+
+        let line = line.unwrap();
+        if util::is_blank_or_comment(&line) {
+            continue;
+        }
+
+        let cmd = prompt.parse_command(&line);
+        if let Some(code) = command::execute(cmd, prompt) {
+            return code;
+        }
+    }
+
+    // Like other shells, the status of the last command is the exit code.
+    last_status(prompt)
+}
+
+/// Yields the status of the last command, `$?`, or 0 if not set.
+fn last_status(prompt: &Prompt) -> i32 {
+    let ctx = prompt.context.borrow();
+    ctx.env
+        .get("?")
+        .and_then(|code| code.parse().ok())
+        .unwrap_or(0)
+}
+
+/// Outcome of running lines via `run_lines_with_report()`.
+#[derive(Debug, PartialEq)]
+pub struct Report {
+    /// Number of commands that succeeded.
+    pub ok: usize,
+
+    /// Line number, line, and exit code of the command that failed, if any.
+    pub failed: Option<(usize, String, i32)>,
+
+    /// Exit code of the shell.
+    pub code: i32,
+}
+
+impl Report {
+    /// Yields summary like "3 ok, 1 failed: line 7: make (exit 2)".
+    pub fn summary(&self) -> String {
+        match &self.failed {
+            Some((num, line, code)) => format!(
+                "{} ok, 1 failed: line {}: {} (exit {})",
+                self.ok, num, line, code
+            ),
+            None => format!("{} ok", self.ok),
+        }
+    }
+}
+
+/// Reads commands from `reader` like `run_lines()` but stops at the first command that fails.
+/// Yields a report of how many commands succeeded and which one failed, if any.
+pub fn run_lines_with_report<R: BufRead>(reader: R, prompt: &mut Prompt) -> Report {
+    let mut report = Report {
+        ok: 0,
+        failed: None,
+        code: 0,
+    };
+    for (num, line) in reader.lines().enumerate() {
+        let line = match line {
+            Ok(line) => line,
+            Err(_) => {
+                report.code = 1;
+                break;
+            }
+        };
+        if util::is_blank_or_comment(&line) {
+            continue;
+        }
+
+        let success = match prompt.parse_command(&line) {
+            Ok(mut cmd) => cmd.execute(prompt),
+            Err(err) => {
+                println!("{}", err);
+                Ok(false)
+            }
+        };
+        match success {
+            Ok(true) => report.ok += 1,
+            Ok(false) => {
+                // Not all commands set $?, like when failing to parse arguments.
+                let code = match prompt.context.borrow().env.get("?") {
+                    Some(code) if code != "0" => code.parse().unwrap_or(1),
+                    _ => 1,
+                };
+                report.failed = Some((num + 1, line.trim().to_string(), code));
+                report.code = code;
+                break;
+            }
+            Err(code) => {
+                report.code = code;
+                break;
+            }
+        }
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::Cursor;
+
+    #[test]
+    fn long_version_includes_version() {
+        assert!(LONG_VERSION.starts_with(&format!("{} (", VERSION)));
+        assert!(LONG_VERSION.ends_with(')'));
+    }
+
+    #[test]
+    fn banner_disabled_by_default() {
+        assert_eq!(banner(&Config::default()), None);
+    }
+
+    #[test]
+    fn banner_enabled() {
+        let config = Config {
+            show_banner: true,
+            ..Config::default()
+        };
+        let banner = banner(&config).unwrap();
+        assert!(banner.starts_with(&format!("Carapace {}", VERSION)));
+    }
+
+    #[test]
+    fn run_lines_empty() {
+        let mut prompt = Prompt::create(context::default());
+        assert_eq!(0, run_lines(Cursor::new(""), &mut prompt));
+    }
+
+    #[test]
+    fn run_lines_exit_code() {
+        let mut prompt = Prompt::create(context::default());
+        assert_eq!(3, run_lines(Cursor::new("exit 3\nexit 4\n"), &mut prompt));
+    }
+
+    #[test]
+    fn run_lines_last_status() {
+        let mut prompt = Prompt::create(context::default());
+        let script = "sh -c 'exit 3'\ntrue\nsh -c 'exit 4'\n# Comment.\n\n";
+        assert_eq!(4, run_lines(Cursor::new(script), &mut prompt));
+        assert_eq!(0, run_lines(Cursor::new("false\ntrue\n"), &mut prompt));
+
+        // Builtins that fail without setting $? yield 1.
+        let script = "readonly X=1\nexport X=2\n";
+        assert_eq!(1, run_lines(Cursor::new(script), &mut prompt));
+    }
+
+    #[test]
+    fn run_lines_errexit() {
+        let mut prompt = Prompt::create(context::default());
+        let script = "set -e\nsh -c 'exit 3'\nexit 4\n";
+        assert_eq!(3, run_lines(Cursor::new(script), &mut prompt));
+
+        let mut prompt = Prompt::create(context::default());
+        let script = "set -e\nreadonly X=1\nexport X=2\nexit 4\n";
+        assert_eq!(1, run_lines(Cursor::new(script), &mut prompt));
+
+        // The status is checked in conditions.
+        let mut prompt = Prompt::create(context::default());
+        let script = "set -e\nreadonly X=1\nfalse || true\nexport X=2 || true\nexit 4\n";
+        assert_eq!(4, run_lines(Cursor::new(script), &mut prompt));
+    }
+
+    #[test]
+    fn report_summary() {
+        let report = Report {
+            ok: 3,
+            failed: Some((7, "make".to_string(), 2)),
+            code: 2,
+        };
+        assert_eq!(report.summary(), "3 ok, 1 failed: line 7: make (exit 2)");
+
+        let report = Report {
+            ok: 2,
+            failed: None,
+            code: 0,
+        };
+        assert_eq!(report.summary(), "2 ok");
+    }
+
+    #[test]
+    fn run_lines_with_report_stops_at_failure() {
+        let mut prompt = Prompt::create(context::default());
+        let script = "true\n# Comment.\n\ntrue\nsh -c 'exit 2'\ntrue\n";
+        let report = run_lines_with_report(Cursor::new(script), &mut prompt);
+        assert_eq!(
+            report,
+            Report {
+                ok: 2,
+                failed: Some((5, "sh -c 'exit 2'".to_string(), 2)),
+                code: 2,
+            }
+        );
+    }
+
+    #[test]
+    fn run_lines_with_report_exit() {
+        let mut prompt = Prompt::create(context::default());
+        let report = run_lines_with_report(Cursor::new("true\nexit 3\ntrue\n"), &mut prompt);
+        assert_eq!(
+            report,
+            Report {
+                ok: 1,
+                failed: None,
+                code: 3,
+            }
+        );
+    }
+
+    #[test]
+    fn run_lines_skips_comments_and_blanks() {
+        let mut prompt = Prompt::create(context::default());
+
+        // With errexit enabled, any comment or blank line treated as a command would fail and exit
+        // the shell with a non-zero code before reaching the last line.
+        let script = r#"# Script with comments.
+set -e
+
+    # Indented comment.
+	
+#exit 5
+exit 3
+"#;
+        assert_eq!(3, run_lines(Cursor::new(script), &mut prompt));
+    }
+}
@@ -2,11 +2,9 @@ use std::process;
 
 use clap::{App, Arg};
 
-const VERSION: &str = env!("CARGO_PKG_VERSION");
-
 fn main() {
     let matches = App::new("Carapace")
-        .version(VERSION)
+        .version(carapace::LONG_VERSION)
         .about("Shell written in Rust.")
         .arg(
             Arg::with_name("command")
@@ -24,6 +22,24 @@ fn main() {
                 .help("Commands read from standard input.")
                 .conflicts_with("command"),
         )
+        .arg(
+            Arg::with_name("command_file")
+                .short("f")
+                .long("command-file")
+                .value_name("file")
+                .help("Commands read from file, one per line.")
+                .takes_value(true)
+                .conflicts_with_all(&["command", "stdin"]),
+        )
+        .arg(
+            Arg::with_name("report")
+                .long("report")
+                .help(
+                    "With --stdin, stop at the first failing command and print a summary of \
+                     how many commands succeeded and which one failed.",
+                )
+                .requires("stdin"),
+        )
         .arg(
             Arg::with_name("config")
                 .long("config")
@@ -34,6 +50,26 @@ fn main() {
                 .value_name("config")
                 .takes_value(true),
         )
+        .arg(
+            Arg::with_name("dump_config").long("dump-config").help(
+                "Print the effective config as JSON, after loading the config file, and exit.",
+            ),
+        )
+        .arg(
+            Arg::with_name("norc")
+                .long("norc")
+                .alias("noprofile")
+                .help(
+                    "Start without loading config or history, using defaults instead. Nothing \
+                     is written to ~/.carapace either.",
+                )
+                .conflicts_with("config"),
+        )
+        .arg(Arg::with_name("time_startup").long("time-startup").help(
+            "Print how long each startup phase took to stderr, like loading the config \
+                     and scanning $PATH, and continue normally. Also enabled via \
+                     CARAPACE_TIMINGS=1.",
+        ))
         .arg(
             Arg::with_name("verbose")
                 .short("v")
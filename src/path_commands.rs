@@ -1,13 +1,16 @@
 use std::borrow::Borrow;
-use std::collections::BTreeSet;
+use std::collections::BTreeMap;
 use std::env;
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 use is_executable::IsExecutable;
 
+/// Search path used when `$PATH` isn't set, like after `unset PATH`.
+pub const DEFAULT_PATH: &str = "/usr/local/sbin:/usr/local/bin:/usr/sbin:/usr/bin:/sbin:/bin";
+
 type Value = String;
-type Container = BTreeSet<Value>;
+type Container = BTreeMap<Value, PathBuf>;
 
 #[derive(Default)]
 pub struct PathCommands {
@@ -22,31 +25,44 @@ impl PathCommands {
         pc
     }
 
-    /// Finds all executable programs in $PATH and adds the base file names to the internal set.
-    pub fn rehash(&mut self) {
+    /// Finds all executable programs in $PATH and maps the base file names to their full paths. The
+    /// first occurrence in $PATH order takes precedence, like when resolving programs. Returns the
+    /// number of directories searched. Falls back to `DEFAULT_PATH` if $PATH isn't set.
+    pub fn rehash(&mut self) -> usize {
+        match env::var("PATH") {
+            Ok(value) => self.rehash_from(&value),
+            Err(_) => self.rehash_from(DEFAULT_PATH),
+        }
+    }
+
+    /// Like `rehash()` but searches the colon-separated directories of `path`.
+    pub fn rehash_from(&mut self, path: &str) -> usize {
         self.clear();
 
-        if let Ok(value) = env::var("PATH") {
-            let dirs: Vec<&str> = value.split(':').filter(|x| !x.is_empty()).collect();
-            for dir in dirs {
-                let path = Path::new(dir);
-                if !path.exists() || !path.is_dir() {
-                    continue;
-                }
+        let mut dir_count = 0;
+        let dirs: Vec<&str> = path.split(':').filter(|x| !x.is_empty()).collect();
+        for dir in dirs {
+            let path = Path::new(dir);
+            if !path.exists() || !path.is_dir() {
+                continue;
+            }
 
-                // Find executable files at the top-level of the directory.
-                if let Ok(rd) = fs::read_dir(dir) {
-                    for entry in rd.flatten() {
-                        let path = entry.path();
-                        if path.is_file() && path.is_executable() {
-                            if let Some(file_name) = path.file_name().unwrap().to_str() {
-                                self.insert(file_name.to_string());
+            // Find executable files at the top-level of the directory.
+            if let Ok(rd) = fs::read_dir(dir) {
+                dir_count += 1;
+                for entry in rd.flatten() {
+                    let path = entry.path();
+                    if path.is_file() && path.is_executable() {
+                        if let Some(file_name) = path.file_name().unwrap().to_str() {
+                            if !self.contains(file_name) {
+                                self.insert(file_name.to_string(), path.clone());
                             }
                         }
                     }
                 }
             }
         }
+        dir_count
     }
 
     pub fn len(&self) -> usize {
@@ -57,8 +73,26 @@ impl PathCommands {
         self.commands.is_empty()
     }
 
-    pub fn insert(&mut self, value: Value) {
-        self.commands.insert(value);
+    pub fn insert(&mut self, value: Value, path: PathBuf) {
+        self.commands.insert(value, path);
+    }
+
+    /// Removes command from the set and returns whether it was present.
+    pub fn remove<S>(&mut self, value: &S) -> bool
+    where
+        S: ?Sized + Ord,
+        Value: Borrow<S>,
+    {
+        self.commands.remove(value).is_some()
+    }
+
+    /// Returns full path of command, if known.
+    pub fn get<S>(&self, value: &S) -> Option<&PathBuf>
+    where
+        S: ?Sized + Ord,
+        Value: Borrow<S>,
+    {
+        self.commands.get(value)
     }
 
     pub fn clear(&mut self) {
@@ -70,7 +104,7 @@ impl PathCommands {
         S: ?Sized + Ord,
         Value: Borrow<S>,
     {
-        self.commands.contains(value)
+        self.commands.contains_key(value)
     }
 }
 
@@ -84,6 +118,48 @@ impl AsRef<Container> for PathCommands {
 mod tests {
     use super::*;
 
+    use std::os::unix::fs::PermissionsExt;
+
+    use tempfile::TempDir;
+
+    /// Creates file `name` in `dir` with permission `mode`.
+    fn create_file(dir: &Path, name: &str, mode: u32) {
+        let path = dir.join(name);
+        fs::write(&path, "").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(mode)).unwrap();
+    }
+
+    #[test]
+    fn rehash_from_counts() {
+        let dir1 = TempDir::new().unwrap();
+        let dir2 = TempDir::new().unwrap();
+        create_file(dir1.path(), "prog1", 0o755);
+        create_file(dir1.path(), "data", 0o644);
+        create_file(dir2.path(), "prog1", 0o755);
+        create_file(dir2.path(), "prog2", 0o755);
+
+        let path = format!(
+            "{}:{}:/carapace/no/such/dir",
+            dir1.path().display(),
+            dir2.path().display()
+        );
+        let mut pc = PathCommands::default();
+        assert_eq!(pc.rehash_from(&path), 2);
+        assert_eq!(pc.len(), 2);
+
+        // First occurrence wins.
+        assert_eq!(pc.get("prog1"), Some(&dir1.path().join("prog1")));
+        assert_eq!(pc.get("prog2"), Some(&dir2.path().join("prog2")));
+    }
+
+    #[test]
+    fn rehash_from_clears() {
+        let mut pc = PathCommands::default();
+        pc.insert("foo".to_string(), PathBuf::from("/bin/foo"));
+        assert_eq!(pc.rehash_from(""), 0);
+        assert!(pc.is_empty());
+    }
+
     #[test]
     fn empty_default() {
         let pc = PathCommands::default();
@@ -95,7 +171,7 @@ mod tests {
         let mut pc = PathCommands::default();
         assert_eq!(0, pc.len());
 
-        pc.insert("foo".to_string());
+        pc.insert("foo".to_string(), PathBuf::from("/bin/foo"));
         assert_eq!(1, pc.len());
     }
 
@@ -104,7 +180,7 @@ mod tests {
         let mut pc = PathCommands::default();
         assert!(pc.is_empty());
 
-        pc.insert("foo".to_string());
+        pc.insert("foo".to_string(), PathBuf::from("/bin/foo"));
         assert!(!pc.is_empty());
     }
 
@@ -113,11 +189,11 @@ mod tests {
         let mut pc = PathCommands::default();
         assert_eq!(0, pc.len());
 
-        pc.insert("foo".to_string());
+        pc.insert("foo".to_string(), PathBuf::from("/bin/foo"));
         assert_eq!(1, pc.len());
 
-        pc.insert("bar".to_string());
-        pc.insert("baz".to_string());
+        pc.insert("bar".to_string(), PathBuf::from("/bin/bar"));
+        pc.insert("baz".to_string(), PathBuf::from("/bin/baz"));
         assert_eq!(3, pc.len());
     }
 
@@ -126,20 +202,37 @@ mod tests {
         let mut pc = PathCommands::default();
         assert!(pc.is_empty());
 
-        pc.insert("foo".to_string());
-        pc.insert("bar".to_string());
-        pc.insert("baz".to_string());
+        pc.insert("foo".to_string(), PathBuf::from("/bin/foo"));
+        pc.insert("bar".to_string(), PathBuf::from("/bin/bar"));
+        pc.insert("baz".to_string(), PathBuf::from("/bin/baz"));
         assert_eq!(3, pc.len());
 
         pc.clear();
         assert!(pc.is_empty());
     }
 
+    #[test]
+    fn remove() {
+        let mut pc = PathCommands::default();
+        assert!(!pc.remove("foo"));
+        pc.insert("foo".to_string(), PathBuf::from("/bin/foo"));
+        assert!(pc.remove("foo"));
+        assert!(pc.is_empty());
+    }
+
+    #[test]
+    fn get() {
+        let mut pc = PathCommands::default();
+        assert_eq!(pc.get("foo"), None);
+        pc.insert("foo".to_string(), PathBuf::from("/bin/foo"));
+        assert_eq!(pc.get("foo"), Some(&PathBuf::from("/bin/foo")));
+    }
+
     #[test]
     fn contains() {
         let mut pc = PathCommands::default();
         assert!(!pc.contains("foo"));
-        pc.insert("foo".to_string());
+        pc.insert("foo".to_string(), PathBuf::from("/bin/foo"));
         assert!(pc.contains("foo"));
     }
 }
@@ -1,28 +1,89 @@
+use crate::arith;
+use crate::command::array_assign_command::ArrayAssignCommand;
+use crate::command::background_command::BackgroundCommand;
+use crate::command::confirm_command::ConfirmCommand;
+use crate::command::pipeline_command::PipelineCommand;
+use crate::command::redirect_command::RedirectCommand;
+use crate::command::sequence_command::SequenceCommand;
 use crate::command::{self, Command};
+use crate::config::{self, Config};
 use crate::context::Context;
 use crate::editor::{self, EditorHelper};
+use crate::env::{Env, ReadonlyError};
+use crate::timings::Timings;
+use crate::tokenizer::{self, CommandArgsSplitError, Redirection};
 use crate::util;
 
 use std::collections::{HashMap, HashSet};
 use std::env;
 use std::error::Error;
 use std::fmt;
-use std::io::Write;
-use std::path::{Path, PathBuf};
+use std::io::{self, Write};
+use std::path::{Component, Path, PathBuf};
 
+use glob::Pattern;
+use regex::Regex;
 use termcolor::{BufferWriter, Color, ColorChoice, ColorSpec, WriteColor};
 
+use rustyline::config::Configurer;
 use rustyline::error::ReadlineError;
-use rustyline::Editor;
+use rustyline::{EditMode, Editor};
 
 /// Fallback textual prompt if term formatting fails.
 const SAFE_PROMPT: &str = "carapace % ";
 
+/// Default prefix of xtrace output if `$PS4` isn't set.
+const DEFAULT_PS4: &str = "+carapace> ";
+
 /// Shell root user id
 const UID_ROOT: u32 = 0;
 
+/// Number of readline errors in a row after which input is treated as ended, since the terminal
+/// is likely gone and prompting again would spin.
+const MAX_READLINE_ERRORS: usize = 10;
+
+lazy_static! {
+    /// Array assignment, like `arr=(a b c)`.
+    static ref ARRAY_ASSIGN_REGEX: Regex = Regex::new(r"^(\w+)=\((.*)\)$").unwrap();
+}
+
 pub type PromptResult = Result<Box<dyn Command>, Box<dyn Error>>;
 
+/// Statement as typed and its expanded form. Only the former is kept in history, since expanded
+/// globs can be huge, and only the latter is executed and traced.
+pub struct Expansion {
+    /// Statement verbatim as typed.
+    pub raw: String,
+
+    pub program: String,
+    pub args: Vec<String>,
+
+    /// Files that the input and output are redirected to, like via "< in.txt" or "> out.txt".
+    pub redirects: Vec<Redirection>,
+}
+
+impl Expansion {
+    /// Yields the expanded command line, like "ls -l /tmp".
+    pub fn line(&self) -> String {
+        let mut line = self.program.clone();
+        for arg in &self.args {
+            line.push(' ');
+            line.push_str(arg);
+        }
+        line
+    }
+}
+
+/// Outcome of running a command via `Prompt::run_tokens()`.
+#[derive(Debug, PartialEq)]
+pub struct Outcome {
+    /// Exit status of the command, which is also in `$?`.
+    pub status: i32,
+
+    /// Exit code if the shell is to exit, like via `exit` or a failure with `errexit` enabled.
+    pub exit: Option<i32>,
+}
+
 /// Controls showing the prompt and yielding lines from stdin.
 pub struct Prompt {
     /// General context of the shell.
@@ -36,13 +97,44 @@ pub struct Prompt {
 
     /// Environment keys to be deleted before next command due to inline env vars.
     delete_env: HashSet<String>,
+
+    /// Whether or not to save history when dropped.
+    persist_history: bool,
+
+    /// Whether `$PROMPT_COMMAND` is running, to not run it recursively.
+    in_prompt_command: bool,
+
+    /// Whether the `on_cd` hook of the config is running, to not run it again if it changes cwd.
+    in_cd_hook: bool,
+
+    /// Number of readline errors in a row, other than interrupts and EOF.
+    readline_errors: usize,
+
+    /// Whether the session was saved via `shutdown()` and no input was parsed since, such that it
+    /// isn't saved again when dropped.
+    shut_down: bool,
 }
 
 impl Prompt {
     /// Creates prompt from context and loads history and environment.
     pub fn new(context: Context) -> Prompt {
+        Prompt::new_timed(context, &mut Timings::default())
+    }
+
+    /// Creates prompt like `new()` and records the durations of loading history and environment in
+    /// `timings`.
+    pub fn new_timed(context: Context, timings: &mut Timings) -> Prompt {
+        let mut p = Prompt::create(context);
+        timings.measure("history load", || p.load_history());
+        timings.measure("env setup", || p.setup_env());
+        p
+    }
+
+    /// Create prompt from context like `new()` but neither load nor save history, like with
+    /// `--norc`.
+    pub fn without_history(context: Context) -> Prompt {
         let mut p = Prompt::create(context);
-        p.load_history();
+        p.persist_history = false;
         p.setup_env();
         p
     }
@@ -55,125 +147,401 @@ impl Prompt {
             editor,
             restore_env: HashMap::new(),
             delete_env: HashSet::new(),
+            persist_history: true,
+            in_prompt_command: false,
+            in_cd_hook: false,
+            readline_errors: 0,
+            shut_down: false,
         }
     }
 
     /// Shows prompt and reads command and arguments from stdin.
     pub fn show_parse_command(&mut self) -> PromptResult {
+        self.notify_finished_jobs();
+        self.run_prompt_command();
+
         let prompt_txt = self.prompt();
 
         let input = self.editor.readline(prompt_txt.as_ref());
+        self.parse_input(input)
+    }
+
+    /// Parses `input` read by the editor. Other errors than interrupts and EOF, like transient ones
+    /// of the terminal, are printed and yield no command such that the shell prompts again, unless
+    /// they keep occurring.
+    fn parse_input(&mut self, input: rustyline::Result<String>) -> PromptResult {
         match input {
-            Ok(line) => self.parse_command(&line),
+            Ok(line) => {
+                self.readline_errors = 0;
+                self.parse_command(&line)
+            }
             Err(ReadlineError::Interrupted) => {
+                self.readline_errors = 0;
+
                 // TODO: Unhandled for now!
                 println!("^C");
                 Err(Box::new(NoCommandError))
             }
             Err(ReadlineError::Eof) => Err(Box::new(EofError)),
             Err(err) => {
-                println!("Error: {:?}", err);
-                Err(Box::new(err))
+                self.readline_errors += 1;
+                eprintln!("Could not read input: {}", err);
+                if self.readline_errors >= MAX_READLINE_ERRORS {
+                    eprintln!("Giving up after {} errors in a row", self.readline_errors);
+                    return Err(Box::new(EofError));
+                }
+                Err(Box::new(NoCommandError))
+            }
+        }
+    }
+
+    /// Runs `f` in a position where the exit status of commands is checked, like a condition, so
+    /// that `errexit` doesn't exit the shell on failure.
+    pub fn with_status_checked<T, F>(&mut self, f: F) -> T
+    where
+        F: FnOnce(&mut Prompt) -> T,
+    {
+        self.context.borrow_mut().status_checked += 1;
+        let res = f(self);
+        self.context.borrow_mut().status_checked -= 1;
+        res
+    }
+
+    /// Prints notifications of background jobs that finished since last time, and removes them
+    /// from the jobs table. Like in Bash, $? is not affected by background jobs.
+    fn notify_finished_jobs(&mut self) {
+        for line in self.context.borrow_mut().jobs.reap() {
+            println!("{}", line);
+        }
+    }
+
+    /// Runs the command in `$PROMPT_COMMAND`, if set, like before showing the prompt. It isn't
+    /// added to history and `$?` is kept such that the prompt still sees the status of the last
+    /// command entered.
+    fn run_prompt_command(&mut self) {
+        if self.in_prompt_command {
+            return;
+        }
+        let (command, status) = {
+            let ctx = self.context.borrow();
+            match ctx.env.get("PROMPT_COMMAND") {
+                Some(command) if !command.trim().is_empty() => {
+                    (command.clone(), ctx.env.get("?").cloned())
+                }
+                _ => return,
             }
+        };
+
+        self.in_prompt_command = true;
+        let cmd = self.parse_without_history(&command);
+        command::execute(cmd, self);
+        self.in_prompt_command = false;
+
+        let mut ctx = self.context.borrow_mut();
+        match status {
+            Some(status) => ctx.env.insert("?".to_string(), status),
+            None => ctx.env.remove("?"),
         }
     }
 
-    /// Parses command from input.
+    /// Runs the `on_cd` command of the config, if set, like after the cwd changed. It isn't added to
+    /// history, and directory changes made by it don't run it again.
+    fn run_cd_hook(&mut self) {
+        if self.in_cd_hook {
+            return;
+        }
+        let command = self.context.borrow().config.on_cd.clone();
+        if command.trim().is_empty() {
+            return;
+        }
+
+        self.in_cd_hook = true;
+        let cmd = self.parse_without_history(&command);
+        command::execute(cmd, self);
+        self.in_cd_hook = false;
+    }
+
+    /// Parses command from input, which can be several statements separated by ';', newlines,
+    /// "&&", or "||". The input is added to history unless ignored via `$HISTIGNORE`.
     pub fn parse_command(&mut self, input: &str) -> PromptResult {
+        self.shut_down = false;
+        if !self.ignored_in_history(input) {
+            self.editor.add_history_entry(input);
+        }
+        self.parse_without_history(input)
+    }
+
+    /// Whether `input` isn't added to history, which is when the trimmed line matches any of the
+    /// colon-separated glob patterns of `$HISTIGNORE`, like "ls:cd*:exit".
+    fn ignored_in_history(&self, input: &str) -> bool {
+        let ctx = self.context.borrow();
+        let patterns = match ctx.env.get("HISTIGNORE") {
+            Some(patterns) => patterns,
+            None => return false,
+        };
+        let line = input.trim();
+        patterns
+            .split(':')
+            .filter(|pattern| !pattern.is_empty())
+            .filter_map(|pattern| Pattern::new(pattern).ok())
+            .any(|pattern| pattern.matches(line))
+    }
+
+    /// Runs `program` with `args` as given, for embedders that split input into words themselves.
+    /// No expansion is performed, so variables, aliases, "~", globs, and quotes are kept as is, and
+    /// nothing is added to history. Builtins and registered commands are run like when parsed.
+    pub fn run_tokens(&mut self, program: String, args: Vec<String>) -> Outcome {
+        self.restore_env();
+        let cmd = command::parse(program, args, &self.context.borrow());
+        let exit = command::execute(Ok(cmd), self);
+        let status = exit.unwrap_or_else(|| {
+            let ctx = self.context.borrow();
+            ctx.env
+                .get("?")
+                .and_then(|code| code.parse().ok())
+                .unwrap_or(0)
+        });
+        Outcome { status, exit }
+    }
+
+    /// Parses command from input like `parse_command()` without adding it to history.
+    pub(crate) fn parse_without_history(&mut self, input: &str) -> PromptResult {
         self.restore_env();
-        self.editor.add_history_entry(input);
 
-        let mut input = input.trim().to_string();
+        let input = input.trim();
         if input.is_empty() {
             return Err(Box::new(NoCommandError));
         }
 
+        // Echo input to stderr so it doesn't mix with the output of commands.
         if self.context.borrow().verbose > 0 {
-            println!("{}", input);
+            eprintln!("{}", input);
+        }
+
+        // Check quoting before any substitution so that syntax errors point into the input as it
+        // was written.
+        let mut statements = tokenizer::split_statements(input)?;
+        match statements.len() {
+            0 => Err(Box::new(NoCommandError)),
+            1 => self.parse_statement(&statements.remove(0).1),
+            _ => Ok(Box::new(SequenceCommand::new(statements))),
         }
+    }
+
+    /// Parses a single statement, without separators, into a command. Variables are substituted
+    /// and inline env vars set at this point, so each statement of a sequence is parsed right
+    /// before it runs.
+    pub fn parse_statement(&mut self, input: &str) -> PromptResult {
+        self.restore_env();
+
+        // A trailing '&', like in "sleep 10 &", runs the statement in the background.
+        let (input, background) = tokenizer::split_background(input);
+
+        // Assignments are recognized before expansion, like in Bash.
+        if let Some(caps) = ARRAY_ASSIGN_REGEX.captures(input) {
+            let values = self.substitute_arithmetic(&caps[2])?;
+            let values = self.substitute_commands(&values)?;
+            let values = self.context.borrow().env.replace_vars(&values);
+            let values = tokenizer::split(&values)?;
+            return Ok(Box::new(ArrayAssignCommand::new(
+                caps[1].to_string(),
+                values,
+            )));
+        }
+
+        // Each command of a pipeline, like "ls | grep foo", is expanded on its own.
+        let mut cmds = Vec::new();
+        let mut lines = Vec::new();
+        for input in tokenizer::split_pipeline(input)? {
+            let expansion = self.expand(&input)?;
+
+            // Show fully expanded command program and arguments on stderr with xtrace option
+            // enabled.
+            if self.context.borrow().xtrace {
+                eprintln!("{}", self.xtrace_line(&expansion.program, &expansion.args));
+            }
+
+            lines.push(expansion.line());
+            let mut ctx = self.context.borrow_mut();
+            *ctx.command_counts
+                .entry(expansion.program.clone())
+                .or_insert(0) += 1;
+            let cmd = command::parse(expansion.program, expansion.args, &ctx);
+            cmds.push(if expansion.redirects.is_empty() {
+                cmd
+            } else {
+                Box::new(RedirectCommand::new(cmd, expansion.redirects))
+            });
+        }
+
+        let mut cmd = if cmds.len() == 1 {
+            cmds.remove(0)
+        } else {
+            Box::new(PipelineCommand::new(cmds))
+        };
+        if background {
+            cmd = Box::new(BackgroundCommand::new(cmd, input.to_string()));
+        }
+        let ctx = self.context.borrow();
+        if lines.iter().any(|line| ctx.config.needs_confirmation(line)) {
+            return Ok(Box::new(ConfirmCommand::new(lines.join(" | "), cmd)));
+        }
+        Ok(cmd)
+    }
+
+    /// Replaces each `$(...)` in `input` with the output of its command, which is parsed and run
+    /// like a statement without being added to history. Within double quotes, the output is kept as
+    /// one word, and otherwise it is split into words at whitespace. The output is escaped such that
+    /// it is otherwise only globbed, like "$HOME" or quotes within it are kept as is.
+    fn substitute_commands(&mut self, input: &str) -> Result<String, CommandArgsSplitError> {
+        tokenizer::replace_substitutions(input, |command, quoted| {
+            let output = self.command_output(command);
+            if quoted {
+                let output = util::double_quote(&output);
+                output[1..output.len() - 1].to_string()
+            } else {
+                let words: Vec<String> = output
+                    .split_whitespace()
+                    .map(|word| util::escape_word(word).replace("\\*", "*"))
+                    .collect();
+                words.join(" ")
+            }
+        })
+    }
+
+    /// Yields the output of `command` like for `$(...)`.
+    fn command_output(&mut self, command: &str) -> String {
+        let cmd = self.parse_without_history(command);
+        command::capture(cmd, self)
+    }
 
-        // Replace all `$VAR` and `${VAR}` occurrences with values from environment.
-        input = self.context.borrow().env.replace_vars(&input);
+    /// Replaces each `$((...))` in `input` with the value of its expression. Arithmetic expansions,
+    /// commands, and variables within are substituted first, like in "$(($(nproc) * $n))". On
+    /// errors, like division by zero, `$?` is set to 1.
+    fn substitute_arithmetic(&mut self, input: &str) -> Result<String, Box<dyn Error>> {
+        tokenizer::replace_arithmetic(input, |expr| {
+            let expr = self.substitute_arithmetic(expr)?;
+            let expr =
+                tokenizer::replace_substitutions(&expr, |command, _| self.command_output(command))?;
+            let mut ctx = self.context.borrow_mut();
+            let expr = ctx.env.replace_vars(&expr);
+            match arith::evaluate(&expr, &ctx.env) {
+                Ok(value) => Ok(value.to_string()),
+                Err(err) => {
+                    ctx.env.insert("?".to_string(), "1".to_string());
+                    Err(Box::new(err) as Box<dyn Error>)
+                }
+            }
+        })
+    }
 
-        let mut values: Vec<String> = input.split_whitespace().map(|x| x.to_string()).collect();
+    /// Expands statement `input` into the program and arguments to execute by substituting
+    /// arithmetic, commands, variables, aliases, "~", and globs. Inline env vars, like "A=1" in
+    /// "A=1 prog", are set until the next statement is parsed.
+    pub fn expand(&mut self, input: &str) -> Result<Expansion, Box<dyn Error>> {
+        // Replace all `$((...))` with the values of their expressions, all `$(...)` with the
+        // output of their commands, and all `$VAR` and `${VAR}` occurrences with values from
+        // environment.
+        let raw = input;
+        let input = Prompt::expand_braces(input);
+        let input = self.substitute_arithmetic(&input)?;
+        let input = self.substitute_commands(&input)?;
+        let input = self.context.borrow().env.replace_vars(&input);
 
         // Detect any temporary, inline env vars, like "A=42 ./prog" etc. Also replace any use of
         // the inline env vars in the current input. And remember which env vars to remove and old
-        // values to replace them with for next command.
-        let mut abort_inline = false;
-        values = values
-            .into_iter()
-            .filter_map(|v| {
-                let mut ctx = self.context.borrow_mut();
-                if abort_inline {
-                    return Some(ctx.env.replace_vars(&v));
-                }
-                if let Some(pos) = v.find('=') {
-                    let (k, val) = (v[..pos].to_string(), v[pos + 1..].to_string());
-                    if ctx.env.contains_key(&k) {
-                        self.restore_env
-                            .insert(k.clone(), ctx.env.as_ref()[&k].clone());
-                    } else {
-                        self.delete_env.insert(k.clone());
-                    }
-                    ctx.env.insert(k, val);
-                    None
-                } else {
-                    // Stop looking for inline env vars at first command so env to be permanently
-                    // exported aren't replaced. For instance, "B=2" must still be exported in "A=1
-                    // export B=2".
-                    abort_inline = true;
-
-                    Some(ctx.env.replace_vars(&v))
-                }
-            })
-            .collect();
+        // values to replace them with for next command. Like in bash, values are tilde expanded
+        // but neither glob expanded nor split into words, so "A='x *' prog" sets "x *".
+        let mut rest = input.as_str();
+        loop {
+            let (word, after) = tokenizer::first_word(rest);
+            let (k, val) = match word.find('=') {
+                Some(pos) => (
+                    word[..pos].to_string(),
+                    Prompt::assignment_value(&word[pos + 1..])?,
+                ),
+                None => break,
+            };
+            rest = after;
+
+            let mut ctx = self.context.borrow_mut();
+            if ctx.env.is_readonly(&k) {
+                return Err(Box::new(ReadonlyError(k)));
+            }
+            if ctx.env.contains_key(&k) {
+                self.restore_env
+                    .insert(k.clone(), ctx.env.as_ref()[&k].clone());
+            } else {
+                self.delete_env.insert(k.clone());
+            }
+            ctx.env.insert(k, val);
+        }
+
+        // Redirections before the program, like "< in.txt grep foo" or "<in.txt grep foo", are
+        // moved after it.
+        let mut leading = Vec::new();
+        loop {
+            let (word, after) = tokenizer::first_word(rest);
+            let attached = tokenizer::REDIRECT_OPERATORS
+                .iter()
+                .any(|op| word.len() > op.len() && word.starts_with(op));
+            if attached {
+                leading.push(word);
+                rest = after;
+                continue;
+            }
+            let (path, after) = tokenizer::first_word(after);
+            if !tokenizer::REDIRECT_OPERATORS.contains(&word) || path.is_empty() {
+                break;
+            }
+            leading.push(word);
+            leading.push(path);
+            rest = after;
+        }
+
+        // Stop looking for inline env vars at first command so env to be permanently exported
+        // aren't replaced. For instance, "B=2" must still be exported in "A=1 export B=2".
+        let mut values: Vec<String> = {
+            let ctx = self.context.borrow();
+            tokenizer::raw_words(rest)
+                .iter()
+                .map(|v| ctx.env.replace_vars(v))
+                .collect()
+        };
 
         // Values can be empty after substitution, like if $- = "" and input is "$-".
         if values.is_empty() {
             return Err(Box::new(NoCommandError));
         }
 
-        // Check if program is an alias, and substitute in values.
-        if self
-            .context
-            .borrow()
-            .config
-            .aliases
-            .contains_key(&values[0])
+        // Check if program is an alias, and substitute in values, unless disabled.
         {
-            let alias_values: Vec<String> = self.context.borrow().config.aliases[&values[0]]
-                .split_whitespace()
-                .map(|x| x.to_string())
-                .collect();
-            let mut new_values = alias_values;
-            new_values.append(&mut values.drain(1..).collect());
-            values = new_values;
-        }
-
-        // Replace all ~ with home dir (for parts starting with it only).
-        let home_dir = dirs_next::home_dir().unwrap_or_default();
-        values = values
-            .into_iter()
-            .map(|mut x| {
-                if !x.starts_with('~') {
-                    x
-                } else {
-                    let cnt = if x.starts_with("~/") { 2 } else { 1 };
-                    let rest: String = x.drain(cnt..).collect();
-                    if let Ok(res) = home_dir.join(&rest).into_os_string().into_string() {
-                        res
-                    } else {
-                        x
-                    }
-                }
-            })
-            .collect();
+            let ctx = self.context.borrow();
+            if !ctx.noalias {
+                values = Prompt::substitute_alias(values, &ctx.config.aliases, &ctx.env);
+            }
+        }
 
-        // Replace all file globs, like "C*" -> ["Cargo.lock", "Cargo.toml"].
+        // An alias value starting with a comment leaves nothing to run, like a commented line.
+        if values[0].starts_with('#') {
+            return Err(Box::new(NoCommandError));
+        }
+
+        {
+            let ctx = self.context.borrow();
+            let leading = leading.iter().flat_map(|word| word.split_whitespace());
+            values.splice(1..1, leading.map(|word| ctx.env.replace_vars(word)));
+        }
+
+        // Replace all ~ with home dir (for parts starting with it only, or segments of path lists,
+        // like "PATH=~/bin:~/sbin").
+        values = values.iter().map(|x| util::expand_tildes(x)).collect();
+
+        // Replace all file globs, like "C*" -> ["Cargo.lock", "Cargo.toml"], unless disabled.
+        let noglob = self.context.borrow().noglob;
         let mut expanded_values = Vec::new();
         for v in &values {
-            if v.contains('*') {
+            if !noglob && v.contains('*') {
                 expanded_values.append(&mut util::expand_glob(v));
             } else {
                 expanded_values.push(v.to_string());
@@ -183,31 +551,109 @@ impl Prompt {
         let mut program = expanded_values[0].clone();
         let mut args: Vec<String> = expanded_values.drain(1..).collect();
 
-        // Split arguments by preserving quoted segments.
-        let split_args = shlex::split(&args.join(" "));
-        if split_args.is_none() {
-            // Docs: "An input string is erroneous if it ends while inside a quotation or right
-            // after an unescaped backslash."
-            return Err(Box::new(CommandArgsSplitError));
-        }
-        args = split_args.unwrap();
+        // Split arguments by preserving quoted segments, and take out any redirections. Their files
+        // are expanded like the arguments.
+        let (split_args, redirects) = tokenizer::split_redirects(&args.join(" "))?;
+        args = split_args;
 
         // If input is an existing folder, and auto_cd is enabled, then set "cd" as the
-        // program.
-        if self.context.borrow().config.auto_cd
-            && expanded_values.len() == 1
-            && Path::new(&values[0]).is_dir()
-        {
+        // program. A command of the same name is run instead, like for a folder named "test".
+        let auto_cd = {
+            let ctx = self.context.borrow();
+            ctx.config.auto_cd
+                && args.is_empty()
+                && Path::new(&program).is_dir()
+                && !ctx.is_command(&program)
+        };
+        if auto_cd {
             args = vec![program];
             program = "cd".to_string();
         }
 
-        // Show fully expanded command program and arguments with xtrace option enabled.
-        if self.context.borrow().xtrace {
-            println!("+carapace> {} {}", program, args.join(" "));
+        Ok(Expansion {
+            raw: raw.to_string(),
+            program,
+            args,
+            redirects,
+        })
+    }
+
+    /// Yields the value of an inline env var assignment from the `raw` text after '=', like
+    /// "~/'my dir'" for "A=~/'my dir'", with quotes removed and a "~" expanded at the start of
+    /// each colon-separated segment, like in "PATH=~/bin:~/sbin".
+    fn assignment_value(raw: &str) -> Result<String, CommandArgsSplitError> {
+        // A value starting with '#' is not a comment.
+        if raw.starts_with('#') {
+            return Ok(raw.to_string());
+        }
+        let mut values = Vec::new();
+        for segment in util::split_path_list(raw) {
+            let value = tokenizer::split(segment)?
+                .into_iter()
+                .next()
+                .unwrap_or_default();
+
+            // Only expand the unquoted part before the first '/', such that "~'x'" isn't expanded.
+            let end = segment.find('/').unwrap_or(segment.len());
+            if segment.starts_with('~') && !segment[..end].contains(['\'', '"', '\\']) {
+                values.push(util::expand_tilde(&value));
+            } else {
+                values.push(value);
+            }
+        }
+        Ok(values.join(":"))
+    }
+
+    /// Substitutes the program of `values`, the first word, if it is an alias. Arguments are never
+    /// substituted, so "echo ll" keeps "ll" even if it is an alias. Variables in the alias value
+    /// are replaced from `env` at this point, when the alias is used.
+    ///
+    /// The alias value is otherwise used verbatim. A '#' in it follows the rules of the command
+    /// line it is substituted into, so a word starting with '#' comments out the rest of that line,
+    /// including arguments given to the alias.
+    fn substitute_alias(
+        mut values: Vec<String>,
+        aliases: &HashMap<String, String>,
+        env: &Env,
+    ) -> Vec<String> {
+        match values.first().and_then(|program| aliases.get(program)) {
+            Some(alias) => {
+                let mut new_values: Vec<String> = env
+                    .replace_vars(&Prompt::expand_braces(alias))
+                    .split_whitespace()
+                    .map(|x| x.to_string())
+                    .collect();
+                new_values.extend(values.drain(1..));
+                new_values
+            }
+            None => values,
+        }
+    }
+
+    /// Expands braces of the words of `input` as written, except leading inline env vars.
+    fn expand_braces(input: &str) -> String {
+        if !input.contains('{') {
+            return input.to_string();
+        }
+        let mut assignments = true;
+        let mut words = Vec::new();
+        for word in tokenizer::raw_words(input) {
+            assignments = assignments && word.contains('=');
+            if assignments {
+                words.push(word);
+            } else {
+                words.extend(util::expand_braces(&word));
+            }
         }
+        words.join(" ")
+    }
 
-        Ok(command::parse(program, args))
+    /// Yields xtrace output of `program` and `args` prefixed by `$PS4`, or "+carapace> " if not
+    /// set.
+    fn xtrace_line(&self, program: &str, args: &[String]) -> String {
+        let ctx = self.context.borrow();
+        let prefix = ctx.env.get("PS4").map_or(DEFAULT_PS4, |v| v.as_str());
+        format!("{}{} {}", prefix, program, args.join(" "))
     }
 
     /// Check if any env vars must be replaced/deleted due to inline env vars from last command.
@@ -225,13 +671,24 @@ impl Prompt {
         self.restore_env.clear();
     }
 
-    /// Yields the textual prompt with term colors.
+    /// Yields the textual prompt with term colors, unless `$NO_COLOR` is set to anything, like
+    /// described at https://no-color.org.
     fn prompt(&self) -> String {
+        self.prompt_in(env::current_dir().ok().as_deref())
+    }
+
+    /// Yields the textual prompt like `prompt()` with `cwd` as the current directory, if known.
+    fn prompt_in(&self, cwd: Option<&Path>) -> String {
         // In case of failure, use safe prompt. It is a closure so it is only allocated if it is
         // needed.
         let safe_prompt = || SAFE_PROMPT.to_string();
 
-        let bufwtr = BufferWriter::stderr(ColorChoice::Always);
+        let choice = if self.context.borrow().env.contains_key("NO_COLOR") {
+            ColorChoice::Never
+        } else {
+            ColorChoice::Always
+        };
+        let bufwtr = BufferWriter::stderr(choice);
         let mut buffer = bufwtr.buffer();
         let mut color = ColorSpec::new();
         let mut bright_color = ColorSpec::new();
@@ -245,14 +702,15 @@ impl Prompt {
             println!("Failed to write to term!");
         }
 
-        if let Ok(cwd) = env::current_dir() {
+        if let Some(cwd) = cwd {
             if buffer
                 .set_color(bright_color.set_fg(Some(Color::Blue)))
                 .is_err()
             {
                 return safe_prompt();
             }
-            if write!(&mut buffer, " {}", cwd.display()).is_err() {
+            let cwd = util::printable(&util::abbreviate_home(cwd, &util::home_dir()));
+            if write!(&mut buffer, " {}", cwd).is_err() {
                 println!("Failed to write to term!");
             }
         }
@@ -280,27 +738,74 @@ impl Prompt {
     }
 
     fn load_history(&mut self) {
-        let path = dirs_next::home_dir()
-            .unwrap()
-            .join(".carapace")
-            .join("history");
+        let path = util::carapace_dir().join("history");
         if self.editor.load_history(&path).is_err() {
             println!("No history loaded.");
         }
     }
 
     pub fn save_history(&mut self) {
-        let path = dirs_next::home_dir()
-            .unwrap()
-            .join(".carapace")
-            .join("history");
+        let path = util::carapace_dir().join("history");
         if let Err(err) = self.editor.save_history(&path) {
             println!("Could not save history to: {}\n{}", path.display(), err);
         }
     }
 
-    /// Load environment entries from config into session environment, and set $? = 0 and $- = "" or
-    /// "v" if verbose > 0.
+    /// Saves the session and flushes output before the shell exits with `code`, like via `exit` or
+    /// `quit`, such that it happens the same way regardless of how the shell is left. Yields
+    /// `Err(code)` to be passed on as the result of the command.
+    pub fn shutdown(&mut self, code: i32) -> Result<bool, i32> {
+        self.save_session();
+        self.shut_down = true;
+        Err(code)
+    }
+
+    /// Whether `shutdown()` was done and no input was parsed since.
+    pub fn is_shut_down(&self) -> bool {
+        self.shut_down
+    }
+
+    /// Saves history, unless disabled, and the edit mode, and flushes stdout and stderr.
+    fn save_session(&mut self) {
+        if self.persist_history {
+            self.save_history();
+        }
+        self.save_edit_mode();
+        let _ = io::stdout().flush();
+        let _ = io::stderr().flush();
+    }
+
+    /// Sets edit `mode` of the editor for the rest of the session, and `$EDIT_MODE` to its name.
+    pub fn set_edit_mode(&mut self, mode: EditMode) {
+        self.editor.set_edit_mode(mode);
+        let mut ctx = self.context.borrow_mut();
+        ctx.config.edit_mode = mode;
+        ctx.env.insert(
+            "EDIT_MODE".to_string(),
+            config::edit_mode_name(mode).to_string(),
+        );
+        ctx.set_option_origin("edit_mode");
+    }
+
+    /// Writes edit mode to the config file if it was changed in this session and
+    /// `persist_edit_mode` is enabled. The file is read again so that only the edit mode changes.
+    fn save_edit_mode(&self) {
+        let ctx = self.context.borrow();
+        if !ctx.config.persist_edit_mode || !ctx.option_origins.contains_key("edit_mode") {
+            return;
+        }
+        if let Some(path) = &ctx.config_path {
+            let mut config = Config::default();
+            config.load(path.to_str());
+            if config.edit_mode != ctx.config.edit_mode {
+                config.edit_mode = ctx.config.edit_mode;
+                config.save(path);
+            }
+        }
+    }
+
+    /// Load environment entries from config into session environment, and set $? = 0, $- = "" or
+    /// "v" if verbose > 0, and $EDIT_MODE.
     fn setup_env(&mut self) {
         let ctx = &mut self.context.borrow_mut();
 
@@ -313,6 +818,12 @@ impl Prompt {
         }
 
         ctx.env.insert("?".to_string(), "0".to_string());
+        ctx.env.insert(
+            "CARAPACE_VERSION".to_string(),
+            crate::LONG_VERSION.to_string(),
+        );
+        let edit_mode = config::edit_mode_name(ctx.config.edit_mode).to_string();
+        ctx.env.insert("EDIT_MODE".to_string(), edit_mode);
 
         let verbose = ctx.verbose;
         ctx.env.insert(
@@ -325,39 +836,108 @@ impl Prompt {
         );
     }
 
-    /// Sets current working directory.
+    /// Sets current working directory. With `physical`, symbolic links are resolved, like `cd -P`,
+    /// otherwise the logical path is kept, like `cd -L`. `$PWD` and `$OLDPWD` are updated, and the
+    /// `on_cd` command of the config runs.
+    ///
+    /// If the cwd no longer exists, like when removed by another process, `$PWD` is used instead
+    /// such that absolute paths, and relative ones that still exist from there, can be changed to.
     ///
     /// Returns the old cwd on success and None otherwise.
-    pub fn set_cwd(&mut self, dir: &Path) -> Option<String> {
-        let fallback = "/";
-        let oldpwd = env::current_dir()
-            .unwrap_or_else(|_| Path::new(fallback).to_path_buf())
-            .to_str()
-            .unwrap_or(fallback)
-            .to_string();
+    pub fn set_cwd(&mut self, dir: &Path, physical: bool) -> Option<String> {
+        let pwd = self.context.borrow().env.get("PWD").cloned();
+        let cwd = env::current_dir();
+        let oldpwd = match Prompt::logical_cwd(cwd.as_deref(), pwd.as_deref()) {
+            Some(cwd) => cwd,
+            None => {
+                if let Err(err) = &cwd {
+                    eprintln!("Current directory is missing: {}", err);
+                }
+                match pwd {
+                    Some(pwd) if Path::new(&pwd).is_absolute() => pwd,
+                    _ if dir.is_absolute() => "/".to_string(),
+                    _ => {
+                        println!("Could not change to {}: unknown cwd", dir.display());
+                        return None;
+                    }
+                }
+            }
+        };
+        let target = match Prompt::resolve_cwd(Path::new(&oldpwd), dir, physical) {
+            Ok(target) => target,
+            Err(err) => {
+                println!("Could not change to {}: {}", dir.display(), err);
+                return None;
+            }
+        };
 
         // Don't change cwd if input is the same!
-        let canon_path = dir.canonicalize().unwrap_or_default();
-        if canon_path == PathBuf::from(&oldpwd) {
+        if target == Path::new(&oldpwd) {
             return None;
         }
 
-        if let Err(err) = env::set_current_dir(dir) {
+        if let Err(err) = env::set_current_dir(&target) {
             println!("Could not change to {}: {}", dir.display(), err);
             None
         } else {
-            self.context
-                .borrow_mut()
-                .env
-                .insert("OLDPWD".to_string(), oldpwd.clone());
+            {
+                let mut ctx = self.context.borrow_mut();
+                ctx.env.insert("OLDPWD".to_string(), oldpwd.clone());
+                ctx.env
+                    .insert("PWD".to_string(), target.to_string_lossy().into_owned());
+            }
+            self.run_cd_hook();
             Some(oldpwd)
         }
     }
+
+    /// Yields the logical cwd of the actual `cwd`, which is `pwd` if it refers to the same
+    /// directory, since it keeps any symbolic links used to get there. Yields None if the actual cwd
+    /// couldn't be determined.
+    fn logical_cwd(cwd: Result<&Path, &io::Error>, pwd: Option<&str>) -> Option<String> {
+        let cwd = cwd.ok()?;
+        if let Some(pwd) = pwd {
+            let pwd_path = Path::new(pwd);
+            if pwd_path.is_absolute() && pwd_path.canonicalize().ok() == cwd.canonicalize().ok() {
+                return Some(pwd.to_string());
+            }
+        }
+        Some(cwd.to_string_lossy().into_owned())
+    }
+
+    /// Resolves `dir` relative to the logical cwd `current`. With `physical`, all symbolic links
+    /// are resolved. Otherwise "." and ".." are removed lexically, keeping symbolic links, unless
+    /// the result doesn't exist, like when ".." leaves a symbolic link, where the physical path is
+    /// used instead.
+    pub fn resolve_cwd(current: &Path, dir: &Path, physical: bool) -> io::Result<PathBuf> {
+        let joined = current.join(dir);
+        if physical {
+            return joined.canonicalize();
+        }
+
+        let mut logical = PathBuf::new();
+        for component in joined.components() {
+            match component {
+                Component::CurDir => {}
+                Component::ParentDir => {
+                    logical.pop();
+                }
+                _ => logical.push(component),
+            }
+        }
+        if logical.is_dir() {
+            Ok(logical)
+        } else {
+            joined.canonicalize()
+        }
+    }
 }
 
 impl Drop for Prompt {
     fn drop(&mut self) {
-        self.save_history();
+        if !self.shut_down {
+            self.save_session();
+        }
     }
 }
 
@@ -384,29 +964,21 @@ impl fmt::Display for NoCommandError {
     }
 }
 
-#[derive(Debug)]
-struct CommandArgsSplitError;
-
-impl Error for CommandArgsSplitError {}
-
-impl fmt::Display for CommandArgsSplitError {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(
-            f,
-            r#"Syntax error: failed to parse arguments
-Possible unmatching quote or unescaped sequence"#
-        )
-    }
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    use crate::arith::{ArithError, ArithErrorKind};
     use crate::command::cd_command::CdCommand;
     use crate::command::general_command::GeneralCommand;
     use crate::config::Config;
     use crate::context;
+    use crate::tokenizer::SplitErrorKind;
+
+    use std::fs;
+    use std::time::{Duration, Instant};
+
+    use tempfile::TempDir;
 
     macro_rules! create_test_prompt_with_config {
         ($p:ident, $cfg:expr) => {
@@ -416,10 +988,49 @@ mod tests {
         };
     }
 
+    #[test]
+    fn parse_input_survives_readline_error() {
+        let mut prompt = Prompt::create(context::default());
+        let error = || Err(ReadlineError::Io(io::Error::other("resized")));
+
+        let cmd = prompt.parse_input(error());
+        assert!(cmd.as_ref().err().unwrap().is::<NoCommandError>());
+        assert_eq!(command::execute(cmd, &mut prompt), None);
+
+        // Input is read as usual afterwards.
+        let cmd = prompt.parse_input(Ok("true".to_string()));
+        assert_eq!(command::execute(cmd, &mut prompt), None);
+        assert_eq!(prompt.context.borrow().env["?"], "0");
+    }
+
+    #[test]
+    fn parse_input_gives_up_after_readline_errors() {
+        let mut prompt = Prompt::create(context::default());
+        let error = || Err(ReadlineError::Io(io::Error::other("gone")));
+        for _ in 1..MAX_READLINE_ERRORS {
+            assert!(prompt
+                .parse_input(error())
+                .err()
+                .unwrap()
+                .is::<NoCommandError>());
+        }
+
+        // A successful read starts counting again.
+        assert!(prompt.parse_input(Ok("true".to_string())).is_ok());
+        for _ in 1..MAX_READLINE_ERRORS {
+            assert!(prompt
+                .parse_input(error())
+                .err()
+                .unwrap()
+                .is::<NoCommandError>());
+        }
+        assert!(prompt.parse_input(error()).err().unwrap().is::<EofError>());
+    }
+
     #[test]
     fn parse_command_empty() {
         let mut prompt = Prompt::create(context::default());
-        let cmd = prompt.parse_command(&String::new());
+        let cmd = prompt.parse_command("");
         assert!(cmd.is_err());
         assert!(cmd.err().unwrap().is::<NoCommandError>());
     }
@@ -442,12 +1053,43 @@ mod tests {
     fn parse_command_auto_cd() {
         // auto-cd is enabled per default in Config.
         let mut prompt = Prompt::create(context::default());
-        let cmd = prompt.parse_command(".");
+        let cmd = prompt.parse_command("..");
         assert!(cmd.is_ok());
 
         let cmd = cmd.unwrap();
         let cd_cmd = cmd.as_any().downcast_ref::<CdCommand>().unwrap();
-        assert_eq!(cd_cmd.path, ".");
+        assert_eq!(cd_cmd.path, "..");
+    }
+
+    #[test]
+    fn parse_command_auto_cd_not_for_commands() {
+        // The "src" folder of the crate is changed to unless there is a command of that name.
+        let mut prompt = Prompt::create(context::default());
+        let is_cd = |prompt: &mut Prompt, input: &str| {
+            let cmd = prompt.parse_command(input).unwrap();
+            cmd.as_any().downcast_ref::<CdCommand>().is_some()
+        };
+        assert!(is_cd(&mut prompt, "src"));
+
+        prompt
+            .context
+            .borrow_mut()
+            .commands
+            .insert("src".to_string(), PathBuf::from("/usr/bin/src"));
+        let cmd = prompt.parse_command("src").unwrap();
+        let cmd = cmd.as_any().downcast_ref::<GeneralCommand>().unwrap();
+        assert_eq!(cmd.program, "src");
+        assert!(is_cd(&mut prompt, "./src"));
+
+        // Builtins, like "." for `source`, are commands too.
+        assert!(!is_cd(&mut prompt, "."));
+    }
+
+    #[test]
+    fn parse_command_auto_cd_not_with_args() {
+        let mut prompt = Prompt::create(context::default());
+        let cmd = prompt.parse_command(". file").unwrap();
+        assert!(cmd.as_any().downcast_ref::<CdCommand>().is_none());
     }
 
     #[test]
@@ -458,15 +1100,65 @@ mod tests {
             .borrow_mut()
             .env
             .insert("HELLO".to_string(), "WORLD".to_string());
-        let cmd = prompt.parse_command("echo $HELLO");
+        let cmd = prompt.parse_command("prog $HELLO");
         assert!(cmd.is_ok());
 
         let cmd = cmd.unwrap();
         let general_cmd = cmd.as_any().downcast_ref::<GeneralCommand>().unwrap();
-        assert_eq!(general_cmd.program, "echo".to_string());
+        assert_eq!(general_cmd.program, "prog".to_string());
         assert_eq!(general_cmd.args, vec!["WORLD".to_string()]);
     }
 
+    #[test]
+    fn parse_command_long_line_linear() {
+        let mut prompt = Prompt::create(context::default());
+        for i in 0..100 {
+            prompt
+                .context
+                .borrow_mut()
+                .env
+                .insert(format!("VAR{}", i), i.to_string());
+        }
+
+        // Expanding a line with twice as many variables must take about twice as long, not four
+        // times like when expanding each variable via a pass over the whole line, or over all
+        // words. The fastest of a few runs is compared, with a little slack, to not fail on
+        // hiccups of busy machines.
+        let line = |len: usize| {
+            let mut line = "echo".to_string();
+            let mut i = 0;
+            while line.len() < len {
+                line.push_str(&format!(" $VAR{} ${{VAR{}}}", i % 100, (i + 1) % 100));
+                i += 1;
+            }
+            (line, i)
+        };
+        let mut time = |line: &str| {
+            (0..3)
+                .map(|_| {
+                    let start = Instant::now();
+                    prompt.expand(line).unwrap();
+                    start.elapsed()
+                })
+                .min()
+                .unwrap()
+        };
+        let (short, _) = line(50_000);
+        let (long, i) = line(100_000);
+        let (short_time, long_time) = (time(&short), time(&long));
+        assert!(
+            long_time < short_time * 3 + Duration::from_millis(20),
+            "{:?} vs {:?}",
+            long_time,
+            short_time
+        );
+
+        let expansion = prompt.expand(&long).unwrap();
+        assert_eq!(expansion.args.len(), i * 2);
+        assert_eq!(expansion.args[0], "0");
+        assert_eq!(expansion.args[1], "1");
+    }
+
     #[test]
     fn parse_command_alias_substituted() {
         let mut config = Config::default();
@@ -482,16 +1174,29 @@ mod tests {
         assert_eq!(general_cmd.args, vec!["-l".to_string(), "-F".to_string()]);
     }
 
+    #[test]
+    fn parse_command_alias_not_substituted_when_disabled() {
+        let mut config = Config::default();
+        config.aliases.insert("l".to_string(), "ls -l".to_string());
+        create_test_prompt_with_config!(prompt, config);
+        prompt.context.borrow_mut().noalias = true;
+
+        let cmd = prompt.parse_command("l -F").unwrap();
+        let general_cmd = cmd.as_any().downcast_ref::<GeneralCommand>().unwrap();
+        assert_eq!(general_cmd.program, "l");
+        assert_eq!(general_cmd.args, vec!["-F"]);
+    }
+
     #[test]
     fn parse_command_inline_env_vars() {
         let mut prompt = Prompt::create(context::default());
 
-        let cmd = prompt.parse_command("A=1 echo test");
+        let cmd = prompt.parse_command("A=1 prog test");
         assert!(cmd.is_ok());
 
         let cmd = cmd.unwrap();
         let general_cmd = cmd.as_any().downcast_ref::<GeneralCommand>().unwrap();
-        assert_eq!(general_cmd.program, "echo".to_string());
+        assert_eq!(general_cmd.program, "prog".to_string());
         assert_eq!(general_cmd.args, vec!["test".to_string()]);
 
         assert!(prompt.delete_env.contains("A"));
@@ -501,12 +1206,12 @@ mod tests {
     fn parse_command_inline_env_vars_replaced_for_invocation() {
         let mut prompt = Prompt::create(context::default());
 
-        let cmd = prompt.parse_command("A=1 echo $A");
+        let cmd = prompt.parse_command("A=1 prog $A");
         assert!(cmd.is_ok());
 
         let cmd = cmd.unwrap();
         let general_cmd = cmd.as_any().downcast_ref::<GeneralCommand>().unwrap();
-        assert_eq!(general_cmd.program, "echo".to_string());
+        assert_eq!(general_cmd.program, "prog".to_string());
         assert_eq!(general_cmd.args, vec!["1".to_string()]);
 
         assert!(prompt.delete_env.contains("A"));
@@ -521,12 +1226,12 @@ mod tests {
             .env
             .insert("A".to_string(), "42".to_string());
 
-        let cmd = prompt.parse_command("A=1 echo $A");
+        let cmd = prompt.parse_command("A=1 prog $A");
         assert!(cmd.is_ok());
 
         let cmd = cmd.unwrap();
         let general_cmd = cmd.as_any().downcast_ref::<GeneralCommand>().unwrap();
-        assert_eq!(general_cmd.program, "echo".to_string());
+        assert_eq!(general_cmd.program, "prog".to_string());
 
         // $A is replaced with "42" before the inline replacement since it already exists in the
         // environment.
@@ -547,12 +1252,12 @@ mod tests {
             .env
             .insert("A".to_string(), "42".to_string());
 
-        let cmd = prompt.parse_command("A=1 echo $A");
+        let cmd = prompt.parse_command("A=1 prog $A");
         assert!(cmd.is_ok());
 
         let cmd = cmd.unwrap();
         let general_cmd = cmd.as_any().downcast_ref::<GeneralCommand>().unwrap();
-        assert_eq!(general_cmd.program, "echo".to_string());
+        assert_eq!(general_cmd.program, "prog".to_string());
 
         // $A is replaced with "42" before the inline replacement since it already exists in the
         // environment.
@@ -573,15 +1278,119 @@ mod tests {
     }
 
     #[test]
-    fn parse_command_double_quoted_args() {
+    fn parse_command_inline_env_vars_readonly() {
         let mut prompt = Prompt::create(context::default());
+        {
+            let env = &mut prompt.context.borrow_mut().env;
+            env.insert("A".to_string(), "42".to_string());
+            env.set_readonly("A".to_string());
+        }
 
-        let cmd = prompt.parse_command("echo before \"hello 'there' world\" after");
-        assert!(cmd.is_ok());
-
-        let cmd = cmd.unwrap();
+        let cmd = prompt.parse_command("A=1 echo test");
+        assert!(cmd.is_err());
+        assert!(cmd.err().unwrap().is::<ReadonlyError>());
+        assert_eq!(
+            prompt.context.borrow().env.get("A"),
+            Some(&"42".to_string())
+        );
+    }
+
+    #[test]
+    fn parse_command_inline_env_vars_tilde_expanded() {
+        let mut prompt = Prompt::create(context::default());
+        let cmd = prompt.parse_command("DIR=~/x echo test");
+        assert!(cmd.is_ok());
+
+        let home = util::home_dir();
+        assert_eq!(
+            prompt.context.borrow().env.get("DIR"),
+            Some(&home.join("x").display().to_string())
+        );
+    }
+
+    #[test]
+    fn parse_command_tilde_expanded_in_path_lists() {
+        let mut prompt = Prompt::create(context::default());
+        let cmd = prompt
+            .parse_command("X=~/a:~/b prog X=~/a:~/b ~/a:~/b a~b c:~/d")
+            .unwrap();
+
+        let home = util::home_dir();
+        let list = format!("{}:{}", home.join("a").display(), home.join("b").display());
+        assert_eq!(prompt.context.borrow().env.get("X"), Some(&list));
+
+        let cmd = cmd.as_any().downcast_ref::<GeneralCommand>().unwrap();
+        assert_eq!(
+            cmd.args,
+            vec![
+                format!("X={}", list),
+                list,
+                "a~b".to_string(),
+                "c:~/d".to_string()
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_command_inline_env_vars_not_glob_expanded() {
+        let mut prompt = Prompt::create(context::default());
+        let cmd = prompt.parse_command("FILES='*' prog test");
+        assert!(cmd.is_ok());
+
+        let cmd = cmd.unwrap();
         let general_cmd = cmd.as_any().downcast_ref::<GeneralCommand>().unwrap();
-        assert_eq!(general_cmd.program, "echo".to_string());
+        assert_eq!(general_cmd.program, "prog".to_string());
+        assert_eq!(general_cmd.args, vec!["test".to_string()]);
+        assert_eq!(
+            prompt.context.borrow().env.get("FILES"),
+            Some(&"*".to_string())
+        );
+    }
+
+    #[test]
+    fn parse_command_inline_env_vars_not_word_split() {
+        let mut prompt = Prompt::create(context::default());
+        let cmd = prompt.parse_command("A=\"x  *\" B=y\\ z echo test");
+        assert!(cmd.is_ok());
+
+        let ctx = prompt.context.borrow();
+        assert_eq!(ctx.env.get("A"), Some(&"x  *".to_string()));
+        assert_eq!(ctx.env.get("B"), Some(&"y z".to_string()));
+    }
+
+    #[test]
+    fn assignment_value() {
+        let home = util::home_dir();
+        assert_eq!(Prompt::assignment_value("").unwrap(), "");
+        assert_eq!(Prompt::assignment_value("'a b'").unwrap(), "a b");
+        assert_eq!(Prompt::assignment_value("#x").unwrap(), "#x");
+        assert_eq!(
+            Prompt::assignment_value("~/'my dir'").unwrap(),
+            home.join("my dir").display().to_string()
+        );
+        assert_eq!(Prompt::assignment_value("'~'/x").unwrap(), "~/x");
+        assert_eq!(
+            Prompt::assignment_value("~/a:~/b").unwrap(),
+            format!("{}:{}", home.join("a").display(), home.join("b").display())
+        );
+        assert_eq!(
+            Prompt::assignment_value("a~b:'~/c':\\~:'x:~y'").unwrap(),
+            "a~b:~/c:~:x:~y"
+        );
+        assert_eq!(Prompt::assignment_value("\\~").unwrap(), "~");
+        assert!(Prompt::assignment_value("'x").is_err());
+    }
+
+    #[test]
+    fn parse_command_double_quoted_args() {
+        let mut prompt = Prompt::create(context::default());
+
+        let cmd = prompt.parse_command("prog before \"hello 'there' world\" after");
+        assert!(cmd.is_ok());
+
+        let cmd = cmd.unwrap();
+        let general_cmd = cmd.as_any().downcast_ref::<GeneralCommand>().unwrap();
+        assert_eq!(general_cmd.program, "prog".to_string());
         assert_eq!(
             general_cmd.args,
             vec![
@@ -596,12 +1405,12 @@ mod tests {
     fn parse_command_double_quoted_args_with_end_symbol() {
         let mut prompt = Prompt::create(context::default());
 
-        let cmd = prompt.parse_command("echo \"hello \"x");
+        let cmd = prompt.parse_command("prog \"hello \"x");
         assert!(cmd.is_ok());
 
         let cmd = cmd.unwrap();
         let general_cmd = cmd.as_any().downcast_ref::<GeneralCommand>().unwrap();
-        assert_eq!(general_cmd.program, "echo".to_string());
+        assert_eq!(general_cmd.program, "prog".to_string());
         assert_eq!(general_cmd.args, vec!["hello x".to_string(),]);
     }
 
@@ -609,12 +1418,12 @@ mod tests {
     fn parse_command_double_quoted_args_with_start_symbol() {
         let mut prompt = Prompt::create(context::default());
 
-        let cmd = prompt.parse_command("echo x\"hello \"");
+        let cmd = prompt.parse_command("prog x\"hello \"");
         assert!(cmd.is_ok());
 
         let cmd = cmd.unwrap();
         let general_cmd = cmd.as_any().downcast_ref::<GeneralCommand>().unwrap();
-        assert_eq!(general_cmd.program, "echo".to_string());
+        assert_eq!(general_cmd.program, "prog".to_string());
         assert_eq!(general_cmd.args, vec!["xhello ".to_string(),]);
     }
 
@@ -622,12 +1431,12 @@ mod tests {
     fn parse_command_single_quoted_args() {
         let mut prompt = Prompt::create(context::default());
 
-        let cmd = prompt.parse_command("echo before 'hello \"there\" world' after");
+        let cmd = prompt.parse_command("prog before 'hello \"there\" world' after");
         assert!(cmd.is_ok());
 
         let cmd = cmd.unwrap();
         let general_cmd = cmd.as_any().downcast_ref::<GeneralCommand>().unwrap();
-        assert_eq!(general_cmd.program, "echo".to_string());
+        assert_eq!(general_cmd.program, "prog".to_string());
         assert_eq!(
             general_cmd.args,
             vec![
@@ -644,7 +1453,10 @@ mod tests {
 
         let cmd = prompt.parse_command("echo \"hello");
         assert!(cmd.is_err());
-        assert!(cmd.err().unwrap().is::<CommandArgsSplitError>());
+        let err = cmd.err().unwrap();
+        let err = err.downcast_ref::<CommandArgsSplitError>().unwrap();
+        assert_eq!(err.kind, SplitErrorKind::UnterminatedDoubleQuote);
+        assert_eq!(err.pos, 5);
     }
 
     #[test]
@@ -653,7 +1465,10 @@ mod tests {
 
         let cmd = prompt.parse_command("echo 'before' \"hello 'after'");
         assert!(cmd.is_err());
-        assert!(cmd.err().unwrap().is::<CommandArgsSplitError>());
+        let err = cmd.err().unwrap();
+        let err = err.downcast_ref::<CommandArgsSplitError>().unwrap();
+        assert_eq!(err.kind, SplitErrorKind::UnterminatedDoubleQuote);
+        assert_eq!(err.pos, 14);
     }
 
     #[test]
@@ -662,7 +1477,10 @@ mod tests {
 
         let cmd = prompt.parse_command("echo 'hello");
         assert!(cmd.is_err());
-        assert!(cmd.err().unwrap().is::<CommandArgsSplitError>());
+        let err = cmd.err().unwrap();
+        let err = err.downcast_ref::<CommandArgsSplitError>().unwrap();
+        assert_eq!(err.kind, SplitErrorKind::UnterminatedSingleQuote);
+        assert_eq!(err.pos, 5);
     }
 
     #[test]
@@ -671,9 +1489,629 @@ mod tests {
 
         let cmd = prompt.parse_command("echo \"before\" 'hello \"after\"");
         assert!(cmd.is_err());
+        let err = cmd.err().unwrap();
+        let err = err.downcast_ref::<CommandArgsSplitError>().unwrap();
+        assert_eq!(err.kind, SplitErrorKind::UnterminatedSingleQuote);
+        assert_eq!(err.pos, 14);
+    }
+
+    #[test]
+    fn prompt_no_color() {
+        let prompt = Prompt::create(context::default());
+        prompt.context.borrow_mut().env.remove("NO_COLOR");
+        assert!(prompt.prompt().contains('\x1b'));
+
+        prompt
+            .context
+            .borrow_mut()
+            .env
+            .insert("NO_COLOR".to_string(), String::new());
+        let text = prompt.prompt();
+        assert!(!text.contains('\x1b'));
+        assert!(text.starts_with("carapace "));
+        assert!(text.ends_with(" % ") || text.ends_with(" # "));
+    }
+
+    #[test]
+    fn prompt_width_with_wide_chars() {
+        let prompt = Prompt::create(context::default());
+        prompt.context.borrow_mut().env.remove("NO_COLOR");
+        let text = prompt.prompt_in(Some(Path::new("/tmp/日本")));
+        assert!(text.contains("/tmp/日本"));
+
+        // "carapace /tmp/日本 % " where each of the two wide characters takes up two columns.
+        assert_eq!(util::display_width(&text), 21);
+        assert!(text.chars().count() > 21);
+    }
+
+    #[test]
+    fn prompt_escapes_control_chars() {
+        let prompt = Prompt::create(context::default());
+        let text = prompt.prompt_in(Some(Path::new("/tmp/a\nb")));
+        assert!(!text.contains('\n'));
+        assert!(text.contains("/tmp/a\\nb"));
+    }
+
+    #[test]
+    fn prompt_command_runs_once_per_prompt() {
+        let mut prompt = Prompt::create(context::default());
+        prompt.run_prompt_command();
+
+        prompt.context.borrow_mut().env.insert(
+            "PROMPT_COMMAND".to_string(),
+            "export COUNT=x$COUNT; false".to_string(),
+        );
+        prompt
+            .context
+            .borrow_mut()
+            .env
+            .insert("COUNT".to_string(), String::new());
+        prompt
+            .context
+            .borrow_mut()
+            .env
+            .insert("?".to_string(), "3".to_string());
+        prompt.run_prompt_command();
+        prompt.run_prompt_command();
+
+        let ctx = prompt.context.borrow();
+        assert_eq!(ctx.env["COUNT"], "xx");
+        assert_eq!(ctx.env["?"], "3");
+        assert_eq!(prompt.editor.history().len(), 0);
+    }
+
+    #[test]
+    fn prompt_command_not_recursive() {
+        let mut prompt = Prompt::create(context::default());
+        prompt
+            .context
+            .borrow_mut()
+            .env
+            .insert("PROMPT_COMMAND".to_string(), "export COUNT=x".to_string());
+        prompt.in_prompt_command = true;
+        prompt.run_prompt_command();
+        assert!(!prompt.context.borrow().env.contains_key("COUNT"));
+    }
+
+    #[test]
+    fn with_status_checked_nests() {
+        let mut prompt = Prompt::create(context::default());
+        prompt.context.borrow_mut().errexit = true;
+        assert!(prompt.context.borrow().exits_on_failure());
+        prompt.with_status_checked(|prompt| {
+            prompt.with_status_checked(|prompt| {
+                assert_eq!(prompt.context.borrow().status_checked, 2);
+            });
+            assert!(!prompt.context.borrow().exits_on_failure());
+        });
+        assert_eq!(prompt.context.borrow().status_checked, 0);
+        assert!(prompt.context.borrow().exits_on_failure());
+    }
+
+    fn execute_input(prompt: &mut Prompt, input: &str) {
+        let cmd = prompt.parse_command(input);
+        assert!(command::execute(cmd, prompt).is_none());
+    }
+
+    #[test]
+    fn substitute_alias_program_only() {
+        let mut aliases = HashMap::new();
+        aliases.insert("ll".to_string(), "ls -l".to_string());
+        let env = Env::default();
+        let values = |v: &[&str]| v.iter().map(|x| x.to_string()).collect::<Vec<String>>();
+
+        assert_eq!(
+            Prompt::substitute_alias(values(&["ll", "-a"]), &aliases, &env),
+            values(&["ls", "-l", "-a"])
+        );
+        assert_eq!(
+            Prompt::substitute_alias(values(&["echo", "ll"]), &aliases, &env),
+            values(&["echo", "ll"])
+        );
+        assert_eq!(
+            Prompt::substitute_alias(values(&["ll", "ll"]), &aliases, &env),
+            values(&["ls", "-l", "ll"])
+        );
+    }
+
+    #[test]
+    fn substitute_alias_replaces_vars() {
+        let mut aliases = HashMap::new();
+        aliases.insert("home".to_string(), "ls $HOME ${X}".to_string());
+        let mut env = Env::default();
+        env.insert("HOME".to_string(), "/home/user".to_string());
+        env.insert("X".to_string(), "-l -a".to_string());
+
+        let values = vec!["home".to_string(), "$HOME".to_string()];
+        assert_eq!(
+            Prompt::substitute_alias(values, &aliases, &env),
+            vec!["ls", "/home/user", "-l", "-a", "$HOME"]
+        );
+    }
+
+    #[test]
+    fn parse_command_alias_replaces_vars_when_used() {
+        let mut prompt = Prompt::create(context::default());
+        {
+            let mut ctx = prompt.context.borrow_mut();
+            ctx.config
+                .aliases
+                .insert("lh".to_string(), "ls $HOME".to_string());
+            ctx.env.insert("HOME".to_string(), "/a".to_string());
+        }
+
+        let cmd = prompt.parse_command("lh").unwrap();
+        let cmd = cmd.as_any().downcast_ref::<GeneralCommand>().unwrap();
+        assert_eq!(cmd.args, vec!["/a"]);
+
+        // The current value is used, not the one when the alias was defined.
+        prompt
+            .context
+            .borrow_mut()
+            .env
+            .insert("HOME".to_string(), "/b".to_string());
+        let cmd = prompt.parse_command("lh").unwrap();
+        let cmd = cmd.as_any().downcast_ref::<GeneralCommand>().unwrap();
+        assert_eq!(cmd.args, vec!["/b"]);
+    }
+
+    #[test]
+    fn parse_command_counts_commands() {
+        let mut prompt = Prompt::create(context::default());
+        for input in ["ls -l", "ls; true", "echo a | ls"] {
+            prompt.parse_command(input).unwrap();
+        }
+        let counts = &prompt.context.borrow().command_counts;
+        assert_eq!(counts.get("ls"), Some(&2));
+        assert_eq!(counts.get("echo"), Some(&1));
+
+        // Statements of a sequence are counted when they are parsed to run.
+        assert_eq!(counts.get("true"), None);
+    }
+
+    #[test]
+    fn parse_command_alias_comment() {
+        let mut prompt = Prompt::create(context::default());
+        {
+            let aliases = &mut prompt.context.borrow_mut().config.aliases;
+            aliases.insert("x".to_string(), "prog hi # bye".to_string());
+            aliases.insert("y".to_string(), "prog a#b '# kept'".to_string());
+            aliases.insert("z".to_string(), "# only a comment".to_string());
+        }
+
+        // The comment lasts until the end of the resulting line, "prog hi # bye foo".
+        let cmd = prompt.parse_command("x foo").unwrap();
+        let cmd = cmd.as_any().downcast_ref::<GeneralCommand>().unwrap();
+        assert_eq!(cmd.program, "prog");
+        assert_eq!(cmd.args, vec!["hi"]);
+
+        // Only words starting with an unquoted '#' start a comment.
+        let cmd = prompt.parse_command("y c").unwrap();
+        let cmd = cmd.as_any().downcast_ref::<GeneralCommand>().unwrap();
+        assert_eq!(cmd.args, vec!["a#b", "# kept", "c"]);
+
+        let cmd = prompt.parse_command("z foo");
+        assert!(cmd.err().unwrap().is::<NoCommandError>());
+    }
+
+    #[test]
+    fn parse_command_alias_not_substituted_in_arguments() {
+        let mut prompt = Prompt::create(context::default());
+        prompt
+            .context
+            .borrow_mut()
+            .config
+            .aliases
+            .insert("ll".to_string(), "ls -l".to_string());
+
+        let cmd = prompt.parse_command("prog ll").unwrap();
+        let cmd = cmd.as_any().downcast_ref::<GeneralCommand>().unwrap();
+        assert_eq!(cmd.program, "prog");
+        assert_eq!(cmd.args, vec!["ll"]);
+
+        // Inline env vars come before the program.
+        let cmd = prompt.parse_command("A=1 ll ll").unwrap();
+        let cmd = cmd.as_any().downcast_ref::<GeneralCommand>().unwrap();
+        assert_eq!(cmd.program, "ls");
+        assert_eq!(cmd.args, vec!["-l", "ll"]);
+    }
+
+    #[test]
+    fn parse_command_noglob() {
+        let mut prompt = Prompt::create(context::default());
+        prompt.context.borrow_mut().noglob = true;
+
+        let cmd = prompt.parse_command("ls C*").unwrap();
+        let cmd = cmd.as_any().downcast_ref::<GeneralCommand>().unwrap();
+        assert_eq!(cmd.args, vec!["C*"]);
+
+        prompt.context.borrow_mut().noglob = false;
+        let cmd = prompt.parse_command("ls C*").unwrap();
+        let cmd = cmd.as_any().downcast_ref::<GeneralCommand>().unwrap();
+        assert!(cmd.args.contains(&"Cargo.toml".to_string()));
+    }
+
+    #[test]
+    fn parse_command_needs_confirmation() {
+        let mut prompt = Prompt::create(context::default());
+        prompt.context.borrow_mut().config.confirm_commands = vec!["rm -rf".to_string()];
+
+        let cmd = prompt.parse_command("rm -rf /tmp/x").unwrap();
+        let cmd = cmd.as_any().downcast_ref::<ConfirmCommand>().unwrap();
+        assert_eq!(cmd.line, "rm -rf /tmp/x");
+        assert!(cmd.cmd.as_any().downcast_ref::<GeneralCommand>().is_some());
+
+        let cmd = prompt.parse_command("rm /tmp/x").unwrap();
+        assert!(cmd.as_any().downcast_ref::<GeneralCommand>().is_some());
+    }
+
+    #[test]
+    fn expansion_line() {
+        let mut prompt = Prompt::create(context::default());
+        assert_eq!(prompt.expand("ls   -l  /tmp").unwrap().line(), "ls -l /tmp");
+        assert_eq!(prompt.expand("ls").unwrap().line(), "ls");
+    }
+
+    #[test]
+    fn history_keeps_input_verbatim() {
+        let mut prompt = Prompt::create(context::default());
+        let cmd = prompt.parse_command("ls *").unwrap();
+        let cmd = cmd.as_any().downcast_ref::<GeneralCommand>().unwrap();
+        assert!(cmd.args.contains(&"Cargo.toml".to_string()));
+
+        let history = prompt.editor.history();
+        assert_eq!(history.len(), 1);
+        assert_eq!(history.get(0).unwrap(), "ls *");
+    }
+
+    #[test]
+    fn history_ignores_histignore_patterns() {
+        let mut prompt = Prompt::create(context::default());
+        prompt
+            .context
+            .borrow_mut()
+            .env
+            .insert("HISTIGNORE".to_string(), "ls::cd*".to_string());
+
+        for input in ["ls", "  ls ", "cd", "cd /tmp", "ls -l", "pwd", "lsblk"] {
+            let _ = prompt.parse_command(input);
+        }
+        let history = prompt.editor.history();
+        let entries: Vec<&String> = history.iter().collect();
+        assert_eq!(entries, vec!["ls -l", "pwd", "lsblk"]);
+
+        // Empty keeps everything.
+        prompt
+            .context
+            .borrow_mut()
+            .env
+            .insert("HISTIGNORE".to_string(), "".to_string());
+        let _ = prompt.parse_command("ls");
+        assert_eq!(prompt.editor.history().len(), 4);
+    }
+
+    #[test]
+    fn expand_keeps_raw_statement() {
+        let mut prompt = Prompt::create(context::default());
+        prompt
+            .context
+            .borrow_mut()
+            .env
+            .insert("X".to_string(), "C".to_string());
+        let expansion = prompt.expand("ls ${X}*").unwrap();
+        assert_eq!(expansion.raw, "ls ${X}*");
+        assert_eq!(expansion.program, "ls");
+        assert!(expansion.args.contains(&"Cargo.toml".to_string()));
+    }
+
+    #[test]
+    fn expand_redirects() {
+        let mut prompt = Prompt::create(context::default());
+        prompt
+            .context
+            .borrow_mut()
+            .config
+            .aliases
+            .insert("g".to_string(), "grep -i".to_string());
+
+        // Also before the program, which can be an alias.
+        for input in [
+            "grep -i foo < 'in file' > out.txt",
+            "< 'in file' grep -i foo > out.txt",
+            "< 'in file' > out.txt g foo",
+            "grep -i foo<'in file' >out.txt",
+            "<'in file' >out.txt g foo",
+        ] {
+            let expansion = prompt.expand(input).unwrap();
+            assert_eq!(expansion.program, "grep", "{}", input);
+            assert_eq!(expansion.args, vec!["-i", "foo"], "{}", input);
+            assert_eq!(expansion.redirects.len(), 2, "{}", input);
+            assert!(expansion
+                .redirects
+                .contains(&Redirection::Stdin("in file".to_string())));
+        }
+
+        assert!(prompt.expand("< in.txt").is_err());
+        assert!(prompt.expand("grep foo <").is_err());
+    }
+
+    #[test]
+    fn parse_command_array_assignment() {
+        let mut prompt = Prompt::create(context::default());
+        let cmd = prompt.parse_command(r#"arr=(a "b c" 'd')"#).unwrap();
+        let cmd = cmd.as_any().downcast_ref::<ArrayAssignCommand>().unwrap();
+        assert_eq!(cmd.name, "arr");
+        assert_eq!(cmd.values, vec!["a", "b c", "d"]);
+
+        let cmd = prompt.parse_command("arr=()").unwrap();
+        let cmd = cmd.as_any().downcast_ref::<ArrayAssignCommand>().unwrap();
+        assert!(cmd.values.is_empty());
+    }
+
+    #[test]
+    fn parse_command_array_assignment_unmatched_quote() {
+        let mut prompt = Prompt::create(context::default());
+        let cmd = prompt.parse_command(r#"arr=(a "b)"#);
         assert!(cmd.err().unwrap().is::<CommandArgsSplitError>());
     }
 
+    #[test]
+    fn parse_command_array_expansion() {
+        let mut prompt = Prompt::create(context::default());
+        prompt
+            .context
+            .borrow_mut()
+            .env
+            .insert("X".to_string(), "x".to_string());
+        execute_input(&mut prompt, r#"arr=(a "b c" $X)"#);
+
+        let expect_args = |prompt: &mut Prompt, input: &str, args: &[&str]| {
+            let cmd = prompt.parse_command(input).unwrap();
+            let cmd = cmd.as_any().downcast_ref::<GeneralCommand>().unwrap();
+            assert_eq!(cmd.args, args, "{}", input);
+        };
+        expect_args(&mut prompt, "prog ${arr[1]}", &["b", "c"]);
+        expect_args(&mut prompt, r#"prog "${arr[1]}""#, &["b c"]);
+        expect_args(&mut prompt, "prog ${arr[2]} $arr", &["x", "a"]);
+        expect_args(&mut prompt, "prog ${arr[@]}", &["a", "b", "c", "x"]);
+        expect_args(&mut prompt, r#"prog "${arr[@]}""#, &["a", "b c", "x"]);
+        expect_args(&mut prompt, r#"prog "${arr[*]}""#, &["a b c x"]);
+        expect_args(&mut prompt, "prog ${#arr[@]}", &["3"]);
+    }
+
+    #[test]
+    fn parse_command_substitution() {
+        let mut prompt = Prompt::create(context::default());
+        let expect_args = |prompt: &mut Prompt, input: &str, args: &[&str]| {
+            let cmd = prompt.parse_command(input).unwrap();
+            let cmd = cmd.as_any().downcast_ref::<GeneralCommand>().unwrap();
+            assert_eq!(cmd.args, args, "{}", input);
+        };
+        expect_args(&mut prompt, "prog $(echo a b)", &["a", "b"]);
+        expect_args(&mut prompt, r#"prog "$(echo a b)" c"#, &["a b", "c"]);
+        expect_args(&mut prompt, r#"prog "$(printf '"$x')""#, &[r#""$x"#]);
+        expect_args(&mut prompt, "prog $(echo $(echo nested))", &["nested"]);
+        expect_args(
+            &mut prompt,
+            "prog $(printf 'a;b' | tr ';' ' ')",
+            &["a", "b"],
+        );
+        expect_args(&mut prompt, "prog '$(echo a)'", &["$(echo a)"]);
+
+        execute_input(&mut prompt, "arr=($(echo x y))");
+        expect_args(&mut prompt, "prog ${#arr[@]}", &["2"]);
+
+        let cmd = prompt.parse_command("$(echo prog) hi").unwrap();
+        let cmd = cmd.as_any().downcast_ref::<GeneralCommand>().unwrap();
+        assert_eq!(cmd.program, "prog");
+    }
+
+    #[test]
+    fn parse_command_backtick_substitution() {
+        let mut prompt = Prompt::create(context::default());
+        let cmd = prompt
+            .parse_command(r#"prog `echo a b` "`echo c d`" \` `echo \`echo e\``"#)
+            .unwrap();
+        let cmd = cmd.as_any().downcast_ref::<GeneralCommand>().unwrap();
+        assert_eq!(cmd.args, vec!["a", "b", "c d", "`", "e"]);
+
+        let err = prompt.parse_command("prog `echo a").err().unwrap();
+        let err = err.downcast_ref::<CommandArgsSplitError>().unwrap();
+        assert_eq!(err.kind, SplitErrorKind::UnterminatedBacktick);
+    }
+
+    #[test]
+    fn parse_command_brace_expansion() {
+        let mut prompt = Prompt::create(context::default());
+        let cmd = prompt
+            .parse_command(
+                r#"prog file.{txt,md} {1..3} pre{a,b{1,2}}post '{x,y}' "a {x,y}" \{x,y}"#,
+            )
+            .unwrap();
+        let cmd = cmd.as_any().downcast_ref::<GeneralCommand>().unwrap();
+        assert_eq!(
+            cmd.args,
+            vec![
+                "file.txt",
+                "file.md",
+                "1",
+                "2",
+                "3",
+                "preapost",
+                "preb1post",
+                "preb2post",
+                "{x,y}",
+                "a {x,y}",
+                "{x,y}"
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_command_brace_expansion_before_substitution() {
+        let mut prompt = Prompt::create(context::default());
+        prompt
+            .context
+            .borrow_mut()
+            .env
+            .insert("F".to_string(), "x{1,2}".to_string());
+        let cmd = prompt.parse_command("prog $F ${F}y {Z..a}").unwrap();
+        let cmd = cmd.as_any().downcast_ref::<GeneralCommand>().unwrap();
+        assert_eq!(
+            cmd.args,
+            vec!["x{1,2}", "x{1,2}y", "Z", "[", "\\", "]", "^", "_", "`", "a"]
+        );
+    }
+
+    #[test]
+    fn parse_command_arithmetic_expansion() {
+        let mut prompt = Prompt::create(context::default());
+        prompt
+            .context
+            .borrow_mut()
+            .env
+            .insert("x".to_string(), "5".to_string());
+        let cmd = prompt
+            .parse_command(
+                r#"prog $((2 + 3 * 4)) "$((x+1))" $(($x * $((2)))) $(( $(echo 7) % 4 ))"#,
+            )
+            .unwrap();
+        let cmd = cmd.as_any().downcast_ref::<GeneralCommand>().unwrap();
+        assert_eq!(cmd.args, vec!["14", "6", "10", "3"]);
+
+        let cmd = prompt.parse_command("export y=$((x * 2))");
+        assert_eq!(command::execute(cmd, &mut prompt), None);
+        assert_eq!(prompt.context.borrow().env["y"], "10");
+    }
+
+    #[test]
+    fn parse_command_arithmetic_division_by_zero() {
+        let mut prompt = Prompt::create(context::default());
+        let err = prompt.parse_command("prog $((1 / 0))").err().unwrap();
+        let err = err.downcast_ref::<ArithError>().unwrap();
+        assert_eq!(err.kind, ArithErrorKind::DivisionByZero);
+
+        let cmd = prompt.parse_command("prog $((1 % 0))");
+        assert_eq!(command::execute(cmd, &mut prompt), None);
+        assert_eq!(prompt.context.borrow().env["?"], "1");
+    }
+
+    #[test]
+    fn parse_command_substitution_does_not_exit() {
+        let mut prompt = Prompt::create(context::default());
+        prompt.context.borrow_mut().errexit = true;
+        let cmd = prompt.parse_command("true $(exit 3) $(false)");
+        assert_eq!(command::execute(cmd, &mut prompt), None);
+    }
+
+    #[test]
+    fn parse_command_substitution_status() {
+        // Without a command, $? is the status of the last substitution, like for "x=$(false)".
+        let mut prompt = Prompt::create(context::default());
+        for (input, status) in [
+            ("x=$(false)", "1"),
+            ("x=$(true)", "0"),
+            ("x=$(true) y=$(sh -c 'exit 3')", "3"),
+        ] {
+            let cmd = prompt.parse_command(input);
+            assert_eq!(command::execute(cmd, &mut prompt), None);
+
+            let cmd = prompt.parse_command("prog $?").unwrap();
+            let cmd = cmd.as_any().downcast_ref::<GeneralCommand>().unwrap();
+            assert_eq!(cmd.args, vec![status], "{}", input);
+        }
+    }
+
+    /// Creates "real/sub" and symbolic link "link" to "real" in a temporary directory, and yields
+    /// the directory and its canonical path.
+    fn symlinked_tree() -> (TempDir, PathBuf) {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        fs::create_dir_all(root.join("real").join("sub")).unwrap();
+        std::os::unix::fs::symlink(root.join("real"), root.join("link")).unwrap();
+        (dir, root)
+    }
+
+    #[test]
+    fn resolve_cwd_logical_keeps_symlink() {
+        let (_dir, root) = symlinked_tree();
+        let res = Prompt::resolve_cwd(&root, Path::new("link"), false).unwrap();
+        assert_eq!(res, root.join("link"));
+
+        let res = Prompt::resolve_cwd(&root.join("link"), Path::new("sub"), false).unwrap();
+        assert_eq!(res, root.join("link").join("sub"));
+    }
+
+    #[test]
+    fn resolve_cwd_logical_parent_of_symlink() {
+        let (_dir, root) = symlinked_tree();
+        let res = Prompt::resolve_cwd(&root.join("link"), Path::new(".."), false).unwrap();
+        assert_eq!(res, root);
+
+        let res = Prompt::resolve_cwd(&root, Path::new("./link/sub/../."), false).unwrap();
+        assert_eq!(res, root.join("link"));
+    }
+
+    #[test]
+    fn resolve_cwd_physical_resolves_symlink() {
+        let (_dir, root) = symlinked_tree();
+        let res = Prompt::resolve_cwd(&root, Path::new("link"), true).unwrap();
+        assert_eq!(res, root.join("real"));
+
+        let res = Prompt::resolve_cwd(&root, &root.join("link").join("sub"), true).unwrap();
+        assert_eq!(res, root.join("real").join("sub"));
+    }
+
+    #[test]
+    fn resolve_cwd_missing() {
+        let (_dir, root) = symlinked_tree();
+        assert!(Prompt::resolve_cwd(&root, Path::new("nope"), false).is_err());
+        assert!(Prompt::resolve_cwd(&root, Path::new("nope"), true).is_err());
+    }
+
+    #[test]
+    fn logical_cwd() {
+        let (_dir, root) = symlinked_tree();
+        let (real, link) = (root.join("real"), root.join("link"));
+        let link_str = link.to_str().unwrap();
+        assert_eq!(
+            Prompt::logical_cwd(Ok(&real), Some(link_str)),
+            Some(link_str.to_string())
+        );
+        assert_eq!(
+            Prompt::logical_cwd(Ok(&root), Some(link_str)),
+            Some(root.to_string_lossy().into_owned())
+        );
+        assert_eq!(
+            Prompt::logical_cwd(Ok(&real), None),
+            Some(real.to_string_lossy().into_owned())
+        );
+
+        // Like when the cwd was removed.
+        let err = io::Error::from(io::ErrorKind::NotFound);
+        assert_eq!(Prompt::logical_cwd(Err(&err), Some(link_str)), None);
+    }
+
+    #[test]
+    fn xtrace_line_default_prefix() {
+        let prompt = Prompt::create(context::default());
+        assert_eq!(
+            prompt.xtrace_line("ls", &["-l".to_string()]),
+            "+carapace> ls -l"
+        );
+    }
+
+    #[test]
+    fn xtrace_line_ps4_prefix() {
+        let prompt = Prompt::create(context::default());
+        prompt
+            .context
+            .borrow_mut()
+            .env
+            .insert("PS4".to_string(), "+ ".to_string());
+        assert_eq!(prompt.xtrace_line("ls", &["-l".to_string()]), "+ ls -l");
+    }
+
     #[test]
     fn setup_env() {
         let ctx = context::default();
@@ -705,6 +2143,37 @@ mod tests {
 
         assert!(env.contains_key("HELLO"));
         assert_eq!("42,84", env["HELLO"]);
+
+        assert_eq!(crate::LONG_VERSION, env["CARAPACE_VERSION"]);
+        assert_eq!("emacs", env["EDIT_MODE"]);
+    }
+
+    #[test]
+    fn save_edit_mode_when_persisted() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("config.json");
+        let path_str = path.to_str().unwrap();
+        let read_edit_mode = || Config::new(Some(path_str)).edit_mode;
+
+        // Not written back without `persist_edit_mode`.
+        let ctx = context::new(0, Some(path_str));
+        {
+            let mut prompt = Prompt::create(ctx.clone());
+            prompt.set_edit_mode(EditMode::Vi);
+        }
+        assert_eq!(read_edit_mode(), EditMode::Emacs);
+
+        // Other entries of the file are kept as is.
+        fs::write(&path, r#"{"persist_edit_mode": true, "on_cd": "true"}"#).unwrap();
+        let ctx = context::new(0, Some(path_str));
+        {
+            let mut prompt = Prompt::create(ctx.clone());
+            prompt.set_edit_mode(EditMode::Vi);
+            ctx.borrow_mut().config.on_cd = "false".to_string();
+        }
+        let config = Config::new(Some(path_str));
+        assert_eq!(config.edit_mode, EditMode::Vi);
+        assert_eq!(config.on_cd, "true");
     }
 
     #[test]
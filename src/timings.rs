@@ -0,0 +1,88 @@
+//! Durations of the startup phases of the shell, like loading the config and scanning `$PATH`,
+//! printed with `--time-startup` or `CARAPACE_TIMINGS=1` to find out where time goes on slow
+//! filesystems.
+
+use std::time::{Duration, Instant};
+
+/// Durations of named phases in the order they were measured.
+#[derive(Default)]
+pub struct Timings {
+    phases: Vec<(String, Duration)>,
+}
+
+impl Timings {
+    /// Whether timings are to be printed, which is via `flag` or `$CARAPACE_TIMINGS` set to "1".
+    pub fn enabled(flag: bool) -> bool {
+        flag || std::env::var("CARAPACE_TIMINGS").is_ok_and(|value| value == "1")
+    }
+
+    /// Runs `f` and records how long it took as phase `name`, and yields its result.
+    pub fn measure<T, F>(&mut self, name: &str, f: F) -> T
+    where
+        F: FnOnce() -> T,
+    {
+        let start = Instant::now();
+        let res = f();
+        self.phases.push((name.to_string(), start.elapsed()));
+        res
+    }
+
+    pub fn phases(&self) -> &[(String, Duration)] {
+        &self.phases
+    }
+
+    /// Total duration of all phases.
+    pub fn total(&self) -> Duration {
+        self.phases.iter().map(|(_, duration)| *duration).sum()
+    }
+
+    /// Yields a line per phase and the total, like "config load: 1.234 ms".
+    pub fn report(&self) -> String {
+        let line = |name: &str, duration: Duration| {
+            format!("{}: {:.3} ms", name, duration.as_secs_f64() * 1000.0)
+        };
+        self.phases
+            .iter()
+            .map(|(name, duration)| line(name, *duration))
+            .chain(std::iter::once(line("total", self.total())))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn measure_in_order() {
+        let mut timings = Timings::default();
+        assert_eq!(timings.measure("first", || 42), 42);
+        timings.measure("second", || std::thread::sleep(Duration::from_millis(5)));
+
+        let names: Vec<_> = timings.phases().iter().map(|(name, _)| name).collect();
+        assert_eq!(names, vec!["first", "second"]);
+        assert!(timings.phases()[1].1 >= Duration::from_millis(5));
+        assert!(timings.total() >= timings.phases()[1].1);
+    }
+
+    #[test]
+    fn report() {
+        let mut timings = Timings::default();
+        timings
+            .phases
+            .push(("config load".to_string(), Duration::from_micros(1500)));
+        timings
+            .phases
+            .push(("PATH rehash".to_string(), Duration::from_millis(20)));
+        assert_eq!(
+            timings.report(),
+            "config load: 1.500 ms\nPATH rehash: 20.000 ms\ntotal: 21.500 ms"
+        );
+    }
+
+    #[test]
+    fn enabled_by_flag() {
+        assert!(Timings::enabled(true));
+    }
+}
@@ -0,0 +1,1321 @@
+//! Quote-aware splitting of input into words, like a POSIX shell does.
+
+use std::error::Error;
+use std::fmt;
+
+/// Kind of error when splitting input into words.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum SplitErrorKind {
+    UnterminatedSingleQuote,
+    UnterminatedDoubleQuote,
+    DanglingBackslash,
+    MissingPipelineCommand,
+    MissingRedirectTarget,
+    UnterminatedSubstitution,
+    UnterminatedBacktick,
+}
+
+impl fmt::Display for SplitErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let msg = match self {
+            SplitErrorKind::UnterminatedSingleQuote => "unterminated single quote",
+            SplitErrorKind::UnterminatedDoubleQuote => "unterminated double quote",
+            SplitErrorKind::DanglingBackslash => "dangling backslash",
+            SplitErrorKind::MissingPipelineCommand => "missing command in pipeline",
+            SplitErrorKind::MissingRedirectTarget => "missing file to redirect to",
+            SplitErrorKind::UnterminatedSubstitution => "unterminated command substitution",
+            SplitErrorKind::UnterminatedBacktick => "unterminated backtick",
+        };
+        write!(f, "{}", msg)
+    }
+}
+
+/// Error of splitting input into words, with the position of the offending character.
+#[derive(Debug, PartialEq)]
+pub struct CommandArgsSplitError {
+    pub kind: SplitErrorKind,
+
+    /// Character position in `input`, like of the opening quote that isn't terminated.
+    pub pos: usize,
+
+    pub input: String,
+}
+
+impl Error for CommandArgsSplitError {}
+
+impl fmt::Display for CommandArgsSplitError {
+    /// Shows the input with a caret under the offending character.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Syntax error: {}", self.kind)?;
+        writeln!(f, "  {}", self.input)?;
+        write!(f, "  {}^", " ".repeat(self.pos))
+    }
+}
+
+/// Splits `input` into words separated by whitespace while preserving quoted segments.
+///
+/// Within single quotes all characters are kept as is. Within double quotes, a backslash only
+/// escapes `$`, `` ` ``, `"`, `\`, or newline. Elsewhere, a backslash escapes any character, and a
+/// backslash-newline pair is removed. A word starting with '#' starts a comment that lasts until the
+/// end of the line.
+pub fn split(input: &str) -> Result<Vec<String>, CommandArgsSplitError> {
+    Ok(split_words(input, false)?
+        .into_iter()
+        .map(|w| w.text)
+        .collect())
+}
+
+/// Unquoted words that redirect the input or output of a command to the file following them.
+pub const REDIRECT_OPERATORS: &[&str] = &["<", ">", ">>", "&>", "&>>", "2>", "2>>"];
+
+/// Redirection of the input or output of a command. They apply in the order given, so "2>&1" sends
+/// stderr where stdout goes at that point.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Redirection {
+    /// Input read from file, via "< in.txt".
+    Stdin(String),
+
+    /// Output written to file, via "> out.txt", or appended to it, via ">> out.txt".
+    Stdout { path: String, append: bool },
+
+    /// Errors written to file, via "2> errs.txt", or appended to it, via "2>> errs.txt".
+    Stderr { path: String, append: bool },
+
+    /// Errors written where the output goes, via "2>&1".
+    StderrToStdout,
+}
+
+/// Splits `input` into words like `split()`, and takes out the redirections: any of the
+/// `REDIRECT_OPERATORS` together with the file following it, and "2>&1". "&> all.txt" is the same
+/// as "> all.txt 2>&1", and "&>> all.txt" as ">> all.txt 2>&1". Unquoted operators don't need to
+/// be separate words, like in "echo hi>out.txt 2>/dev/null" or "cat <in.txt".
+pub fn split_redirects(
+    input: &str,
+) -> Result<(Vec<String>, Vec<Redirection>), CommandArgsSplitError> {
+    let mut words = Vec::new();
+    let mut redirects = Vec::new();
+    let mut iter = split_words(input, true)?.into_iter();
+    while let Some(word) = iter.next() {
+        if !word.quoted && word.text == "2>&1" {
+            redirects.push(Redirection::StderrToStdout);
+            continue;
+        }
+        if word.quoted || !REDIRECT_OPERATORS.contains(&word.text.as_str()) {
+            words.push(word.text);
+            continue;
+        }
+        let path = match iter.next() {
+            Some(path) => path.text,
+            None => {
+                return Err(CommandArgsSplitError {
+                    kind: SplitErrorKind::MissingRedirectTarget,
+                    pos: word.pos,
+                    input: input.to_string(),
+                })
+            }
+        };
+        let append = word.text.ends_with(">>");
+        redirects.push(match word.text.as_ref() {
+            "<" => Redirection::Stdin(path),
+            "2>" | "2>>" => Redirection::Stderr { path, append },
+            _ => Redirection::Stdout { path, append },
+        });
+        if word.text.starts_with('&') {
+            redirects.push(Redirection::StderrToStdout);
+        }
+    }
+    Ok((words, redirects))
+}
+
+/// Word of input split by `split_words()`.
+struct Word {
+    text: String,
+
+    /// Whether any part of the word was quoted or escaped, like "'>'".
+    quoted: bool,
+
+    /// Character position in the input where the word starts.
+    pos: usize,
+}
+
+/// Splits `input` into words. With `operators`, unquoted '<' and '>' end the current word and start
+/// a word of their own with the redirection operator, like "2>" and "out.txt" of "2>out.txt".
+fn split_words(input: &str, operators: bool) -> Result<Vec<Word>, CommandArgsSplitError> {
+    let error = |kind, pos| {
+        Err(CommandArgsSplitError {
+            kind,
+            pos,
+            input: input.to_string(),
+        })
+    };
+
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut quoted = false;
+    let mut start = 0;
+    let mut chars = input.chars().enumerate().peekable();
+    while let Some((pos, ch)) = chars.next() {
+        if word.is_none() {
+            start = pos;
+            quoted = false;
+        }
+        if matches!(ch, '\'' | '"' | '\\') {
+            quoted = true;
+        }
+        match ch {
+            '\'' => {
+                let word = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some((_, '\'')) => break,
+                        Some((_, ch)) => word.push(ch),
+                        None => return error(SplitErrorKind::UnterminatedSingleQuote, pos),
+                    }
+                }
+            }
+            '"' => {
+                let word = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some((_, '"')) => break,
+                        Some((_, '$')) if chars.peek().map(|(_, next)| *next) == Some('(') => {
+                            chars.next();
+                            word.push_str("$(");
+                            let mut chars = chars.by_ref().map(|(_, ch)| ch);
+                            if !take_substitution(&mut chars, word) {
+                                return error(SplitErrorKind::UnterminatedSubstitution, pos);
+                            }
+                        }
+                        Some((tick_pos, '`')) => {
+                            word.push('`');
+                            if !take_backticks(&mut chars.by_ref().map(|(_, ch)| ch), word) {
+                                return error(SplitErrorKind::UnterminatedBacktick, tick_pos);
+                            }
+                        }
+                        Some((escape_pos, '\\')) => match chars.next() {
+                            Some((_, '\n')) => {}
+                            Some((_, ch)) if matches!(ch, '$' | '`' | '"' | '\\') => word.push(ch),
+                            Some((_, ch)) => {
+                                word.push('\\');
+                                word.push(ch);
+                            }
+                            None => return error(SplitErrorKind::DanglingBackslash, escape_pos),
+                        },
+                        Some((_, ch)) => word.push(ch),
+                        None => return error(SplitErrorKind::UnterminatedDoubleQuote, pos),
+                    }
+                }
+            }
+            '\\' => match chars.next() {
+                Some((_, '\n')) => {}
+                Some((_, ch)) => word.get_or_insert_with(String::new).push(ch),
+                None => return error(SplitErrorKind::DanglingBackslash, pos),
+            },
+            '`' => {
+                let word = word.get_or_insert_with(String::new);
+                word.push(ch);
+                if !take_backticks(&mut chars.by_ref().map(|(_, ch)| ch), word) {
+                    return error(SplitErrorKind::UnterminatedBacktick, pos);
+                }
+            }
+            '#' if word.is_none() => {
+                // Skip comment until end of line.
+                for (_, ch) in chars.by_ref() {
+                    if ch == '\n' {
+                        break;
+                    }
+                }
+            }
+            _ if ch.is_whitespace() => {
+                if let Some(text) = word.take() {
+                    words.push(Word {
+                        text,
+                        quoted,
+                        pos: start,
+                    });
+                }
+            }
+            '<' | '>' if operators => {
+                // An unquoted "2" or "&" right before '>' is part of the operator, like "2>".
+                let mut op = match word.take() {
+                    Some(text) if !quoted && ch == '>' && (text == "2" || text == "&") => text,
+                    Some(text) => {
+                        words.push(Word {
+                            text,
+                            quoted,
+                            pos: start,
+                        });
+                        start = pos;
+                        String::new()
+                    }
+                    None => String::new(),
+                };
+                op.push(ch);
+                let ahead: String = chars.clone().take(2).map(|(_, ch)| ch).collect();
+                if ch == '>' && ahead.starts_with('>') {
+                    chars.next();
+                    op.push('>');
+                } else if op == "2>" && ahead == "&1" {
+                    chars.nth(1);
+                    op.push_str("&1");
+                }
+                words.push(Word {
+                    text: op,
+                    quoted: false,
+                    pos: start,
+                });
+            }
+            _ => word.get_or_insert_with(String::new).push(ch),
+        }
+    }
+    if let Some(text) = word {
+        words.push(Word {
+            text,
+            quoted,
+            pos: start,
+        });
+    }
+    Ok(words)
+}
+
+/// Splits the first word off `input`, ignoring leading whitespace, and yields it unmodified, with
+/// quotes and backslashes kept, together with the remaining input. Whitespace in quotes or escaped
+/// by backslash doesn't end the word, like in "A='x y' prog".
+pub fn first_word(input: &str) -> (&str, &str) {
+    let input = input.trim_start();
+    let mut chars = input.char_indices();
+    while let Some((pos, ch)) = chars.next() {
+        match ch {
+            '\\' => {
+                chars.next();
+            }
+            '\'' | '"' => {
+                while let Some((_, next)) = chars.next() {
+                    if next == ch {
+                        break;
+                    }
+                    if ch == '"' && next == '\\' {
+                        chars.next();
+                    }
+                }
+            }
+            _ if ch.is_whitespace() => return (&input[..pos], &input[pos..]),
+            _ => {}
+        }
+    }
+    (input, "")
+}
+
+/// Splits `input` into words as written, with quotes, backslashes, and command substitutions kept.
+/// Whitespace in quotes or command substitutions doesn't end a word, like in "echo $(date +%s)".
+pub fn raw_words(input: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut chars = input.chars().peekable();
+    while let Some(ch) = chars.next() {
+        if ch.is_whitespace() {
+            if !word.is_empty() {
+                words.push(std::mem::take(&mut word));
+            }
+            continue;
+        }
+        word.push(ch);
+        match ch {
+            '\\' => word.extend(chars.next()),
+            '\'' => {
+                for next in chars.by_ref() {
+                    word.push(next);
+                    if next == ch {
+                        break;
+                    }
+                }
+            }
+            '"' => {
+                take_double_quoted(&mut chars, &mut word);
+            }
+            '`' => {
+                take_backticks(&mut chars, &mut word);
+            }
+            '$' if chars.peek() == Some(&'(') => {
+                word.extend(chars.next());
+                take_substitution(&mut chars, &mut word);
+            }
+            _ => {}
+        }
+    }
+    if !word.is_empty() {
+        words.push(word);
+    }
+    words
+}
+
+/// Separator preceding a statement, which decides whether the statement runs.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Separator {
+    /// Always runs, like the first statement or after ';' or a newline.
+    Sequence,
+
+    /// Runs if the previous statement succeeded, after "&&".
+    And,
+
+    /// Runs if the previous statement failed, after "||".
+    Or,
+}
+
+/// Splits `input` into statements separated by ';', newlines, "&&", and "||" outside of quotes
+/// and comments. Each statement is yielded with its preceding separator, and empty statements are
+/// skipped.
+pub fn split_statements(input: &str) -> Result<Vec<(Separator, String)>, CommandArgsSplitError> {
+    // Validate quoting first so that the scanning below can assume terminated quotes.
+    split(input)?;
+
+    let mut statements = Vec::new();
+    let mut separator = Separator::Sequence;
+    let mut statement = String::new();
+    let mut push = |separator: Separator, statement: &mut String| {
+        let text = statement.trim();
+        if !text.is_empty() {
+            statements.push((separator, text.to_string()));
+        }
+        statement.clear();
+    };
+
+    let mut chars = input.chars().peekable();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '\\' => {
+                statement.push(ch);
+                if let Some(next) = chars.next() {
+                    statement.push(next);
+                }
+                continue;
+            }
+            '\'' => {
+                statement.push(ch);
+                for next in chars.by_ref() {
+                    statement.push(next);
+                    if next == ch {
+                        break;
+                    }
+                }
+                continue;
+            }
+            '"' => {
+                statement.push(ch);
+                take_double_quoted(&mut chars, &mut statement);
+                continue;
+            }
+            '`' => {
+                statement.push(ch);
+                take_backticks(&mut chars, &mut statement);
+                continue;
+            }
+            '$' if chars.peek() == Some(&'(') => {
+                chars.next();
+                statement.push_str("$(");
+                take_substitution(&mut chars, &mut statement);
+                continue;
+            }
+            '#' if statement.is_empty() || statement.ends_with(char::is_whitespace) => {
+                // Skip comment until end of line.
+                for next in chars.by_ref() {
+                    if next == '\n' {
+                        break;
+                    }
+                }
+                push(separator, &mut statement);
+                separator = Separator::Sequence;
+                continue;
+            }
+            ';' | '\n' => {
+                push(separator, &mut statement);
+                separator = Separator::Sequence;
+                continue;
+            }
+            '&' | '|' if chars.peek() == Some(&ch) => {
+                chars.next();
+                push(separator, &mut statement);
+                separator = if ch == '&' {
+                    Separator::And
+                } else {
+                    Separator::Or
+                };
+                continue;
+            }
+            _ => statement.push(ch),
+        }
+    }
+    push(separator, &mut statement);
+    Ok(statements)
+}
+
+/// Splits statement `input` into the commands of a pipeline separated by '|' outside of quotes,
+/// like "ls | grep foo". Statements are expected to be split by `split_statements()` already, so
+/// "||" isn't a separator here. A statement without '|' yields itself. Each side of a '|' must
+/// have a command, so "ls |" is an error. A "|&" also pipes stderr, like "2>&1 |".
+pub fn split_pipeline(input: &str) -> Result<Vec<String>, CommandArgsSplitError> {
+    let error = |pos| {
+        Err(CommandArgsSplitError {
+            kind: SplitErrorKind::MissingPipelineCommand,
+            pos,
+            input: input.to_string(),
+        })
+    };
+
+    let mut commands = Vec::new();
+    let mut command = String::new();
+    let mut chars = input.chars().enumerate();
+    while let Some((pos, ch)) = chars.next() {
+        match ch {
+            '\\' => {
+                command.push(ch);
+                if let Some((_, next)) = chars.next() {
+                    command.push(next);
+                }
+            }
+            '\'' => {
+                command.push(ch);
+                for (_, next) in chars.by_ref() {
+                    command.push(next);
+                    if next == ch {
+                        break;
+                    }
+                }
+            }
+            '"' => {
+                command.push(ch);
+                take_double_quoted(&mut chars.by_ref().map(|(_, ch)| ch), &mut command);
+            }
+            '`' => {
+                command.push(ch);
+                take_backticks(&mut chars.by_ref().map(|(_, ch)| ch), &mut command);
+            }
+            '$' if chars.clone().next().map(|(_, next)| next) == Some('(') => {
+                chars.next();
+                command.push_str("$(");
+                take_substitution(&mut chars.by_ref().map(|(_, ch)| ch), &mut command);
+            }
+            '|' => {
+                if command.trim().is_empty() {
+                    return error(pos);
+                }
+                if chars.clone().next().map(|(_, next)| next) == Some('&') {
+                    chars.next();
+                    command.truncate(command.trim_end().len());
+                    command.push_str(" 2>&1");
+                }
+                commands.push(command.trim().to_string());
+                command.clear();
+
+                // The position of the last '|' is reported if nothing follows it.
+                if chars.clone().all(|(_, ch)| ch.is_whitespace()) {
+                    return error(pos);
+                }
+            }
+            _ => command.push(ch),
+        }
+    }
+    commands.push(command.trim().to_string());
+    Ok(commands)
+}
+
+/// Moves the rest of a command substitution from `chars`, after its "$(", to `out` up to and
+/// including the matching ')'. Parentheses in quotes don't count, like in "$(echo ')')". Yields
+/// whether the substitution is terminated.
+pub(crate) fn take_substitution<I: Iterator<Item = char>>(chars: &mut I, out: &mut String) -> bool {
+    let mut depth = 1;
+    while let Some(ch) = chars.next() {
+        out.push(ch);
+        match ch {
+            '\\' => {
+                if let Some(next) = chars.next() {
+                    out.push(next);
+                }
+            }
+            '\'' => {
+                for next in chars.by_ref() {
+                    out.push(next);
+                    if next == ch {
+                        break;
+                    }
+                }
+            }
+            '"' if !take_double_quoted(chars, out) => return false,
+            '`' if !take_backticks(chars, out) => return false,
+            '(' => depth += 1,
+            ')' => {
+                depth -= 1;
+                if depth == 0 {
+                    return true;
+                }
+            }
+            _ => {}
+        }
+    }
+    false
+}
+
+/// Moves the rest of a double-quoted segment from `chars`, after its opening '"', to `out` up to
+/// and including the closing '"'. Command substitutions within are moved as a whole, such that
+/// their quotes don't end the segment, like in `"$(date +"%Y")"`. Yields whether the segment is
+/// terminated.
+fn take_double_quoted<I: Iterator<Item = char>>(chars: &mut I, out: &mut String) -> bool {
+    let mut dollar = false;
+    while let Some(ch) = chars.next() {
+        out.push(ch);
+        match ch {
+            '"' => return true,
+            '\\' => {
+                if let Some(next) = chars.next() {
+                    out.push(next);
+                }
+            }
+            '(' if dollar && !take_substitution(chars, out) => return false,
+            '`' if !take_backticks(chars, out) => return false,
+            _ => {}
+        }
+        dollar = ch == '$';
+    }
+    false
+}
+
+/// Moves the rest of a command substitution in backticks from `chars`, after the opening '`', to
+/// `out` up to and including the closing '`'. Escaped backticks within don't end it, like in
+/// "`echo \``". Yields whether the substitution is terminated.
+pub(crate) fn take_backticks<I: Iterator<Item = char>>(chars: &mut I, out: &mut String) -> bool {
+    while let Some(ch) = chars.next() {
+        out.push(ch);
+        match ch {
+            '`' => return true,
+            '\\' => {
+                if let Some(next) = chars.next() {
+                    out.push(next);
+                }
+            }
+            _ => {}
+        }
+    }
+    false
+}
+
+/// Yields the command of a substitution in backticks without them, where a backslash only escapes
+/// '`', '$', or '\', like "echo `date`" for "`echo \`date\``".
+fn backticks_command(text: &str) -> String {
+    let mut command = String::with_capacity(text.len());
+    let mut chars = text[1..text.len() - 1].chars().peekable();
+    while let Some(ch) = chars.next() {
+        match chars.peek() {
+            Some('`' | '$' | '\\') if ch == '\\' => command.extend(chars.next()),
+            _ => command.push(ch),
+        }
+    }
+    command
+}
+
+/// Replaces each command substitution in `input`, like "$(date)" or "`date`", with the text yielded
+/// by `output` for its command and whether it is within double quotes, like in `echo "$(date)"`.
+/// Nested substitutions are part of the command, like "echo $(date)" of "$(echo $(date))", and
+/// nothing is replaced within single quotes or when escaped, like "\$(date)" or "\`".
+pub fn replace_substitutions<F>(input: &str, mut output: F) -> Result<String, CommandArgsSplitError>
+where
+    F: FnMut(&str, bool) -> String,
+{
+    let mut res = String::with_capacity(input.len());
+    let mut double_quoted = false;
+    let mut chars = input.chars().enumerate().peekable();
+    while let Some((pos, ch)) = chars.next() {
+        match ch {
+            '\\' => {
+                res.push(ch);
+                if let Some((_, next)) = chars.next() {
+                    res.push(next);
+                }
+            }
+            '\'' if !double_quoted => {
+                res.push(ch);
+                for (_, next) in chars.by_ref() {
+                    res.push(next);
+                    if next == ch {
+                        break;
+                    }
+                }
+            }
+            '"' => {
+                double_quoted = !double_quoted;
+                res.push(ch);
+            }
+            '$' if chars.peek().map(|(_, next)| *next) == Some('(') => {
+                chars.next();
+                let mut command = String::new();
+                if !take_substitution(&mut chars.by_ref().map(|(_, ch)| ch), &mut command) {
+                    return Err(CommandArgsSplitError {
+                        kind: SplitErrorKind::UnterminatedSubstitution,
+                        pos,
+                        input: input.to_string(),
+                    });
+                }
+                res.push_str(&output(&command[..command.len() - 1], double_quoted));
+            }
+            '`' => {
+                let mut text = String::from(ch);
+                if !take_backticks(&mut chars.by_ref().map(|(_, ch)| ch), &mut text) {
+                    return Err(CommandArgsSplitError {
+                        kind: SplitErrorKind::UnterminatedBacktick,
+                        pos,
+                        input: input.to_string(),
+                    });
+                }
+                res.push_str(&output(&backticks_command(&text), double_quoted));
+            }
+            _ => res.push(ch),
+        }
+    }
+    Ok(res)
+}
+
+/// Yields the expression of a command substitution that is an arithmetic expansion, like "1 + 2"
+/// for "(1 + 2))" after the "$(" of "$((1 + 2))". A command in a subshell isn't one, like
+/// "(a) | (b))" of "$((a) | (b))", since the first '(' isn't matched by the last but one ')'.
+fn arithmetic_expression(command: &str) -> Option<&str> {
+    let expr = command.strip_prefix('(')?.strip_suffix("))")?;
+    let mut depth = 0;
+    for ch in expr.chars() {
+        match ch {
+            '(' => depth += 1,
+            ')' if depth == 0 => return None,
+            ')' => depth -= 1,
+            _ => {}
+        }
+    }
+    Some(expr)
+}
+
+/// Replaces each arithmetic expansion in `input`, like "$((1 + 2))", with the text yielded by
+/// `eval` for its expression, or stops at the first error of `eval`. Nothing is replaced within
+/// single quotes, command substitutions, or when escaped, like "\$((1 + 2))". Unterminated
+/// expansions are kept as is since `split_statements()` has already reported them.
+pub fn replace_arithmetic<F, E>(input: &str, mut eval: F) -> Result<String, E>
+where
+    F: FnMut(&str) -> Result<String, E>,
+{
+    let mut res = String::with_capacity(input.len());
+    let mut double_quoted = false;
+    let mut chars = input.chars().peekable();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '\\' => {
+                res.push(ch);
+                res.extend(chars.next());
+            }
+            '\'' if !double_quoted => {
+                res.push(ch);
+                for next in chars.by_ref() {
+                    res.push(next);
+                    if next == ch {
+                        break;
+                    }
+                }
+            }
+            '"' => {
+                double_quoted = !double_quoted;
+                res.push(ch);
+            }
+            '$' if chars.peek() == Some(&'(') => {
+                chars.next();
+                let mut command = String::new();
+                let terminated = take_substitution(&mut chars, &mut command);
+                match arithmetic_expression(&command) {
+                    Some(expr) if terminated => res.push_str(&eval(expr)?),
+                    _ => {
+                        res.push_str("$(");
+                        res.push_str(&command);
+                    }
+                }
+            }
+            '`' => {
+                res.push(ch);
+                take_backticks(&mut chars, &mut res);
+            }
+            _ => res.push(ch),
+        }
+    }
+    Ok(res)
+}
+
+/// Splits a trailing '&' off statement `input`, like "sleep 10 &", and yields the rest and whether
+/// it is to run in the background. An escaped '&', like "echo a\&", is kept. Statements are
+/// expected to be split by `split_statements()` already, so "&&" isn't a separator here.
+pub fn split_background(input: &str) -> (&str, bool) {
+    let input = input.trim_end();
+    let rest = match input.strip_suffix('&') {
+        Some(rest) => rest,
+        None => return (input, false),
+    };
+    let backslashes = rest.chars().rev().take_while(|&ch| ch == '\\').count();
+    if backslashes % 2 == 1 {
+        return (input, false);
+    }
+    (rest.trim_end(), true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn split_ok(input: &str) -> Vec<String> {
+        split(input).unwrap()
+    }
+
+    fn split_err(input: &str) -> (SplitErrorKind, usize) {
+        let err = split(input).unwrap_err();
+        assert_eq!(err.input, input);
+        (err.kind, err.pos)
+    }
+
+    #[test]
+    fn split_whitespace() {
+        assert_eq!(split_ok(""), Vec::<String>::new());
+        assert_eq!(split_ok("  "), Vec::<String>::new());
+        assert_eq!(split_ok("a b\tc\n d "), vec!["a", "b", "c", "d"]);
+    }
+
+    #[test]
+    fn split_single_quotes() {
+        assert_eq!(split_ok("'a b' c"), vec!["a b", "c"]);
+        assert_eq!(split_ok(r#"'a "b" \c'"#), vec![r#"a "b" \c"#]);
+        assert_eq!(split_ok("''"), vec![""]);
+    }
+
+    #[test]
+    fn split_double_quotes() {
+        assert_eq!(split_ok(r#""a b" c"#), vec!["a b", "c"]);
+        assert_eq!(split_ok(r#""a 'b'""#), vec!["a 'b'"]);
+        assert_eq!(split_ok(r#""\"\\\$\`\n""#), vec![r#""\$`\n"#]);
+        assert_eq!(split_ok(r#""""#), vec![""]);
+    }
+
+    #[test]
+    fn split_adjacent_segments() {
+        assert_eq!(split_ok(r#"a"b c"'d e'f"#), vec!["ab cd ef"]);
+    }
+
+    #[test]
+    fn split_backslash() {
+        assert_eq!(split_ok(r"a\ b c"), vec!["a b", "c"]);
+        assert_eq!(split_ok(r"\'a\'"), vec!["'a'"]);
+        assert_eq!(split_ok("a\\\nb"), vec!["ab"]);
+    }
+
+    #[test]
+    fn split_comment() {
+        assert_eq!(split_ok("a #b c"), vec!["a"]);
+        assert_eq!(split_ok("a#b"), vec!["a#b"]);
+        assert_eq!(split_ok("a #b\nc"), vec!["a", "c"]);
+    }
+
+    #[test]
+    fn unterminated_single_quote() {
+        assert_eq!(
+            split_err("echo 'hello"),
+            (SplitErrorKind::UnterminatedSingleQuote, 5)
+        );
+        assert_eq!(
+            split_err(r#"echo "before" 'hello "after""#),
+            (SplitErrorKind::UnterminatedSingleQuote, 14)
+        );
+    }
+
+    #[test]
+    fn unterminated_double_quote() {
+        assert_eq!(
+            split_err("echo \"hello"),
+            (SplitErrorKind::UnterminatedDoubleQuote, 5)
+        );
+        assert_eq!(
+            split_err("echo 'before' \"hello 'after'"),
+            (SplitErrorKind::UnterminatedDoubleQuote, 14)
+        );
+    }
+
+    #[test]
+    fn dangling_backslash() {
+        assert_eq!(
+            split_err(r"echo a\"),
+            (SplitErrorKind::DanglingBackslash, 6)
+        );
+        assert_eq!(
+            split_err(r#"echo "a\"#),
+            (SplitErrorKind::DanglingBackslash, 7)
+        );
+    }
+
+    #[test]
+    fn position_counts_chars() {
+        assert_eq!(
+            split_err("ø 'a"),
+            (SplitErrorKind::UnterminatedSingleQuote, 2)
+        );
+    }
+
+    #[test]
+    fn error_display() {
+        let err = split("echo \"hello").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Syntax error: unterminated double quote\n  echo \"hello\n       ^"
+        );
+    }
+
+    #[test]
+    fn first_word() {
+        assert_eq!(super::first_word(""), ("", ""));
+        assert_eq!(super::first_word("  ls -l"), ("ls", " -l"));
+        assert_eq!(super::first_word("ls"), ("ls", ""));
+        assert_eq!(super::first_word("A='x y' prog"), ("A='x y'", " prog"));
+        assert_eq!(
+            super::first_word(r#"A="x \" y" prog"#),
+            (r#"A="x \" y""#, " prog")
+        );
+        assert_eq!(super::first_word(r"A=x\ y prog"), (r"A=x\ y", " prog"));
+    }
+
+    #[test]
+    fn raw_words() {
+        assert!(super::raw_words("  ").is_empty());
+        assert_eq!(super::raw_words(" ls  -l "), vec!["ls", "-l"]);
+        assert_eq!(
+            super::raw_words(r#"echo 'a b' "c \" d" e\ f"#),
+            vec!["echo", "'a b'", r#""c \" d""#, r"e\ f"]
+        );
+        assert_eq!(
+            super::raw_words("echo x$(echo {a,b} ')') `date +%s`"),
+            vec!["echo", "x$(echo {a,b} ')')", "`date +%s`"]
+        );
+        assert_eq!(
+            super::raw_words(r#"echo "$(date +"%Y %m")""#),
+            vec!["echo", r#""$(date +"%Y %m")""#]
+        );
+    }
+
+    fn statements(input: &str) -> Vec<(Separator, String)> {
+        split_statements(input).unwrap()
+    }
+
+    #[test]
+    fn split_statements_single() {
+        assert_eq!(
+            statements("echo a b"),
+            vec![(Separator::Sequence, "echo a b".to_string())]
+        );
+        assert_eq!(statements(""), vec![]);
+    }
+
+    #[test]
+    fn split_statements_separators() {
+        assert_eq!(
+            statements("echo a; echo b\necho c && echo d || echo e"),
+            vec![
+                (Separator::Sequence, "echo a".to_string()),
+                (Separator::Sequence, "echo b".to_string()),
+                (Separator::Sequence, "echo c".to_string()),
+                (Separator::And, "echo d".to_string()),
+                (Separator::Or, "echo e".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn split_statements_skips_empty() {
+        assert_eq!(
+            statements(";echo a;; ;echo b;"),
+            vec![
+                (Separator::Sequence, "echo a".to_string()),
+                (Separator::Sequence, "echo b".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn split_statements_keeps_quoted_and_escaped() {
+        assert_eq!(
+            statements(r#"echo 'a; b' "c && d" e\;f "g\" || h""#),
+            vec![(
+                Separator::Sequence,
+                r#"echo 'a; b' "c && d" e\;f "g\" || h""#.to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn split_statements_single_ampersand_and_pipe() {
+        assert_eq!(
+            statements("sleep 1 & echo a | b"),
+            vec![(Separator::Sequence, "sleep 1 & echo a | b".to_string())]
+        );
+    }
+
+    #[test]
+    fn split_statements_comment() {
+        assert_eq!(
+            statements("echo a # b; c\necho d; echo#e"),
+            vec![
+                (Separator::Sequence, "echo a".to_string()),
+                (Separator::Sequence, "echo d".to_string()),
+                (Separator::Sequence, "echo#e".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn split_pipeline() {
+        assert_eq!(super::split_pipeline("ls -l").unwrap(), vec!["ls -l"]);
+        assert_eq!(
+            super::split_pipeline("ls | grep foo|wc -l").unwrap(),
+            vec!["ls", "grep foo", "wc -l"]
+        );
+        assert_eq!(
+            super::split_pipeline(r#"echo "a | b" 'c|d' e\|f | cat"#).unwrap(),
+            vec![r#"echo "a | b" 'c|d' e\|f"#, "cat"]
+        );
+        assert_eq!(
+            super::split_pipeline(r#"echo "\"|" | cat"#).unwrap(),
+            vec![r#"echo "\"|""#, "cat"]
+        );
+    }
+
+    #[test]
+    fn split_pipeline_stderr() {
+        assert_eq!(
+            super::split_pipeline("make |& grep error|&wc -l | cat").unwrap(),
+            vec!["make 2>&1", "grep error 2>&1", "wc -l", "cat"]
+        );
+        assert_eq!(
+            super::split_pipeline("echo '|&' |& cat").unwrap(),
+            vec!["echo '|&' 2>&1", "cat"]
+        );
+    }
+
+    #[test]
+    fn split_pipeline_missing_command() {
+        for (input, pos) in [
+            ("| cat", 0),
+            ("ls |", 3),
+            ("ls |  ", 3),
+            ("ls | | cat", 5),
+            ("ls |&", 3),
+            ("|& cat", 0),
+        ] {
+            let err = super::split_pipeline(input).unwrap_err();
+            assert_eq!(err.kind, SplitErrorKind::MissingPipelineCommand);
+            assert_eq!(err.pos, pos, "{}", input);
+        }
+    }
+
+    #[test]
+    fn split_statements_substitution_kept() {
+        assert_eq!(
+            super::split_statements("echo $(a; b && c) ; ls").unwrap(),
+            vec![
+                (Separator::Sequence, "echo $(a; b && c)".to_string()),
+                (Separator::Sequence, "ls".to_string())
+            ]
+        );
+        assert_eq!(
+            super::split_pipeline("echo $(ls | wc -l) | cat").unwrap(),
+            vec!["echo $(ls | wc -l)", "cat"]
+        );
+        assert_eq!(
+            super::split_statements(r#"echo "$(echo "a;b")"; ls"#).unwrap(),
+            vec![
+                (Separator::Sequence, r#"echo "$(echo "a;b")""#.to_string()),
+                (Separator::Sequence, "ls".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn replace_substitutions() {
+        let replace = |input| {
+            super::replace_substitutions(input, |command, quoted| {
+                format!("<{}{}>", command, if quoted { " quoted" } else { "" })
+            })
+        };
+        assert_eq!(replace("echo $(date)").unwrap(), "echo <date>");
+        assert_eq!(
+            replace(r#"echo "a $(date)""#).unwrap(),
+            r#"echo "a <date quoted>""#
+        );
+        assert_eq!(replace("echo '$(date)'").unwrap(), "echo '$(date)'");
+        assert_eq!(replace(r"echo \$(date)").unwrap(), r"echo \$(date)");
+        assert_eq!(
+            replace("echo $(echo $(date)) $(echo ')')").unwrap(),
+            "echo <echo $(date)> <echo ')'>"
+        );
+        assert_eq!(
+            replace(r#"echo "'$(a)'""#).unwrap(),
+            r#"echo "'<a quoted>'""#
+        );
+        assert_eq!(replace("echo $HOME (x)").unwrap(), "echo $HOME (x)");
+
+        let err = replace("echo $(date").unwrap_err();
+        assert_eq!(err.kind, SplitErrorKind::UnterminatedSubstitution);
+        assert_eq!(err.pos, 5);
+    }
+
+    #[test]
+    fn replace_arithmetic() {
+        let replace = |input| {
+            super::replace_arithmetic(input, |expr| match expr {
+                "1 / 0" => Err(expr.to_string()),
+                _ => Ok(format!("<{}>", expr)),
+            })
+        };
+        assert_eq!(replace("echo $((1 + 2))").unwrap(), "echo <1 + 2>");
+        assert_eq!(
+            replace(r#"echo "a $(( (1 + 2) * 3 ))""#).unwrap(),
+            r#"echo "a < (1 + 2) * 3 >""#
+        );
+        assert_eq!(replace("echo '$((1))'").unwrap(), "echo '$((1))'");
+        assert_eq!(replace(r"echo \$((1))").unwrap(), r"echo \$((1))");
+        assert_eq!(
+            replace("echo $(echo $((1))) `echo $((2))`").unwrap(),
+            "echo $(echo $((1))) `echo $((2))`"
+        );
+        assert_eq!(replace("echo $((a) | (b))").unwrap(), "echo $((a) | (b))");
+        assert_eq!(replace("echo $(date) $((1").unwrap(), "echo $(date) $((1");
+        assert_eq!(replace("echo $((1 / 0))").unwrap_err(), "1 / 0");
+    }
+
+    #[test]
+    fn replace_backticks() {
+        let replace = |input| {
+            super::replace_substitutions(input, |command, quoted| {
+                format!("<{}{}>", command, if quoted { " quoted" } else { "" })
+            })
+        };
+        assert_eq!(replace("echo `date`").unwrap(), "echo <date>");
+        assert_eq!(
+            replace(r#"echo "`date` x""#).unwrap(),
+            r#"echo "<date quoted> x""#
+        );
+        assert_eq!(replace("echo '`date`'").unwrap(), "echo '`date`'");
+        assert_eq!(replace(r"echo \`date\`").unwrap(), r"echo \`date\`");
+        assert_eq!(
+            replace(r"echo `echo \`date\`` `printf '\\n\$'`").unwrap(),
+            r"echo <echo `date`> <printf '\n$'>"
+        );
+        assert_eq!(
+            replace("echo $(echo `date`)").unwrap(),
+            "echo <echo `date`>"
+        );
+
+        let err = replace("echo `date").unwrap_err();
+        assert_eq!(err.kind, SplitErrorKind::UnterminatedBacktick);
+        assert_eq!(err.pos, 5);
+    }
+
+    #[test]
+    fn split_backticks() {
+        assert_eq!(
+            super::split_statements("echo `a; b` | cat; ls").unwrap(),
+            vec![
+                (Separator::Sequence, "echo `a; b` | cat".to_string()),
+                (Separator::Sequence, "ls".to_string())
+            ]
+        );
+        assert_eq!(
+            super::split_pipeline("echo `a | b` | cat").unwrap(),
+            vec!["echo `a | b`", "cat"]
+        );
+        assert_eq!(super::split(r"a\`b").unwrap(), vec!["a`b"]);
+
+        let err = super::split_statements("echo \"`date\"").unwrap_err();
+        assert_eq!(err.kind, SplitErrorKind::UnterminatedBacktick);
+        assert_eq!(err.pos, 6);
+    }
+
+    #[test]
+    fn split_background() {
+        assert_eq!(super::split_background("sleep 10 &"), ("sleep 10", true));
+        assert_eq!(super::split_background("sleep 10&  "), ("sleep 10", true));
+        assert_eq!(super::split_background("sleep 10"), ("sleep 10", false));
+        assert_eq!(super::split_background(r"echo a\&"), (r"echo a\&", false));
+        assert_eq!(super::split_background(r"echo a\\&"), (r"echo a\\", true));
+        assert_eq!(super::split_background("echo '&'"), ("echo '&'", false));
+        assert_eq!(super::split_background("&"), ("", true));
+    }
+
+    #[test]
+    fn split_redirects() {
+        let stdout = |path: &str, append| Redirection::Stdout {
+            path: path.to_string(),
+            append,
+        };
+        let stderr = |path: &str, append| Redirection::Stderr {
+            path: path.to_string(),
+            append,
+        };
+        let words = |words: &[&str]| words.iter().map(|w| w.to_string()).collect::<Vec<_>>();
+        assert_eq!(
+            super::split_redirects("hello > out.txt").unwrap(),
+            (words(&["hello"]), vec![stdout("out.txt", false)])
+        );
+        assert_eq!(
+            super::split_redirects(">> 'my log' a b").unwrap(),
+            (words(&["a", "b"]), vec![stdout("my log", true)])
+        );
+        assert_eq!(
+            super::split_redirects("a &> all.txt").unwrap(),
+            (
+                words(&["a"]),
+                vec![stdout("all.txt", false), Redirection::StderrToStdout]
+            )
+        );
+        assert_eq!(
+            super::split_redirects("a &>> all.txt").unwrap(),
+            (
+                words(&["a"]),
+                vec![stdout("all.txt", true), Redirection::StderrToStdout]
+            )
+        );
+        assert_eq!(
+            super::split_redirects("a 2> e.txt b 2>> e2.txt").unwrap(),
+            (
+                words(&["a", "b"]),
+                vec![stderr("e.txt", false), stderr("e2.txt", true)]
+            )
+        );
+
+        // The order is kept.
+        assert_eq!(
+            super::split_redirects("a 2>&1 > out.txt < in.txt").unwrap(),
+            (
+                words(&["a"]),
+                vec![
+                    Redirection::StderrToStdout,
+                    stdout("out.txt", false),
+                    Redirection::Stdin("in.txt".to_string())
+                ]
+            )
+        );
+
+        // Quoted or escaped ones are arguments.
+        assert_eq!(
+            super::split_redirects(r#"'>' ">>" \> '&>' "<" '2>&1' 2\> 'a>b' a\>b"#).unwrap(),
+            (
+                words(&[">", ">>", ">", "&>", "<", "2>&1", "2>", "a>b", "a>b"]),
+                vec![]
+            )
+        );
+    }
+
+    #[test]
+    fn split_redirects_attached() {
+        let stdout = |path: &str, append| Redirection::Stdout {
+            path: path.to_string(),
+            append,
+        };
+        let stdin = |path: &str| Redirection::Stdin(path.to_string());
+        let words = |words: &[&str]| words.iter().map(|w| w.to_string()).collect::<Vec<_>>();
+        assert_eq!(
+            super::split_redirects("echo hi>out").unwrap(),
+            (words(&["echo", "hi"]), vec![stdout("out", false)])
+        );
+        assert_eq!(
+            super::split_redirects("cat <in").unwrap(),
+            (words(&["cat"]), vec![stdin("in")])
+        );
+        assert_eq!(
+            super::split_redirects("ls >o5 2>&1 a>>'my log'<in").unwrap(),
+            (
+                words(&["ls", "a"]),
+                vec![
+                    stdout("o5", false),
+                    Redirection::StderrToStdout,
+                    stdout("my log", true),
+                    stdin("in")
+                ]
+            )
+        );
+
+        // An escaped '&' is an argument, but the operator following it isn't.
+        assert_eq!(
+            super::split_redirects(r"echo \&>>out").unwrap(),
+            (words(&["echo", "&"]), vec![stdout("out", true)])
+        );
+    }
+
+    #[test]
+    fn split_redirects_attached_stderr() {
+        let stderr = |path: &str, append| Redirection::Stderr {
+            path: path.to_string(),
+            append,
+        };
+        let all = |path: &str, append| {
+            vec![
+                Redirection::Stdout {
+                    path: path.to_string(),
+                    append,
+                },
+                Redirection::StderrToStdout,
+            ]
+        };
+        let words = |words: &[&str]| words.iter().map(|w| w.to_string()).collect::<Vec<_>>();
+        assert_eq!(
+            super::split_redirects("ls 2>/dev/null").unwrap(),
+            (words(&["ls"]), vec![stderr("/dev/null", false)])
+        );
+        assert_eq!(
+            super::split_redirects("make 2>>errs.txt").unwrap(),
+            (words(&["make"]), vec![stderr("errs.txt", true)])
+        );
+        assert_eq!(
+            super::split_redirects("make &>all.txt").unwrap(),
+            (words(&["make"]), all("all.txt", false))
+        );
+        assert_eq!(
+            super::split_redirects("make &>>all.txt").unwrap(),
+            (words(&["make"]), all("all.txt", true))
+        );
+
+        // Only a whole unquoted "2" is the file descriptor, like not in "a2>x" or "'2'>x".
+        assert_eq!(
+            super::split_redirects("a2>x '2'>y").unwrap(),
+            (
+                words(&["a2", "2"]),
+                vec![
+                    Redirection::Stdout {
+                        path: "x".to_string(),
+                        append: false
+                    },
+                    Redirection::Stdout {
+                        path: "y".to_string(),
+                        append: false
+                    }
+                ]
+            )
+        );
+    }
+
+    #[test]
+    fn split_redirects_missing_target() {
+        for (input, pos) in [
+            (">", 0),
+            ("a >", 2),
+            ("a b  >>  ", 5),
+            ("a &>", 2),
+            ("a <", 2),
+        ] {
+            let err = super::split_redirects(input).unwrap_err();
+            assert_eq!(err.kind, SplitErrorKind::MissingRedirectTarget);
+            assert_eq!(err.pos, pos, "{}", input);
+        }
+    }
+
+    #[test]
+    fn split_statements_error() {
+        let err = split_statements("echo a; echo 'b").unwrap_err();
+        assert_eq!(err.kind, SplitErrorKind::UnterminatedSingleQuote);
+        assert_eq!(err.pos, 13);
+    }
+}
@@ -3,12 +3,207 @@ use json::JsonValue;
 use regex::Regex;
 
 use std::collections::HashMap;
+use std::env;
+use std::ffi::{CStr, CString, OsStr, OsString};
+use std::iter::Peekable;
+use std::mem;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::ptr;
+use std::sync::Once;
+
+use unicode_width::UnicodeWidthChar;
+
+use crate::tokenizer;
 
 lazy_static! {
     static ref WORD_REGEX: Regex = Regex::new(r"(\w+)").unwrap();
     static ref GLOB_REGEX: Regex = Regex::new(r"(([\w\d.\\/\.]*\*[\w\d.\\/\.]*)+)").unwrap();
 }
 
+static HOME_DIR_WARNING: Once = Once::new();
+
+/// Yields the home directory of the user. If it can't be determined, like in some containers, it
+/// falls back to `$HOME` and then to the temporary directory, with a warning printed once.
+pub fn home_dir() -> PathBuf {
+    match known_home_dir(dirs_next::home_dir(), env::var_os("HOME")) {
+        Some(dir) => dir,
+        None => {
+            let dir = env::temp_dir();
+            HOME_DIR_WARNING.call_once(|| {
+                eprintln!(
+                    "Warning: could not determine home directory, using: {}",
+                    dir.display()
+                );
+            });
+            dir
+        }
+    }
+}
+
+/// Yields `home` if known, otherwise `env_home` if not empty.
+fn known_home_dir(home: Option<PathBuf>, env_home: Option<OsString>) -> Option<PathBuf> {
+    home.or_else(|| env_home.filter(|h| !h.is_empty()).map(PathBuf::from))
+}
+
+/// Yields the directory of carapace files, like config and history: "~/.carapace".
+pub fn carapace_dir() -> PathBuf {
+    home_dir().join(".carapace")
+}
+
+/// Yields `path` with the `home` directory prefix replaced by "~", like "~/src" for "/home/user/src".
+pub fn abbreviate_home(path: &Path, home: &Path) -> String {
+    if home.as_os_str().is_empty() || home == Path::new("/") {
+        return path.display().to_string();
+    }
+    match path.strip_prefix(home) {
+        Ok(rest) if rest.as_os_str().is_empty() => "~".to_string(),
+        Ok(rest) => format!("~/{}", rest.display()),
+        Err(_) => path.display().to_string(),
+    }
+}
+
+/// Yields `text` with control characters escaped, like "\n" for a newline, such that showing it,
+/// like in the prompt, doesn't move the cursor.
+pub fn printable(text: &str) -> String {
+    text.chars()
+        .map(|c| {
+            if c.is_control() {
+                c.escape_default().to_string()
+            } else {
+                c.to_string()
+            }
+        })
+        .collect()
+}
+
+/// Yields the number of terminal columns `text` takes up. ANSI escape sequences, like colors, and
+/// anything between the `\x01` and `\x02` markers of non-printing sequences take up none, while
+/// wide characters, like "日", take up two.
+pub fn display_width(text: &str) -> usize {
+    let mut width = 0;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\x1b' => {
+                // Control sequences end with a byte in the range '@' to '~'.
+                if chars.next_if_eq(&'[').is_some() {
+                    for c in chars.by_ref() {
+                        if ('@'..='~').contains(&c) {
+                            break;
+                        }
+                    }
+                }
+            }
+            '\x01' => chars.by_ref().take_while(|&c| c != '\x02').for_each(drop),
+            _ => width += c.width().unwrap_or(0),
+        }
+    }
+    width
+}
+
+/// Yields the home directory of `user`, if the user exists.
+fn user_home_dir(user: &str) -> Option<PathBuf> {
+    let name = CString::new(user).ok()?;
+    let mut passwd: libc::passwd = unsafe { mem::zeroed() };
+    let mut result = ptr::null_mut();
+    let mut buf = vec![0 as libc::c_char; 4096];
+    let code = unsafe {
+        libc::getpwnam_r(
+            name.as_ptr(),
+            &mut passwd,
+            buf.as_mut_ptr(),
+            buf.len(),
+            &mut result,
+        )
+    };
+    if code != 0 || result.is_null() {
+        return None;
+    }
+    let dir = unsafe { CStr::from_ptr(passwd.pw_dir) };
+    Some(PathBuf::from(OsStr::from_bytes(dir.to_bytes())))
+}
+
+/// Expands a leading "~" of `word` to the home directory, like "~/src" to "/home/user/src", and
+/// "~name" to the home directory of user "name". Unknown users are left as-is.
+pub fn expand_tilde(word: &str) -> String {
+    let rest = match word.strip_prefix('~') {
+        Some(rest) => rest,
+        None => return word.to_string(),
+    };
+    let (user, path) = match rest.find('/') {
+        Some(pos) => (&rest[..pos], &rest[pos + 1..]),
+        None => (rest, ""),
+    };
+    let dir = if user.is_empty() {
+        Some(home_dir())
+    } else {
+        user_home_dir(user)
+    };
+    match dir {
+        Some(dir) if rest.contains('/') => dir.join(path).display().to_string(),
+        Some(dir) => dir.display().to_string(),
+        None => word.to_string(),
+    }
+}
+
+/// Expands "~" of `word` like `expand_tilde()`, and also after each ':' if `word` is a list of
+/// paths, like "~/bin:~/sbin", or an assignment of one, like "PATH=~/bin:~/sbin" given to `export`.
+/// A "~" in the middle of a segment, like in "a~b", is kept as is.
+pub fn expand_tildes(word: &str) -> String {
+    let (name, list) = match word.split_once('=') {
+        Some((name, value)) if is_var_name(name) => (Some(name), value),
+        _ if word.starts_with('~') => (None, word),
+        _ => return word.to_string(),
+    };
+    let list: Vec<String> = split_path_list(list)
+        .into_iter()
+        .map(expand_tilde)
+        .collect();
+    match name {
+        Some(name) => format!("{}={}", name, list.join(":")),
+        None => list.join(":"),
+    }
+}
+
+/// Splits `list` at each ':' that is neither quoted nor escaped, like ["~/bin", "'a:b'"] for
+/// "~/bin:'a:b'".
+pub fn split_path_list(list: &str) -> Vec<&str> {
+    let mut segments = Vec::new();
+    let mut start = 0;
+    let mut quote = None;
+    let mut chars = list.char_indices();
+    while let Some((pos, ch)) = chars.next() {
+        match (quote, ch) {
+            (None | Some('"'), '\\') => {
+                chars.next();
+            }
+            (None, '\'' | '"') => quote = Some(ch),
+            (Some(open), _) if ch == open => quote = None,
+            (None, ':') => {
+                segments.push(&list[start..pos]);
+                start = pos + 1;
+            }
+            _ => {}
+        }
+    }
+    segments.push(&list[start..]);
+    segments
+}
+
+/// Whether `name` is a valid variable name, like "PATH" or "_x1", but not "1x" or "a-b".
+fn is_var_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    matches!(chars.next(), Some(ch) if ch.is_ascii_alphabetic() || ch == '_')
+        && chars.all(|ch| ch.is_ascii_alphanumeric() || ch == '_')
+}
+
+/// Whether the shell was started as a login shell, which is when its name is prefixed with '-',
+/// like "-carapace".
+pub fn is_login_shell(name: &str) -> bool {
+    name.starts_with('-')
+}
+
 /// Check if `pos`ition is within first word in `text`.
 pub fn in_first_word(pos: usize, text: &str) -> bool {
     if let Some(wpos) = text.find(char::is_whitespace) {
@@ -17,6 +212,130 @@ pub fn in_first_word(pos: usize, text: &str) -> bool {
     true
 }
 
+/// Check if `line` is empty, only whitespace, or a comment starting with '#'.
+pub fn is_blank_or_comment(line: &str) -> bool {
+    let line = line.trim_start();
+    line.is_empty() || line.starts_with('#')
+}
+
+/// Puts `value` in double quotes and escapes characters that are special within them, such that
+/// it can be used as input again.
+pub fn double_quote(value: &str) -> String {
+    let mut quoted = String::from("\"");
+    for ch in value.chars() {
+        if matches!(ch, '"' | '\\' | '$' | '`') {
+            quoted.push('\\');
+        }
+        quoted.push(ch);
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Escapes whitespace and characters that are special to the shell in `value` with backslashes,
+/// such that it is parsed as a single word, like "my\ file.txt" for "my file.txt".
+pub fn escape_word(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for ch in value.chars() {
+        if ch.is_whitespace()
+            || matches!(
+                ch,
+                '"' | '\''
+                    | '\\'
+                    | '$'
+                    | '`'
+                    | '*'
+                    | '#'
+                    | '~'
+                    | '|'
+                    | '&'
+                    | ';'
+                    | '<'
+                    | '>'
+                    | '('
+                    | ')'
+                    | '{'
+                    | '}'
+            )
+        {
+            escaped.push('\\');
+        }
+        escaped.push(ch);
+    }
+    escaped
+}
+
+/// Interprets the escape sequence following a backslash, which has already been consumed from
+/// `chars`, and yields the resulting bytes. Supports `\\`, `\a`, `\b`, `\e`, `\f`, `\n`, `\r`,
+/// `\t`, `\v`, octal `\NNN`, and hexadecimal `\xHH`, where the latter two yield a single byte
+/// like in other shells. Unknown sequences are kept as is.
+pub fn unescape_one<I>(chars: &mut Peekable<I>) -> Vec<u8>
+where
+    I: Iterator<Item = char>,
+{
+    let ch = match chars.next() {
+        Some(ch) => ch,
+        None => return b"\\".to_vec(),
+    };
+    let res = match ch {
+        '\\' => b'\\',
+        'a' => 0x07,
+        'b' => 0x08,
+        'e' => 0x1b,
+        'f' => 0x0c,
+        'n' => b'\n',
+        'r' => b'\r',
+        't' => b'\t',
+        'v' => 0x0b,
+        '0'..='7' => {
+            let mut value = ch.to_digit(8).unwrap();
+            for _ in 0..2 {
+                match chars.peek().and_then(|c| c.to_digit(8)) {
+                    Some(digit) => {
+                        value = value * 8 + digit;
+                        chars.next();
+                    }
+                    None => break,
+                }
+            }
+            value as u8
+        }
+        'x' => {
+            let mut value = None;
+            for _ in 0..2 {
+                match chars.peek().and_then(|c| c.to_digit(16)) {
+                    Some(digit) => {
+                        value = Some(value.unwrap_or(0) * 16 + digit);
+                        chars.next();
+                    }
+                    None => break,
+                }
+            }
+            match value {
+                Some(value) => value as u8,
+                None => return b"\\x".to_vec(),
+            }
+        }
+        _ => return format!("\\{}", ch).into_bytes(),
+    };
+    vec![res]
+}
+
+/// Interprets all escape sequences of `value` as done by `unescape_one`. Bytes that don't form
+/// valid UTF-8 are replaced.
+pub fn unescape(value: &str) -> String {
+    let mut output = Vec::new();
+    let mut chars = value.chars().peekable();
+    while let Some(ch) = chars.next() {
+        if ch == '\\' {
+            output.append(&mut unescape_one(&mut chars));
+        } else {
+            output.extend_from_slice(ch.encode_utf8(&mut [0; 4]).as_bytes());
+        }
+    }
+    String::from_utf8_lossy(&output).into_owned()
+}
+
 // TODO: -> Option<String>
 pub fn word_at_pos(pos: usize, text: &str) -> String {
     assert!(pos <= text.len());
@@ -61,10 +380,155 @@ pub fn json_obj_to_hash_map(obj: &JsonValue) -> HashMap<String, String> {
     map
 }
 
+/// Expands braces of `word` into the cartesian product of their alternatives, like Bash, such that
+/// "pre{a,b}post" yields ["preapost", "prebpost"] and "{a,b}{1,2}" yields ["a1", "a2", "b1", "b2"].
+/// Alternatives are comma lists, which can be nested, like "{a,b{1,2}}", or ranges of numbers or
+/// letters, like "{1..5}", "{10..0..5}", "{01..10}", or "{a..e}". Braces that are unbalanced, hold
+/// a single element, like "{a}", are quoted or escaped, or follow a '$' are kept as is.
+pub fn expand_braces(word: &str) -> Vec<String> {
+    let marks = brace_marks(word);
+    for (i, &(open, ch)) in marks.iter().enumerate() {
+        if ch != '{' || word[..open].ends_with('$') {
+            continue;
+        }
+
+        // Find the matching '}' and the commas separating the alternatives.
+        let mut depth = 0;
+        let mut commas = Vec::new();
+        let mut close = None;
+        for &(pos, ch) in &marks[i + 1..] {
+            match ch {
+                '{' => depth += 1,
+                '}' if depth == 0 => {
+                    close = Some(pos);
+                    break;
+                }
+                '}' => depth -= 1,
+                ',' if depth == 0 => commas.push(pos),
+                _ => {}
+            }
+        }
+        let close = match close {
+            Some(close) => close,
+            None => continue,
+        };
+
+        let alternatives = if commas.is_empty() {
+            match brace_range(&word[open + 1..close]) {
+                Some(alternatives) => alternatives,
+                None => continue,
+            }
+        } else {
+            let mut alternatives = Vec::new();
+            let mut start = open + 1;
+            for end in commas.into_iter().chain(std::iter::once(close)) {
+                alternatives.extend(expand_braces(&word[start..end]));
+                start = end + 1;
+            }
+            alternatives
+        };
+
+        let prefix = &word[..open];
+        let suffixes = expand_braces(&word[close + 1..]);
+        return alternatives
+            .iter()
+            .flat_map(|alt| {
+                suffixes
+                    .iter()
+                    .map(move |suffix| format!("{}{}{}", prefix, alt, suffix))
+            })
+            .collect();
+    }
+    vec![word.to_string()]
+}
+
+/// Yields the positions of '{', '}', and ',' in `word` that are neither quoted, escaped, nor part
+/// of a command substitution, like "$(echo {a,b})", whose command expands its own braces.
+fn brace_marks(word: &str) -> Vec<(usize, char)> {
+    let mut marks = Vec::new();
+    let mut quote = None;
+    let mut chars = word.char_indices().peekable();
+    while let Some((pos, ch)) = chars.next() {
+        match (quote, ch) {
+            (None | Some('"'), '\\') => {
+                chars.next();
+            }
+            (None | Some('"'), '$') if matches!(chars.peek(), Some((_, '('))) => {
+                chars.next();
+                let mut chars = chars.by_ref().map(|(_, ch)| ch);
+                tokenizer::take_substitution(&mut chars, &mut String::new());
+            }
+            (None | Some('"'), '`') => {
+                let mut chars = chars.by_ref().map(|(_, ch)| ch);
+                tokenizer::take_backticks(&mut chars, &mut String::new());
+            }
+            (None, '\'' | '"') => quote = Some(ch),
+            (Some(open), _) if ch == open => quote = None,
+            (None, '{' | '}' | ',') => marks.push((pos, ch)),
+            _ => {}
+        }
+    }
+    marks
+}
+
+/// Yields the elements of brace range `range`, like ["1", "3", "5"] for "1..5..2". Numbers with
+/// leading zeros are padded to the same width, like "08" and "09" of "08..10". Letters are escaped
+/// since ranges can span special characters, like '`' and '\' of "Z..a".
+fn brace_range(range: &str) -> Option<Vec<String>> {
+    let parts: Vec<&str> = range.split("..").collect();
+    let (start, end, step) = match parts[..] {
+        [start, end] => (start, end, 1),
+        [start, end, step] => (start, end, step.parse::<i64>().ok()?),
+        _ => return None,
+    };
+    let step = step.unsigned_abs().max(1) as usize;
+    let steps = |start: i64, end: i64| -> Vec<i64> {
+        if start <= end {
+            (start..=end).step_by(step).collect()
+        } else {
+            (end..=start).rev().step_by(step).collect()
+        }
+    };
+
+    if let (Ok(first), Ok(last)) = (start.parse::<i64>(), end.parse::<i64>()) {
+        let padded = |number: &str| {
+            let digits = number.trim_start_matches('-');
+            digits.len() > 1 && digits.starts_with('0')
+        };
+        let width = if padded(start) || padded(end) {
+            start.len().max(end.len())
+        } else {
+            0
+        };
+        return Some(
+            steps(first, last)
+                .iter()
+                .map(|number| format!("{:0width$}", number, width = width))
+                .collect(),
+        );
+    }
+
+    let letter = |text: &str| match text.as_bytes() {
+        [ch] if ch.is_ascii_alphabetic() => Some(*ch as i64),
+        _ => None,
+    };
+    let letters = steps(letter(start)?, letter(end)?);
+    Some(
+        letters
+            .iter()
+            .map(|ch| escape_word(&(*ch as u8 as char).to_string()))
+            .collect(),
+    )
+}
+
+/// Yields the paths matching glob pattern `input`, or `input` itself if none match or it isn't a
+/// valid pattern, like "a[*", as in Bash.
 pub fn expand_glob(input: &str) -> Vec<String> {
     let mut res = Vec::new();
-    for path in glob(input).unwrap().filter_map(Result::ok) {
-        res.push(path.to_str().unwrap().to_string());
+    if let Ok(paths) = glob(input) {
+        for path in paths.filter_map(Result::ok) {
+            res.push(path.to_str().unwrap().to_string());
+        }
     }
     if res.is_empty() {
         res.push(input.to_string());
@@ -76,6 +540,130 @@ pub fn expand_glob(input: &str) -> Vec<String> {
 mod tests {
     use super::*;
 
+    #[test]
+    fn expand_glob_invalid_pattern() {
+        assert_eq!(expand_glob("a[*"), vec!["a[*"]);
+        assert_eq!(expand_glob("[z-a]"), vec!["[z-a]"]);
+    }
+
+    #[test]
+    fn known_home_dir_prefers_home() {
+        let home = known_home_dir(Some(PathBuf::from("/home/a")), Some(OsString::from("/b")));
+        assert_eq!(home, Some(PathBuf::from("/home/a")));
+    }
+
+    #[test]
+    fn expand_tilde() {
+        let home = home_dir();
+        assert_eq!(super::expand_tilde("~"), home.display().to_string());
+        assert_eq!(
+            super::expand_tilde("~/"),
+            home.join("").display().to_string()
+        );
+        assert_eq!(
+            super::expand_tilde("~/src"),
+            home.join("src").display().to_string()
+        );
+        assert_eq!(super::expand_tilde("src/~"), "src/~");
+        let root = user_home_dir("root").unwrap();
+        assert_eq!(
+            super::expand_tilde("~root/x"),
+            root.join("x").display().to_string()
+        );
+        assert_eq!(super::expand_tilde("~no-such-user/x"), "~no-such-user/x");
+    }
+
+    #[test]
+    fn expand_tildes() {
+        let home = home_dir();
+        let (a, b) = (home.join("a"), home.join("b"));
+        let (a, b) = (a.display(), b.display());
+        assert_eq!(super::expand_tildes("~/a:~/b"), format!("{}:{}", a, b));
+        assert_eq!(super::expand_tildes("X=~/a:~/b"), format!("X={}:{}", a, b));
+        assert_eq!(super::expand_tildes("X=c:~/a"), format!("X=c:{}", a));
+        assert_eq!(super::expand_tildes("a~b"), "a~b");
+        assert_eq!(super::expand_tildes("c:~/a"), "c:~/a");
+        assert_eq!(super::expand_tildes("--opt=~/a"), "--opt=~/a");
+        assert_eq!(super::expand_tildes("X='~/a:~/b'"), "X='~/a:~/b'");
+    }
+
+    #[test]
+    fn split_path_list() {
+        assert_eq!(super::split_path_list("a"), vec!["a"]);
+        assert_eq!(super::split_path_list("a::b:"), vec!["a", "", "b", ""]);
+        assert_eq!(
+            super::split_path_list(r#"~/a:'b:c':"d:e":f\:g"#),
+            vec!["~/a", "'b:c'", r#""d:e""#, r"f\:g"]
+        );
+    }
+
+    #[test]
+    fn known_home_dir_falls_back_to_env() {
+        let home = known_home_dir(None, Some(OsString::from("/b")));
+        assert_eq!(home, Some(PathBuf::from("/b")));
+    }
+
+    #[test]
+    fn known_home_dir_none() {
+        assert_eq!(known_home_dir(None, None), None);
+        assert_eq!(known_home_dir(None, Some(OsString::new())), None);
+    }
+
+    #[test]
+    fn carapace_dir_in_home_dir() {
+        assert_eq!(carapace_dir(), home_dir().join(".carapace"));
+    }
+
+    #[test]
+    fn abbreviate_home_paths() {
+        let home = Path::new("/home/user");
+        assert_eq!(abbreviate_home(Path::new("/home/user"), home), "~");
+        assert_eq!(abbreviate_home(Path::new("/home/user/src"), home), "~/src");
+        assert_eq!(
+            abbreviate_home(Path::new("/home/username"), home),
+            "/home/username"
+        );
+        assert_eq!(abbreviate_home(Path::new("/tmp"), home), "/tmp");
+    }
+
+    #[test]
+    fn abbreviate_home_root_or_empty_home() {
+        assert_eq!(abbreviate_home(Path::new("/tmp"), Path::new("/")), "/tmp");
+        assert_eq!(abbreviate_home(Path::new("/tmp"), Path::new("")), "/tmp");
+    }
+
+    #[test]
+    fn printable() {
+        assert_eq!(super::printable("/tmp/日本"), "/tmp/日本");
+        assert_eq!(super::printable("a\nb\tc\x1b[0m"), "a\\nb\\tc\\u{1b}[0m");
+    }
+
+    #[test]
+    fn display_width() {
+        assert_eq!(super::display_width(""), 0);
+        assert_eq!(super::display_width("abc"), 3);
+        assert_eq!(super::display_width("日本"), 4);
+        assert_eq!(super::display_width("é"), 1);
+        assert_eq!(
+            super::display_width("\x1b[0m\x1b[32mab\x1b[1;34m日\x1b[0m"),
+            4
+        );
+        assert_eq!(
+            super::display_width("\x01\x1b[32m\x02ab\x01ignored\x02c"),
+            3
+        );
+        assert_eq!(super::display_width("a\x07b"), 2);
+    }
+
+    #[test]
+    fn is_login_shell() {
+        assert!(super::is_login_shell("-carapace"));
+        assert!(super::is_login_shell("-"));
+        assert!(!super::is_login_shell("carapace"));
+        assert!(!super::is_login_shell("/usr/bin/carapace"));
+        assert!(!super::is_login_shell(""));
+    }
+
     #[test]
     fn in_first_word_beginning() {
         assert!(in_first_word(0, "hello world"));
@@ -101,6 +689,82 @@ mod tests {
         assert!(!in_first_word(6, "hello world"));
     }
 
+    #[test]
+    fn is_blank_or_comment_empty() {
+        assert!(is_blank_or_comment(""));
+        assert!(is_blank_or_comment("   \t"));
+    }
+
+    #[test]
+    fn is_blank_or_comment_comment() {
+        assert!(is_blank_or_comment("# comment"));
+        assert!(is_blank_or_comment("  #comment"));
+    }
+
+    #[test]
+    fn is_blank_or_comment_command() {
+        assert!(!is_blank_or_comment("ls -l"));
+        assert!(!is_blank_or_comment("echo # not a whole-line comment"));
+    }
+
+    #[test]
+    fn double_quote_plain() {
+        assert_eq!(double_quote("/bin:/usr/bin"), r#""/bin:/usr/bin""#);
+    }
+
+    #[test]
+    fn double_quote_special_chars() {
+        assert_eq!(
+            double_quote(r#"say "hi" \ $HOME `x`"#),
+            r#""say \"hi\" \\ \$HOME \`x\`""#
+        );
+    }
+
+    #[test]
+    fn escape_word_plain() {
+        assert_eq!(escape_word("/tmp/file.txt"), "/tmp/file.txt");
+    }
+
+    #[test]
+    fn escape_word_special_chars() {
+        assert_eq!(escape_word("my file.txt"), r"my\ file.txt");
+        assert_eq!(escape_word("a\tb"), "a\\\tb");
+        assert_eq!(escape_word(r#"it's "$x" (1)"#), r#"it\'s\ \"\$x\"\ \(1\)"#);
+        assert_eq!(escape_word(r"a\b*;#"), r"a\\b\*\;\#");
+        assert_eq!(escape_word("{a,b}"), r"\{a,b\}");
+    }
+
+    #[test]
+    fn unescape_one_sequences() {
+        let unescape = |s: &str| unescape_one(&mut s.chars().peekable());
+        assert_eq!(unescape("n"), b"\n");
+        assert_eq!(unescape("t"), b"\t");
+        assert_eq!(unescape("\\"), b"\\");
+        assert_eq!(unescape("101"), b"A");
+        assert_eq!(unescape("0"), b"\0");
+        assert_eq!(unescape("377"), b"\xff");
+        assert_eq!(unescape("x41"), b"A");
+        assert_eq!(unescape("xff"), b"\xff");
+        assert_eq!(unescape("x"), b"\\x");
+        assert_eq!(unescape("q"), b"\\q");
+        assert_eq!(unescape(""), b"\\");
+    }
+
+    #[test]
+    fn unescape_string() {
+        assert_eq!(unescape(r"a\nb\tc\\d"), "a\nb\tc\\d");
+        assert_eq!(unescape("plain"), "plain");
+        assert_eq!(unescape(r"\q"), r"\q");
+        assert_eq!(unescape(r"\377"), "\u{fffd}");
+    }
+
+    #[test]
+    fn unescape_one_stops_after_sequence() {
+        let mut chars = "1018".chars().peekable();
+        assert_eq!(unescape_one(&mut chars), b"A");
+        assert_eq!(chars.collect::<String>(), "8");
+    }
+
     #[test]
     fn word_at_pos_beginning() {
         assert_eq!(word_at_pos(0, "hello world and universe"), "hello");
@@ -165,4 +829,57 @@ mod tests {
         assert!(map.contains_key("three"));
         assert_eq!(map.get("three"), Some(&"3".to_string()));
     }
+
+    #[test]
+    fn expand_braces_lists() {
+        assert_eq!(expand_braces("pre{a,b}post"), vec!["preapost", "prebpost"]);
+        assert_eq!(
+            expand_braces("file.{txt,md,rs}"),
+            vec!["file.txt", "file.md", "file.rs"]
+        );
+        assert_eq!(expand_braces("{a,b}{1,2}"), vec!["a1", "a2", "b1", "b2"]);
+        assert_eq!(expand_braces("{a,b{1,2}}"), vec!["a", "b1", "b2"]);
+        assert_eq!(expand_braces("x{a,}"), vec!["xa", "x"]);
+    }
+
+    #[test]
+    fn expand_braces_ranges() {
+        assert_eq!(expand_braces("{1..5}"), vec!["1", "2", "3", "4", "5"]);
+        assert_eq!(expand_braces("{3..-1}"), vec!["3", "2", "1", "0", "-1"]);
+        assert_eq!(expand_braces("{10..0..5}"), vec!["10", "5", "0"]);
+        assert_eq!(expand_braces("{08..10}"), vec!["08", "09", "10"]);
+        assert_eq!(expand_braces("{a..e..2}"), vec!["a", "c", "e"]);
+        assert_eq!(expand_braces("{C..A}x"), vec!["Cx", "Bx", "Ax"]);
+        assert_eq!(
+            expand_braces("{Z..a}"),
+            vec!["Z", "[", "\\\\", "]", "^", "_", "\\`", "a"]
+        );
+        assert_eq!(
+            expand_braces("v{1..2}.{a,b}"),
+            vec!["v1.a", "v1.b", "v2.a", "v2.b"]
+        );
+    }
+
+    #[test]
+    fn expand_braces_literal() {
+        for word in [
+            "{a}", "{}", "{a,b", "a,b}", "'{a,b}'", "\\{a,b}", "${a,b}", "{1..}", "{1..b}",
+            "{ab..c}",
+        ] {
+            assert_eq!(expand_braces(word), vec![word]);
+        }
+        assert_eq!(expand_braces("{a}{b,c}"), vec!["{a}b", "{a}c"]);
+        for word in ["$(echo {a,b})", "`echo {a,b}`", "\"$(echo {a,b})\""] {
+            assert_eq!(expand_braces(word), vec![word]);
+        }
+        assert_eq!(
+            expand_braces("{1,2}$(echo {a,b})"),
+            vec!["1$(echo {a,b})", "2$(echo {a,b})"]
+        );
+        assert_eq!(expand_braces("{x{a,b}"), vec!["{xa", "{xb"]);
+        assert_eq!(
+            expand_braces("\"{a,b}\"{1,2}"),
+            vec!["\"{a,b}\"1", "\"{a,b}\"2"]
+        );
+    }
 }
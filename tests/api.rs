@@ -0,0 +1,88 @@
+//! Tests using carapace as a library, like when embedded in another program.
+
+use std::any::Any;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use carapace::command::{self, Command};
+use carapace::context;
+use carapace::prompt::{Outcome, Prompt};
+
+/// Custom command that records its arguments.
+struct RecordCommand {
+    args: Vec<String>,
+    record: Rc<RefCell<Vec<String>>>,
+}
+
+impl Command for RecordCommand {
+    fn execute(&mut self, _prompt: &mut Prompt) -> Result<bool, i32> {
+        self.record.borrow_mut().append(&mut self.args);
+        Ok(true)
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+#[test]
+fn custom_command() {
+    let record = Rc::new(RefCell::new(Vec::new()));
+
+    let ctx = context::default();
+    {
+        let record = record.clone();
+        ctx.borrow_mut().register_command("record", move |args| {
+            Box::new(RecordCommand {
+                args,
+                record: record.clone(),
+            })
+        });
+    }
+
+    let mut prompt = Prompt::create(ctx);
+    let cmd = prompt.parse_command("record hello world");
+    assert_eq!(command::execute(cmd, &mut prompt), None);
+    assert_eq!(*record.borrow(), vec!["hello", "world"]);
+}
+
+#[test]
+fn run_tokens() {
+    let mut prompt = Prompt::create(context::default());
+    let outcome = prompt.run_tokens("echo".to_string(), vec!["hi".to_string()]);
+    assert_eq!(
+        outcome,
+        Outcome {
+            status: 0,
+            exit: None
+        }
+    );
+
+    let outcome = prompt.run_tokens("false".to_string(), vec![]);
+    assert_eq!(outcome.status, 1);
+    assert_eq!(prompt.context.borrow().env.get("?"), Some(&"1".to_string()));
+
+    let outcome = prompt.run_tokens("exit".to_string(), vec!["3".to_string()]);
+    assert_eq!(outcome.exit, Some(3));
+}
+
+#[test]
+fn run_tokens_not_expanded() {
+    let record = Rc::new(RefCell::new(Vec::new()));
+
+    let ctx = context::default();
+    {
+        let record = record.clone();
+        ctx.borrow_mut().register_command("record", move |args| {
+            Box::new(RecordCommand {
+                args,
+                record: record.clone(),
+            })
+        });
+    }
+
+    let mut prompt = Prompt::create(ctx);
+    let args = vec!["$HOME".to_string(), "*".to_string(), "'a b'".to_string()];
+    prompt.run_tokens("record".to_string(), args.clone());
+    assert_eq!(*record.borrow(), args);
+}
@@ -0,0 +1,703 @@
+//! Tests running the carapace binary to check behavior only observable at the process level, like
+//! which output stream is written to.
+
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Output, Stdio};
+
+use carapace::config::Config;
+use tempfile::TempDir;
+
+/// Runs carapace with `args` and `stdin` as input, using `home` as home directory so that no config
+/// or history of the user is touched.
+fn carapace(home: &Path, args: &[&str], stdin: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_carapace"))
+        .args(args)
+        .env("HOME", home)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(stdin.as_bytes())
+        .unwrap();
+    child.wait_with_output().unwrap()
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}
+
+#[test]
+fn xtrace_written_to_stderr() {
+    let home = TempDir::new().unwrap();
+    let output = carapace(home.path(), &["-s"], "set -x\necho hello\n");
+    assert!(output.status.success());
+    assert!(stdout(&output).contains("hello"));
+    assert!(!stdout(&output).contains("+carapace>"));
+    assert!(stderr(&output).contains("+carapace> echo hello"));
+}
+
+#[test]
+fn verbose_input_echo_written_to_stderr() {
+    let home = TempDir::new().unwrap();
+    let output = carapace(home.path(), &["-v", "-s"], "echo hello\n");
+    assert!(output.status.success());
+    assert_eq!(1, stdout(&output).matches("hello").count());
+    assert!(stderr(&output).contains("echo hello"));
+}
+
+#[test]
+fn verbose_level_from_command_line() {
+    let home = TempDir::new().unwrap();
+    let output = carapace(home.path(), &["-vvv", "-c", "set -o"], "");
+    assert!(output.status.success());
+    assert!(stdout(&output).contains("verbose            3      (command line)"));
+
+    let output = carapace(home.path(), &["-c", "set -o"], "");
+    assert!(stdout(&output).contains("verbose            0      (default)"));
+}
+
+#[test]
+fn inherited_output_is_not_decoded() {
+    let home = TempDir::new().unwrap();
+    // Use `env` to run the printf program instead of the builtin.
+    let output = carapace(home.path(), &["-c", r"env printf '\377\000\001\n'"], "");
+    assert!(output.status.success());
+    assert!(output.stdout.ends_with(&[0o377, 0, 1, b'\n']));
+}
+
+#[test]
+fn exit_codes_are_masked() {
+    let home = TempDir::new().unwrap();
+    for (arg, code) in [("256", 0), ("-1", 255), ("3", 3)] {
+        let output = carapace(home.path(), &["-c", &format!("exit {}", arg)], "");
+        assert_eq!(output.status.code(), Some(code), "exit {}", arg);
+    }
+}
+
+#[test]
+fn exit_requires_numeric_argument() {
+    let home = TempDir::new().unwrap();
+    let output = carapace(home.path(), &["-c", "exit foo"], "");
+    assert_eq!(output.status.code(), Some(2));
+    assert!(stderr(&output).contains("exit: foo: numeric argument required"));
+}
+
+#[test]
+fn banner_only_shown_interactively() {
+    let home = TempDir::new().unwrap();
+    let config = home.path().join("config.json");
+    std::fs::write(&config, r#"{"show_banner": true}"#).unwrap();
+    let config = config.to_str().unwrap();
+
+    let banner = format!("Carapace {}", env!("CARGO_PKG_VERSION"));
+    let output = carapace(home.path(), &["--config", config], "exit\n");
+    assert!(stdout(&output).contains(&banner));
+
+    let output = carapace(home.path(), &["--config", config, "-c", "exit"], "");
+    assert!(!stdout(&output).contains(&banner));
+
+    let output = carapace(home.path(), &["--config", config, "-s"], "exit\n");
+    assert!(!stdout(&output).contains(&banner));
+}
+
+#[test]
+fn source_cycle_detected() {
+    let home = TempDir::new().unwrap();
+    let file = home.path().join("init");
+    std::fs::write(&file, format!("source {}\n", file.display())).unwrap();
+
+    let output = carapace(
+        home.path(),
+        &["-c", &format!("source {}", file.display())],
+        "",
+    );
+    assert!(stderr(&output).contains("source: cycle detected:"));
+    assert_eq!(output.status.code(), Some(1));
+}
+
+#[test]
+fn version_includes_build_info() {
+    let home = TempDir::new().unwrap();
+    let output = carapace(home.path(), &["--version"], "");
+    let prefix = format!("Carapace {} (", env!("CARGO_PKG_VERSION"));
+    assert!(stdout(&output).starts_with(&prefix));
+}
+
+#[test]
+fn cd_logical_and_physical() {
+    let home = TempDir::new().unwrap();
+    let root = home.path().canonicalize().unwrap();
+    std::fs::create_dir(root.join("real")).unwrap();
+    std::os::unix::fs::symlink(root.join("real"), root.join("link")).unwrap();
+
+    let link = root.join("link");
+    let script = format!(
+        "cd {0}\nprintf '%s\\n' $PWD\ncd -P {0}\nprintf '%s\\n' $PWD $OLDPWD\n",
+        link.display()
+    );
+    let output = carapace(home.path(), &["-s"], &script);
+    let expected = format!(
+        "{}\n{}\n{}\n",
+        link.display(),
+        root.join("real").display(),
+        link.display()
+    );
+    assert!(stdout(&output).ends_with(&expected));
+}
+
+#[test]
+fn cd_recovers_from_removed_cwd() {
+    let home = TempDir::new().unwrap();
+    let root = home.path().canonicalize().unwrap();
+    let gone = root.join("gone");
+    std::fs::create_dir(&gone).unwrap();
+
+    let script = format!(
+        "cd {gone}\nrmdir {gone}\ncd {root}\nprintf '%s\\n' $PWD $OLDPWD\n",
+        gone = gone.display(),
+        root = root.display()
+    );
+    let output = carapace(home.path(), &["-s"], &script);
+    assert!(output.status.success());
+    assert!(stderr(&output).contains("Current directory is missing:"));
+    let expected = format!("{}\n{}\n", root.display(), gone.display());
+    assert!(stdout(&output).ends_with(&expected));
+
+    // Relative paths are resolved from $PWD.
+    std::fs::create_dir(&gone).unwrap();
+    let script = format!(
+        "cd {gone}\nrmdir {gone}\ncd ..\nprintf '%s\\n' $PWD\n",
+        gone = gone.display()
+    );
+    let output = carapace(home.path(), &["-s"], &script);
+    assert!(stdout(&output).ends_with(&format!("{}\n", root.display())));
+}
+
+#[test]
+fn pushd_without_dir_swaps_top_entries() {
+    let home = TempDir::new().unwrap();
+    let root = home.path().canonicalize().unwrap();
+    let (a, b) = (root.join("a"), root.join("b"));
+    std::fs::create_dir(&a).unwrap();
+    std::fs::create_dir(&b).unwrap();
+
+    let script = format!(
+        "cd {}\npushd {}\npushd\nprintf '%s\\n' $PWD\npushd\nprintf '%s\\n' $PWD\n",
+        a.display(),
+        b.display()
+    );
+    let output = carapace(home.path(), &["-s"], &script);
+    // The stack is printed with the home directory abbreviated, and the initial directory at the
+    // bottom.
+    let cwd = std::env::current_dir().unwrap();
+    let expected = format!(
+        "[~/b] {cwd}\n{a}\n[~/a] {cwd}\n{b}\n",
+        cwd = cwd.display(),
+        a = a.display(),
+        b = b.display()
+    );
+    assert!(stdout(&output).ends_with(&expected));
+}
+
+#[test]
+fn command_string_runs_all_statements() {
+    let home = TempDir::new().unwrap();
+    let output = carapace(
+        home.path(),
+        &["-c", "echo a; echo b && echo c || echo d"],
+        "",
+    );
+    assert!(output.status.success());
+    assert!(stdout(&output).ends_with("a\nb\nc\n"));
+
+    let output = carapace(home.path(), &["-c", "false || exit 4; exit 5"], "");
+    assert_eq!(output.status.code(), Some(4));
+}
+
+#[test]
+fn and_or_bind_looser_than_pipes() {
+    let home = TempDir::new().unwrap();
+    let output = carapace(
+        home.path(),
+        &[
+            "-c",
+            "false && echo no || echo yes; printf 'a\\nb\\n' | grep -q b && echo piped | tr a-z A-Z",
+        ],
+        "",
+    );
+    assert!(output.status.success());
+    assert!(
+        stdout(&output).ends_with("yes\nPIPED\n"),
+        "{}",
+        stdout(&output)
+    );
+}
+
+#[test]
+fn confirm_commands_abort() {
+    let home = TempDir::new().unwrap();
+    let config = home.path().join("config.json");
+    std::fs::write(&config, r#"{"confirm_commands": ["rm -rf"]}"#).unwrap();
+    let file = home.path().join("keep");
+    std::fs::create_dir(&file).unwrap();
+
+    let command = format!("rm -rf {}", file.display());
+    let config = config.display().to_string();
+    let args = ["--config", config.as_str(), "-c", command.as_str()];
+    let output = carapace(home.path(), &args, "n\n");
+    assert!(stdout(&output).contains(&format!("Aborted: {}", command)));
+    assert!(file.exists());
+
+    let output = carapace(home.path(), &args, "y\n");
+    assert!(output.status.success());
+    assert!(!file.exists());
+}
+
+#[test]
+fn custom_config_created_and_loaded() {
+    let home = TempDir::new().unwrap();
+    let config = home.path().join("nested").join("dirs").join("config.json");
+    let config_arg = config.to_str().unwrap();
+
+    // Defaults are written when the file, and its folders, don't exist yet.
+    let output = carapace(home.path(), &["--config", config_arg, "-c", "exit"], "");
+    assert!(output.status.success());
+    assert!(std::fs::read_to_string(&config)
+        .unwrap()
+        .contains("\"max_history_size\": 1000"));
+    assert!(!home.path().join(".carapace").join("config.json").exists());
+
+    std::fs::write(&config, r#"{"env": {"FOO": "bar"}}"#).unwrap();
+    let output = carapace(
+        home.path(),
+        &["--config", config_arg, "-c", "printf '[%s]' $FOO"],
+        "",
+    );
+    assert!(stdout(&output).ends_with("[bar]"));
+}
+
+#[test]
+fn unset_path_warns_and_falls_back() {
+    let home = TempDir::new().unwrap();
+    let output = carapace(home.path(), &["-c", "unset PATH; printenv PATH"], "");
+    assert!(output.status.success());
+    assert!(stderr(&output).contains("unset: warning: unsetting PATH will break command lookup"));
+    assert!(stdout(&output).ends_with("/usr/bin:/sbin:/bin\n"));
+}
+
+#[test]
+fn command_string_exits_with_last_status() {
+    let home = TempDir::new().unwrap();
+    for (command, code) in [("false", 1), ("true", 0), ("true; sh -c 'exit 3'", 3)] {
+        let output = carapace(home.path(), &["-c", command], "");
+        assert_eq!(output.status.code(), Some(code), "{}", command);
+    }
+}
+
+#[test]
+fn stdin_exits_with_last_status() {
+    let home = TempDir::new().unwrap();
+    let output = carapace(home.path(), &["-s"], "true\nsh -c 'exit 3'\n");
+    assert_eq!(output.status.code(), Some(3));
+
+    let output = carapace(home.path(), &["-s"], "false\ntrue\n");
+    assert_eq!(output.status.code(), Some(0));
+
+    let output = carapace(home.path(), &["-s"], "set -e\nfalse\necho after\n");
+    assert_eq!(output.status.code(), Some(1));
+    assert!(!stdout(&output).contains("after"));
+}
+
+#[test]
+fn command_file_exits_with_last_status() {
+    let home = TempDir::new().unwrap();
+    let file = home.path().join("commands");
+    std::fs::write(&file, "# Comment.\nprintf '%s\\n' a\n\nsh -c 'exit 5'\n").unwrap();
+    let file = file.to_str().unwrap();
+
+    let output = carapace(home.path(), &["-f", file], "printf '%s\\n' b\n");
+    assert_eq!(output.status.code(), Some(5));
+    assert!(stdout(&output).ends_with("a\n"));
+
+    let output = carapace(home.path(), &["--command-file", file, "-s"], "");
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("cannot be used with"));
+
+    let missing = home.path().join("missing");
+    let output = carapace(home.path(), &["-f", missing.to_str().unwrap()], "");
+    assert_eq!(output.status.code(), Some(127));
+    assert!(stderr(&output).contains("Could not read commands from:"));
+}
+
+#[test]
+fn report_summary_of_stdin() {
+    let home = TempDir::new().unwrap();
+    let input = "true\ntrue\ntrue\nsh -c 'exit 2'\ntrue\n";
+    let output = carapace(home.path(), &["-s", "--report"], input);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(stdout(&output).ends_with("3 ok, 1 failed: line 4: sh -c 'exit 2' (exit 2)\n"));
+
+    let output = carapace(home.path(), &["-s", "--report"], "true\n");
+    assert!(output.status.success());
+    assert!(stdout(&output).ends_with("1 ok\n"));
+}
+
+#[test]
+fn dump_config_round_trips() {
+    let home = TempDir::new().unwrap();
+    let config = home.path().join("config.json");
+    let data = r#"{"max_history_size": 42, "edit_mode": "vi", "aliases": {"ll": "ls -l"},
+                   "confirm_commands": ["rm -rf"]}"#;
+    std::fs::write(&config, data).unwrap();
+
+    let args = ["--config", config.to_str().unwrap(), "--dump-config"];
+    let output = carapace(home.path(), &args, "");
+    assert!(output.status.success());
+
+    let mut dumped = Config::default();
+    assert!(dumped.decode(&stdout(&output)));
+    let mut expected = Config::default();
+    assert!(expected.decode(data));
+    dumped.loaded.clear();
+    expected.loaded.clear();
+    assert_eq!(dumped, expected);
+    assert!(!home.path().join(".carapace").join("history").exists());
+
+    let output = carapace(home.path(), &["--norc", "--dump-config"], "");
+    assert!(stdout(&output).starts_with(&Config::default().encode()));
+}
+
+#[test]
+fn on_cd_runs_once_per_directory_change() {
+    let home = TempDir::new().unwrap();
+    let root = home.path().canonicalize().unwrap();
+    let (a, b) = (root.join("a"), root.join("b"));
+    std::fs::create_dir(&a).unwrap();
+    std::fs::create_dir(&b).unwrap();
+    let dir = home.path().join(".carapace");
+    std::fs::create_dir(&dir).unwrap();
+    let config = dir.join("config.json");
+    std::fs::write(&config, r#"{"on_cd": "echo hook $PWD"}"#).unwrap();
+
+    // Changing to the same or a missing directory doesn't run the hook.
+    let script = format!(
+        "cd {a}\ncd {a}\ncd {root}/missing\npushd {b}\npopd\n",
+        a = a.display(),
+        b = b.display(),
+        root = root.display()
+    );
+    let output = carapace(home.path(), &["-s"], &script);
+    assert!(output.status.success());
+    let out = stdout(&output);
+    let hooks: Vec<&str> = out.lines().filter(|l| l.starts_with("hook ")).collect();
+    let expected = vec![
+        format!("hook {}", a.display()),
+        format!("hook {}", b.display()),
+        format!("hook {}", a.display()),
+    ];
+    assert_eq!(hooks, expected);
+
+    // A hook that changes directory itself doesn't run again.
+    let hook = format!(r#"{{"on_cd": "cd {}"}}"#, b.display());
+    std::fs::write(&config, hook).unwrap();
+    let script = format!("cd {}\nprintf '[%s]' $PWD\n", a.display());
+    let output = carapace(home.path(), &["-s"], &script);
+    assert!(output.status.success());
+    assert!(stdout(&output).ends_with(&format!("[{}]", b.display())));
+}
+
+#[test]
+fn pipeline() {
+    let home = TempDir::new().unwrap();
+    let script = "echo hello world | tr a-z A-Z\n\
+                  echo \"a | b\" 'c|d'\n\
+                  true | false\n\
+                  echo status $?\n";
+    let output = carapace(home.path(), &["-s"], script);
+    assert!(stdout(&output).ends_with("HELLO WORLD\na | b c|d\nstatus 1\n"));
+
+    let output = carapace(home.path(), &["-s"], "ls |\n");
+    assert!(stdout(&output).contains("missing command in pipeline"));
+}
+
+#[test]
+fn pipeline_with_stderr() {
+    let home = TempDir::new().unwrap();
+    let script = "sh -c 'echo err >&2' | grep -c err
+                  sh -c 'echo err >&2' |& grep -c err
+";
+    let output = carapace(home.path(), &["-s"], script);
+    assert!(stdout(&output).ends_with(
+        "0
+1
+"
+    ));
+    assert!(stderr(&output).contains("err"));
+}
+
+#[test]
+fn pipeline_with_builtins() {
+    let home = TempDir::new().unwrap();
+
+    // Builtins can be first, writing to the pipe, or last, where their output isn't piped.
+    let script = "echo marker\n\
+                  history | tr a-z A-Z\n\
+                  echo ignored | printf '%s\\n' last\n\
+                  printf '%s\\n' b a | sort\n";
+    let output = carapace(home.path(), &["-s"], script);
+    assert!(output.status.success());
+    let out = stdout(&output);
+    assert!(out.contains("ECHO MARKER"), "{}", out);
+    assert!(out.ends_with("last\na\nb\n"), "{}", out);
+}
+
+#[test]
+fn output_redirection() {
+    let home = TempDir::new().unwrap();
+
+    // Builtins and programs, also at the end of a pipeline, with "~" expanded in the target.
+    let script = "printf '%s\\n' a > ~/out.txt\n\
+                  printf '%s\\n' b >> ~/out.txt\n\
+                  echo c | tr c C >> ~/out.txt\n\
+                  echo shown\n";
+    let output = carapace(home.path(), &["-s"], script);
+    assert!(output.status.success());
+    assert!(stdout(&output).ends_with("shown\n"), "{}", stdout(&output));
+    assert_eq!(
+        std::fs::read_to_string(home.path().join("out.txt")).unwrap(),
+        "a\nb\nC\n"
+    );
+}
+
+#[test]
+fn source_errexit_reports_failing_line() {
+    let home = TempDir::new().unwrap();
+    let script = home.path().join("script");
+    std::fs::write(&script, "true\n\nsh -c 'exit 4'\necho after\n").unwrap();
+
+    let input = format!("source -e {}\n", script.display());
+    let output = carapace(home.path(), &["-s"], &input);
+    assert_eq!(output.status.code(), Some(4));
+    assert!(!stdout(&output).contains("after"));
+    assert!(
+        stderr(&output).contains(&format!(
+            "source: {}: line 3: sh -c 'exit 4'",
+            script.display()
+        )),
+        "{}",
+        stderr(&output)
+    );
+}
+
+#[test]
+fn complete_lists_specs() {
+    let home = TempDir::new().unwrap();
+    let script = "complete -f vim\ncomplete -r cd\necho listed\ncomplete\n";
+    let output = carapace(home.path(), &["-s"], script);
+    assert!(output.status.success());
+    assert!(
+        stdout(&output).ends_with("listed\ncomplete -d pushd\ncomplete -f vim\n"),
+        "{}",
+        stdout(&output)
+    );
+}
+
+#[test]
+fn norc_skips_config_and_history() {
+    let home = TempDir::new().unwrap();
+    let dir = home.path().join(".carapace");
+    std::fs::create_dir(&dir).unwrap();
+    std::fs::write(dir.join("config.json"), r#"{"env": {"FOO": "bar"}}"#).unwrap();
+
+    let output = carapace(home.path(), &["-c", "printf '[%s]' $FOO"], "");
+    assert!(stdout(&output).ends_with("[bar]"));
+    assert!(dir.join("history").exists());
+    std::fs::remove_file(dir.join("history")).unwrap();
+
+    for flag in &["--norc", "--noprofile"] {
+        let output = carapace(home.path(), &[flag, "-c", "printf '[%s]' $FOO"], "");
+        assert!(output.status.success());
+        assert!(!stdout(&output).contains("bar"));
+        assert!(!dir.join("history").exists());
+    }
+}
+
+#[test]
+fn no_home_env_does_not_panic() {
+    let output = Command::new(env!("CARGO_BIN_EXE_carapace"))
+        .args(["-c", "exit 7"])
+        .env_remove("HOME")
+        .stdin(Stdio::null())
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(7));
+}
+
+#[test]
+fn time_startup_written_to_stderr() {
+    let home = TempDir::new().unwrap();
+    let output = carapace(home.path(), &["--time-startup", "-c", "printf hi"], "");
+    assert!(output.status.success());
+    assert!(stdout(&output).ends_with("hi"));
+    let err = stderr(&output);
+    for phase in &[
+        "config load",
+        "PATH rehash",
+        "history load",
+        "env setup",
+        "total",
+    ] {
+        assert!(err.contains(&format!("{}: ", phase)), "{}", err);
+    }
+
+    let output = carapace(home.path(), &["-c", "printf hi"], "");
+    assert!(!stderr(&output).contains("total: "));
+}
+
+#[test]
+fn command_substitution_captures_output() {
+    let home = TempDir::new().unwrap();
+    let output = carapace(
+        home.path(),
+        &["-c", r#"printf '[%s]' $(echo a b) "$(complete -p none)""#],
+        "",
+    );
+    assert!(stdout(&output).ends_with("[a][b][complete: none: no completion specification]"));
+}
+
+#[test]
+fn command_substitution_output_only_split_and_globbed() {
+    let home = TempDir::new().unwrap();
+    let output = carapace(
+        home.path(),
+        &[
+            "-c",
+            r#"printf '[%s]' $(printf '$HOME "a  b"') "$(printf '$HOME\n  x')" $(printf 'Cargo.to*')"#,
+        ],
+        "",
+    );
+    assert!(stdout(&output).ends_with("[$HOME][\"a][b\"][$HOME\n  x][Cargo.toml]"));
+}
+
+#[test]
+fn invalid_glob_kept_literal() {
+    let home = TempDir::new().unwrap();
+    let output = carapace(home.path(), &["-c", "echo a[* $(printf 'b[*')"], "");
+    assert!(output.status.success());
+    assert!(stdout(&output).ends_with("a[* b[*\n"));
+}
+
+#[test]
+fn attached_redirections() {
+    let home = TempDir::new().unwrap();
+    let out = home.path().join("out");
+    let output = carapace(
+        home.path(),
+        &["-s"],
+        &format!("echo hi>{0}\ncat <{0}\n", out.display()),
+    );
+    assert_eq!(std::fs::read_to_string(&out).unwrap(), "hi\n");
+    assert!(stdout(&output).ends_with("hi\n"));
+}
+
+#[test]
+fn attached_stderr_redirection() {
+    let home = TempDir::new().unwrap();
+    let errs = home.path().join("errs");
+    let output = carapace(
+        home.path(),
+        &[
+            "-c",
+            &format!(
+                "ls /carapace/no/such/dir 2>/dev/null; ls /carapace/no/such/dir 2>>{}",
+                errs.display()
+            ),
+        ],
+        "",
+    );
+    assert!(!stderr(&output).contains("/carapace/no/such/dir"));
+    assert!(std::fs::read_to_string(&errs)
+        .unwrap()
+        .contains("/carapace/no/such/dir"));
+}
+
+#[test]
+fn brace_expansion() {
+    let home = TempDir::new().unwrap();
+    let output = carapace(
+        home.path(),
+        &["-c", "printf '[%s]' pre{a,b}post {1..3} '{x,y}'"],
+        "",
+    );
+    assert!(stdout(&output).ends_with("[preapost][prebpost][1][2][3][{x,y}]"));
+
+    let output = carapace(
+        home.path(),
+        &["-s"],
+        "export F='x{1,2}'\nprintf '[%s]' $F {a..C}\n",
+    );
+    assert!(stdout(&output).ends_with("[x{1,2}][a][`][_][^][]][\\][[][Z][Y][X][W][V][U][T][S][R][Q][P][O][N][M][L][K][J][I][H][G][F][E][D][C]"));
+}
+
+#[test]
+fn arithmetic_expansion() {
+    let home = TempDir::new().unwrap();
+    let output = carapace(
+        home.path(),
+        &["-s"],
+        "export x=5\nprintf '[%s]' $((2 + 3 * 4)) $((x+1))\nprintf $((1 / 0))\nprintf '[%s]' $?\n",
+    );
+    assert!(stdout(&output).contains("[14][6]"));
+    assert!(stdout(&output).contains("1 / 0: division by 0"));
+    assert!(stdout(&output).ends_with("[1]"));
+}
+
+#[test]
+fn quit_and_exit_save_history() {
+    for (word, code) in &[("quit", 0), ("exit 4", 4)] {
+        let home = TempDir::new().unwrap();
+        let output = carapace(
+            home.path(),
+            &["-s"],
+            &format!("echo one\n{}\necho two\n", word),
+        );
+        assert_eq!(output.status.code(), Some(*code));
+        assert!(!stdout(&output).contains("two"));
+        let history = std::fs::read_to_string(home.path().join(".carapace/history")).unwrap();
+        assert!(history.contains("echo one"), "{}", history);
+        assert!(history.contains(word), "{}", history);
+    }
+}
+
+#[test]
+fn aliases_not_expanded_when_not_interactive() {
+    let home = TempDir::new().unwrap();
+    let dir = home.path().join(".carapace");
+    std::fs::create_dir(&dir).unwrap();
+    let config = dir.join("config.json");
+    std::fs::write(&config, r#"{"aliases": {"greet": "printf hi"}}"#).unwrap();
+
+    let output = carapace(home.path(), &["-c", "greet"], "");
+    assert!(!stdout(&output).ends_with("hi"));
+
+    // Enabled via the option or in the config.
+    let output = carapace(home.path(), &["-s"], "set -o expand_aliases\ngreet\n");
+    assert!(stdout(&output).ends_with("hi"));
+    std::fs::write(
+        &config,
+        r#"{"aliases": {"greet": "printf hi"}, "expand_aliases": true}"#,
+    )
+    .unwrap();
+    let output = carapace(home.path(), &["-c", "greet"], "");
+    assert!(stdout(&output).ends_with("hi"));
+}